@@ -88,18 +88,21 @@ export const handlers = [
       messages,
       total: 100,
       has_more: before ? false : true,
+      server_time: new Date().toISOString(),
     };
     return HttpResponse.json(response);
   }),
 
-  http.post(`${API_BASE}/chat/:room/send`, ({ request }) => {
+  http.post(`${API_BASE}/chat/:room/send`, async ({ request }) => {
     const authHeader = request.headers.get('Authorization');
     if (!authHeader) {
       return new HttpResponse(null, { status: 401 });
     }
 
+    const body = await request.json() as { idempotency_key?: string };
     return HttpResponse.json({
-      id: 'new-msg-123',
+      message_id: body.idempotency_key ? `msg-${body.idempotency_key}` : 'new-msg-123',
+      seq: 1,
       timestamp: new Date().toISOString(),
     });
   }),
@@ -117,12 +120,14 @@ export const handlers = [
           username: 'testuser',
           connected_rooms: ['general'],
           connected_at: new Date().toISOString(),
+          connection_count: 1,
         },
         {
           user_id: 'user-456',
           username: 'anotheruser',
           connected_rooms: ['general', 'private-room'],
           connected_at: new Date().toISOString(),
+          connection_count: 2,
         },
       ],
       total_count: 2,
@@ -131,7 +136,7 @@ export const handlers = [
   }),
 
   // Search endpoints
-  http.get(`${API_BASE}/search`, ({ request }) => {
+  http.get(`${API_BASE}/search/messages`, ({ request }) => {
     const authHeader = request.headers.get('Authorization');
     if (!authHeader) {
       return new HttpResponse(null, { status: 401 });
@@ -139,26 +144,32 @@ export const handlers = [
 
     const url = new URL(request.url);
     const query = url.searchParams.get('q');
+    const offset = parseInt(url.searchParams.get('offset') || '0');
+    // author=degraded の場合は Postgres フォールバック時の応答を返す
+    const degraded = url.searchParams.get('author') === 'degraded';
 
     return HttpResponse.json({
       results: [
         {
           message: {
             id: 'msg-search-1',
-            room_id: 'general',
+            room_id: url.searchParams.get('room') || 'general',
             author_id: 'user-123',
             author_name: 'testuser',
             content: `Found message containing: ${query}`,
             message_type: 'text',
             created_at: new Date().toISOString(),
           },
-          highlights: [`<mark>${query}</mark>`],
-          score: 0.95,
+          highlights: degraded ? [] : [`<mark>${query}</mark>`],
+          score: degraded ? 0 : 0.95,
         },
       ],
-      total_hits: 1,
+      total_hits: offset + 1,
+      total_hits_estimated: !degraded,
       query_time_ms: 5,
       has_more: false,
+      degraded,
+      server_time: new Date().toISOString(),
     });
   }),
 
@@ -92,9 +92,16 @@ describe('API Service', () => {
       const content = 'Hello, world!';
       const response = await api.sendMessage('general', content);
       
-      expect(response).toHaveProperty('id');
+      expect(response).toHaveProperty('message_id');
+      expect(response).toHaveProperty('seq');
       expect(response).toHaveProperty('timestamp');
     });
+
+    it('should send the idempotency key with a message', async () => {
+      const response = await api.sendMessage('general', 'Hello again', undefined, 'client-1');
+
+      expect(response.message_id).toBe('msg-client-1');
+    });
   });
 
   describe('Search', () => {
@@ -126,6 +133,15 @@ describe('API Service', () => {
       
       expect(response).toHaveProperty('results');
       expect(response).toHaveProperty('total_hits');
+      expect(response.degraded).toBe(false);
+    });
+
+    it('should pass author and paging params', async () => {
+      const response = await api.searchMessages('test query', undefined, 'degraded', 20, 40);
+
+      expect(response.degraded).toBe(true);
+      expect(response.total_hits).toBe(41);
+      expect(response.results[0].highlights).toEqual([]);
     });
   });
 
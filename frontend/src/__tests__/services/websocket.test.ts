@@ -0,0 +1,91 @@
+import { describe, it, expect, vi } from 'vitest';
+import { WebSocketService } from '../../services/websocket';
+
+describe('WebSocket Service', () => {
+  describe('Reconnect backoff', () => {
+    // random() = 0.5 のときジッターは0になる
+    const noJitter = () => 0.5;
+
+    it('should double the delay on each attempt', () => {
+      expect(WebSocketService.computeReconnectDelay(1, noJitter)).toBe(1000);
+      expect(WebSocketService.computeReconnectDelay(2, noJitter)).toBe(2000);
+      expect(WebSocketService.computeReconnectDelay(3, noJitter)).toBe(4000);
+    });
+
+    it('should cap the delay at 30 seconds', () => {
+      expect(WebSocketService.computeReconnectDelay(6, noJitter)).toBe(30000);
+      expect(WebSocketService.computeReconnectDelay(20, noJitter)).toBe(30000);
+    });
+
+    it('should apply bounded jitter', () => {
+      expect(WebSocketService.computeReconnectDelay(1, () => 0)).toBe(700);
+      expect(WebSocketService.computeReconnectDelay(1, () => 1)).toBe(1300);
+    });
+  });
+
+  describe('Close classification', () => {
+    it('should not reconnect when authentication failed', () => {
+      expect(WebSocketService.classifyClose(1008, 'invalid token')).toEqual({
+        code: 1008,
+        reason: 'invalid token',
+        kind: 'auth_failed',
+        willReconnect: false,
+      });
+      expect(WebSocketService.classifyClose(1008, 'Connection limit exceeded').kind).toBe('connection_limit');
+      expect(WebSocketService.classifyClose(1008, 'Replaced by a newer connection').kind).toBe('replaced');
+    });
+
+    it('should reconnect after server-side or network failures', () => {
+      expect(WebSocketService.classifyClose(1001, '')).toMatchObject({ kind: 'server_shutdown', willReconnect: true });
+      expect(WebSocketService.classifyClose(1011, 'server error')).toMatchObject({ kind: 'server_error', willReconnect: true });
+      expect(WebSocketService.classifyClose(1013, 'Client is too slow')).toMatchObject({ kind: 'slow_consumer', willReconnect: true });
+      expect(WebSocketService.classifyClose(1006, '')).toMatchObject({ kind: 'abnormal', willReconnect: true });
+    });
+
+    it('should treat a normal close as final', () => {
+      expect(WebSocketService.classifyClose(1000, 'Client disconnect')).toMatchObject({ kind: 'normal', willReconnect: false });
+    });
+  });
+
+  describe('Room switching', () => {
+    it('should leave and join on the open socket', () => {
+      const service = new WebSocketService();
+      const send = vi.fn();
+      (service as any).socket = { readyState: WebSocket.OPEN, send };
+
+      service.switchRoom(null, 'general');
+      service.switchRoom('general', 'random');
+      // 同じルームへの切り替えでは何も送らない
+      service.switchRoom('random', 'random');
+
+      expect(send.mock.calls.map(([json]) => JSON.parse(json))).toEqual([
+        { type: 'join_room', room: 'general' },
+        { type: 'leave_room', room: 'general' },
+        { type: 'join_room', room: 'random' },
+      ]);
+      // 再接続時に参加し直すのは現在のルームだけ
+      expect([...(service as any).joinedRooms]).toEqual(['random']);
+    });
+  });
+
+  describe('Server time', () => {
+    it('should record the clock offset from server_time', () => {
+      const service = new WebSocketService();
+      const serverTime = new Date(Date.now() + 60_000).toISOString();
+
+      (service as any).handleMessage({ data: JSON.stringify({ type: 'server_time', server_time: serverTime }) });
+
+      expect(service.getServerTimeOffset()).toBeGreaterThan(59_000);
+      expect(service.getServerTimeOffset()).toBeLessThanOrEqual(60_000);
+    });
+  });
+
+  describe('Authentication', () => {
+    it('should pass the token as a subprotocol instead of the URL', () => {
+      expect(WebSocketService.authProtocols('abc.def.ghi')).toEqual([
+        'miuchi-chat',
+        'bearer.abc.def.ghi',
+      ]);
+    });
+  });
+});
@@ -0,0 +1,16 @@
+import { describe, it, expect } from 'vitest';
+import { normalizeMessageType } from '../../types';
+
+describe('normalizeMessageType', () => {
+  it('should accept WebSocket and REST spellings', () => {
+    expect(normalizeMessageType('image')).toBe('image');
+    expect(normalizeMessageType('File')).toBe('file');
+    expect(normalizeMessageType('System')).toBe('system');
+  });
+
+  it('should fall back to text for unknown or missing types', () => {
+    expect(normalizeMessageType('sticker')).toBe('text');
+    expect(normalizeMessageType(undefined)).toBe('text');
+    expect(normalizeMessageType(42)).toBe('text');
+  });
+});
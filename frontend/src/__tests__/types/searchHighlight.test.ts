@@ -0,0 +1,24 @@
+import { describe, it, expect } from 'vitest';
+import { parseHighlight } from '../../types';
+
+describe('parseHighlight', () => {
+  it('should split marked and plain text', () => {
+    expect(parseHighlight('hello <mark>world</mark>!')).toEqual([
+      { text: 'hello ', highlighted: false },
+      { text: 'world', highlighted: true },
+      { text: '!', highlighted: false },
+    ]);
+  });
+
+  it('should keep other markup as plain text', () => {
+    expect(parseHighlight('<img src=x onerror=alert(1)> <mark>x</mark>')).toEqual([
+      { text: '<img src=x onerror=alert(1)> ', highlighted: false },
+      { text: 'x', highlighted: true },
+    ]);
+  });
+
+  it('should return the whole text when nothing is marked', () => {
+    expect(parseHighlight('plain')).toEqual([{ text: 'plain', highlighted: false }]);
+    expect(parseHighlight('')).toEqual([]);
+  });
+});
@@ -0,0 +1,29 @@
+import { describe, it, expect } from 'vitest';
+import { readFileSync } from 'fs';
+import { resolve } from 'path';
+import { WS_MESSAGE_TYPES, type WsMessage } from '../../types';
+
+// バックエンドの tests/ws_protocol_tests.rs と共有しているフィクスチャ
+const fixtures: WsMessage[] = JSON.parse(
+  readFileSync(resolve(__dirname, '../../../../tests/fixtures/ws_messages.json'), 'utf-8')
+);
+
+describe('WsMessage protocol', () => {
+  it('should recognize every server fixture type', () => {
+    for (const fixture of fixtures) {
+      expect(Object.keys(WS_MESSAGE_TYPES)).toContain(fixture.type);
+    }
+  });
+
+  it('should have a fixture for every frontend message type', () => {
+    const fixtureTypes = new Set(fixtures.map(f => f.type));
+    for (const type of Object.keys(WS_MESSAGE_TYPES)) {
+      expect(fixtureTypes.has(type as WsMessage['type'])).toBe(true);
+    }
+  });
+
+  it('should not carry a token on join_room', () => {
+    const join = fixtures.find(f => f.type === 'join_room');
+    expect(join).toEqual({ type: 'join_room', room: 'general' });
+  });
+});
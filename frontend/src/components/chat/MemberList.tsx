@@ -51,20 +51,19 @@ export default function MemberList({ room, isVisible, onClose }: MemberListProps
       
       const response = await api.inviteUser(room.name, inviteUsername.trim())
       
-      if (response.success) {
-        setInviteSuccess(response.message)
-        setInviteUsername('')
-        setShowInviteForm(false)
-        // メンバー一覧を再読み込み
-        setTimeout(() => {
-          loadMembers()
-        }, 500)
-      } else {
-        setInviteError(response.message)
-      }
+      setInviteSuccess(response.message)
+      setInviteUsername('')
+      setShowInviteForm(false)
+      // メンバー一覧を再読み込み
+      setTimeout(() => {
+        loadMembers()
+      }, 500)
     } catch (error: any) {
       console.error('Failed to invite user:', error)
-      if (error.response?.status === 404) {
+      const serverMessage = error.response?.data?.error?.message
+      if (serverMessage) {
+        setInviteError(serverMessage)
+      } else if (error.response?.status === 404) {
         setInviteError('ユーザーが見つかりません')
       } else if (error.response?.status === 403) {
         setInviteError('招待する権限がありません')
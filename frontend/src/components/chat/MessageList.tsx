@@ -1,19 +1,47 @@
 import { useEffect, useRef } from 'react'
+import { normalizeMessageType } from '../../types'
 import type { Message } from '../../types'
 
+// http(s) の URL だけを画像・リンクとして使う（javascript: などは本文として表示する）
+const toSafeUrl = (value: string): string | null => {
+  try {
+    const url = new URL(value.trim())
+    return url.protocol === 'http:' || url.protocol === 'https:' ? url.href : null
+  } catch {
+    return null
+  }
+}
+
+const fileNameFromUrl = (url: string) => {
+  const name = new URL(url).pathname.split('/').pop() || ''
+  try {
+    return decodeURIComponent(name) || url
+  } catch {
+    return name || url
+  }
+}
+
 interface MessageListProps {
   messages: Message[]
   isLoading?: boolean
+  onRetry?: (message: Message) => void
+  // 検索結果から移動したメッセージ（読み込み済みならそこまでスクロールして強調する）
+  highlightMessageId?: string | null
 }
 
-export default function MessageList({ messages, isLoading = false }: MessageListProps) {
+export default function MessageList({ messages, isLoading = false, onRetry, highlightMessageId }: MessageListProps) {
   const messagesEndRef = useRef<HTMLDivElement>(null)
   
   console.log('MessageList received messages:', messages)
 
   useEffect(() => {
+    const target = highlightMessageId && document.getElementById(`message-${highlightMessageId}`)
+    if (target) {
+      target.scrollIntoView({ behavior: 'smooth', block: 'center' })
+      return
+    }
     messagesEndRef.current?.scrollIntoView({ behavior: 'smooth' })
-  }, [messages])
+  }, [messages, highlightMessageId])
 
   const formatTime = (timestamp: string) => {
     return new Date(timestamp).toLocaleTimeString('ja-JP', {
@@ -40,6 +68,57 @@ export default function MessageList({ messages, isLoading = false }: MessageList
     }
   }
 
+  // message_type ごとの本文（URL でない image / file と未知のタイプはテキストとして表示）
+  const renderContent = (message: Message) => {
+    const type = normalizeMessageType(message.message_type)
+    const url = type === 'image' || type === 'file' ? toSafeUrl(message.content) : null
+
+    if (type === 'image' && url) {
+      return (
+        <a href={url} target="_blank" rel="noopener noreferrer">
+          <img
+            src={url}
+            alt="画像"
+            loading="lazy"
+            style={{
+              maxWidth: '320px',
+              maxHeight: '240px',
+              borderRadius: '4px',
+              border: '1px solid var(--background2)',
+              objectFit: 'contain'
+            }}
+          />
+        </a>
+      )
+    }
+
+    if (type === 'file' && url) {
+      return (
+        <a
+          href={url}
+          download
+          target="_blank"
+          rel="noopener noreferrer"
+          style={{ fontSize: '0.9rem', color: 'var(--accent)', wordBreak: 'break-all' }}
+        >
+          📎 {fileNameFromUrl(url)}
+        </a>
+      )
+    }
+
+    return (
+      <div style={{ 
+        fontSize: '0.9rem', 
+        lineHeight: '1.4',
+        color: 'var(--foreground0)',
+        wordBreak: 'break-word',
+        whiteSpace: 'pre-wrap'
+      }}>
+        {message.content}
+      </div>
+    )
+  }
+
   const groupMessagesByDate = (messages: Message[]) => {
     const groups: { [date: string]: Message[] } = {}
     
@@ -107,11 +186,35 @@ export default function MessageList({ messages, isLoading = false }: MessageList
             {/* その日のメッセージ */}
             {dayMessages.map((message, index) => {
               const prevMessage = index > 0 ? dayMessages[index - 1] : null
-              const showAvatar = !prevMessage || prevMessage.author_id !== message.author_id
+              const showAvatar = !prevMessage
+                || prevMessage.author_id !== message.author_id
+                || normalizeMessageType(prevMessage.message_type) === 'system'
+
+              // システムメッセージ（/me や参加通知など）は投稿者の表示なしで控えめに表示
+              if (normalizeMessageType(message.message_type) === 'system') {
+                return (
+                  <div key={message.client_msg_id ?? message.id} id={`message-${message.id}`} style={{
+                    textAlign: 'center',
+                    fontSize: '0.8rem',
+                    fontStyle: 'italic',
+                    color: 'var(--foreground2)',
+                    margin: '0.25rem 0',
+                    wordBreak: 'break-word'
+                  }}>
+                    {message.content}
+                    <span style={{ marginLeft: '0.5rem', fontSize: '0.7rem', fontFamily: 'var(--font-mono)' }}>
+                      {formatTime(message.created_at)}
+                    </span>
+                  </div>
+                )
+              }
 
               return (
-                <div key={message.id} is-="row" gap-="2" align-="start" style={{ 
-                  marginTop: showAvatar ? '0.5rem' : '0.1rem'
+                <div key={message.client_msg_id ?? message.id} id={`message-${message.id}`} is-="row" gap-="2" align-="start" style={{ 
+                  marginTop: showAvatar ? '0.5rem' : '0.1rem',
+                  opacity: message.status === 'pending' ? 0.6 : 1,
+                  backgroundColor: message.id === highlightMessageId ? 'var(--background2)' : undefined,
+                  transition: 'background-color 0.5s'
                 }}>
                   {/* アバター */}
                   <div style={{ width: '2rem', display: 'flex', justifyContent: 'center' }}>
@@ -185,6 +288,9 @@ export default function MessageList({ messages, isLoading = false }: MessageList
                         }}>
                           {message.author_name}
                         </span>
+                        {message.author_is_bot && (
+                          <span is-="badge" variant-="background0">BOT</span>
+                        )}
                         <span style={{ 
                           fontSize: '0.7rem', 
                           color: 'var(--foreground2)',
@@ -194,15 +300,40 @@ export default function MessageList({ messages, isLoading = false }: MessageList
                         </span>
                       </div>
                     )}
-                    <div style={{ 
-                      fontSize: '0.9rem', 
-                      lineHeight: '1.4',
-                      color: 'var(--foreground0)',
-                      wordBreak: 'break-word',
-                      whiteSpace: 'pre-wrap'
-                    }}>
-                      {message.content}
-                    </div>
+                    {message.quoted && (
+                      <a href={`#message-${message.quoted.id}`} style={{
+                        display: 'block',
+                        borderLeft: '2px solid var(--background2)',
+                        paddingLeft: '0.5rem',
+                        marginBottom: '0.2rem',
+                        fontSize: '0.8rem',
+                        color: 'var(--foreground2)',
+                        textDecoration: 'none',
+                        wordBreak: 'break-word'
+                      }}>
+                        <span style={{ fontWeight: 'bold' }}>{message.quoted.username}</span>{' '}
+                        {message.quoted.content_preview}
+                      </a>
+                    )}
+                    {renderContent(message)}
+                    {message.status === 'failed' && (
+                      <div is-="row" gap-="1" align-="center" style={{ marginTop: '0.2rem' }}>
+                        <span style={{ fontSize: '0.7rem', color: 'var(--error)' }}>
+                          送信に失敗しました
+                        </span>
+                        {onRetry && (
+                          <button
+                            is-="button"
+                            size-="small"
+                            variant-="background2"
+                            style={{ fontSize: '0.7rem' }}
+                            onClick={() => onRetry(message)}
+                          >
+                            再送信
+                          </button>
+                        )}
+                      </div>
+                    )}
                   </div>
                 </div>
               )
@@ -7,9 +7,11 @@ interface RoomListProps {
   selectedRoom: Room | null
   onRoomSelect: (room: Room) => void
   onRoomCreate?: (room: Room) => void
+  // 変わるたびにルーム一覧を再取得する
+  refreshKey?: number
 }
 
-export default function RoomList({ selectedRoom, onRoomSelect, onRoomCreate }: RoomListProps) {
+export default function RoomList({ selectedRoom, onRoomSelect, onRoomCreate, refreshKey }: RoomListProps) {
   const [rooms, setRooms] = useState<Room[]>([])
   const [isLoading, setIsLoading] = useState(true)
   const [showCreateForm, setShowCreateForm] = useState(false)
@@ -20,7 +22,7 @@ export default function RoomList({ selectedRoom, onRoomSelect, onRoomCreate }: R
 
   useEffect(() => {
     loadRooms()
-  }, [])
+  }, [refreshKey])
 
   const loadRooms = async () => {
     try {
@@ -73,7 +75,8 @@ export default function RoomList({ selectedRoom, onRoomSelect, onRoomCreate }: R
       if (error.response?.status === 409) {
         setCreateError(`チャンネル名 "${newRoomName}" は既に使用されています`)
       } else if (error.response?.status === 400) {
-        setCreateError('チャンネル名が無効です')
+        // サーバーが返す検証メッセージ（使用可能な文字・長さ）をそのまま表示
+        setCreateError(error.response?.data?.error?.message ?? 'チャンネル名が無効です')
       } else {
         setCreateError('チャンネルの作成に失敗しました')
       }
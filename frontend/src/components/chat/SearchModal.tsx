@@ -1,17 +1,23 @@
 import { useState, useEffect } from 'react'
 import { api } from '../../services/api'
-import type { SearchResponse } from '../../types'
+import { SearchResults } from './SearchResults'
+import type { Message, Room, SearchResponse } from '../../types'
+
+// 1回の検索・「さらに表示」で取得する件数
+const SEARCH_PAGE_SIZE = 20
 
 interface SearchModalProps {
     isVisible: boolean;
     onClose: () => void;
-    currentRoom?: string;
+    currentRoom?: Room | null;
+    onJumpToMessage: (message: Message) => void;
 }
 
-export function SearchModal({ isVisible, onClose, currentRoom }: SearchModalProps) {
+export function SearchModal({ isVisible, onClose, currentRoom, onJumpToMessage }: SearchModalProps) {
     const [query, setQuery] = useState('')
     const [searchResults, setSearchResults] = useState<SearchResponse | null>(null)
     const [isSearching, setIsSearching] = useState(false)
+    const [isLoadingMore, setIsLoadingMore] = useState(false)
     const [error, setError] = useState<string | null>(null)
     const [searchInCurrentRoom, setSearchInCurrentRoom] = useState(true)
 
@@ -39,8 +45,7 @@ export function SearchModal({ isVisible, onClose, currentRoom }: SearchModalProp
         setError(null)
 
         try {
-            const roomFilter = searchInCurrentRoom ? currentRoom : undefined
-            const results = await api.searchMessages(query.trim(), roomFilter)
+            const results = await api.searchMessages(query.trim(), roomFilter(), undefined, SEARCH_PAGE_SIZE)
             setSearchResults(results)
         } catch (err) {
             console.error('Search failed:', err)
@@ -51,6 +56,35 @@ export function SearchModal({ isVisible, onClose, currentRoom }: SearchModalProp
         }
     }
 
+    const roomFilter = () => (searchInCurrentRoom ? currentRoom?.name : undefined)
+
+    // 続きを取得して末尾に追加する
+    const handleLoadMore = async () => {
+        if (!searchResults || isLoadingMore) return
+
+        setIsLoadingMore(true)
+        try {
+            const next = await api.searchMessages(
+                query.trim(),
+                roomFilter(),
+                undefined,
+                SEARCH_PAGE_SIZE,
+                searchResults.results.length
+            )
+            setSearchResults({ ...next, results: [...searchResults.results, ...next.results] })
+        } catch (err) {
+            console.error('Search failed:', err)
+            setError('検索に失敗しました')
+        } finally {
+            setIsLoadingMore(false)
+        }
+    }
+
+    const handleJump = (message: Message) => {
+        onJumpToMessage(message)
+        handleClose()
+    }
+
     const handleClear = () => {
         setQuery('')
         setSearchResults(null)
@@ -191,7 +225,7 @@ export function SearchModal({ isVisible, onClose, currentRoom }: SearchModalProp
                                     checked={searchInCurrentRoom}
                                     onChange={(e) => setSearchInCurrentRoom(e.target.checked)}
                                 />
-                                #{currentRoom} 内のみ検索
+                                #{currentRoom.name} 内のみ検索
                             </label>
                         </div>
                     )}
@@ -227,118 +261,13 @@ export function SearchModal({ isVisible, onClose, currentRoom }: SearchModalProp
                             キーワードを入力して検索ボタンを押してください
                         </div>
                     ) : searchResults ? (
-                        <>
-                            {/* 結果のサマリー */}
-                            <div style={{
-                                marginBottom: '1rem',
-                                padding: '0.5rem',
-                                backgroundColor: 'var(--background1)',
-                                borderRadius: '4px',
-                                fontSize: '0.8rem',
-                                color: 'var(--foreground1)'
-                            }}>
-                                <strong>{searchResults.total_hits}</strong>件の結果 
-                                ({searchResults.query_time_ms}ms)
-                                {searchInCurrentRoom && currentRoom && (
-                                    <span> - #{currentRoom} 内</span>
-                                )}
-                            </div>
-
-                            {/* 結果リスト */}
-                            {searchResults.results.length === 0 ? (
-                                <div style={{
-                                    textAlign: 'center',
-                                    color: 'var(--foreground1)',
-                                    padding: '2rem',
-                                    fontSize: '0.9rem'
-                                }}>
-                                    検索結果が見つかりませんでした
-                                </div>
-                            ) : (
-                                <div>
-                                    {searchResults.results.map((result) => {
-                                        const { message, highlights, score } = result
-                                        const createdAt = new Date(message.created_at)
-                                        
-                                        return (
-                                            <div 
-                                                key={message.id} 
-                                                style={{
-                                                    padding: '0.75rem',
-                                                    marginBottom: '0.5rem',
-                                                    backgroundColor: 'var(--background1)',
-                                                    border: '1px solid var(--background2)',
-                                                    borderRadius: '4px'
-                                                }}
-                                            >
-                                                {/* メッセージヘッダー */}
-                                                <div style={{
-                                                    display: 'flex',
-                                                    justifyContent: 'space-between',
-                                                    alignItems: 'center',
-                                                    marginBottom: '0.5rem'
-                                                }}>
-                                                    <div style={{
-                                                        display: 'flex',
-                                                        alignItems: 'center',
-                                                        gap: '0.5rem'
-                                                    }}>
-                                                        <span style={{
-                                                            fontWeight: 'bold',
-                                                            fontSize: '0.8rem',
-                                                            color: 'var(--primary)'
-                                                        }}>
-                                                            {message.author_name}
-                                                        </span>
-                                                        <span style={{
-                                                            fontSize: '0.7rem',
-                                                            color: 'var(--foreground2)',
-                                                            fontFamily: 'var(--font-mono)'
-                                                        }}>
-                                                            #{currentRoom || 'general'}
-                                                        </span>
-                                                    </div>
-                                                    <div style={{
-                                                        display: 'flex',
-                                                        alignItems: 'center',
-                                                        gap: '0.5rem',
-                                                        fontSize: '0.7rem',
-                                                        color: 'var(--foreground2)'
-                                                    }}>
-                                                        <span>
-                                                            {createdAt.toLocaleDateString()} {createdAt.toLocaleTimeString()}
-                                                        </span>
-                                                        <span>
-                                                            スコア: {score.toFixed(1)}
-                                                        </span>
-                                                    </div>
-                                                </div>
-                                                
-                                                {/* メッセージ内容 */}
-                                                <div style={{
-                                                    fontSize: '0.9rem',
-                                                    lineHeight: '1.4',
-                                                    color: 'var(--foreground0)'
-                                                }}>
-                                                    {highlights.length > 0 ? (
-                                                        <div 
-                                                            dangerouslySetInnerHTML={{ 
-                                                                __html: highlights[0].replace(
-                                                                    /<mark>/g, 
-                                                                    '<mark style="background-color: var(--warning); color: var(--background0); padding: 1px 2px; border-radius: 2px;">'
-                                                                )
-                                                            }}
-                                                        />
-                                                    ) : (
-                                                        <div>{message.content}</div>
-                                                    )}
-                                                </div>
-                                            </div>
-                                        )
-                                    })}
-                                </div>
-                            )}
-                        </>
+                        <SearchResults
+                            searchResults={searchResults}
+                            currentRoom={currentRoom}
+                            isLoadingMore={isLoadingMore}
+                            onLoadMore={handleLoadMore}
+                            onJumpToMessage={handleJump}
+                        />
                     ) : null}
                 </div>
             </div>
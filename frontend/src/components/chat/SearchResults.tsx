@@ -1,107 +1,185 @@
-import { formatDistanceToNow } from 'date-fns'
-import { ja } from 'date-fns/locale'
-import type { SearchResponse } from '../../types'
+import { parseHighlight } from '../../types'
+import type { Message, Room, SearchResponse, SearchResult } from '../../types'
 
 interface SearchResultsProps {
-    searchResults: SearchResponse | null;
-    isSearching: boolean;
-    onClose: () => void;
+    searchResults: SearchResponse;
+    currentRoom?: Room | null;
+    isLoadingMore: boolean;
+    onLoadMore: () => void;
+    onJumpToMessage: (message: Message) => void;
 }
 
-export function SearchResults({ searchResults, isSearching, onClose }: SearchResultsProps) {
-    if (isSearching) {
-        return (
-            <div className="p-3 text-center">
-                <div className="spinner-border text-primary me-2" role="status">
-                    <span className="visually-hidden">検索中...</span>
-                </div>
-                検索中...
-            </div>
-        )
+// ハイライトがあれば <mark> 部分を強調し、なければ本文をそのまま表示する
+function ResultContent({ result }: { result: SearchResult }) {
+    if (result.highlights.length === 0) {
+        return <>{result.message.content}</>
     }
 
-    if (!searchResults) {
-        return null
-    }
+    return (
+        <>
+            {parseHighlight(result.highlights[0]).map((segment, index) =>
+                segment.highlighted ? (
+                    <mark key={index} style={{
+                        backgroundColor: 'var(--warning)',
+                        color: 'var(--background0)',
+                        padding: '1px 2px',
+                        borderRadius: '2px'
+                    }}>
+                        {segment.text}
+                    </mark>
+                ) : (
+                    <span key={index}>{segment.text}</span>
+                )
+            )}
+        </>
+    )
+}
 
-    const { results, total_hits, query_time_ms } = searchResults
+export function SearchResults({
+    searchResults,
+    currentRoom,
+    isLoadingMore,
+    onLoadMore,
+    onJumpToMessage
+}: SearchResultsProps) {
+    const { results, total_hits, total_hits_estimated, query_time_ms, has_more, degraded } = searchResults
 
     return (
-        <div className="flex-grow-1 overflow-auto">
-            <div className="border-bottom p-2 d-flex justify-content-between align-items-center">
-                <div className="small text-muted">
-                    {total_hits}件の結果 ({query_time_ms}ms)
-                </div>
-                <button 
-                    className="btn btn-outline-secondary btn-sm"
-                    onClick={onClose}
-                >
-                    閉じる
-                </button>
+        <>
+            {/* 結果のサマリー */}
+            <div style={{
+                marginBottom: '1rem',
+                padding: '0.5rem',
+                backgroundColor: 'var(--background1)',
+                borderRadius: '4px',
+                fontSize: '0.8rem',
+                color: 'var(--foreground1)'
+            }}>
+                {total_hits_estimated && '約'}<strong>{total_hits}</strong>件の結果
+                ({query_time_ms}ms)
+                {degraded && (
+                    <div style={{ marginTop: '0.25rem', color: 'var(--warning)' }}>
+                        検索サーバーに接続できないため簡易検索の結果を表示しています（新しい順・ハイライトなし）
+                    </div>
+                )}
             </div>
-            
+
+            {/* 結果リスト */}
             {results.length === 0 ? (
-                <div className="p-3 text-center text-muted">
+                <div style={{
+                    textAlign: 'center',
+                    color: 'var(--foreground1)',
+                    padding: '2rem',
+                    fontSize: '0.9rem'
+                }}>
                     検索結果が見つかりませんでした
                 </div>
             ) : (
-                <div className="p-2">
+                <div>
                     {results.map((result) => {
-                        const { message, highlights, score } = result
+                        const { message, score } = result
                         const createdAt = new Date(message.created_at)
-                        
+
                         return (
-                            <div key={message.id} className="border-bottom border-secondary py-2">
-                                <div className="d-flex justify-content-between align-items-start mb-1">
-                                    <div className="d-flex align-items-center gap-2">
-                                        {message.author_avatar && (
-                                            <img
-                                                src={message.author_avatar}
-                                                alt={message.author_name}
-                                                className="rounded-circle"
-                                                width="24"
-                                                height="24"
-                                            />
-                                        )}
-                                        <span className="fw-bold text-primary">
+                            <button
+                                key={message.id}
+                                onClick={() => onJumpToMessage(message)}
+                                title="メッセージへ移動"
+                                style={{
+                                    display: 'block',
+                                    width: '100%',
+                                    textAlign: 'left',
+                                    cursor: 'pointer',
+                                    padding: '0.75rem',
+                                    marginBottom: '0.5rem',
+                                    backgroundColor: 'var(--background1)',
+                                    border: '1px solid var(--background2)',
+                                    borderRadius: '4px',
+                                    color: 'inherit',
+                                    font: 'inherit'
+                                }}
+                            >
+                                {/* メッセージヘッダー */}
+                                <div style={{
+                                    display: 'flex',
+                                    justifyContent: 'space-between',
+                                    alignItems: 'center',
+                                    marginBottom: '0.5rem'
+                                }}>
+                                    <div style={{
+                                        display: 'flex',
+                                        alignItems: 'center',
+                                        gap: '0.5rem'
+                                    }}>
+                                        <span style={{
+                                            fontWeight: 'bold',
+                                            fontSize: '0.8rem',
+                                            color: 'var(--primary)'
+                                        }}>
                                             {message.author_name}
                                         </span>
-                                        <span className="badge bg-secondary small">
-                                            {message.room_id}
-                                        </span>
+                                        {currentRoom?.id === message.room_id && (
+                                            <span style={{
+                                                fontSize: '0.7rem',
+                                                color: 'var(--foreground2)',
+                                                fontFamily: 'var(--font-mono)'
+                                            }}>
+                                                #{currentRoom.name}
+                                            </span>
+                                        )}
                                     </div>
-                                    <div className="d-flex align-items-center gap-2">
-                                        <span className="small text-muted">
-                                            スコア: {score.toFixed(2)}
-                                        </span>
-                                        <span className="small text-muted">
-                                            {formatDistanceToNow(createdAt, { 
-                                                addSuffix: true, 
-                                                locale: ja 
-                                            })}
+                                    <div style={{
+                                        display: 'flex',
+                                        alignItems: 'center',
+                                        gap: '0.5rem',
+                                        fontSize: '0.7rem',
+                                        color: 'var(--foreground2)'
+                                    }}>
+                                        <span>
+                                            {createdAt.toLocaleDateString()} {createdAt.toLocaleTimeString()}
                                         </span>
+                                        {!degraded && (
+                                            <span>
+                                                スコア: {score.toFixed(1)}
+                                            </span>
+                                        )}
                                     </div>
                                 </div>
-                                
-                                <div className="small">
-                                    {highlights.length > 0 ? (
-                                        <div 
-                                            className="text-light"
-                                            dangerouslySetInnerHTML={{ 
-                                                __html: highlights[0] 
-                                            }}
-                                        />
-                                    ) : (
-                                        <div className="text-light">
-                                            {message.content}
-                                        </div>
-                                    )}
+
+                                {/* メッセージ内容 */}
+                                <div style={{
+                                    fontSize: '0.9rem',
+                                    lineHeight: '1.4',
+                                    color: 'var(--foreground0)',
+                                    wordBreak: 'break-word',
+                                    whiteSpace: 'pre-wrap'
+                                }}>
+                                    <ResultContent result={result} />
                                 </div>
-                            </div>
+                            </button>
                         )
                     })}
+
+                    {has_more && (
+                        <button
+                            onClick={onLoadMore}
+                            disabled={isLoadingMore}
+                            style={{
+                                width: '100%',
+                                padding: '0.5rem',
+                                backgroundColor: 'transparent',
+                                color: 'var(--foreground1)',
+                                border: '1px solid var(--background2)',
+                                borderRadius: '4px',
+                                cursor: isLoadingMore ? 'not-allowed' : 'pointer',
+                                fontSize: '0.8rem'
+                            }}
+                        >
+                            {isLoadingMore ? '読み込み中...' : 'さらに表示'}
+                        </button>
+                    )}
                 </div>
             )}
-        </div>
+        </>
     )
-}
\ No newline at end of file
+}
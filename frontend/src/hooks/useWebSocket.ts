@@ -1,5 +1,7 @@
 import { useEffect, useRef, useState } from 'react'
 import { wsService, ConnectionState } from '../services/websocket'
+import type { WsCloseInfo } from '../services/websocket'
+import { normalizeMessageType } from '../types'
 import type { Message, WsMessage } from '../types'
 
 interface UseWebSocketOptions {
@@ -7,18 +9,30 @@ interface UseWebSocketOptions {
   onMessage?: (message: Message) => void
   onRoomCreated?: (room: any) => void
   onError?: (error: any) => void
+  onMessageAck?: (clientMsgId: string, serverId: string, timestamp: string) => void
+  onMessageNack?: (clientMsgId: string, reason: string) => void
+  // サーバーに接続を閉じられたとき（理由と再接続するかを含む）
+  onClose?: (info: WsCloseInfo) => void
+  // プライベートルームに招待されたとき（ルーム一覧の再取得に使う）
+  onInvitedToRoom?: (room: string, byUsername: string) => void
+  // ルームの名前・説明・公開設定が変わったとき
+  onRoomUpdated?: (update: Extract<WsMessage, { type: 'room_updated' }>) => void
 }
 
 interface UseWebSocketReturn {
-  connectionStatus: 'connecting' | 'connected' | 'disconnected' | 'failed'
-  sendMessage: (content: string) => void
+  connectionStatus: 'connecting' | 'connected' | 'reconnecting' | 'disconnected' | 'failed'
+  // 最後にサーバーから閉じられた理由（再接続に成功するとクリアされる）
+  lastClose: WsCloseInfo | null
+  // WebSocket で送れた場合は true（未接続なら送らずに false）
+  sendMessage: (content: string, clientMsgId?: string) => boolean
   joinRoom: (roomId: string) => void
   leaveRoom: (roomId: string) => void
 }
 
 export function useWebSocket(options: UseWebSocketOptions = {}): UseWebSocketReturn {
-  const { roomId, onMessage, onError } = options
-  const [connectionStatus, setConnectionStatus] = useState<'connecting' | 'connected' | 'disconnected' | 'failed'>('disconnected')
+  const { roomId, onMessage, onError, onMessageAck, onMessageNack, onClose, onInvitedToRoom, onRoomUpdated } = options
+  const [connectionStatus, setConnectionStatus] = useState<'connecting' | 'connected' | 'reconnecting' | 'disconnected' | 'failed'>('disconnected')
+  const [lastClose, setLastClose] = useState<WsCloseInfo | null>(null)
   const currentRoomRef = useRef<string | null>(null)
 
   useEffect(() => {
@@ -40,6 +54,10 @@ export function useWebSocket(options: UseWebSocketOptions = {}): UseWebSocketRet
           break
         case ConnectionState.CONNECTED:
           setConnectionStatus('connected')
+          setLastClose(null)
+          break
+        case ConnectionState.RECONNECTING:
+          setConnectionStatus('reconnecting')
           break
         case ConnectionState.DISCONNECTED:
           setConnectionStatus('disconnected')
@@ -69,18 +87,44 @@ export function useWebSocket(options: UseWebSocketOptions = {}): UseWebSocketRet
               room_id: message.room,
               author_id: message.user_id,
               author_name: message.username,
+              author_avatar: message.avatar_url ?? undefined,
+              author_is_bot: message.is_bot,
               content: message.content,
-              message_type: message.message_type as 'text' | 'image' | 'file' | 'system',
+              message_type: normalizeMessageType(message.message_type),
+              seq: message.seq,
+              quoted: message.quoted ?? null,
               created_at: typeof message.timestamp === 'string' ? message.timestamp : new Date(message.timestamp || Date.now()).toISOString()
             }
             onMessageRef.current(msg)
           }
           break
+        case 'message_ack':
+          if (onMessageAckRef.current) {
+            onMessageAckRef.current(message.client_msg_id, message.server_id, message.timestamp)
+          }
+          break
+        case 'message_nack':
+          console.warn('Message rejected by server:', message.reason)
+          if (onMessageNackRef.current) {
+            onMessageNackRef.current(message.client_msg_id, message.reason)
+          }
+          break
         case 'user_joined':
         case 'user_left':
+        case 'member_added':
           // Handle user join/leave events if needed
           console.log(`User ${message.username} ${message.type.split('_')[1]} room ${message.room}`)
           break
+        case 'invited_to_room':
+          if (onInvitedToRoomRef.current) {
+            onInvitedToRoomRef.current(message.room, message.by_username)
+          }
+          break
+        case 'room_updated':
+          if (onRoomUpdatedRef.current) {
+            onRoomUpdatedRef.current(message)
+          }
+          break
         case 'error':
           console.error('WebSocket error:', message.message)
           
@@ -112,8 +156,17 @@ export function useWebSocket(options: UseWebSocketOptions = {}): UseWebSocketRet
       }
     }
 
+    const handleClose = (info: WsCloseInfo) => {
+      if (!isActive) return
+      setLastClose(info)
+      if (onCloseRef.current) {
+        onCloseRef.current(info)
+      }
+    }
+
     const unsubscribeStateChange = wsService.onConnectionStateChange(handleConnectionStateChange)
     const unsubscribeMessage = wsService.onMessage(handleMessage)
+    const unsubscribeClose = wsService.onClose(handleClose)
     
     // 既に接続されている場合は新たに接続しない
     if (!wsService.isConnected()) {
@@ -137,6 +190,7 @@ export function useWebSocket(options: UseWebSocketOptions = {}): UseWebSocketRet
       // ハンドラーの登録解除
       unsubscribeStateChange()
       unsubscribeMessage()
+      unsubscribeClose()
       setConnectionStatus('disconnected')
     }
   }, []) // 依存関係を空配列にしてマウント時のみ実行
@@ -144,35 +198,52 @@ export function useWebSocket(options: UseWebSocketOptions = {}): UseWebSocketRet
   // ハンドラーの更新をrefで管理（重複登録を避ける）
   const onMessageRef = useRef(onMessage)
   const onErrorRef = useRef(onError)
+  const onMessageAckRef = useRef(onMessageAck)
+  const onMessageNackRef = useRef(onMessageNack)
+  const onCloseRef = useRef(onClose)
+  const onInvitedToRoomRef = useRef(onInvitedToRoom)
+  const onRoomUpdatedRef = useRef(onRoomUpdated)
   
   useEffect(() => {
     onMessageRef.current = onMessage
     onErrorRef.current = onError
-  }, [onMessage, onError])
+    onMessageAckRef.current = onMessageAck
+    onMessageNackRef.current = onMessageNack
+    onCloseRef.current = onClose
+    onInvitedToRoomRef.current = onInvitedToRoom
+    onRoomUpdatedRef.current = onRoomUpdated
+  }, [onMessage, onError, onMessageAck, onMessageNack, onClose])
+
+  // ルームの切り替えは同じソケット上の leave_room / join_room で行う
+  // （未接続の間は参加ルームだけ記録され、接続時にまとめて参加する）
+  useEffect(() => {
+    if (roomId && currentRoomRef.current !== roomId) {
+      console.log(`Switching room: ${currentRoomRef.current} -> ${roomId}`)
+      wsService.switchRoom(currentRoomRef.current, roomId)
+      currentRoomRef.current = roomId
+    }
+  }, [roomId])
 
+  // アンマウント時は現在のルームから抜ける（ソケットはセッション中維持する）
   useEffect(() => {
-    if (roomId && connectionStatus === 'connected' && currentRoomRef.current !== roomId) {
+    return () => {
       if (currentRoomRef.current) {
         wsService.leaveRoom(currentRoomRef.current)
+        currentRoomRef.current = null
       }
-      
-      console.log(`Attempting to join room: ${roomId}`)
-      wsService.joinRoom(roomId)
-      currentRoomRef.current = roomId
     }
-  }, [roomId, connectionStatus])
+  }, [])
 
-  const sendMessage = (content: string) => {
+  const sendMessage = (content: string, clientMsgId?: string) => {
     if (roomId && connectionStatus === 'connected') {
-      wsService.sendChatMessage(roomId, content)
+      wsService.sendChatMessage(roomId, content, 'text', clientMsgId)
+      return true
     }
+    return false
   }
 
   const joinRoom = (newRoomId: string) => {
-    if (currentRoomRef.current) {
-      wsService.leaveRoom(currentRoomRef.current)
-    }
-    wsService.joinRoom(newRoomId)
+    wsService.switchRoom(currentRoomRef.current, newRoomId)
     currentRoomRef.current = newRoomId
   }
 
@@ -185,6 +256,7 @@ export function useWebSocket(options: UseWebSocketOptions = {}): UseWebSocketRet
 
   return {
     connectionStatus,
+    lastClose,
     sendMessage,
     joinRoom,
     leaveRoom
@@ -10,10 +10,25 @@ import MemberList from '../components/chat/MemberList'
 import { SearchModal } from '../components/chat/SearchModal'
 import { CallControls } from '../components/chat/CallControls'
 import '../components/chat/CallControls.css'
-import type { Message, Room } from '../types'
+import type { WsCloseInfo, WsCloseKind } from '../services/websocket'
+import type { Message, Room, WsMessage } from '../types'
+
+// サーバーに接続を閉じられたときの表示
+const CLOSE_MESSAGES: Record<WsCloseKind, string> = {
+    normal: '切断されました',
+    server_shutdown: 'サーバーが再起動中です。再接続しています…',
+    auth_failed: 'ログインの有効期限が切れました。再度ログインしてください',
+    connection_limit: '同時接続数の上限に達しました。他のタブを閉じてから再読み込みしてください',
+    replaced: '別のタブで接続されたため切断されました',
+    policy: 'サーバーに接続を拒否されました',
+    too_large: 'メッセージが大きすぎるため切断されました。再接続しています…',
+    slow_consumer: '受信が追いつかないため切断されました。再接続しています…',
+    server_error: 'サーバーエラーが発生しました。再接続しています…',
+    abnormal: '接続が切れました。再接続しています…',
+}
 
 export default function ChatPage() {
-    const { user } = useAuth()
+    const { user, logout } = useAuth()
     // デフォルトでgeneralルームを選択
     const [selectedRoom, setSelectedRoom] = useState<Room | null>({
         id: '00000000-0000-0000-0000-000000000001',
@@ -26,11 +41,47 @@ export default function ChatPage() {
     const [showMemberList, setShowMemberList] = useState(false)
     const [showSearchModal, setShowSearchModal] = useState(false)
     const [onlineUsers, setOnlineUsers] = useState<{user_id: string, username: string}[]>([])
+    // REST で送信した時刻（「送信しました（オフラインモード）」を一時的に表示する）
+    const [offlineSentAt, setOfflineSentAt] = useState<number | null>(null)
+    // 検索結果から移動したメッセージ（一時的に強調表示する）
+    const [highlightMessageId, setHighlightMessageId] = useState<string | null>(null)
+    // 招待されたときにルーム一覧を再取得させる
+    const [roomListVersion, setRoomListVersion] = useState(0)
 
     // useCallbackでコールバック関数を最適化
     const handleMessage = useCallback((message: Message) => {
         console.log('New message received:', message)
-        setMessages(prev => [...prev, message])
+        // WebSocket の配信はルーム名で届くため、表示中のルームの ID に読み替える
+        if (selectedRoom && message.room_id === selectedRoom.name) {
+            message = { ...message, room_id: selectedRoom.id }
+        }
+        // ACK済みの楽観的メッセージと同じIDであれば追加しない
+        setMessages(prev => prev.some(m => m.id === message.id) ? prev : [...prev, message])
+    }, [selectedRoom])
+
+    const handleMessageAck = useCallback((clientMsgId: string, serverId: string, timestamp: string) => {
+        setMessages(prev => {
+            // 一時エントリを同じ位置のままサーバーIDに置き換える
+            const confirmed = prev.map(m =>
+                m.client_msg_id === clientMsgId
+                    ? { ...m, id: serverId, created_at: timestamp, status: 'sent' as const }
+                    : m
+            )
+            // ACKより先にブロードキャストが届いていた場合の重複を除去（先に並んでいる方を残す）
+            const seen = new Set<string>()
+            return confirmed.filter(m => {
+                if (seen.has(m.id)) return false
+                seen.add(m.id)
+                return true
+            })
+        })
+    }, [])
+
+    const handleMessageNack = useCallback((clientMsgId: string, reason: string) => {
+        console.warn('Message send failed:', clientMsgId, reason)
+        setMessages(prev => prev.map(m =>
+            m.client_msg_id === clientMsgId ? { ...m, status: 'failed' as const } : m
+        ))
     }, [])
 
     const handleError = useCallback((error: Error) => {
@@ -44,17 +95,52 @@ export default function ChatPage() {
         }
     }, [selectedRoom])
 
+    const handleClose = useCallback((info: WsCloseInfo) => {
+        // 認証が切れた場合は再接続せずログイン画面へ戻す
+        if (info.kind === 'auth_failed') {
+            logout()
+        }
+    }, [logout])
+
+    const handleInvitedToRoom = useCallback(() => {
+        setRoomListVersion(v => v + 1)
+    }, [])
+
+    const handleRoomUpdated = useCallback((update: Extract<WsMessage, { type: 'room_updated' }>) => {
+        setRoomListVersion(v => v + 1)
+        // 表示中のルームの名前が変わってもそのまま開いておく（メッセージの取得は新しい名前で行う）
+        setSelectedRoom(prev => prev && prev.name === update.old_name
+            ? { ...prev, name: update.new_name, description: update.description ?? undefined, is_public: update.is_public }
+            : prev)
+    }, [])
+
     const {
         connectionStatus,
-        sendMessage: wsSendMessage,
-        joinRoom,
-        leaveRoom
+        lastClose,
+        sendMessage: wsSendMessage
     } = useWebSocket({
         roomId: selectedRoom?.id,
         onMessage: handleMessage,
-        onError: handleError
+        onError: handleError,
+        onMessageAck: handleMessageAck,
+        onMessageNack: handleMessageNack,
+        onClose: handleClose,
+        onInvitedToRoom: handleInvitedToRoom,
+        onRoomUpdated: handleRoomUpdated
     })
 
+    useEffect(() => {
+        if (offlineSentAt === null) return
+        const timer = setTimeout(() => setOfflineSentAt(null), 3000)
+        return () => clearTimeout(timer)
+    }, [offlineSentAt])
+
+    useEffect(() => {
+        if (highlightMessageId === null) return
+        const timer = setTimeout(() => setHighlightMessageId(null), 3000)
+        return () => clearTimeout(timer)
+    }, [highlightMessageId])
+
     // 初期のgeneralルームのメッセージをロード
     useEffect(() => {
         if (selectedRoom && selectedRoom.name === 'general') {
@@ -80,14 +166,10 @@ export default function ChatPage() {
         }
     }
 
+    // WebSocket のルーム切り替え（leave_room / join_room）は useWebSocket が roomId の変化で行う
     const handleRoomSelect = async (room: Room) => {
         console.log('🚪 Room select triggered:', room.name, room.id)
         
-        if (selectedRoom) {
-            console.log('👋 Leaving current room:', selectedRoom.name)
-            await leaveRoom(selectedRoom.id)
-        }
-        
         setSelectedRoom(room)
         setMessages([]) // Clear messages when switching rooms
         setIsLoadingMessages(true)
@@ -106,18 +188,72 @@ export default function ChatPage() {
         } finally {
             setIsLoadingMessages(false)
         }
-        
-        console.log('🔗 Joining room via WebSocket:', room.id)
-        await joinRoom(room.id)
     }
 
     const handleSendMessage = (content: string) => {
         if (selectedRoom && user) {
-            // WebSocketで送信（楽観的更新は削除してサーバーからの応答を待つ）
-            wsSendMessage(content)
+            // 楽観的に一時IDで表示し、message_ackで確定させる
+            const clientMsgId = `${Date.now()}-${Math.random().toString(36).slice(2, 10)}`
+            const optimisticMessage: Message = {
+                id: `temp-${clientMsgId}`,
+                room_id: selectedRoom.id,
+                author_id: user.id,
+                author_name: user.username,
+                author_avatar: user.avatar_url,
+                content,
+                message_type: 'text',
+                created_at: new Date().toISOString(),
+                client_msg_id: clientMsgId,
+                status: 'pending'
+            }
+            setMessages(prev => [...prev, optimisticMessage])
+            deliverMessage(selectedRoom, content, clientMsgId)
         }
     }
 
+    const handleRetryMessage = (message: Message) => {
+        if (!message.client_msg_id || !selectedRoom) return
+        setMessages(prev => prev.map(m =>
+            m.client_msg_id === message.client_msg_id ? { ...m, status: 'pending' as const } : m
+        ))
+        deliverMessage(selectedRoom, message.content, message.client_msg_id)
+    }
+
+    // WebSocket が切れている間は REST API で送り、レスポンスを ACK の代わりにする。
+    // client_msg_id を冪等キーにするので、再送しても二重に投稿されない
+    const deliverMessage = (room: Room, content: string, clientMsgId: string) => {
+        if (wsSendMessage(content, clientMsgId)) return
+
+        api.sendMessage(room.name, content, undefined, clientMsgId)
+            .then(response => {
+                handleMessageAck(clientMsgId, response.message_id, response.timestamp)
+                setOfflineSentAt(Date.now())
+            })
+            .catch(error => {
+                console.error('Failed to send message via REST:', error)
+                handleMessageNack(clientMsgId, error.response?.data?.error?.message ?? error.message)
+            })
+    }
+
+    // 検索結果のメッセージへ移動する（別のルームならルームを切り替えてから）
+    const handleJumpToMessage = async (message: Message) => {
+        if (selectedRoom?.id !== message.room_id) {
+            try {
+                const response = await api.getRooms({ limit: 100 })
+                const room = (response.rooms as Room[]).find(r => r.id === message.room_id)
+                if (!room) {
+                    console.warn('Room of the search result is not in the room list:', message.room_id)
+                    return
+                }
+                await handleRoomSelect(room)
+            } catch (error) {
+                console.error('Failed to open the room of the search result:', error)
+                return
+            }
+        }
+        setHighlightMessageId(message.id)
+    }
+
     const handleRoomCreate = async (newRoom: Room) => {
         // Automatically join the newly created room
         await handleRoomSelect(newRoom)
@@ -169,6 +305,7 @@ export default function ChatPage() {
                         selectedRoom={selectedRoom}
                         onRoomSelect={handleRoomSelect}
                         onRoomCreate={handleRoomCreate}
+                        refreshKey={roomListVersion}
                     />
                 </div>
 
@@ -223,6 +360,11 @@ export default function ChatPage() {
                                 alignItems: 'center',
                                 gap: '0.5rem'
                             }}>
+                                {offlineSentAt !== null && (
+                                    <span style={{ fontSize: '12px', color: 'var(--foreground1)' }}>
+                                        送信しました（オフラインモード）
+                                    </span>
+                                )}
                                 {connectionStatus === 'connected' ? (
                                     <FaWifi 
                                         size={12} 
@@ -235,9 +377,15 @@ export default function ChatPage() {
                                             fontSize: '12px',
                                             color: 'var(--warning)'
                                         }}
-                                        title={connectionStatus === 'connecting' ? 'Connecting...' : 'Disconnected'}
+                                        title={
+                                            lastClose ? CLOSE_MESSAGES[lastClose.kind]
+                                                : connectionStatus === 'connecting' ? 'Connecting...'
+                                                : connectionStatus === 'reconnecting' ? 'Reconnecting...'
+                                                : 'Disconnected'
+                                        }
                                     >
-                                        ⚠
+                                        {connectionStatus === 'reconnecting' ? '↻' : '⚠'}
+                                        {lastClose && ` ${CLOSE_MESSAGES[lastClose.kind]}`}
                                     </span>
                                 )}
                                 
@@ -289,12 +437,14 @@ export default function ChatPage() {
                         <MessageList 
                             messages={messages.filter(msg => msg.room_id === selectedRoom.id)}
                             isLoading={isLoadingMessages}
+                            onRetry={handleRetryMessage}
+                            highlightMessageId={highlightMessageId}
                         />
 
                         {/* 入力エリア */}
+                        {/* 未接続の間も REST で送信できるので入力は無効にしない */}
                         <MessageInput
                             onSendMessage={handleSendMessage}
-                            disabled={connectionStatus !== 'connected'}
                             roomName={selectedRoom.name}
                         />
                     </>
@@ -319,7 +469,8 @@ export default function ChatPage() {
                 <SearchModal
                     isVisible={showSearchModal}
                     onClose={() => setShowSearchModal(false)}
-                    currentRoom={selectedRoom?.name}
+                    currentRoom={selectedRoom}
+                    onJumpToMessage={handleJumpToMessage}
                 />
             </div>
         </div>
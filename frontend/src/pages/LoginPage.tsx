@@ -1,6 +1,12 @@
+import { useSearchParams } from 'react-router-dom'
 import { useAuth } from '../contexts/AuthContext'
 import './LoginPage.css'
 
+// バックエンドが /login?error=... でリダイレクトしたときの表示
+const LOGIN_ERRORS: Record<string, string> = {
+    org_membership_required: '許可された GitHub 組織のメンバーのみログインできます',
+}
+
 const MIUCHI_ASCII_ART = `
 ███╗   ███╗ ██╗ ██╗   ██╗  ██████╗ ██╗  ██╗ ██╗     
 ████╗ ████║ ██║ ██║   ██║ ██╔════╝ ██║  ██║ ██║     
@@ -13,6 +19,9 @@ https://github.com/miuchi-net
 
 export default function LoginPage() {
     const { login, devLogin, isLoading } = useAuth()
+    const [searchParams] = useSearchParams()
+    const errorCode = searchParams.get('error')
+    const errorMessage = errorCode ? (LOGIN_ERRORS[errorCode] ?? 'ログインに失敗しました') : null
 
     const handleGitHubLogin = async () => {
         await login()
@@ -36,6 +45,8 @@ export default function LoginPage() {
 
                 {/* ログインボタン */}
                 <div className="login-section">
+                    {errorMessage && <div className="login-error">{errorMessage}</div>}
+
                     <button
                         className="login-button"
                         variant-="primary"
@@ -1,5 +1,5 @@
 import axios from 'axios'
-import type { TokenResponse, User } from '../types'
+import type { SearchResponse, SendMessageResponse, TokenResponse, User } from '../types'
 
 const API_BASE_URL = import.meta.env.VITE_API_URL || 'http://localhost:3001'
 
@@ -35,7 +35,8 @@ apiClient.interceptors.response.use(
         // HTTPステータスエラーの処理
         switch (error.response.status) {
             case 401:
-                console.warn('Authentication failed, redirecting to login')
+                // details.reason で失敗理由（token_expired / revoked_session など）がわかる
+                console.warn('Authentication failed, redirecting to login:', error.response.data?.error?.details?.reason)
                 localStorage.removeItem('token')
                 window.location.href = '/login'
                 break
@@ -66,8 +67,11 @@ export const api = {
         return response.data
     },
 
-    devLogin: async (): Promise<TokenResponse> => {
-        const response = await apiClient.post('/auth/dev-login')
+    // username を指定すると別の開発用ユーザーでログインする（複数ブラウザでの確認用）
+    devLogin: async (username?: string): Promise<TokenResponse> => {
+        const response = await apiClient.post('/auth/dev-login', undefined, {
+            params: username ? { username } : undefined,
+        })
         return response.data
     },
 
@@ -92,10 +96,18 @@ export const api = {
         return response.data
     },
 
-    sendMessage: async (room: string, content: string, messageType = 'text') => {
+    // REST の message_type は PascalCase（省略時は Text）。
+    // idempotencyKey を渡すと、再送しても同じメッセージとして扱われる
+    sendMessage: async (
+        room: string,
+        content: string,
+        messageType?: 'Text' | 'Image' | 'File',
+        idempotencyKey?: string
+    ): Promise<SendMessageResponse> => {
         const response = await apiClient.post(`/chat/${room}/send`, {
             content,
             message_type: messageType,
+            idempotency_key: idempotencyKey,
         })
         return response.data
     },
@@ -109,13 +121,22 @@ export const api = {
         return response.data
     },
 
-    getRooms: async () => {
-        const response = await apiClient.get('/chat/rooms')
+    getRooms: async (options: { q?: string; limit?: number; offset?: number } = {}) => {
+        const params = new URLSearchParams()
+        if (options.q) params.append('q', options.q)
+        if (options.limit) params.append('limit', options.limit.toString())
+        if (options.offset) params.append('offset', options.offset.toString())
+
+        const response = await apiClient.get(`/chat/rooms?${params}`)
         return response.data
     },
 
-    getRoomMembers: async (room: string) => {
-        const response = await apiClient.get(`/chat/${room}/members`)
+    getRoomMembers: async (room: string, options: { limit?: number; offset?: number } = {}) => {
+        const params = new URLSearchParams()
+        if (options.limit) params.append('limit', options.limit.toString())
+        if (options.offset) params.append('offset', options.offset.toString())
+
+        const response = await apiClient.get(`/chat/${room}/members?${params}`)
         return response.data
     },
 
@@ -132,9 +153,19 @@ export const api = {
     },
 
     // Search endpoints
-    searchMessages: async (query: string, room?: string) => {
+    // room / author はルーム名・ユーザー名で絞り込む
+    searchMessages: async (
+        query: string,
+        room?: string,
+        author?: string,
+        limit?: number,
+        offset?: number
+    ): Promise<SearchResponse> => {
         const params = new URLSearchParams({ q: query })
         if (room) params.append('room', room)
+        if (author) params.append('author', author)
+        if (limit) params.append('limit', limit.toString())
+        if (offset) params.append('offset', offset.toString())
 
         const response = await apiClient.get(`/search/messages?${params}`)
         return response.data
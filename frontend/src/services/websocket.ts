@@ -5,11 +5,12 @@ const WS_URL = import.meta.env.VITE_WS_URL || 'ws://localhost:3001/ws'
 // WebSocket接続の設定
 const HEARTBEAT_INTERVAL = 30000 // 30秒
 const CONNECTION_TIMEOUT = 10000 // 10秒
-const MAX_RECONNECT_ATTEMPTS = 5 // 再接続試行回数を減らす
-const RECONNECT_DELAY_BASE = 2000 // 2秒に増加
-const MAX_RECONNECT_DELAY = 60000 // 60秒に増加
+const RECONNECT_DELAY_BASE = 1000 // 1秒から開始（1s, 2s, 4s...）
+const MAX_RECONNECT_DELAY = 30000 // 最大30秒
+const RECONNECT_JITTER_RATIO = 0.3 // 遅延の±30%のジッター
 const MESSAGE_QUEUE_SIZE = 100
-const MIN_RECONNECT_INTERVAL = 5000 // 最小再接続間隔: 5秒
+const WS_SUBPROTOCOL = 'miuchi-chat' // サーバー側 src/ws.rs の WS_SUBPROTOCOL と同期
+const WS_AUTH_PROTOCOL_PREFIX = 'bearer.'
 
 interface QueuedMessage {
     message: WsMessage
@@ -17,6 +18,30 @@ interface QueuedMessage {
     retries: number
 }
 
+// サーバーが接続を閉じた理由の分類（src/ws.rs のクローズコードと reason に対応）
+type WsCloseKind =
+    | 'normal'           // 1000: 正常終了
+    | 'server_shutdown'  // 1001 / 1012: サーバーの停止・再起動
+    | 'auth_failed'      // 1008: トークンなし・無効・期限切れ、ユーザー削除
+    | 'connection_limit' // 1008: 同時接続数の上限
+    | 'replaced'         // 1008: 新しい接続に置き換えられた
+    | 'policy'           // 1008 / 1003: その他のポリシー違反
+    | 'too_large'        // 1009: フレームが大きすぎる
+    | 'slow_consumer'    // 1013: 受信が追いつかない
+    | 'server_error'     // 1011: サーバー内部エラー
+    | 'abnormal'         // 1006 など: ネットワーク切断
+
+interface WsCloseInfo {
+    code: number
+    reason: string
+    kind: WsCloseKind
+    // 自動で再接続するか（false ならログインし直すなどの操作が必要）
+    willReconnect: boolean
+}
+
+// 認証失敗時の reason（src/ws.rs の WsAuthError）
+const AUTH_CLOSE_REASONS = ['missing token', 'invalid token', 'user not found']
+
 enum ConnectionState {
     DISCONNECTED = 'disconnected',
     CONNECTING = 'connecting',
@@ -29,16 +54,17 @@ class WebSocketService {
     private socket: WebSocket | null = null
     private messageHandlers: Set<(message: WsMessage) => void> = new Set()
     private connectionStateHandlers: Set<(state: ConnectionState) => void> = new Set()
+    private closeHandlers: Set<(info: WsCloseInfo) => void> = new Set()
     private reconnectAttempts = 0
-    private maxReconnectAttempts = MAX_RECONNECT_ATTEMPTS
     private reconnectTimeout: number | null = null
     private heartbeatInterval: number | null = null
     private connectionState: ConnectionState = ConnectionState.DISCONNECTED
     private messageQueue: QueuedMessage[] = []
     private currentToken: string = ''
     private lastPingTime: number = 0
-    private lastReconnectAttempt: number = 0 // 最後の再接続試行時刻
+    private serverTimeOffset: number = 0 // サーバー時刻 - ローカル時刻（ミリ秒）
     private isManualDisconnect: boolean = false // 手動切断フラグ
+    private joinedRooms: Set<string> = new Set() // 再接続時に再参加するルーム
     private connectionMetrics = {
         totalConnections: 0,
         totalReconnections: 0,
@@ -53,19 +79,28 @@ class WebSocketService {
             this.currentToken = token
             this.isManualDisconnect = false // 手動切断フラグをリセット
             
+            // 既存のソケットは再接続を誘発しないようにハンドラーを外して閉じる
             if (this.socket) {
-                this.disconnect()
+                this.socket.onopen = null
+                this.socket.onmessage = null
+                this.socket.onclose = null
+                this.socket.onerror = null
+                this.socket.close(1000, 'Reconnecting')
+                this.socket = null
             }
 
-            this.updateConnectionState(ConnectionState.CONNECTING)
+            const isReconnect = this.reconnectAttempts > 0
+            if (!isReconnect) {
+                this.updateConnectionState(ConnectionState.CONNECTING)
+            }
             this.connectionMetrics.totalConnections++
 
             try {
-                // WebSocketのURLにtokenをクエリパラメータとして追加
-                const wsUrl = `${WS_URL}?token=${encodeURIComponent(token)}`
+                // トークンはURLに載せず（ログや履歴に残るため）サブプロトコルで渡す
+                const wsUrl = WS_URL
                 console.log('Attempting to connect to:', wsUrl)
                 
-                this.socket = new WebSocket(wsUrl)
+                this.socket = new WebSocket(wsUrl, WebSocketService.authProtocols(token))
                 
                 // 接続タイムアウト
                 const connectionTimeout = setTimeout(() => {
@@ -82,6 +117,7 @@ class WebSocketService {
                     this.updateConnectionState(ConnectionState.CONNECTED)
                     this.reconnectAttempts = 0
                     this.startHeartbeat()
+                    this.rejoinRooms()
                     this.processMessageQueue()
                     resolve()
                 }
@@ -92,18 +128,14 @@ class WebSocketService {
 
                 this.socket.onclose = (event) => {
                     clearTimeout(connectionTimeout)
+                    // 接続確立前に閉じられた場合はPromiseを失敗させる（既に解決済みなら無視される）
+                    reject(new Error(`Connection failed: ${event.code} ${event.reason}`))
                     this.handleClose(event)
-                    if (this.connectionState === ConnectionState.CONNECTING) {
-                        reject(new Error(`Connection failed: ${event.code} ${event.reason}`))
-                    }
                 }
 
                 this.socket.onerror = (error) => {
                     clearTimeout(connectionTimeout)
                     this.handleConnectionError(error)
-                    if (this.connectionState === ConnectionState.CONNECTING) {
-                        reject(error)
-                    }
                 }
             } catch (error) {
                 console.error('Failed to create WebSocket connection:', error)
@@ -125,11 +157,17 @@ class WebSocketService {
         
         this.updateConnectionState(ConnectionState.DISCONNECTED)
         this.messageQueue = []
+        this.joinedRooms.clear()
         this.reconnectAttempts = 0 // 再接続カウンターをリセット
         console.log('WebSocket disconnected by client')
     }
 
     joinRoom(room: string) {
+        this.joinedRooms.add(room)
+        // 未接続の場合は再接続時の再参加で送信されるのでキューには積まない
+        if (!this.isConnected()) {
+            return
+        }
         const message: WsMessage = {
             type: 'join_room',
             room,
@@ -137,17 +175,33 @@ class WebSocketService {
         this.sendMessage(message)
     }
 
-    sendChatMessage(room: string, content: string, messageType = 'text') {
+    // 開いているソケットのまま参加ルームを切り替える（leave_room → join_room、再接続はしない）
+    switchRoom(from: string | null, to: string) {
+        if (from === to) {
+            return
+        }
+        if (from) {
+            this.leaveRoom(from)
+        }
+        this.joinRoom(to)
+    }
+
+    sendChatMessage(room: string, content: string, messageType = 'text', clientMsgId?: string) {
         const message: WsMessage = {
             type: 'send_message',
             room,
             content,
             message_type: messageType,
+            client_msg_id: clientMsgId,
         }
         this.sendMessage(message)
     }
 
     leaveRoom(room: string) {
+        this.joinedRooms.delete(room)
+        if (!this.isConnected()) {
+            return
+        }
         const message: WsMessage = {
             type: 'leave_room',
             room,
@@ -176,6 +230,12 @@ class WebSocketService {
         return () => this.messageHandlers.delete(wrappedHandler)
     }
     
+    // サーバー側から閉じられたときに通知する（手動切断では呼ばれない）
+    onClose(handler: (info: WsCloseInfo) => void) {
+        this.closeHandlers.add(handler)
+        return () => this.closeHandlers.delete(handler) // 登録解除関数を返す
+    }
+
     onConnectionStateChange(handler: (state: ConnectionState) => void) {
         this.connectionStateHandlers.add(handler)
         return () => this.connectionStateHandlers.delete(handler) // 登録解除関数を返す
@@ -192,6 +252,20 @@ class WebSocketService {
     getConnectionMetrics() {
         return { ...this.connectionMetrics }
     }
+
+    getReconnectAttempts(): number {
+        return this.reconnectAttempts
+    }
+
+    // 接続時に受け取った server_time から求めた、サーバーとの時計のずれ（ミリ秒）
+    getServerTimeOffset(): number {
+        return this.serverTimeOffset
+    }
+
+    // サーバーの時計での現在時刻（「◯分前」などの相対時刻の基準に使う）
+    serverNow(): number {
+        return Date.now() + this.serverTimeOffset
+    }
     
     // プライベートメソッド
     private sendMessage(message: WsMessage) {
@@ -229,12 +303,20 @@ class WebSocketService {
         
         console.debug('Message queued:', message.type)
         
-        // 再接続を試行
-        if (this.connectionState === ConnectionState.DISCONNECTED) {
+        // 再接続を試行（手動切断後は再接続しない）
+        if (this.connectionState === ConnectionState.DISCONNECTED && !this.isManualDisconnect) {
             this.reconnect()
         }
     }
     
+    // 再接続後に参加中だったルームへ再参加
+    private rejoinRooms() {
+        this.joinedRooms.forEach(room => {
+            console.log('Rejoining room after (re)connect:', room)
+            this.sendMessage({ type: 'join_room', room })
+        })
+    }
+    
     private processMessageQueue() {
         if (this.messageQueue.length === 0) return
         
@@ -266,6 +348,15 @@ class WebSocketService {
                 return
             }
             
+            // サーバー時刻との差を記録（伝送遅延の分はずれとして残る）
+            if (message.type === 'server_time') {
+                const serverTime = Date.parse(message.server_time)
+                if (!Number.isNaN(serverTime)) {
+                    this.serverTimeOffset = serverTime - Date.now()
+                }
+                return
+            }
+            
             // レート制限エラーの処理
             if (message.type === 'rate_limited') {
                 console.warn('Rate limited, retry after:', message.retry_after, 'seconds')
@@ -297,24 +388,23 @@ class WebSocketService {
             return
         }
         
-        if (event.code === 1000) {
-            // 正常な切断
-            this.updateConnectionState(ConnectionState.DISCONNECTED)
-            return
-        }
-        
-        if (event.code === 1008 || event.code === 1003) {
-            // 認証エラーまたは無効なデータ
-            console.error('Connection rejected by server:', event.reason)
-            this.updateConnectionState(ConnectionState.FAILED)
-            return
-        }
-        
-        // 予期しない切断の場合は再接続を試行
-        if (this.reconnectAttempts < this.maxReconnectAttempts) {
+        const info = WebSocketService.classifyClose(event.code, event.reason)
+        this.closeHandlers.forEach(handler => {
+            try {
+                handler(info)
+            } catch (error) {
+                console.error('Error in close handler:', error)
+            }
+        })
+
+        if (info.willReconnect) {
+            // 予期しない切断の場合は再接続を試行
             this.scheduleReconnect()
+        } else if (info.kind === 'normal') {
+            this.updateConnectionState(ConnectionState.DISCONNECTED)
         } else {
-            console.error('Max reconnection attempts reached')
+            // 認証エラーや接続数超過など、再接続しても解決しない
+            console.error('Connection rejected by server:', event.code, event.reason)
             this.updateConnectionState(ConnectionState.FAILED)
         }
     }
@@ -338,32 +428,17 @@ class WebSocketService {
     }
     
     private scheduleReconnect() {
-        if (this.reconnectTimeout) {
-            return
-        }
-        
-        // 最小再接続間隔をチェック
-        const now = Date.now()
-        const timeSinceLastAttempt = now - this.lastReconnectAttempt
-        if (timeSinceLastAttempt < MIN_RECONNECT_INTERVAL) {
-            const additionalDelay = MIN_RECONNECT_INTERVAL - timeSinceLastAttempt
-            console.log(`Delaying reconnect by additional ${additionalDelay}ms to respect minimum interval`)
-            setTimeout(() => this.scheduleReconnect(), additionalDelay)
+        if (this.reconnectTimeout || this.isManualDisconnect) {
             return
         }
         
         this.updateConnectionState(ConnectionState.RECONNECTING)
         this.reconnectAttempts++
         this.connectionMetrics.totalReconnections++
-        this.lastReconnectAttempt = now
         
-        // 指数バックオフ
-        const delay = Math.min(
-            RECONNECT_DELAY_BASE * Math.pow(2, this.reconnectAttempts - 1),
-            MAX_RECONNECT_DELAY
-        )
+        const delay = WebSocketService.computeReconnectDelay(this.reconnectAttempts)
         
-        console.log(`Scheduling reconnect in ${delay}ms (attempt ${this.reconnectAttempts}/${this.maxReconnectAttempts})`)
+        console.log(`Scheduling reconnect in ${delay}ms (attempt ${this.reconnectAttempts})`)
         
         this.reconnectTimeout = window.setTimeout(() => {
             this.reconnectTimeout = null
@@ -371,21 +446,61 @@ class WebSocketService {
         }, delay)
     }
     
+    // クローズコードと reason から切断理由と再接続するかを決める
+    static classifyClose(code: number, reason: string): WsCloseInfo {
+        const info = (kind: WsCloseKind, willReconnect: boolean): WsCloseInfo =>
+            ({ code, reason, kind, willReconnect })
+
+        switch (code) {
+            case 1000:
+                return info('normal', false)
+            case 1001:
+            case 1012:
+                return info('server_shutdown', true)
+            case 1003:
+                return info('policy', false)
+            case 1008:
+                if (AUTH_CLOSE_REASONS.includes(reason)) return info('auth_failed', false)
+                if (reason === 'Connection limit exceeded') return info('connection_limit', false)
+                if (reason === 'Replaced by a newer connection') return info('replaced', false)
+                return info('policy', false)
+            case 1009:
+                return info('too_large', true)
+            case 1011:
+                return info('server_error', true)
+            case 1013:
+                return info('slow_consumer', true)
+            default:
+                return info('abnormal', true)
+        }
+    }
+
+    // 認証用のサブプロトコル（サーバーは miuchi-chat を選択して返す）
+    static authProtocols(token: string): string[] {
+        return [WS_SUBPROTOCOL, `${WS_AUTH_PROTOCOL_PREFIX}${token}`]
+    }
+
+    // 指数バックオフ（1s, 2s, 4s... 最大30s）にジッターを加えた遅延
+    static computeReconnectDelay(attempt: number, random: () => number = Math.random): number {
+        const exponential = Math.min(
+            RECONNECT_DELAY_BASE * Math.pow(2, Math.max(attempt - 1, 0)),
+            MAX_RECONNECT_DELAY
+        )
+        const jitter = exponential * RECONNECT_JITTER_RATIO * (random() * 2 - 1)
+        return Math.max(0, Math.round(exponential + jitter))
+    }
+    
     private async reconnect() {
-        if (!this.currentToken) {
-            console.error('Cannot reconnect: no token available')
+        if (!this.currentToken || this.isManualDisconnect) {
+            console.error('Cannot reconnect: no token available or disconnected manually')
             return
         }
         
         try {
             await this.connect(this.currentToken)
         } catch (error) {
+            // 失敗時の再スケジュールは onclose -> handleClose で行われる
             console.error('Reconnection failed:', error)
-            if (this.reconnectAttempts < this.maxReconnectAttempts) {
-                this.scheduleReconnect()
-            } else {
-                this.updateConnectionState(ConnectionState.FAILED)
-            }
         }
     }
     
@@ -476,5 +591,5 @@ export class WebSocketManager {
 }
 
 // エクスポート
-export { ConnectionState }
-export type { QueuedMessage }
+export { ConnectionState, WebSocketService }
+export type { QueuedMessage, WsCloseInfo, WsCloseKind }
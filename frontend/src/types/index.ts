@@ -6,15 +6,45 @@ export interface User {
     display_name?: string;
 }
 
+export type MessageType = 'text' | 'image' | 'file' | 'system';
+
+const MESSAGE_TYPES: readonly MessageType[] = ['text', 'image', 'file', 'system'];
+
+// サーバーの message_type を正規化する
+// （WebSocket は "image"、REST は "Image" の形式。未知の値は text として扱う）
+export function normalizeMessageType(value: unknown): MessageType {
+    const type = typeof value === 'string' ? value.toLowerCase() : '';
+    return (MESSAGE_TYPES as readonly string[]).includes(type) ? (type as MessageType) : 'text';
+}
+
 export interface Message {
     id: string;
     room_id: string;
     author_id: string;
     author_name: string;
     author_avatar?: string;
+    author_is_bot?: boolean;
     content: string;
-    message_type?: 'text' | 'image' | 'file' | 'system';
+    // サーバーで RENDER_MARKDOWN が有効なときだけ付く、サニタイズ済みの HTML（正は content）
+    rendered_html?: string | null;
+    message_type?: MessageType;
     created_at: string;
+    updated_at?: string;
+    edited?: boolean;
+    // ルーム内の投稿順の連番（送信中のメッセージにはまだない）
+    seq?: number;
+    // 引用しているメッセージ（引用元が削除された場合は null）
+    quoted?: QuotedMessage | null;
+    // 楽観的送信用（クライアント側のみ）
+    client_msg_id?: string;
+    status?: 'pending' | 'sent' | 'failed';
+}
+
+// 引用元メッセージの表示用の要約
+export interface QuotedMessage {
+    id: string;
+    username: string;
+    content_preview: string;
 }
 
 export interface Room {
@@ -23,6 +53,16 @@ export interface Room {
     description?: string;
     is_public?: boolean;
     created_at?: string;
+    last_message?: LastMessagePreview | null;
+    last_activity_at?: string;
+    member_count?: number;
+    message_count?: number;
+}
+
+export interface LastMessagePreview {
+    content: string;
+    username: string;
+    timestamp: string;
 }
 
 export interface TokenResponse {
@@ -40,15 +80,24 @@ export interface MessagesResponse {
     messages: Message[];
     total: number;
     has_more: boolean;
+    // 同じ向きの続き用（通常は最古のIDを before に、after 指定時は最新のIDを after に渡す）
+    next_cursor?: string | null;
+    // 逆向き用（通常は最新のIDを after に、after 指定時は最古のIDを before に渡す）
+    prev_cursor?: string | null;
+    // 応答時のサーバー時刻（時計のずれの補正に使う）
+    server_time: string;
 }
 
 export interface SendMessageRequest {
     content: string;
     message_type?: 'text' | 'image' | 'file';
+    idempotency_key?: string;
+    reply_to_message_id?: string;
 }
 
 export interface SendMessageResponse {
-    id: string;
+    message_id: string;
+    seq: number;
     timestamp: string;
 }
 
@@ -60,10 +109,14 @@ export interface RoomMember {
 
 export interface RoomMembersResponse {
     members: RoomMember[];
+    total: number;
+    has_more: boolean;
 }
 
 export interface RoomsResponse {
     rooms: Room[];
+    total: number;
+    has_more: boolean;
 }
 
 export interface InviteUserRequest {
@@ -80,6 +133,7 @@ export interface OnlineUser {
     username: string;
     connected_rooms: string[];
     connected_at: string;
+    connection_count: number;
 }
 
 export interface OnlineUsersResponse {
@@ -87,21 +141,109 @@ export interface OnlineUsersResponse {
     total_count: number;
 }
 
+// History で再送されるメッセージ
+export interface WsHistoryMessage {
+    id: string;
+    user_id: string;
+    username: string;
+    avatar_url: string | null;
+    is_bot: boolean;
+    content: string;
+    rendered_html?: string | null;
+    message_type: string;
+    seq: number;
+    timestamp: string;
+    quoted?: QuotedMessage | null;
+}
+
+// WebSocket の error メッセージの code（protocol クレートの WsErrorCode と同期すること）
+export const WS_ERROR_CODES = {
+    INVALID_JSON: 4000,
+    UNSUPPORTED_FRAME: 4001,
+    MESSAGE_TOO_LARGE: 4002,
+    INVALID_MESSAGE: 4003,
+    REQUEST_FAILED: 4004,
+    TIMEOUT: 4005,
+    MESSAGES_DROPPED: 4006,
+    INTERNAL: 4010,
+} as const;
+
+export type WsErrorCode = typeof WS_ERROR_CODES[keyof typeof WS_ERROR_CODES];
+
 // WebSocket message types
+// サーバー側 src/ws.rs の WsMessage と同期すること（tests/fixtures/ws_messages.json で検証）
 export type WsMessage =
     | { type: 'join_room'; room: string }
-    | { type: 'send_message'; room: string; content: string; message_type?: string }
+    | { type: 'send_message'; room: string; content: string; message_type?: string; client_msg_id?: string; reply_to_message_id?: string | null }
     | { type: 'leave_room'; room: string }
     | { type: 'ping'; timestamp?: number }
-    | { type: 'room_joined'; room: string; user_id: string; username: string }
-    | { type: 'message'; id: string; room: string; user_id: string; username: string; content: string; message_type: string; timestamp: string }
+    | { type: 'join_all' }
+    | { type: 'leave_all' }
+    | { type: 'sync'; room: string; since_message_id: string }
+    | { type: 'mark_read'; room: string; message_id: string }
+    | { type: 'webrtc_offer'; room: string; to_user_id: string; offer: any }
+    | { type: 'webrtc_answer'; room: string; to_user_id: string; answer: any }
+    | { type: 'webrtc_ice_candidate'; room: string; to_user_id: string; candidate: any }
+    | { type: 'room_joined'; room: string; user_id: string; username: string; last_read_message_id?: string | null }
+    | { type: 'message'; id: string; room: string; user_id: string; username: string; avatar_url: string | null; is_bot: boolean; content: string; rendered_html?: string | null; message_type: string; seq: number; timestamp: string; quoted?: QuotedMessage | null }
+    | { type: 'message_ack'; client_msg_id: string; server_id: string; timestamp: string }
+    | { type: 'message_nack'; client_msg_id: string; reason: string }
     | { type: 'user_joined'; room: string; user_id: string; username: string }
     | { type: 'user_left'; room: string; user_id: string; username: string }
+    | { type: 'member_added'; room: string; user_id: string; username: string }
+    | { type: 'invited_to_room'; room: string; by_username: string }
+    | { type: 'room_updated'; old_name: string; new_name: string; description: string | null; is_public: boolean }
+    | { type: 'message_pinned'; room: string; message_id: string; pinned_by: string; pinned_at: string }
+    | { type: 'message_unpinned'; room: string; message_id: string; unpinned_by: string }
+    | { type: 'seen_update'; room: string; message_id: string; user_id: string }
+    | { type: 'joined_rooms'; rooms: string[] }
+    | { type: 'left_all'; rooms: string[] }
+    | { type: 'history'; room: string; messages: WsHistoryMessage[]; has_more: boolean }
+    | { type: 'link_preview'; room: string; message_id: string; url: string; title: string | null; description: string | null; image_url: string | null }
+    | { type: 'mentioned'; room: string; message_id: string; user_id: string; username: string; content: string; timestamp: string }
     | { type: 'pong'; timestamp?: number }
-    | { type: 'error'; message: string; code?: number }
+    | { type: 'server_time'; server_time: string }
+    | { type: 'error'; message: string; code?: WsErrorCode | null }
     | { type: 'auth_required' }
     | { type: 'rate_limited'; retry_after: number };
 
+// 全メッセージタイプの一覧（型で網羅性を強制）
+export const WS_MESSAGE_TYPES: { [K in WsMessage['type']]: true } = {
+    join_room: true,
+    send_message: true,
+    leave_room: true,
+    ping: true,
+    join_all: true,
+    leave_all: true,
+    sync: true,
+    mark_read: true,
+    webrtc_offer: true,
+    webrtc_answer: true,
+    webrtc_ice_candidate: true,
+    room_joined: true,
+    message: true,
+    message_ack: true,
+    message_nack: true,
+    user_joined: true,
+    user_left: true,
+    member_added: true,
+    invited_to_room: true,
+    room_updated: true,
+    message_pinned: true,
+    message_unpinned: true,
+    seen_update: true,
+    joined_rooms: true,
+    left_all: true,
+    history: true,
+    link_preview: true,
+    mentioned: true,
+    pong: true,
+    server_time: true,
+    error: true,
+    auth_required: true,
+    rate_limited: true,
+};
+
 // Search types
 export interface SearchResult {
     message: Message;
@@ -112,6 +254,38 @@ export interface SearchResult {
 export interface SearchResponse {
     results: SearchResult[];
     total_hits: number;
+    // total_hits が推定値か
+    total_hits_estimated: boolean;
     query_time_ms: number;
     has_more: boolean;
+    // Meilisearch に障害があり Postgres で検索した（ハイライト・関連度なし）
+    degraded: boolean;
+    server_time: string;
+}
+
+export interface HighlightSegment {
+    text: string;
+    highlighted: boolean;
+}
+
+// Meilisearch のハイライト（<mark> で囲まれた箇所）を分割する。
+// 本文はエスケープされていないため HTML として挿入せず、テキストとして描画すること
+export function parseHighlight(highlight: string): HighlightSegment[] {
+    const segments: HighlightSegment[] = [];
+    const pattern = /<mark>([\s\S]*?)<\/mark>/g;
+    let last = 0;
+    for (const match of highlight.matchAll(pattern)) {
+        const index = match.index ?? 0;
+        if (index > last) {
+            segments.push({ text: highlight.slice(last, index), highlighted: false });
+        }
+        if (match[1]) {
+            segments.push({ text: match[1], highlighted: true });
+        }
+        last = index + match[0].length;
+    }
+    if (last < highlight.length) {
+        segments.push({ text: highlight.slice(last), highlighted: false });
+    }
+    return segments;
 }
@@ -0,0 +1,10 @@
+-- Add role column to room_members for moderation permissions
+ALTER TABLE room_members ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'member';
+
+ALTER TABLE room_members ADD CONSTRAINT chk_room_members_role
+    CHECK (role IN ('owner', 'moderator', 'member'));
+
+-- Existing room creators become owners of their rooms
+UPDATE room_members rm SET role = 'owner'
+FROM rooms r
+WHERE rm.room_id = r.id AND rm.user_id = r.created_by;
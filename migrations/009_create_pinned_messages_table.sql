@@ -0,0 +1,11 @@
+-- Create pinned messages table
+CREATE TABLE pinned_messages (
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    pinned_by UUID NOT NULL REFERENCES users(id),
+    pinned_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (room_id, message_id)
+);
+
+-- Create index for newest-first listing
+CREATE INDEX idx_pinned_messages_room_pinned_at ON pinned_messages(room_id, pinned_at DESC);
@@ -0,0 +1,2 @@
+-- Functional index for case-insensitive username lookups (invites)
+CREATE INDEX idx_users_username_lower ON users(LOWER(username));
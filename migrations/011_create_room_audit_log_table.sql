@@ -0,0 +1,13 @@
+-- Create audit log for room administrative actions
+CREATE TABLE room_audit_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    actor_id UUID NOT NULL REFERENCES users(id),
+    action VARCHAR(50) NOT NULL,
+    target_id UUID,
+    metadata JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Create index for newest-first listing per room
+CREATE INDEX idx_room_audit_log_room_created_at ON room_audit_log(room_id, created_at DESC);
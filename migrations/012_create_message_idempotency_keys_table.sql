@@ -0,0 +1,11 @@
+-- Create idempotency keys for deduplicating retried message sends
+CREATE TABLE message_idempotency_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    idempotency_key VARCHAR(255) NOT NULL,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, idempotency_key)
+);
+
+-- Create index for periodic cleanup of expired keys
+CREATE INDEX idx_message_idempotency_keys_created_at ON message_idempotency_keys(created_at);
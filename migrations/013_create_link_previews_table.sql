@@ -0,0 +1,10 @@
+-- Create Open Graph previews for URLs posted in messages
+CREATE TABLE link_previews (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    title TEXT,
+    description TEXT,
+    image_url TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (message_id, url)
+);
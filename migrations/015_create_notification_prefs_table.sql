@@ -0,0 +1,14 @@
+-- Create notification modes enum
+CREATE TYPE notification_mode AS ENUM ('all', 'mentions', 'none');
+
+-- Create per-user notification preferences (room_id NULL = global default)
+CREATE TABLE notification_prefs (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE,
+    mode notification_mode NOT NULL DEFAULT 'all',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- One global default and at most one override per room for each user
+CREATE UNIQUE INDEX idx_notification_prefs_global ON notification_prefs(user_id) WHERE room_id IS NULL;
+CREATE UNIQUE INDEX idx_notification_prefs_room ON notification_prefs(user_id, room_id) WHERE room_id IS NOT NULL;
@@ -0,0 +1,17 @@
+-- Bot identities that post through incoming webhooks are users rows flagged as bots
+ALTER TABLE users ADD COLUMN is_bot BOOLEAN NOT NULL DEFAULT false;
+
+-- Bots have no GitHub account; they get negative github_id values so they never collide with real ones
+CREATE SEQUENCE bot_github_id_seq;
+
+-- Create incoming webhook tokens (only the SHA-256 hash of the token is stored)
+CREATE TABLE webhook_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    bot_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_webhook_tokens_room_id ON webhook_tokens(room_id);
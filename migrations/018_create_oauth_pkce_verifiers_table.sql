@@ -0,0 +1,9 @@
+-- Create PKCE code verifiers for in-flight OAuth logins (keyed by the state token's nonce, single use)
+CREATE TABLE oauth_pkce_verifiers (
+    nonce VARCHAR(64) PRIMARY KEY,
+    verifier VARCHAR(128) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Create index for removing abandoned logins
+CREATE INDEX idx_oauth_pkce_verifiers_created_at ON oauth_pkce_verifiers(created_at);
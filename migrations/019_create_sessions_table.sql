@@ -0,0 +1,13 @@
+-- Create sessions table (one row per issued access token, keyed by the token's jti)
+CREATE TABLE sessions (
+    jti UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_agent TEXT,
+    ip VARCHAR(64),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Create index for listing a user's sessions
+CREATE INDEX idx_sessions_user_id ON sessions(user_id);
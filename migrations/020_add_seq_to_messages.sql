@@ -0,0 +1,40 @@
+-- Per-room monotonic sequence number for messages (authoritative sort key and cursor)
+ALTER TABLE messages ADD COLUMN seq BIGINT;
+
+-- Backfill existing messages in posting order
+UPDATE messages m
+SET seq = numbered.seq
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY room_id ORDER BY created_at, id) AS seq
+    FROM messages
+) numbered
+WHERE m.id = numbered.id;
+
+ALTER TABLE messages ALTER COLUMN seq SET NOT NULL;
+
+CREATE UNIQUE INDEX idx_messages_room_id_seq ON messages(room_id, seq);
+
+-- Per-room counter (kept out of rooms so that posting does not bump rooms.updated_at)
+CREATE TABLE room_message_seqs (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    last_seq BIGINT NOT NULL
+);
+
+INSERT INTO room_message_seqs (room_id, last_seq)
+SELECT room_id, MAX(seq) FROM messages GROUP BY room_id;
+
+-- Assign the next seq from the room's counter on insert. The counter row lock
+-- serializes concurrent inserts into the same room, so seq never goes backwards.
+CREATE OR REPLACE FUNCTION assign_message_seq()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO room_message_seqs (room_id, last_seq)
+    VALUES (NEW.room_id, 1)
+    ON CONFLICT (room_id) DO UPDATE SET last_seq = room_message_seqs.last_seq + 1
+    RETURNING last_seq INTO NEW.seq;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER assign_messages_seq BEFORE INSERT ON messages
+    FOR EACH ROW EXECUTE FUNCTION assign_message_seq();
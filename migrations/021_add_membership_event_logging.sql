@@ -0,0 +1,8 @@
+-- Per-room opt-in: record membership changes (invites) as system messages in the history
+ALTER TABLE rooms ADD COLUMN log_membership_events BOOLEAN NOT NULL DEFAULT false;
+
+-- Author of system messages. github_id 0 is never a real GitHub account
+-- (real ids are positive, bots and named dev users are negative)
+INSERT INTO users (id, github_id, username, is_bot)
+VALUES ('00000000-0000-0000-0000-000000000000'::UUID, 0, 'system', true)
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,2 @@
+-- Per-room slow mode: minimum seconds between consecutive messages from the same user (NULL = off)
+ALTER TABLE rooms ADD COLUMN slow_mode_seconds INTEGER CHECK (slow_mode_seconds > 0);
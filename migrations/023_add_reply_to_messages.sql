@@ -0,0 +1,2 @@
+-- Inline quoting: a message may reference another message in the same room (display only, no thread hierarchy)
+ALTER TABLE messages ADD COLUMN reply_to_message_id UUID REFERENCES messages(id) ON DELETE SET NULL;
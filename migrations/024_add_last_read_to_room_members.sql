@@ -0,0 +1,2 @@
+-- Read markers: the latest message each member has read in the room (NULL = nothing read yet)
+ALTER TABLE room_members ADD COLUMN last_read_message_id UUID REFERENCES messages(id) ON DELETE SET NULL;
@@ -0,0 +1,15 @@
+-- Create message reports table (moderation queue; one report per user per message)
+CREATE TABLE message_reports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    reporter_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    resolved BOOLEAN NOT NULL DEFAULT false,
+    resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_at TIMESTAMPTZ,
+    UNIQUE (message_id, reporter_id)
+);
+
+-- Create index for listing open reports
+CREATE INDEX idx_message_reports_open ON message_reports(message_id) WHERE NOT resolved;
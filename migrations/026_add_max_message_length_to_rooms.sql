@@ -0,0 +1,2 @@
+-- Per-room override of the message length limit in characters (NULL = server default)
+ALTER TABLE rooms ADD COLUMN max_message_length INTEGER CHECK (max_message_length > 0);
@@ -0,0 +1,4 @@
+-- Indexes for the message list ETag: an index-only visible-message count per room
+-- (excluding blocked authors) and the newest edit per room
+CREATE INDEX idx_messages_room_id_user_id ON messages(room_id, user_id);
+CREATE INDEX idx_messages_room_id_updated_at ON messages(room_id, updated_at DESC);
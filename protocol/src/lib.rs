@@ -0,0 +1,282 @@
+//! miuchi.chat の WebSocket プロトコル定義
+//!
+//! サーバー（miuchi-chat）とクライアントの両方が依存する、ワイヤーフォーマットの唯一の定義。
+//! `wasm32-unknown-unknown` でもビルドできるよう、サーバー専用の依存は持たない。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// History で再送されるメッセージ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryMessage {
+    pub id: String,
+    pub user_id: String,
+    pub username: String,
+    pub avatar_url: Option<String>,
+    /// 受信 Webhook などのボットによる投稿か（古いクライアント向けに省略可）
+    #[serde(default)]
+    pub is_bot: bool,
+    pub content: String,
+    /// `content` の Markdown をサニタイズした HTML（サーバーで有効な場合のみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered_html: Option<String>,
+    pub message_type: String,
+    /// ルーム内の投稿順の連番（古いサーバー向けに省略可）
+    #[serde(default)]
+    pub seq: i64,
+    pub timestamp: DateTime<Utc>,
+    /// 引用しているメッセージ（古いサーバー向けに省略可）
+    #[serde(default)]
+    pub quoted: Option<QuotedMessage>,
+}
+
+// 引用元メッセージの表示用の要約（返信の階層は作らない）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotedMessage {
+    pub id: String,
+    pub username: String,
+    /// 本文の先頭（長い場合は切り詰めて末尾に `…`）
+    pub content_preview: String,
+}
+
+/// `error` メッセージの `code`
+///
+/// WebSocket のクローズコード（1000 番台）と混同しないよう、アプリケーション定義の 4000 番台を使う。
+/// クライアントはこの値で分岐するため、既存の値は変更しないこと。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum WsErrorCode {
+    /// JSON として解釈できない、または未知の `type`
+    InvalidJson = 4000,
+    /// バイナリメッセージなど、受け付けない形式のフレーム
+    UnsupportedFrame = 4001,
+    /// メッセージが大きすぎる
+    MessageTooLarge = 4002,
+    /// メッセージの内容が不正（未知の `message_type` など）
+    InvalidMessage = 4003,
+    /// コマンドを処理できなかった（ルームが見つからない、権限がないなど。詳細は `message`）
+    RequestFailed = 4004,
+    /// 一定時間応答がないため接続を閉じる
+    Timeout = 4005,
+    /// 送信キューが満杯でメッセージを破棄した
+    MessagesDropped = 4006,
+    /// サーバー内部のエラー
+    Internal = 4010,
+}
+
+impl From<WsErrorCode> for u16 {
+    fn from(code: WsErrorCode) -> Self {
+        code as u16
+    }
+}
+
+impl TryFrom<u16> for WsErrorCode {
+    type Error = String;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            4000 => Ok(Self::InvalidJson),
+            4001 => Ok(Self::UnsupportedFrame),
+            4002 => Ok(Self::MessageTooLarge),
+            4003 => Ok(Self::InvalidMessage),
+            4004 => Ok(Self::RequestFailed),
+            4005 => Ok(Self::Timeout),
+            4006 => Ok(Self::MessagesDropped),
+            4010 => Ok(Self::Internal),
+            _ => Err(format!("unknown error code: {}", value)),
+        }
+    }
+}
+
+// WebSocketでやり取りするメッセージの形式
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WsMessage {
+    // クライアントからサーバーへ
+    #[serde(rename = "join_room")]
+    JoinRoom { room: String },
+    #[serde(rename = "send_message")]
+    SendMessage {
+        room: String,
+        content: String,
+        message_type: Option<String>,
+        client_msg_id: Option<String>,
+        // 引用する同じルームのメッセージ ID
+        #[serde(default)]
+        reply_to_message_id: Option<String>,
+    },
+    #[serde(rename = "leave_room")]
+    LeaveRoom { room: String },
+    #[serde(rename = "ping")]
+    Ping { timestamp: Option<u64> },
+    #[serde(rename = "join_all")]
+    JoinAll,
+    // この接続だけを参加中の全ルームから退出させる（接続は維持する）
+    #[serde(rename = "leave_all")]
+    LeaveAll,
+    #[serde(rename = "sync")]
+    Sync {
+        room: String,
+        since_message_id: String,
+    },
+    // 既読位置をこのメッセージまで進める（ルームのメンバーのみ）
+    #[serde(rename = "mark_read")]
+    MarkRead { room: String, message_id: String },
+
+    // WebRTC シグナリング用
+    #[serde(rename = "webrtc_offer")]
+    WebRtcOffer {
+        room: String,
+        to_user_id: String,
+        offer: serde_json::Value,
+    },
+    #[serde(rename = "webrtc_answer")]
+    WebRtcAnswer {
+        room: String,
+        to_user_id: String,
+        answer: serde_json::Value,
+    },
+    #[serde(rename = "webrtc_ice_candidate")]
+    WebRtcIceCandidate {
+        room: String,
+        to_user_id: String,
+        candidate: serde_json::Value,
+    },
+
+    // サーバーからクライアントへ
+    #[serde(rename = "room_joined")]
+    RoomJoined {
+        room: String,
+        user_id: String,
+        username: String,
+        /// 参加したユーザーの既読位置（メンバーでない・未読がない場合は null。古いサーバー向けに省略可）
+        #[serde(default)]
+        last_read_message_id: Option<String>,
+    },
+    #[serde(rename = "message")]
+    Message {
+        id: String,
+        room: String,
+        user_id: String,
+        username: String,
+        avatar_url: Option<String>,
+        #[serde(default)]
+        is_bot: bool,
+        content: String,
+        /// `content` の Markdown をサニタイズした HTML（サーバーで有効な場合のみ）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rendered_html: Option<String>,
+        message_type: String,
+        // ルーム内の投稿順の連番。並び順はこれで決める
+        #[serde(default)]
+        seq: i64,
+        timestamp: DateTime<Utc>,
+        // 引用しているメッセージ（古いサーバー向けに省略可）
+        #[serde(default)]
+        quoted: Option<QuotedMessage>,
+    },
+    #[serde(rename = "message_ack")]
+    MessageAck {
+        client_msg_id: String,
+        server_id: String,
+        timestamp: DateTime<Utc>,
+    },
+    #[serde(rename = "message_nack")]
+    MessageNack {
+        client_msg_id: String,
+        reason: String,
+    },
+    #[serde(rename = "user_joined")]
+    UserJoined {
+        room: String,
+        user_id: String,
+        username: String,
+    },
+    #[serde(rename = "user_left")]
+    UserLeft {
+        room: String,
+        user_id: String,
+        username: String,
+    },
+    /// REST API での招待などでメンバーが追加された（オンラインかどうかは問わない）
+    #[serde(rename = "member_added")]
+    MemberAdded {
+        room: String,
+        user_id: String,
+        username: String,
+    },
+    /// プライベートルームに招待された（接続中の場合、この接続はルームに参加済み）
+    #[serde(rename = "invited_to_room")]
+    InvitedToRoom { room: String, by_username: String },
+    /// ルームの名前・説明・公開設定が変わった（名前が変わっても接続はルームに参加したまま）
+    #[serde(rename = "room_updated")]
+    RoomUpdated {
+        old_name: String,
+        new_name: String,
+        description: Option<String>,
+        is_public: bool,
+    },
+    #[serde(rename = "message_pinned")]
+    MessagePinned {
+        room: String,
+        message_id: String,
+        pinned_by: String,
+        pinned_at: DateTime<Utc>,
+    },
+    #[serde(rename = "message_unpinned")]
+    MessageUnpinned {
+        room: String,
+        message_id: String,
+        unpinned_by: String,
+    },
+    /// メンバーの既読位置が進んだ
+    #[serde(rename = "seen_update")]
+    SeenUpdate {
+        room: String,
+        message_id: String,
+        user_id: String,
+    },
+    #[serde(rename = "joined_rooms")]
+    JoinedRooms { rooms: Vec<String> },
+    /// leave_all で退出したルーム
+    #[serde(rename = "left_all")]
+    LeftAll { rooms: Vec<String> },
+    #[serde(rename = "history")]
+    History {
+        room: String,
+        messages: Vec<HistoryMessage>,
+        has_more: bool,
+    },
+    #[serde(rename = "link_preview")]
+    LinkPreview {
+        room: String,
+        message_id: String,
+        url: String,
+        title: Option<String>,
+        description: Option<String>,
+        image_url: Option<String>,
+    },
+    #[serde(rename = "mentioned")]
+    Mentioned {
+        room: String,
+        message_id: String,
+        user_id: String,
+        username: String,
+        content: String,
+        timestamp: DateTime<Utc>,
+    },
+    #[serde(rename = "pong")]
+    Pong { timestamp: Option<u64> },
+    /// 接続直後に送るサーバーの現在時刻（クライアントは時計のずれの補正に使う）
+    #[serde(rename = "server_time")]
+    ServerTime { server_time: DateTime<Utc> },
+    #[serde(rename = "error")]
+    Error {
+        message: String,
+        code: Option<WsErrorCode>,
+    },
+    #[serde(rename = "auth_required")]
+    AuthRequired,
+    #[serde(rename = "rate_limited")]
+    RateLimited { retry_after: u64 },
+}
@@ -1,22 +1,37 @@
 use axum::{
-    extract::{FromRequestParts, Query, State},
+    extract::{FromRequestParts, OptionalFromRequestParts, Path, Query, State},
     http::{request::Parts, StatusCode},
-    response::{Json, Redirect},
-    routing::{get, post},
+    response::{IntoResponse, Json, Redirect, Response},
+    routing::{delete, get, post},
     Router,
 };
 use base64::{engine::general_purpose, Engine as _};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use oauth2::{
-    basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl,
-    Scope, TokenResponse as OAuth2TokenResponse, TokenUrl,
+    basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse as OAuth2TokenResponse,
+    TokenUrl,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
-use utoipa::ToSchema;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex, OnceLock, PoisonError, RwLock},
+    time::Instant,
+};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::models::User;
+use crate::api::pagination::resolve_limit;
+use crate::config::{
+    Config, DEV_NAMED_USER_GITHUB_ID_BASE, DEV_USERNAME, DEV_USER_GITHUB_ID,
+    GITHUB_ORG_MEMBERSHIP_CACHE_TTL, JWT_EXPIRY_HOURS, MAX_DEV_USERNAME_LENGTH,
+    OAUTH_STATE_EXPIRY_MINUTES, SESSION_PAGE_LIMITS,
+};
+use crate::error::{AppError, AppResult};
+use crate::models::{PkceVerifier, Session, User};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -24,10 +39,17 @@ pub struct Claims {
     pub username: String,
     pub email: Option<String>,
     pub aud: String, // Audience
+    pub iss: String, // Issuer
     pub exp: usize,  // Expiration time
     pub iat: usize,  // Issued at
+    /// セッションID（`sessions` テーブルの行）。導入前に発行されたトークンにはない
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<uuid::Uuid>,
 }
 
+// OAuth の state トークンの `aud`（ログイン用のトークンとして使えないよう区別する）
+const OAUTH_STATE_AUDIENCE: &str = "miuchi.chat.oauth";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StateClaims {
     pub nonce: String, // Random nonce
@@ -35,6 +57,93 @@ pub struct StateClaims {
     pub aud: String,   // Audience
 }
 
+/// JWTの署名・検証に使う鍵
+///
+/// 起動時に一度だけ構築してリクエスト間で共有する。`reload` でシークレットを差し替えられる。
+#[derive(Clone)]
+pub struct JwtKeys {
+    inner: Arc<RwLock<Arc<JwtKeySet>>>,
+}
+
+struct JwtKeySet {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    validation: Validation,
+    state_validation: Validation,
+}
+
+impl JwtKeySet {
+    fn new(secret: &str, audience: &str, issuer: &str) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&[audience]);
+        validation.set_issuer(&[issuer]);
+
+        let mut state_validation = Validation::new(Algorithm::HS256);
+        state_validation.set_audience(&[OAUTH_STATE_AUDIENCE]);
+
+        Self {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            validation,
+            state_validation,
+        }
+    }
+
+    // 検証の条件（`aud` / `iss`）はそのままで鍵だけを差し替えたものを作る
+    fn with_secret(&self, secret: &str) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            validation: self.validation.clone(),
+            state_validation: self.state_validation.clone(),
+        }
+    }
+}
+
+impl JwtKeys {
+    /// トークンの `aud` / `iss` が `audience` / `issuer` と一致するものだけを受け付ける
+    pub fn new(secret: &str, audience: &str, issuer: &str) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Arc::new(JwtKeySet::new(
+                secret, audience, issuer,
+            )))),
+        }
+    }
+
+    /// シークレットを差し替える（以降の発行・検証は新しい鍵で行われる）
+    pub fn reload(&self, secret: &str) {
+        let keys = Arc::new(self.current().with_secret(secret));
+        *self.inner.write().unwrap_or_else(PoisonError::into_inner) = keys;
+    }
+
+    fn current(&self) -> Arc<JwtKeySet> {
+        self.inner
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn encode<T: Serialize>(&self, claims: &T) -> jsonwebtoken::errors::Result<String> {
+        encode(&Header::default(), claims, &self.current().encoding)
+    }
+
+    fn decode_claims(&self, token: &str) -> jsonwebtoken::errors::Result<Claims> {
+        let keys = self.current();
+        decode::<Claims>(token, &keys.decoding, &keys.validation).map(|data| data.claims)
+    }
+
+    fn decode_state(&self, token: &str) -> jsonwebtoken::errors::Result<StateClaims> {
+        let keys = self.current();
+        decode::<StateClaims>(token, &keys.decoding, &keys.state_validation).map(|data| data.claims)
+    }
+}
+
+impl std::fmt::Debug for JwtKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtKeys").finish_non_exhaustive()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct GitHubUser {
     pub id: u64,
@@ -53,6 +162,25 @@ pub struct LoginUrlResponse {
 pub struct CallbackQuery {
     pub code: String,
     pub state: String,
+    /// `json` の場合、リダイレクトせずにトークンを JSON で返す（ネイティブ・モバイルクライアント用）
+    #[serde(default)]
+    pub response_mode: CallbackResponseMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CallbackResponseMode {
+    /// フロントエンドのコールバックページへトークン付きでリダイレクトする
+    #[default]
+    Redirect,
+    /// `TokenResponse` を JSON で返す
+    Json,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct DevLoginQuery {
+    /// 指定した名前の開発用ユーザーでログインする（なければ作成）。省略時は dev_user
+    pub username: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -62,6 +190,36 @@ pub struct TokenResponse {
     pub expires_in: u64,
 }
 
+#[derive(Deserialize, IntoParams)]
+pub struct SessionsQuery {
+    pub limit: Option<i64>,
+    /// 前のページの `next_cursor`（指定したセッションより後に並ぶセッションを返す）
+    pub before: Option<String>,
+    /// trueの場合、範囲外の limit を丸めずに 400 を返す
+    pub strict: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+    pub has_more: bool,
+    /// 続きを取得するためのカーソル（返したうちの最後のセッションID）。次の `before` に渡す
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SessionResponse {
+    /// セッションID（トークンの `jti`）
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// このリクエストで使っているトークンのセッションかどうか
+    pub current: bool,
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct UserResponse {
     pub id: String,
@@ -77,138 +235,69 @@ pub struct AuthUser {
     pub username: String,
     pub email: Option<String>,
     pub avatar_url: Option<String>,
+    /// トークンのセッションID（`jti` を持たない古いトークンでは `None`）
+    pub session_id: Option<uuid::Uuid>,
 }
 
-impl FromRequestParts<PgPool> for AuthUser {
-    type Rejection = StatusCode;
-
-    async fn from_request_parts(
-        parts: &mut Parts,
-        state: &PgPool,
-    ) -> Result<Self, Self::Rejection> {
-        let auth_header = parts
-            .headers
-            .get("Authorization")
-            .and_then(|header| header.to_str().ok())
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        if !auth_header.starts_with("Bearer ") {
-            return Err(StatusCode::UNAUTHORIZED);
-        }
-
-        let token = auth_header.trim_start_matches("Bearer ");
-
-        let secret = std::env::var("JWT_SECRET")
-            .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
-        let mut validation = Validation::new(Algorithm::HS256);
-        validation.set_audience(&["miuchi.chat"]);
-
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(secret.as_ref()),
-            &validation,
-        )
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
-
-        // ユーザーIDをUUIDにパース
-        let user_id = token_data
-            .claims
-            .sub
-            .parse::<uuid::Uuid>()
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
-
-        // DBからユーザー情報を取得して検証
-        let user = User::find_by_id(state, user_id)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        Ok(AuthUser {
-            user_id: user.id.to_string(),
-            username: user.username,
-            email: user.email,
-            avatar_url: user.avatar_url,
-        })
-    }
+/// トークン発行時にセッションへ記録する接続元の情報
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: String,
 }
 
-// Combined state用の実装も追加
-impl FromRequestParts<(PgPool, crate::ws::AppState)> for AuthUser {
-    type Rejection = StatusCode;
+impl FromRequestParts<(PgPool, Option<meilisearch_sdk::client::Client>, Arc<Config>)>
+    for ClientInfo
+{
+    type Rejection = Infallible;
 
     async fn from_request_parts(
         parts: &mut Parts,
-        state: &(PgPool, crate::ws::AppState),
+        state: &(PgPool, Option<meilisearch_sdk::client::Client>, Arc<Config>),
     ) -> Result<Self, Self::Rejection> {
-        let auth_header = parts
-            .headers
-            .get("Authorization")
-            .and_then(|header| header.to_str().ok())
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        if !auth_header.starts_with("Bearer ") {
-            return Err(StatusCode::UNAUTHORIZED);
-        }
-
-        let token = auth_header.trim_start_matches("Bearer ");
-
-        let secret = std::env::var("JWT_SECRET")
-            .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
-        let mut validation = Validation::new(Algorithm::HS256);
-        validation.set_audience(&["miuchi.chat"]);
-
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(secret.as_ref()),
-            &validation,
-        )
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
-
-        // ユーザーIDをUUIDにパース
-        let user_id = token_data
-            .claims
-            .sub
-            .parse::<uuid::Uuid>()
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
-
-        // DBからユーザー情報を取得して検証 (combined stateの最初の要素がPgPool)
-        let user = User::find_by_id(&state.0, user_id)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        Ok(AuthUser {
-            user_id: user.id.to_string(),
-            username: user.username,
-            email: user.email,
-            avatar_url: user.avatar_url,
+        Ok(ClientInfo {
+            user_agent: parts
+                .headers
+                .get(axum::http::header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            ip: super::rate_limit::client_ip(&parts.headers, &parts.extensions, &state.2),
         })
     }
 }
 
-pub fn router() -> Router<(PgPool, meilisearch_sdk::client::Client)> {
+/// ログインのルート（接続元 IP ごとのレート制限をかける）
+pub fn login_router() -> Router<(PgPool, Option<meilisearch_sdk::client::Client>, Arc<Config>)> {
     Router::new()
         .route("/login-url", get(login_url))
         .route("/callback", get(callback))
         .route("/dev-login", post(dev_login))
-        .route("/me", get(me))
 }
 
-fn create_oauth_client() -> anyhow::Result<BasicClient> {
-    let client_id =
-        std::env::var("GITHUB_CLIENT_ID_DEV").unwrap_or_else(|_| "dummy_client_id".to_string());
-    let client_secret = std::env::var("GITHUB_CLIENT_SECRET_DEV")
-        .unwrap_or_else(|_| "dummy_client_secret".to_string());
+pub fn router() -> Router<(PgPool, Option<meilisearch_sdk::client::Client>, Arc<Config>)> {
+    Router::new().route("/me", get(me))
+}
+
+/// セッション管理のルート（失効時に WebSocket 接続を閉じるため WebSocket の状態を持つ）
+pub fn sessions_router() -> Router<(
+    PgPool,
+    crate::ws::AppState,
+    Option<meilisearch_sdk::client::Client>,
+    Arc<Config>,
+)> {
+    Router::new()
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/{jti}", delete(revoke_session))
+}
+
+fn create_oauth_client(config: &Config) -> anyhow::Result<BasicClient> {
+    let client_id = config.github_client_id.clone();
+    let client_secret = config.github_client_secret.clone();
 
     let auth_url = AuthUrl::new("https://github.com/login/oauth/authorize".to_string())?;
     let token_url = TokenUrl::new("https://github.com/login/oauth/access_token".to_string())?;
 
-    let redirect_url = RedirectUrl::new(format!(
-        "{}/api/auth/callback",
-        std::env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3001".to_string())
-    ))?;
+    let redirect_url = RedirectUrl::new(format!("{}/api/auth/callback", config.base_url))?;
 
     Ok(BasicClient::new(
         ClientId::new(client_id),
@@ -219,95 +308,75 @@ fn create_oauth_client() -> anyhow::Result<BasicClient> {
     .set_redirect_uri(redirect_url))
 }
 
-fn create_jwt_token(user: &GitHubUser) -> anyhow::Result<String> {
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
-    let now = Utc::now();
-    let exp = now + Duration::hours(24);
-
-    let claims = Claims {
-        sub: user.id.to_string(),
-        username: user.login.clone(),
-        email: user.email.clone(),
-        aud: "miuchi.chat".to_string(),
-        exp: exp.timestamp() as usize,
-        iat: now.timestamp() as usize,
-    };
-
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )?;
+// ユーザーのトークンを発行し、ログインした端末としてセッションに記録する
+async fn issue_session_token(
+    pool: &PgPool,
+    user: &User,
+    client: &ClientInfo,
+    config: &Config,
+) -> anyhow::Result<String> {
+    let jti = uuid::Uuid::new_v4();
+    let (token, expires_at) = create_jwt_token_from_user(user, jti, config)?;
+
+    Session::create(
+        pool,
+        jti,
+        user.id,
+        client.user_agent.as_deref(),
+        Some(&client.ip),
+        expires_at,
+    )
+    .await?;
 
     Ok(token)
 }
 
-fn create_jwt_token_from_user(user: &User) -> anyhow::Result<String> {
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
+// トークンと、その有効期限を返す
+fn create_jwt_token_from_user(
+    user: &User,
+    jti: uuid::Uuid,
+    config: &Config,
+) -> anyhow::Result<(String, DateTime<Utc>)> {
     let now = Utc::now();
-    let exp = now + Duration::hours(24);
+    let exp = now + Duration::hours(JWT_EXPIRY_HOURS);
 
     let claims = Claims {
         sub: user.id.to_string(),
         username: user.username.clone(),
         email: user.email.clone(),
-        aud: "miuchi.chat".to_string(),
+        aud: config.jwt_audience.clone(),
+        iss: config.jwt_issuer.clone(),
         exp: exp.timestamp() as usize,
         iat: now.timestamp() as usize,
+        jti: Some(jti),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )?;
+    let token = config.jwt_keys.encode(&claims)?;
 
-    Ok(token)
+    Ok((token, exp))
 }
 
-fn create_state_token() -> anyhow::Result<String> {
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
+// state トークンと、その nonce（PKCE verifier の保存キー）を返す
+fn create_state_token(config: &Config) -> anyhow::Result<(String, String)> {
     let now = Utc::now();
-    let exp = now + Duration::minutes(5); // 5分で期限切れ
+    let exp = now + Duration::minutes(OAUTH_STATE_EXPIRY_MINUTES);
 
     // ランダムなnonceを生成
     let nonce = general_purpose::URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4().as_bytes());
 
     let claims = StateClaims {
-        nonce,
+        nonce: nonce.clone(),
         exp: exp.timestamp() as usize,
-        aud: "miuchi.chat.oauth".to_string(),
+        aud: OAUTH_STATE_AUDIENCE.to_string(),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )?;
+    let token = config.jwt_keys.encode(&claims)?;
 
-    Ok(token)
+    Ok((token, nonce))
 }
 
-fn verify_state_token(token: &str) -> anyhow::Result<StateClaims> {
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
-    let mut validation = Validation::new(Algorithm::HS256);
-    validation.set_audience(&["miuchi.chat.oauth"]);
-
-    let token_data = decode::<StateClaims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &validation,
-    )?;
-
-    Ok(token_data.claims)
+fn verify_state_token(token: &str, config: &Config) -> anyhow::Result<StateClaims> {
+    Ok(config.jwt_keys.decode_state(token)?)
 }
 
 #[utoipa::path(
@@ -319,19 +388,43 @@ fn verify_state_token(token: &str) -> anyhow::Result<StateClaims> {
     tag = "Authentication"
 )]
 async fn login_url(
-    State((_pool, _meili_client)): State<(PgPool, meilisearch_sdk::client::Client)>,
+    State((pool, _meili_client, config)): State<(
+        PgPool,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )>,
 ) -> Result<Json<LoginUrlResponse>, StatusCode> {
-    let client = create_oauth_client().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let client = create_oauth_client(&config).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // JWT署名付きstateトークンを生成
-    let state_token = create_state_token().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let (state_token, nonce) =
+        create_state_token(&config).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // PKCE: verifier はサーバー側に保存し、callback で1回だけ取り出す
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    PkceVerifier::store(
+        &pool,
+        &nonce,
+        pkce_verifier.secret(),
+        OAUTH_STATE_EXPIRY_MINUTES,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to store PKCE verifier: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
     let csrf_token = CsrfToken::new(state_token.clone());
 
-    let (auth_url, _) = client
+    let mut request = client
         .authorize_url(|| csrf_token)
-        .add_scope(Scope::new("user:email".to_string()))
-        .url();
+        .set_pkce_challenge(pkce_challenge)
+        .add_scope(Scope::new("user:email".to_string()));
+    // 組織のメンバーシップ（非公開を含む）の確認に必要
+    if config.github_allowed_org.is_some() {
+        request = request.add_scope(Scope::new("read:org".to_string()));
+    }
+    let (auth_url, _) = request.url();
 
     Ok(Json(LoginUrlResponse {
         login_url: auth_url.to_string(),
@@ -344,36 +437,57 @@ async fn login_url(
     path = "/auth/callback",
     params(
         ("code" = String, Query, description = "OAuth authorization code"),
-        ("state" = String, Query, description = "CSRF state token")
+        ("state" = String, Query, description = "CSRF state token"),
+        ("response_mode" = Option<CallbackResponseMode>, Query, description = "redirect (default) or json")
     ),
     responses(
-        (status = 302, description = "Redirect to frontend with token"),
+        (status = 200, description = "Token as JSON (response_mode=json)", body = TokenResponse),
+        (status = 302, description = "Redirect to frontend with token (or to the login page when GITHUB_ALLOWED_ORG is set and the user is not a member)"),
         (status = 400, description = "Invalid callback parameters"),
+        (status = 403, description = "Not a member of GITHUB_ALLOWED_ORG (response_mode=json)"),
         (status = 500, description = "Internal server error")
     ),
     tag = "Authentication"
 )]
 async fn callback(
-    State((pool, _meili_client)): State<(PgPool, meilisearch_sdk::client::Client)>,
+    State((pool, _meili_client, config)): State<(
+        PgPool,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )>,
+    client_info: ClientInfo,
     Query(params): Query<CallbackQuery>,
-) -> Result<Redirect, StatusCode> {
+) -> Result<Response, StatusCode> {
     // JWT署名付きstateトークンを検証
     tracing::info!("Callback received with state: {}", params.state);
 
-    match verify_state_token(&params.state) {
+    let state_claims = match verify_state_token(&params.state, &config) {
         Ok(state_claims) => {
             tracing::info!(
                 "Valid state token verified with nonce: {}",
                 state_claims.nonce
             );
+            state_claims
         }
         Err(e) => {
             tracing::warn!("Invalid state token: {}", e);
             return Err(StatusCode::BAD_REQUEST);
         }
-    }
+    };
 
-    let client = create_oauth_client().map_err(|e| {
+    // login-url で保存した PKCE verifier を取り出す（使用済み・期限切れなら拒否）
+    let pkce_verifier = PkceVerifier::take(&pool, &state_claims.nonce, OAUTH_STATE_EXPIRY_MINUTES)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load PKCE verifier: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or_else(|| {
+            tracing::warn!("PKCE verifier missing or already used");
+            StatusCode::BAD_REQUEST
+        })?;
+
+    let client = create_oauth_client(&config).map_err(|e| {
         tracing::error!("Failed to create OAuth client: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
@@ -383,6 +497,7 @@ async fn callback(
     // GitHubからアクセストークンを取得
     let token_result = client
         .exchange_code(AuthorizationCode::new(params.code.clone()))
+        .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
         .request_async(oauth2::reqwest::async_http_client)
         .await
         .map_err(|e| {
@@ -396,7 +511,7 @@ async fn callback(
     tracing::info!("Fetching user info from GitHub API...");
     let http_client = reqwest::Client::new();
     let user_response = http_client
-        .get("https://api.github.com/user")
+        .get(format!("{}/user", GITHUB_API_URL))
         .bearer_auth(token_result.access_token().secret())
         .header("User-Agent", "miuchi.chat")
         .send()
@@ -415,13 +530,54 @@ async fn callback(
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
 
-    let github_user: GitHubUser = user_response.json().await.map_err(|e| {
+    let mut github_user: GitHubUser = user_response.json().await.map_err(|e| {
         tracing::error!("Failed to parse GitHub user response: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
     tracing::info!("Successfully fetched user info for: {}", github_user.login);
 
+    // 公開メールアドレスを設定していないユーザーはメール一覧から主アドレスを取得する
+    if github_user.email.is_none() {
+        github_user.email = fetch_primary_email(
+            &http_client,
+            GITHUB_API_URL,
+            token_result.access_token().secret(),
+        )
+        .await;
+    }
+
+    // 許可された組織のメンバーだけがログインできる（GITHUB_ALLOWED_ORG 設定時）
+    if let Some(org) = config.github_allowed_org.as_deref() {
+        let is_member = is_org_member(
+            &http_client,
+            token_result.access_token().secret(),
+            github_user.id,
+            org,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to check GitHub organization membership: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        if !is_member {
+            tracing::warn!(
+                "Login rejected for {}: not a member of organization {}",
+                github_user.login,
+                org
+            );
+            return match params.response_mode {
+                CallbackResponseMode::Json => Err(StatusCode::FORBIDDEN),
+                CallbackResponseMode::Redirect => Ok(Redirect::to(&format!(
+                    "{}/login?error=org_membership_required",
+                    config.frontend_url
+                ))
+                .into_response()),
+            };
+        }
+    }
+
     // ユーザーをDBに保存またはアップデート
     tracing::info!("Saving user to database...");
     let user = User::create_or_update_from_github(
@@ -441,47 +597,191 @@ async fn callback(
 
     // JWTトークンを生成
     tracing::info!("Generating JWT token...");
-    let jwt_token = create_jwt_token_from_user(&user).map_err(|e| {
-        tracing::error!("Failed to generate JWT token: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let jwt_token = issue_session_token(&pool, &user, &client_info, &config)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to generate JWT token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
 
     tracing::info!("Successfully generated JWT token");
 
-    // フロントエンドのコールバックページにリダイレクトして、トークンをクエリパラメータで渡す
-    let frontend_url =
-        std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:5173".to_string());
-    let redirect_url = format!("{}/callback?token={}", frontend_url, jwt_token);
+    // トークンはどちらの経路でもログに出さない
+    match params.response_mode {
+        CallbackResponseMode::Json => Ok(Json(TokenResponse {
+            access_token: jwt_token,
+            token_type: "Bearer".to_string(),
+            expires_in: (JWT_EXPIRY_HOURS * 60 * 60) as u64,
+        })
+        .into_response()),
+        CallbackResponseMode::Redirect => {
+            // フロントエンドのコールバックページにリダイレクトして、トークンをクエリパラメータで渡す
+            tracing::info!("Redirecting to frontend: {}/callback", config.frontend_url);
+            let redirect_url = format!("{}/callback?token={}", config.frontend_url, jwt_token);
+            Ok(Redirect::to(&redirect_url).into_response())
+        }
+    }
+}
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+#[derive(Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+// 検証済みの主メールアドレスを取得する
+//
+// `user:email` スコープが許可されていない場合などは取得できないので、エラーにせず `None` を返す。
+async fn fetch_primary_email(
+    http_client: &reqwest::Client,
+    api_url: &str,
+    access_token: &str,
+) -> Option<String> {
+    let response = match http_client
+        .get(format!("{}/user/emails", api_url))
+        .bearer_auth(access_token)
+        .header("User-Agent", "miuchi.chat")
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!("Failed to fetch emails from GitHub: {}", e);
+            return None;
+        }
+    };
+
+    if !response.status().is_success() {
+        tracing::info!(
+            "GitHub emails API returned {}; leaving email empty",
+            response.status()
+        );
+        return None;
+    }
+
+    match response.json::<Vec<GitHubEmail>>().await {
+        Ok(emails) => emails
+            .into_iter()
+            .find(|email| email.primary && email.verified)
+            .map(|email| email.email),
+        Err(e) => {
+            tracing::warn!("Failed to parse GitHub emails response: {}", e);
+            None
+        }
+    }
+}
 
-    tracing::info!("Redirecting to frontend: {}", redirect_url);
+#[derive(Deserialize)]
+struct GitHubOrgMembership {
+    state: String,
+}
 
-    Ok(Redirect::to(&redirect_url))
+// 組織名（小文字）と GitHub ユーザー ID ごとの確認時刻とメンバーかどうか
+type OrgMembershipCache = HashMap<(String, u64), (Instant, bool)>;
+
+// ユーザーが組織の有効なメンバーか（招待中は含まない）
+//
+// 結果は `GITHUB_ORG_MEMBERSHIP_CACHE_TTL` の間キャッシュし、短時間の再ログインで API を呼ばない。
+async fn is_org_member(
+    http_client: &reqwest::Client,
+    access_token: &str,
+    github_id: u64,
+    org: &str,
+) -> anyhow::Result<bool> {
+    static CACHE: OnceLock<Mutex<OrgMembershipCache>> = OnceLock::new();
+    let cache = CACHE.get_or_init(Default::default);
+    let key = (org.to_ascii_lowercase(), github_id);
+
+    {
+        let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.retain(|_, (checked_at, _)| checked_at.elapsed() < GITHUB_ORG_MEMBERSHIP_CACHE_TTL);
+        if let Some((_, is_member)) = cache.get(&key) {
+            return Ok(*is_member);
+        }
+    }
+
+    let response = http_client
+        .get(format!("{}/user/memberships/orgs/{}", GITHUB_API_URL, org))
+        .bearer_auth(access_token)
+        .header("User-Agent", "miuchi.chat")
+        .send()
+        .await?;
+
+    // メンバーでない場合、GitHub は 404（または組織のアクセス制限で 403）を返す
+    let is_member = match response.status() {
+        status if status.is_success() => {
+            response.json::<GitHubOrgMembership>().await?.state == "active"
+        }
+        reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::FORBIDDEN => false,
+        status => anyhow::bail!("GitHub API returned {}", status),
+    };
+
+    cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, (Instant::now(), is_member));
+    Ok(is_member)
 }
 
 #[utoipa::path(
     post,
     path = "/auth/dev-login",
+    params(DevLoginQuery),
     responses(
-        (status = 200, description = "Development login successful", body = TokenResponse)
+        (status = 200, description = "Development login successful (the development user is created if missing)", body = TokenResponse),
+        (status = 400, description = "Invalid username"),
+        (status = 403, description = "DEV_MODE is disabled")
     ),
     tag = "Authentication"
 )]
 async fn dev_login(
-    State((pool, _meili_client)): State<(PgPool, meilisearch_sdk::client::Client)>,
+    State((pool, _meili_client, config)): State<(
+        PgPool,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )>,
+    client_info: ClientInfo,
+    Query(params): Query<DevLoginQuery>,
 ) -> Result<Json<TokenResponse>, StatusCode> {
     // 開発環境でのみ有効
-    if std::env::var("DEV_MODE").unwrap_or_else(|_| "false".to_string()) != "true" {
+    if !config.dev_mode {
         return Err(StatusCode::FORBIDDEN);
     }
 
-    // 開発用ユーザーをDBから取得
-    let user = User::find_by_github_id(&pool, 999999)
+    // 名前付きの開発用ユーザー（複数ブラウザでの動作確認用）
+    let (github_id, username) = match params.username {
+        Some(username) => {
+            if !is_valid_dev_username(&username) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            (dev_user_github_id(&username), username)
+        }
+        None => (DEV_USER_GITHUB_ID, DEV_USERNAME.to_string()),
+    };
+
+    // 開発用ユーザーをDBから取得（空のDBでも使えるよう、なければ作成する）
+    let user = match User::find_by_github_id(&pool, github_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    {
+        Some(user) => user,
+        None => {
+            tracing::info!("Creating development user {}", username);
+            User::create_or_update_from_github(&pool, github_id, username, None, None)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to create development user: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?
+        }
+    };
 
-    let jwt_token =
-        create_jwt_token_from_user(&user).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let jwt_token = issue_session_token(&pool, &user, &client_info, &config)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(TokenResponse {
         access_token: jwt_token,
@@ -490,6 +790,25 @@ async fn dev_login(
     }))
 }
 
+// GitHub のユーザー名と同じく英数字・`-`・`_` のみ
+fn is_valid_dev_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= MAX_DEV_USERNAME_LENGTH
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// 名前から決まる開発用ユーザーの github_id（大文字小文字は区別しない）
+//
+// 実際の GitHub ID（正）やボット（-1 から連番）と重ならないよう、
+// `DEV_NAMED_USER_GITHUB_ID_BASE` 以下の範囲に割り当てる。
+fn dev_user_github_id(username: &str) -> i64 {
+    let digest = Sha256::digest(username.to_ascii_lowercase().as_bytes());
+    let offset = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+    DEV_NAMED_USER_GITHUB_ID_BASE - i64::from(offset)
+}
+
 #[utoipa::path(
     get,
     path = "/auth/me",
@@ -503,7 +822,11 @@ async fn dev_login(
     )
 )]
 async fn me(
-    State((_pool, _meili_client)): State<(PgPool, meilisearch_sdk::client::Client)>,
+    State((_pool, _meili_client, _config)): State<(
+        PgPool,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )>,
     user: AuthUser,
 ) -> Json<UserResponse> {
     Json(UserResponse {
@@ -514,79 +837,414 @@ async fn me(
     })
 }
 
-fn verify_jwt(token: &str) -> Result<Claims, StatusCode> {
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
-    let mut validation = Validation::new(Algorithm::HS256);
-    validation.set_audience(&["miuchi.chat"]);
+#[utoipa::path(
+    get,
+    path = "/auth/sessions",
+    params(SessionsQuery),
+    responses(
+        (status = 200, description = "Active sessions of the current user (most recently used first)", body = SessionsResponse),
+        (status = 400, description = "Invalid cursor or limit out of range (strict mode)"),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "Authentication",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn list_sessions(
+    Query(params): Query<SessionsQuery>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<SessionsResponse>> {
+    let limit = resolve_limit(
+        params.limit,
+        SESSION_PAGE_LIMITS,
+        params.strict.unwrap_or(false),
+    )? as i64;
+    let before = params
+        .before
+        .as_deref()
+        .map(|cursor| {
+            cursor
+                .parse::<uuid::Uuid>()
+                .map_err(|_| AppError::bad_request("Invalid cursor"))
+        })
+        .transpose()?;
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    let sessions = Session::find_active_by_user(&state.0, user_id, limit, before).await?;
+    let has_more = sessions.len() == limit as usize;
+    let next_cursor = sessions.last().map(|session| session.jti.to_string());
+
+    Ok(Json(SessionsResponse {
+        sessions: sessions
+            .into_iter()
+            .map(|session| SessionResponse {
+                id: session.jti.to_string(),
+                user_agent: session.user_agent,
+                ip: session.ip,
+                created_at: session.created_at,
+                last_seen: session.last_seen,
+                expires_at: session.expires_at,
+                current: user.session_id == Some(session.jti),
+            })
+            .collect(),
+        has_more,
+        next_cursor,
+    }))
+}
 
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &validation,
+#[utoipa::path(
+    delete,
+    path = "/auth/sessions/{jti}",
+    params(
+        ("jti" = String, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 204, description = "Session revoked; its token is rejected and its WebSocket connections are closed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Session not found (or not owned by the current user)")
+    ),
+    tag = "Authentication",
+    security(
+        ("bearer_auth" = [])
     )
-    .map_err(|_| StatusCode::UNAUTHORIZED)?;
+)]
+async fn revoke_session(
+    State((pool, app_state, _meili_client, _config)): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )>,
+    Path(jti): Path<String>,
+    user: AuthUser,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let jti = jti
+        .parse::<uuid::Uuid>()
+        .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    Ok(token_data.claims)
+    // 他のユーザーのセッションは存在しないものとして扱う
+    let revoked = Session::revoke(&pool, jti, user_id).await.map_err(|e| {
+        tracing::error!("Failed to revoke session: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !revoked {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // 失効したトークンで接続済みの WebSocket も閉じる
+    let closed = crate::ws::close_session_connections(user_id, jti, &app_state).await;
+
+    tracing::info!(
+        "User {} revoked session {} ({} WebSocket connection(s) closed)",
+        user.username,
+        jti,
+        closed
+    );
+    Ok(StatusCode::NO_CONTENT)
 }
 
-impl FromRequestParts<(PgPool, meilisearch_sdk::client::Client)> for AuthUser {
-    type Rejection = StatusCode;
+pub(crate) fn verify_jwt(token: &str, config: &Config) -> Result<Claims, StatusCode> {
+    config
+        .jwt_keys
+        .decode_claims(token)
+        .map_err(|_| StatusCode::UNAUTHORIZED)
+}
 
-    async fn from_request_parts(
-        parts: &mut Parts,
-        _state: &(PgPool, meilisearch_sdk::client::Client),
-    ) -> Result<Self, Self::Rejection> {
-        let auth_header = parts
-            .headers
-            .get("Authorization")
-            .and_then(|header| header.to_str().ok())
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        if !auth_header.starts_with("Bearer ") {
-            return Err(StatusCode::UNAUTHORIZED);
+/// HTTP の認証に失敗した理由
+///
+/// エラーレスポンスの `details.reason` とメトリクスのラベルに使う。クライアントは `token_expired` なら
+/// トークンを取り直し、それ以外は再ログインを促す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AuthFailure {
+    MissingHeader,
+    InvalidScheme,
+    InvalidToken,
+    TokenExpired,
+    RevokedSession,
+    UserNotFound,
+}
+
+impl AuthFailure {
+    fn reason(self) -> &'static str {
+        match self {
+            Self::MissingHeader => "missing_header",
+            Self::InvalidScheme => "invalid_scheme",
+            Self::InvalidToken => "invalid_token",
+            Self::TokenExpired => "token_expired",
+            Self::RevokedSession => "revoked_session",
+            Self::UserNotFound => "user_not_found",
         }
+    }
 
-        let token = &auth_header[7..];
+    fn message(self) -> &'static str {
+        match self {
+            Self::MissingHeader => "認証が必要です。ログインしてください。",
+            Self::InvalidScheme => "Authorization ヘッダーは Bearer 形式で指定してください。",
+            Self::InvalidToken => "トークンが無効です。再度ログインしてください。",
+            Self::TokenExpired => "トークンの有効期限が切れています。",
+            Self::RevokedSession => "セッションは失効しています。再度ログインしてください。",
+            Self::UserNotFound => "ユーザーが存在しません。",
+        }
+    }
 
-        let claims = verify_jwt(token)?;
+    // メトリクスに記録してエラーにする
+    fn reject(self) -> AppError {
+        crate::metrics::auth_failed("http", self.reason());
+        AppError::auth_with_reason(self.reason(), self.message())
+    }
+}
 
-        Ok(AuthUser {
-            user_id: claims.sub,
-            username: claims.username,
-            email: claims.email,
-            avatar_url: None, // WebSocket状態ではavatar情報は不要
-        })
+// Authorization ヘッダーのJWTを検証する（失敗理由はメトリクスに記録し、`details.reason` で返す）
+//
+// セッションID（jti）を持つトークンは、セッションが失効していないことを確認して `last_seen` を更新する。
+async fn authenticate(parts: &Parts, pool: &PgPool, config: &Config) -> AppResult<AuthUser> {
+    let Some(auth_header) = parts
+        .headers
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok())
+    else {
+        return Err(AuthFailure::MissingHeader.reject());
+    };
+
+    let Some(token) = auth_header.strip_prefix("Bearer ") else {
+        return Err(AuthFailure::InvalidScheme.reject());
+    };
+
+    let claims = config.jwt_keys.decode_claims(token).map_err(|e| {
+        let failure = match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthFailure::TokenExpired,
+            _ => AuthFailure::InvalidToken,
+        };
+        failure.reject()
+    })?;
+
+    if let Some(jti) = claims.jti {
+        let user_id = claims
+            .sub
+            .parse::<uuid::Uuid>()
+            .map_err(|_| AuthFailure::InvalidToken.reject())?;
+        if !Session::touch(pool, jti, user_id).await? {
+            // ユーザーを削除するとセッションも消えるため、ユーザーが残っているかで理由を分ける
+            let failure = match User::find_by_id(pool, user_id).await? {
+                Some(_) => AuthFailure::RevokedSession,
+                None => AuthFailure::UserNotFound,
+            };
+            return Err(failure.reject());
+        }
+    }
+
+    Ok(AuthUser {
+        user_id: claims.sub,
+        username: claims.username,
+        email: claims.email,
+        avatar_url: None, // WebSocket状態ではavatar情報は不要
+        session_id: claims.jti,
+    })
+}
+
+impl FromRequestParts<(PgPool, Option<meilisearch_sdk::client::Client>, Arc<Config>)> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &(PgPool, Option<meilisearch_sdk::client::Client>, Arc<Config>),
+    ) -> Result<Self, Self::Rejection> {
+        authenticate(parts, &state.0, &state.2).await
     }
 }
 
-impl FromRequestParts<(PgPool, crate::ws::AppState, meilisearch_sdk::client::Client)> for AuthUser {
-    type Rejection = StatusCode;
+impl
+    FromRequestParts<(
+        PgPool,
+        crate::ws::AppState,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )> for AuthUser
+{
+    type Rejection = AppError;
 
     async fn from_request_parts(
         parts: &mut Parts,
-        _state: &(PgPool, crate::ws::AppState, meilisearch_sdk::client::Client),
+        state: &(
+            PgPool,
+            crate::ws::AppState,
+            Option<meilisearch_sdk::client::Client>,
+            Arc<Config>,
+        ),
     ) -> Result<Self, Self::Rejection> {
-        let auth_header = parts
-            .headers
-            .get("Authorization")
-            .and_then(|header| header.to_str().ok())
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        if !auth_header.starts_with("Bearer ") {
-            return Err(StatusCode::UNAUTHORIZED);
+        authenticate(parts, &state.0, &state.3).await
+    }
+}
+
+// Authorization ヘッダーがない場合は匿名として扱い、ある場合は検証する
+impl
+    OptionalFromRequestParts<(
+        PgPool,
+        crate::ws::AppState,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
+    )> for AuthUser
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &(
+            PgPool,
+            crate::ws::AppState,
+            Option<meilisearch_sdk::client::Client>,
+            Arc<Config>,
+        ),
+    ) -> Result<Option<Self>, Self::Rejection> {
+        if !parts.headers.contains_key("Authorization") {
+            return Ok(None);
         }
+        authenticate(parts, &state.0, &state.3).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DEFAULT_JWT_AUDIENCE, DEFAULT_JWT_ISSUER};
+
+    fn claims() -> Claims {
+        let now = Utc::now();
+        Claims {
+            sub: uuid::Uuid::new_v4().to_string(),
+            username: "octocat".to_string(),
+            email: None,
+            aud: DEFAULT_JWT_AUDIENCE.to_string(),
+            iss: DEFAULT_JWT_ISSUER.to_string(),
+            exp: (now + Duration::hours(1)).timestamp() as usize,
+            iat: now.timestamp() as usize,
+            jti: Some(uuid::Uuid::new_v4()),
+        }
+    }
 
-        let token = &auth_header[7..];
+    // GitHub の `/user/emails` を模したサーバーを起動する
+    async fn spawn_github_emails_mock() -> String {
+        let app = Router::new().route(
+            "/user/emails",
+            get(|headers: axum::http::HeaderMap| async move {
+                let authorization = headers
+                    .get(axum::http::header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok());
+                if authorization != Some("Bearer granted") {
+                    return Err(StatusCode::NOT_FOUND);
+                }
+                Ok(Json(serde_json::json!([
+                    { "email": "old@example.com", "primary": false, "verified": true },
+                    { "email": "unverified@example.com", "primary": true, "verified": false },
+                    { "email": "octocat@example.com", "primary": true, "verified": true }
+                ])))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
 
-        let claims = verify_jwt(token)?;
+    #[tokio::test]
+    async fn test_fetch_primary_email() {
+        let api_url = spawn_github_emails_mock().await;
+        let http_client = reqwest::Client::new();
 
-        Ok(AuthUser {
-            user_id: claims.sub,
-            username: claims.username,
-            email: claims.email,
-            avatar_url: None, // WebSocket状態ではavatar情報は不要
-        })
+        assert_eq!(
+            fetch_primary_email(&http_client, &api_url, "granted").await,
+            Some("octocat@example.com".to_string())
+        );
+        // スコープが許可されていない場合はメールなしで続行する
+        assert_eq!(
+            fetch_primary_email(&http_client, &api_url, "no_scope").await,
+            None
+        );
+    }
+
+    #[test]
+    fn test_dev_user_github_id_is_deterministic_and_out_of_range() {
+        let alice = dev_user_github_id("alice");
+        assert_eq!(alice, dev_user_github_id("Alice"));
+        assert_ne!(alice, dev_user_github_id("bob"));
+        assert!(alice <= DEV_NAMED_USER_GITHUB_ID_BASE);
+    }
+
+    #[test]
+    fn test_is_valid_dev_username() {
+        assert!(is_valid_dev_username("alice"));
+        assert!(is_valid_dev_username("dev-user_2"));
+        assert!(!is_valid_dev_username(""));
+        assert!(!is_valid_dev_username("alice bob"));
+        assert!(!is_valid_dev_username(
+            &"a".repeat(MAX_DEV_USERNAME_LENGTH + 1)
+        ));
+    }
+
+    fn keys(secret: &str) -> JwtKeys {
+        JwtKeys::new(secret, DEFAULT_JWT_AUDIENCE, DEFAULT_JWT_ISSUER)
+    }
+
+    #[test]
+    fn test_jwt_keys_round_trip() {
+        let keys = keys("secret");
+        let token = keys.encode(&claims()).unwrap();
+        assert_eq!(keys.decode_claims(&token).unwrap().username, "octocat");
+    }
+
+    #[test]
+    fn test_jwt_keys_reload_rotates_secret() {
+        let keys = keys("old_secret");
+        let old_token = keys.encode(&claims()).unwrap();
+
+        keys.reload("new_secret");
+
+        assert!(keys.decode_claims(&old_token).is_err());
+        let new_token = keys.encode(&claims()).unwrap();
+        assert!(keys.decode_claims(&new_token).is_ok());
+
+        // 差し替え後も aud / iss の検証は変わらない
+        let mut other_issuer = claims();
+        other_issuer.iss = "https://staging.example.com".to_string();
+        let token = keys.encode(&other_issuer).unwrap();
+        assert!(keys.decode_claims(&token).is_err());
+    }
+
+    #[test]
+    fn test_jwt_keys_reject_other_issuer_and_audience() {
+        let keys = keys("secret");
+
+        let mut other_issuer = claims();
+        other_issuer.iss = "https://staging.example.com".to_string();
+        let token = keys.encode(&other_issuer).unwrap();
+        assert!(keys.decode_claims(&token).is_err());
+
+        let mut other_audience = claims();
+        other_audience.aud = "other.app".to_string();
+        let token = keys.encode(&other_audience).unwrap();
+        assert!(keys.decode_claims(&token).is_err());
+
+        // 環境ごとに設定した値で発行・検証できる
+        let staging = JwtKeys::new("secret", "other.app", "https://staging.example.com");
+        let mut claims = claims();
+        claims.aud = "other.app".to_string();
+        claims.iss = "https://staging.example.com".to_string();
+        let token = staging.encode(&claims).unwrap();
+        assert!(staging.decode_claims(&token).is_ok());
+        assert!(keys.decode_claims(&token).is_err());
     }
 }
@@ -0,0 +1,109 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use meilisearch_sdk::client::Client as MeilisearchClient;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::avatars::avatar_for;
+use crate::config::{Config, AVATAR_CACHE_TTL};
+use crate::error::{AppError, AppResult};
+use crate::models::User;
+
+pub fn router() -> Router<(PgPool, Option<MeilisearchClient>, Arc<Config>)> {
+    Router::new().route("/{user_id}", get(get_avatar))
+}
+
+/// ユーザーのアバター画像を返す
+///
+/// `<img>` から直接参照できるよう認証は不要。取得するのはプロフィールに登録された URL だけで、
+/// アバターがない・取得できないユーザーにはアイデンティコンを返す。
+#[utoipa::path(
+    get,
+    path = "/avatar/{user_id}",
+    params(
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Avatar image (PNG/JPEG/GIF/WebP), or a generated SVG identicon when the user has no reachable avatar"),
+        (status = 304, description = "Not modified (If-None-Match matches the current ETag)"),
+        (status = 400, description = "Invalid user ID"),
+        (status = 404, description = "User not found")
+    ),
+    tag = "Chat"
+)]
+pub async fn get_avatar(
+    State((pool, _meili_client, _config)): State<(PgPool, Option<MeilisearchClient>, Arc<Config>)>,
+    Path(user_id): Path<String>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let user_id = user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+    let user = User::find_by_id(&pool, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("ユーザー"))?;
+
+    let avatar = avatar_for(user.id, user.avatar_url.as_deref()).await;
+
+    let cache_control =
+        HeaderValue::from_str(&format!("public, max-age={}", AVATAR_CACHE_TTL.as_secs()))
+            .map_err(anyhow::Error::from)?;
+    let etag = HeaderValue::from_str(&avatar.etag).map_err(anyhow::Error::from)?;
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &avatar.etag));
+    if not_modified {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+        )
+            .into_response());
+    }
+
+    let content_type = HeaderValue::from_str(&avatar.content_type).map_err(anyhow::Error::from)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control),
+            // 自サイトのオリジンから配信するため、画像以外として解釈させない
+            (
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                HeaderValue::from_static("default-src 'none'"),
+            ),
+        ],
+        avatar.body,
+    )
+        .into_response())
+}
+
+// If-None-Match（カンマ区切り・弱い比較）が ETag に一致するか
+pub(crate) fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("W/\"abc\"", "\"abc\""));
+        assert!(etag_matches("\"x\", \"abc\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"abcd\"", "\"abc\""));
+    }
+}
@@ -1,16 +1,33 @@
 use axum::{
     extract::{Path, Query, State},
-    response::Json,
-    routing::{get, post},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{get, patch, post},
     Router,
 };
 use meilisearch_sdk::client::Client as MeilisearchClient;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::api::auth::AuthUser;
-use crate::models::{DbMessageType, Message as DbMessage, Room};
+use crate::api::avatar::etag_matches;
+use crate::api::pagination::{resolve_limit, resolve_offset};
+use crate::commands::{self, CommandContext, CommandOutput};
+use crate::config::{
+    Config, BATCH_MESSAGE_LIMITS, IDEMPOTENCY_KEY_TTL, MAX_BATCH_MESSAGES_TOTAL,
+    MAX_BATCH_MESSAGE_ROOMS, MAX_SEEN_BY_USERS, MAX_SLOW_MODE_SECONDS, MEMBER_PAGE_LIMITS,
+};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    is_unique_violation, sanitize_content, validate_idempotency_key, validate_message_content,
+    validate_room_name, DbMessageType, DbNotificationMode, Message as DbMessage, MessageWithUser,
+    NewMessage, NewRoom, NotificationPref, QuotePreview, Room, RoomMessagesVersion,
+};
+use crate::ws::WsMessage;
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Message {
@@ -19,9 +36,40 @@ pub struct Message {
     pub author_id: String,
     pub author_name: String,
     pub author_avatar: Option<String>,
+    /// 受信 Webhook などのボットによる投稿か
+    pub author_is_bot: bool,
     pub content: String,
+    /// `content` の Markdown をサニタイズした HTML（`RENDER_MARKDOWN=true` のテキストメッセージのみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered_html: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// updated_at が created_at より後であれば編集済み
+    pub edited: bool,
     pub message_type: MessageType,
+    /// ルーム内の投稿順の連番（並び順の基準）
+    pub seq: i64,
+    /// 引用している同じルームのメッセージ（引用元が削除された場合は null）
+    pub quoted: Option<QuotedMessage>,
+}
+
+/// 引用元メッセージの表示用の要約
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct QuotedMessage {
+    pub id: String,
+    pub username: String,
+    /// 本文の先頭100文字（長い場合は末尾に `…`）
+    pub content_preview: String,
+}
+
+impl From<QuotePreview> for QuotedMessage {
+    fn from(quote: QuotePreview) -> Self {
+        QuotedMessage {
+            id: quote.id.to_string(),
+            username: quote.username,
+            content_preview: quote.content_preview,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -32,23 +80,105 @@ pub enum MessageType {
     System,
 }
 
+impl From<DbMessageType> for MessageType {
+    fn from(message_type: DbMessageType) -> Self {
+        match message_type {
+            DbMessageType::Text => MessageType::Text,
+            DbMessageType::Image => MessageType::Image,
+            DbMessageType::File => MessageType::File,
+            DbMessageType::System => MessageType::System,
+        }
+    }
+}
+
+impl From<MessageWithUser> for Message {
+    fn from(msg: MessageWithUser) -> Self {
+        let quoted = msg.quote().map(QuotedMessage::from);
+        Message {
+            id: msg.id.to_string(),
+            room_id: msg.room_id.to_string(),
+            author_id: msg.user_id.to_string(),
+            author_name: msg.username,
+            author_avatar: msg.avatar_url,
+            author_is_bot: msg.is_bot,
+            content: msg.content,
+            rendered_html: None,
+            created_at: msg.created_at,
+            updated_at: msg.updated_at,
+            edited: msg.updated_at > msg.created_at,
+            message_type: msg.message_type.into(),
+            seq: msg.seq,
+            quoted,
+        }
+    }
+}
+
+impl Message {
+    /// 投稿者の情報付きのメッセージから、設定に応じて表示用の HTML を付けて作る
+    pub(crate) fn rendered(msg: MessageWithUser, config: &Config) -> Self {
+        Message {
+            rendered_html: crate::markdown::rendered_html(config, &msg.message_type, &msg.content),
+            ..Message::from(msg)
+        }
+    }
+}
+
+impl From<MessageType> for DbMessageType {
+    fn from(message_type: MessageType) -> Self {
+        match message_type {
+            MessageType::Text => DbMessageType::Text,
+            MessageType::Image => DbMessageType::Image,
+            MessageType::File => DbMessageType::File,
+            MessageType::System => DbMessageType::System,
+        }
+    }
+}
+
 #[derive(Deserialize, IntoParams)]
 pub struct MessagesQuery {
-    pub limit: Option<u32>,
+    pub limit: Option<i64>,
     pub before: Option<String>,
+    /// 指定したメッセージより新しいメッセージを古い順に返す（`before` とは併用不可）
+    pub after: Option<String>,
+    /// trueの場合、範囲外の limit を丸めずに 400 を返す
+    pub strict: Option<bool>,
 }
 
 #[derive(Deserialize, ToSchema)]
 pub struct SendMessageRequest {
     pub content: String,
     pub message_type: Option<MessageType>,
+    /// 再送時の重複投稿を防ぐキー（未指定時は `X-Idempotency-Key` ヘッダーを使用）
+    pub idempotency_key: Option<String>,
+    /// 引用する同じルームのメッセージ ID
+    pub reply_to_message_id: Option<String>,
 }
 
+// 冪等キーを受け付けるヘッダー（リクエストボディの `idempotency_key` が優先）
+const IDEMPOTENCY_KEY_HEADER: &str = "x-idempotency-key";
+
 #[derive(Deserialize, ToSchema)]
 pub struct CreateRoomRequest {
     pub name: String,
     pub description: Option<String>,
     pub is_public: bool,
+    /// メンバーの追加（招待）をシステムメッセージとして履歴に残す（既定: false）
+    #[serde(default)]
+    pub log_membership_events: bool,
+    /// スローモード: 同じユーザーの連続投稿の最小間隔（秒、1〜21600。省略時はなし）
+    pub slow_mode_seconds: Option<i32>,
+    /// メッセージ本文の文字数上限（1〜`MAX_ROOM_MESSAGE_LENGTH`。省略時はサーバー既定の4000文字）
+    pub max_message_length: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateRoomRequest {
+    /// 新しいルーム名（作成時と同じ規則で正規化・検証する）
+    pub name: Option<String>,
+    /// 新しい説明（空文字列で説明を消す）
+    pub description: Option<String>,
+    /// `false` にするとメンバー以外は参加中の接続ごとルームから外れる
+    pub is_public: Option<bool>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -57,6 +187,9 @@ pub struct CreateRoomResponse {
     pub name: String,
     pub description: Option<String>,
     pub is_public: bool,
+    pub log_membership_events: bool,
+    pub slow_mode_seconds: Option<i32>,
+    pub max_message_length: Option<i32>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -70,6 +203,32 @@ pub struct RoomMember {
 #[derive(Serialize, ToSchema)]
 pub struct RoomMembersResponse {
     pub members: Vec<RoomMember>,
+    pub total: i64,
+    pub has_more: bool,
+}
+
+/// メッセージを既読にしたメンバー
+#[derive(Serialize, ToSchema)]
+pub struct MessageReader {
+    pub user_id: String,
+    pub username: String,
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MessageSeenResponse {
+    /// 既読位置がこのメッセージ以降のメンバー（名前順、最大50人）
+    pub users: Vec<MessageReader>,
+    /// 既読にしたメンバーの総数
+    pub total: i64,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct MembersQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// trueの場合、範囲外の limit / offset を丸めずに 400 を返す
+    pub strict: Option<bool>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -79,11 +238,35 @@ pub struct RoomInfo {
     pub description: Option<String>,
     pub is_public: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// 最新メッセージ（メッセージがない場合はnull）
+    pub last_message: Option<LastMessagePreview>,
+    /// 最新メッセージの日時（メッセージがない場合は作成日時）
+    pub last_activity_at: chrono::DateTime<chrono::Utc>,
+    /// プライベートルームはメンバー数、パブリックルームは発言したことのあるユーザー数
+    pub member_count: i64,
+    pub message_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LastMessagePreview {
+    pub content: String,
+    pub username: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct RoomsResponse {
     pub rooms: Vec<RoomInfo>,
+    pub total: i64,
+    pub has_more: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct RoomsQuery {
+    /// ルーム名の部分一致フィルター
+    pub q: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -97,12 +280,60 @@ pub struct InviteUserResponse {
     pub message: String,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct BatchMessagesRequest {
+    /// 取得するルーム名（最大50件、重複は無視）
+    pub rooms: Vec<String>,
+    /// ルームごとの件数（既定: 1、最大: 50。合計が500件を超える場合は減らす）
+    pub limit_per_room: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchMessagesResponse {
+    /// ルーム名ごとの新しい順のメッセージ（存在しない・閲覧できないルームは含まない）
+    pub rooms: BTreeMap<String, Vec<Message>>,
+    /// 実際に適用したルームごとの件数
+    pub limit_per_room: u32,
+    /// 応答時のサーバー時刻（クライアントは時計のずれの補正に使う）
+    pub server_time: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkInviteRequest {
+    /// 招待するユーザー名（最大50件）
+    pub usernames: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InviteStatus {
+    Invited,
+    AlreadyMember,
+    NotFound,
+    /// 大文字小文字違いのユーザーが複数いて特定できない
+    Ambiguous,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkInviteResult {
+    pub username: String,
+    pub status: InviteStatus,
+    pub user_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkInviteResponse {
+    pub results: Vec<BulkInviteResult>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct OnlineUser {
     pub user_id: String,
     pub username: String,
     pub connected_rooms: Vec<String>,
     pub connected_at: chrono::DateTime<chrono::Utc>,
+    /// 同一ユーザーの同時接続数（タブごとに1接続）
+    pub connection_count: usize,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -114,6 +345,7 @@ pub struct OnlineUsersResponse {
 #[derive(Serialize, ToSchema)]
 pub struct SendMessageResponse {
     pub message_id: String,
+    pub seq: i64,
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
@@ -121,17 +353,204 @@ pub struct SendMessageResponse {
 pub struct MessagesResponse {
     pub messages: Vec<Message>,
     pub has_more: bool,
+    /// 同じ向きに続きを取得するためのカーソル。通常（`before`）は返したうちの最も古いメッセージのIDで
+    /// 次の `before` に、`after` 指定時は最も新しいメッセージのIDで次の `after` に渡す
     pub next_cursor: Option<String>,
+    /// 逆向きに取得するためのカーソル。通常（`before`）は返したうちの最も新しいメッセージのIDで
+    /// `after` に、`after` 指定時は最も古いメッセージのIDで `before` に渡す
+    pub prev_cursor: Option<String>,
+    /// 応答時のサーバー時刻（クライアントは時計のずれの補正に使う）
+    pub server_time: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PinnedMessage {
+    pub message: Message,
+    pub pinned_by: String,
+    pub pinned_by_name: String,
+    pub pinned_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PinnedMessagesResponse {
+    pub pins: Vec<PinnedMessage>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PinMessageResponse {
+    pub message_id: String,
+    pub pinned_by: String,
+    pub pinned_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UnpinMessageResponse {
+    pub success: bool,
+    pub message_id: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AuditLogQuery {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditLogEntry {
+    pub id: String,
+    /// 操作の種類（room.create / room.update / member.invite / message.pin / message.unpin）
+    pub action: String,
+    pub actor_id: String,
+    pub actor_name: String,
+    pub target_id: Option<String>,
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditLogResponse {
+    pub entries: Vec<AuditLogEntry>,
+    pub total: i64,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationMode {
+    All,
+    Mentions,
+    None,
+}
+
+impl From<DbNotificationMode> for NotificationMode {
+    fn from(mode: DbNotificationMode) -> Self {
+        match mode {
+            DbNotificationMode::All => Self::All,
+            DbNotificationMode::Mentions => Self::Mentions,
+            DbNotificationMode::None => Self::None,
+        }
+    }
+}
+
+impl From<NotificationMode> for DbNotificationMode {
+    fn from(mode: NotificationMode) -> Self {
+        match mode {
+            NotificationMode::All => Self::All,
+            NotificationMode::Mentions => Self::Mentions,
+            NotificationMode::None => Self::None,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RoomNotificationPref {
+    pub room: String,
+    pub mode: NotificationMode,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NotificationPrefsResponse {
+    /// 全体の既定値（未設定なら `all`）
+    pub global: NotificationMode,
+    /// ルームごとの上書き設定
+    pub rooms: Vec<RoomNotificationPref>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateNotificationPrefRequest {
+    /// 対象ルーム（省略時は全体の既定値）
+    pub room: Option<String>,
+    /// `null` の場合は設定を削除して既定値に戻す
+    pub mode: Option<NotificationMode>,
 }
 
-pub fn router() -> Router<(PgPool, crate::ws::AppState, MeilisearchClient)> {
+#[derive(Serialize, ToSchema)]
+pub struct BlockUserResponse {
+    pub username: String,
+    pub blocked: bool,
+}
+
+// 一括招待で一度に指定できるユーザー名の上限
+const MAX_BULK_INVITE_USERNAMES: usize = 50;
+
+// ルームあたりのピン留め上限
+const MAX_PINS_PER_ROOM: i64 = 50;
+
+// 管理操作を監査ログに記録（記録に失敗しても操作自体は成功とする）
+pub(super) async fn record_audit(
+    room: &Room,
+    pool: &PgPool,
+    actor_id: uuid::Uuid,
+    action: &str,
+    target_id: Option<uuid::Uuid>,
+    metadata: serde_json::Value,
+) {
+    if let Err(e) = room
+        .log_action(pool, actor_id, action, target_id, metadata)
+        .await
+    {
+        tracing::error!(
+            "Failed to record audit log {} for room {}: {}",
+            action,
+            room.name,
+            e
+        );
+    }
+}
+
+// プライベートルームのメンバー以外のアクセスを拒否する
+//
+// 既定では 403 を返す。`HIDE_PRIVATE_ROOMS=true` のときはルームの存在を知られないよう、
+// 存在しないルームと同じ 404 を返す。
+pub(super) async fn ensure_room_visible(
+    room: &Room,
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+    config: &Config,
+) -> AppResult<()> {
+    if room.is_public || room.is_member(pool, user_id).await? {
+        return Ok(());
+    }
+
+    if config.hide_private_rooms {
+        Err(AppError::not_found("ルーム"))
+    } else {
+        Err(AppError::forbidden(
+            "You are not a member of this private room",
+        ))
+    }
+}
+
+pub fn router() -> Router<(
+    PgPool,
+    crate::ws::AppState,
+    Option<MeilisearchClient>,
+    Arc<Config>,
+)> {
     Router::new()
         .route("/rooms", get(get_rooms).post(create_room))
         .route("/online-users", get(get_online_users))
+        .route("/messages/batch", post(get_messages_batch))
+        .route("/messages/{id}", get(get_message))
+        .route("/{room}", patch(update_room))
         .route("/{room}/messages", get(get_messages))
         .route("/{room}/send", post(send_message))
         .route("/{room}/members", get(get_room_members))
+        .route("/{room}/messages/{message_id}/seen", get(get_message_seen))
         .route("/{room}/invite", post(invite_user))
+        .route("/{room}/invite-bulk", post(invite_users_bulk))
+        .route("/{room}/audit", get(get_audit_log))
+        .route("/{room}/pins", get(get_pinned_messages))
+        .route(
+            "/{room}/pins/{message_id}",
+            post(pin_message).delete(unpin_message),
+        )
+        .route("/blocks/{username}", post(block_user).delete(unblock_user))
+        .route(
+            "/notification-prefs",
+            get(get_notification_prefs).put(update_notification_prefs),
+        )
 }
 
 #[utoipa::path(
@@ -140,203 +559,359 @@ pub fn router() -> Router<(PgPool, crate::ws::AppState, MeilisearchClient)> {
     params(
         ("room" = String, Path, description = "Room name"),
         ("limit" = Option<u32>, Query, description = "Number of messages to retrieve (default: 50, max: 100)"),
-        ("before" = Option<String>, Query, description = "Message ID to fetch messages before (pagination)")
+        ("before" = Option<String>, Query, description = "Message ID to fetch messages before (pagination)"),
+        ("after" = Option<String>, Query, description = "Message ID to fetch newer messages after, oldest first (cannot be combined with before)"),
+        ("strict" = Option<bool>, Query, description = "Reject out-of-range limit with 400 instead of clamping")
     ),
     responses(
-        (status = 200, description = "Messages retrieved successfully", body = MessagesResponse),
+        (status = 200, description = "Messages retrieved successfully (with a weak ETag header)", body = MessagesResponse),
+        (status = 304, description = "Not modified (If-None-Match matches the current ETag)"),
+        (status = 400, description = "Invalid cursor, both before and after supplied, or limit out of range in strict mode"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a member of the private room"),
         (status = 404, description = "Room not found")
     ),
-    tag = "Chat"
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
 )]
 async fn get_messages(
     Path(room_name): Path<String>,
     Query(params): Query<MessagesQuery>,
-    State(state): State<(PgPool, crate::ws::AppState, MeilisearchClient)>,
-) -> Result<Json<MessagesResponse>, axum::http::StatusCode> {
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> AppResult<Response> {
     let pool = &state.0;
-    let limit = params.limit.unwrap_or(50).min(100) as i64;
+    let limit = resolve_limit(
+        params.limit,
+        state.3.message_page_limits,
+        params.strict.unwrap_or(false),
+    )? as i64;
+
+    if params.before.is_some() && params.after.is_some() {
+        return Err(AppError::bad_request(
+            "before and after cannot be used together",
+        ));
+    }
 
     // ルーム名からルームを検索
-    let room = Room::find_by_name(&pool, &room_name)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    let room = Room::find_by_name(pool, &room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
+
+    let viewer_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    // パブリックルームでない場合はメンバーのみ閲覧できる（send_message と同じ規則）
+    ensure_room_visible(&room, pool, viewer_id, &state.3).await?;
 
     // beforeパラメータをUUIDにパース
     let before_id = if let Some(before_str) = &params.before {
         Some(
             before_str
                 .parse::<uuid::Uuid>()
-                .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?,
+                .map_err(|_| AppError::bad_request("Invalid before cursor"))?,
         )
     } else {
         None
     };
 
-    // メッセージを取得
-    let db_messages = DbMessage::find_by_room_with_users(&pool, room.id, limit, before_id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    // afterパラメータはこのルームのメッセージIDでなければならない
+    let after_id = if let Some(after_str) = &params.after {
+        let after_id = after_str
+            .parse::<uuid::Uuid>()
+            .map_err(|_| AppError::bad_request("Invalid after cursor"))?;
+        match DbMessage::find_by_id(pool, after_id).await? {
+            Some(cursor) if cursor.room_id == room.id => Some(after_id),
+            _ => return Err(AppError::bad_request("Invalid after cursor")),
+        }
+    } else {
+        None
+    };
+
+    // 前回から一覧が変わっていなければ本文を返さない（ポーリング用）
+    let version = DbMessage::room_version(pool, room.id, viewer_id).await?;
+    let etag = messages_etag(
+        &version,
+        state.3.render_markdown,
+        limit,
+        before_id,
+        after_id,
+    );
+    let etag_header = HeaderValue::from_str(&format!("W/{}", etag)).map_err(anyhow::Error::from)?;
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &etag));
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag_header)]).into_response());
+    }
+
+    // メッセージを取得（after の場合は古い順、それ以外は新しい順）。ブロック中のユーザーのメッセージは除外
+    let db_messages = match after_id {
+        Some(after_id) => {
+            DbMessage::find_by_room_after(pool, room.id, after_id, limit, Some(viewer_id)).await?
+        }
+        None => {
+            DbMessage::find_by_room_with_users(pool, room.id, limit, before_id, Some(viewer_id))
+                .await?
+        }
+    };
 
     let has_more = db_messages.len() == limit as usize;
+    // 取得した向きの末尾が next_cursor、先頭が prev_cursor（新しい順なら最古と最新）
     let next_cursor = db_messages.last().map(|msg| msg.id.to_string());
+    let prev_cursor = db_messages.first().map(|msg| msg.id.to_string());
 
     // APIレスポンス形式に変換
     let messages: Vec<Message> = db_messages
         .into_iter()
-        .map(|msg| Message {
-            id: msg.id.to_string(),
-            room_id: msg.room_id.to_string(),
-            author_id: msg.user_id.to_string(),
-            author_name: msg.username,
-            author_avatar: msg.avatar_url,
-            content: msg.content,
-            created_at: msg.created_at,
-            message_type: match msg.message_type {
-                DbMessageType::Text => MessageType::Text,
-                DbMessageType::Image => MessageType::Image,
-                DbMessageType::File => MessageType::File,
-                DbMessageType::System => MessageType::System,
-            },
-        })
+        .map(|msg| Message::rendered(msg, &state.3))
         .collect();
 
-    Ok(Json(MessagesResponse {
-        messages,
-        has_more,
-        next_cursor,
-    }))
+    Ok((
+        [(header::ETAG, etag_header)],
+        Json(MessagesResponse {
+            messages,
+            has_more,
+            next_cursor,
+            prev_cursor,
+            server_time: chrono::Utc::now(),
+        }),
+    )
+        .into_response())
+}
+
+// メッセージ一覧の ETag（引用符を含み、W/ は含まない）
+//
+// ルームの状態・Markdown 描画の有無（`rendered_html` が変わる）とページの指定から作る。
+// `server_time` は毎回変わるため弱い ETag として返す。
+fn messages_etag(
+    version: &RoomMessagesVersion,
+    render_markdown: bool,
+    limit: i64,
+    before_id: Option<uuid::Uuid>,
+    after_id: Option<uuid::Uuid>,
+) -> String {
+    let source = format!(
+        "{:?}|{}|{:?}|{:?}|{}|{}|{:?}|{:?}",
+        version.latest_id,
+        version.message_count,
+        version.last_updated_at.map(|at| at.timestamp_micros()),
+        version.authors_updated_at.map(|at| at.timestamp_micros()),
+        render_markdown,
+        limit,
+        before_id,
+        after_id
+    );
+    let digest = Sha256::digest(source.as_bytes());
+    format!("\"{}\"", hex::encode(&digest[..16]))
 }
 
 #[utoipa::path(
-    post,
-    path = "/chat/{room}/send",
+    get,
+    path = "/chat/messages/{id}",
     params(
-        ("room" = String, Path, description = "Room ID")
+        ("id" = String, Path, description = "Message ID")
     ),
-    request_body = SendMessageRequest,
     responses(
-        (status = 200, description = "Message sent successfully", body = SendMessageResponse),
-        (status = 400, description = "Invalid message content"),
+        (status = 200, description = "Message retrieved successfully", body = Message),
+        (status = 400, description = "Invalid message ID"),
         (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Room not found")
+        (status = 404, description = "Message not found or not readable")
     ),
     tag = "Chat",
     security(
         ("bearer_auth" = [])
     )
 )]
-async fn send_message(
-    Path(room_name): Path<String>,
-    State(state): State<(PgPool, crate::ws::AppState, MeilisearchClient)>,
+async fn get_message(
+    Path(message_id): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
     user: AuthUser,
-    Json(payload): Json<SendMessageRequest>,
-) -> Result<Json<SendMessageResponse>, axum::http::StatusCode> {
-    let pool = &state.0;
-    let meili_client = &state.2;
-    // ルーム名からルームを検索
-    let room = Room::find_by_name(&pool, &room_name)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
-
-    // ユーザーIDをUUIDにパース
-    let user_id = user
+) -> AppResult<Json<Message>> {
+    let viewer_id = user
         .user_id
         .parse::<uuid::Uuid>()
-        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+    let message_id = message_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid message ID"))?;
 
-    // パブリックルームでない場合のみメンバーシップをチェック
-    if !room.is_public {
-        let is_member = room
-            .is_member(&pool, user_id)
-            .await
-            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    // 非公開ルームのメッセージの存在を漏らさないよう、閲覧できない場合も 404 にする
+    let message = DbMessage::find_accessible_with_user(&state.0, message_id, viewer_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("メッセージ"))?;
 
-        if !is_member {
-            return Err(axum::http::StatusCode::FORBIDDEN);
-        }
+    Ok(Json(Message::rendered(message, &state.3)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/messages/batch",
+    request_body = BatchMessagesRequest,
+    responses(
+        (status = 200, description = "Latest messages per readable room", body = BatchMessagesResponse),
+        (status = 400, description = "Empty or too many rooms"),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_messages_batch(
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<BatchMessagesRequest>,
+) -> AppResult<Json<BatchMessagesResponse>> {
+    let pool = &state.0;
+
+    let mut room_names = payload.rooms;
+    room_names.sort();
+    room_names.dedup();
+    if room_names.is_empty() || room_names.len() > MAX_BATCH_MESSAGE_ROOMS {
+        return Err(AppError::validation(format!(
+            "rooms には 1 から {} 件のルーム名を指定してください",
+            MAX_BATCH_MESSAGE_ROOMS
+        )));
     }
 
-    // メッセージタイプを変換
-    let db_message_type = match payload.message_type.unwrap_or(MessageType::Text) {
-        MessageType::Text => DbMessageType::Text,
-        MessageType::Image => DbMessageType::Image,
-        MessageType::File => DbMessageType::File,
-        MessageType::System => DbMessageType::System,
-    };
+    // ルーム数が多い場合は、合計が上限に収まるようルームごとの件数を減らす
+    let limit_per_room = resolve_limit(
+        payload.limit_per_room.map(i64::from),
+        BATCH_MESSAGE_LIMITS,
+        false,
+    )?
+    .min(MAX_BATCH_MESSAGES_TOTAL / room_names.len() as u32);
 
-    // メッセージを作成
-    let message = DbMessage::create(
-        &pool,
-        room.id,
-        user_id,
-        payload.content.clone(),
-        db_message_type.clone(),
-    )
-    .await
-    .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    let viewer_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
 
-    // ユーザー情報を取得
-    let user_info = crate::models::User::find_by_id(&pool, user_id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    // 存在しないルームと閲覧できないルームは結果に含めない
+    let rooms = Room::find_accessible_by_names(pool, viewer_id, &room_names).await?;
+    let room_ids: Vec<uuid::Uuid> = rooms.iter().map(|room| room.id).collect();
+    let db_messages =
+        DbMessage::find_latest_in_rooms(pool, &room_ids, limit_per_room as i64, viewer_id).await?;
 
-    // Meilisearchにインデックス追加
-    let index = meili_client.index("messages");
-    let search_document = serde_json::json!({
-        "id": message.id.to_string(),
-        "room_id": room.id.to_string(),
-        "room_name": room.name,
-        "author_id": user_id.to_string(),
-        "author_name": user_info.username,
-        "content": payload.content,
-        "created_at": message.created_at.timestamp(),
-        "message_type": match db_message_type {
-            DbMessageType::Text => "text",
-            DbMessageType::Image => "image",
-            DbMessageType::File => "file",
-            DbMessageType::System => "system",
+    let mut by_room: BTreeMap<String, Vec<Message>> = rooms
+        .iter()
+        .map(|room| (room.name.clone(), Vec::new()))
+        .collect();
+    for msg in db_messages {
+        if let Some(room) = rooms.iter().find(|room| room.id == msg.room_id) {
+            by_room
+                .entry(room.name.clone())
+                .or_default()
+                .push(Message::rendered(msg, &state.3));
         }
-    });
-
-    if let Err(e) = index.add_documents(&[search_document], Some("id")).await {
-        tracing::error!("Failed to index message in Meilisearch: {}", e);
-        // エラーをログに記録するが、メッセージ送信自体は成功とする
     }
 
-    Ok(Json(SendMessageResponse {
-        message_id: message.id.to_string(),
-        timestamp: message.created_at,
+    Ok(Json(BatchMessagesResponse {
+        rooms: by_room,
+        limit_per_room,
+        server_time: chrono::Utc::now(),
     }))
 }
 
 #[utoipa::path(
     post,
-    path = "/chat/rooms",
-    request_body = CreateRoomRequest,
+    path = "/chat/{room}/send",
+    params(
+        ("room" = String, Path, description = "Room ID"),
+        ("X-Idempotency-Key" = Option<String>, Header, description = "Deduplication key used when the body has no idempotency_key")
+    ),
+    request_body = SendMessageRequest,
     responses(
-        (status = 200, description = "Room created successfully", body = CreateRoomResponse),
-        (status = 400, description = "Invalid room data"),
+        (status = 200, description = "Message sent successfully (or the original message for a repeated idempotency key)", body = SendMessageResponse),
+        (status = 400, description = "Invalid message content, idempotency key or quoted message (must be in the same room)"),
         (status = 401, description = "Unauthorized"),
-        (status = 409, description = "Room name already exists")
+        (status = 404, description = "Room not found"),
+        (status = 429, description = "Slow mode: sent again before the room's interval elapsed (see Retry-After)")
     ),
     tag = "Chat",
     security(
         ("bearer_auth" = [])
     )
 )]
-async fn create_room(
-    State(state): State<(PgPool, crate::ws::AppState, MeilisearchClient)>,
+async fn send_message(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
     user: AuthUser,
-    Json(payload): Json<CreateRoomRequest>,
-) -> Result<Json<CreateRoomResponse>, axum::http::StatusCode> {
+    headers: HeaderMap,
+    Json(payload): Json<SendMessageRequest>,
+) -> Result<Response, axum::http::StatusCode> {
     let pool = &state.0;
-    // バリデーション
-    if payload.name.is_empty() || payload.name.len() > 100 {
-        return Err(axum::http::StatusCode::BAD_REQUEST);
-    }
+    let meili_client = state.2.as_ref();
+    // メッセージタイプを変換
+    let db_message_type = DbMessageType::from(payload.message_type.unwrap_or(MessageType::Text));
+
+    // スラッシュコマンドを解釈（未知のコマンドは保存せず 400）
+    let CommandOutput {
+        content,
+        message_type: db_message_type,
+    } = commands::process_message(
+        &payload.content,
+        db_message_type,
+        &CommandContext {
+            username: &user.username,
+        },
+    )
+    .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+
+    // メッセージコンテンツの整形とバリデーション
+    let content = sanitize_content(&content);
+    let content = match state.3.moderator.moderate(&content) {
+        Ok(content) => content,
+        // 禁止語による拒否は理由をエラーの本文で返す
+        Err(err) => return Ok(err.into_response()),
+    };
+
+    // 冪等キー（ボディ優先、なければヘッダー）
+    let idempotency_key = match payload.idempotency_key.as_deref().or_else(|| {
+        headers
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+    }) {
+        Some(key) => Some(
+            validate_idempotency_key(key)
+                .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?
+                .to_string(),
+        ),
+        None => None,
+    };
+
+    // ルーム名からルームを検索
+    let room = Room::find_by_name(pool, &room_name)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
 
     // ユーザーIDをUUIDにパース
     let user_id = user
@@ -344,50 +919,436 @@ async fn create_room(
         .parse::<uuid::Uuid>()
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
 
-    // ルーム名の重複チェック
-    if Room::find_by_name(&pool, &payload.name)
+    // パブリックルームでない場合のみメンバーシップをチェック
+    ensure_room_visible(&room, pool, user_id, &state.3)
         .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-        .is_some()
-    {
-        return Err(axum::http::StatusCode::CONFLICT);
+        .map_err(|e| e.status_code())?;
+
+    // 文字数の上限はルームの設定による
+    validate_message_content(
+        &content,
+        room.message_length_limit(state.3.max_room_message_length),
+    )
+    .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+
+    // 引用できるのは同じルームのメッセージのみ
+    let quoted = match payload.reply_to_message_id.as_deref() {
+        Some(reply_to_message_id) => {
+            let reply_to_message_id = reply_to_message_id
+                .parse::<uuid::Uuid>()
+                .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+            let quoted = DbMessage::find_with_user_in_room(pool, room.id, reply_to_message_id)
+                .await
+                .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(axum::http::StatusCode::BAD_REQUEST)?;
+            Some(QuotePreview::of(&quoted))
+        }
+        None => None,
+    };
+    let new_message = NewMessage {
+        room_id: room.id,
+        user_id,
+        content: content.clone(),
+        message_type: db_message_type.clone(),
+        reply_to_message_id: quoted.as_ref().map(|quote| quote.id),
+    };
+
+    // 保存に失敗した場合や冪等キーによる再送だった場合は、破棄された予約が取り消される
+    let slow_mode_reservation = if room.slow_mode_seconds.is_some() {
+        // 送信済みの冪等キーによる再送はスローモードに数えず、元のメッセージを返す
+        if let Some(key) = &idempotency_key {
+            let original =
+                DbMessage::find_by_idempotency_key(pool, user_id, key, IDEMPOTENCY_KEY_TTL)
+                    .await
+                    .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+            if let Some(original) = original {
+                return Ok(Json(SendMessageResponse {
+                    message_id: original.id.to_string(),
+                    seq: original.seq,
+                    timestamp: original.created_at,
+                })
+                .into_response());
+            }
+        }
+
+        // 間隔内の投稿は 429（Retry-After に送信できるまでの秒数）
+        match crate::slow_mode::reserve(pool, &room, user_id).await {
+            Ok(reservation) => Some(reservation),
+            Err(err) => {
+                let slow_mode = err
+                    .downcast::<crate::slow_mode::SlowModeActive>()
+                    .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+                return Ok(AppError::from(slow_mode).into_response());
+            }
+        }
+    } else {
+        None
+    };
+
+    // メッセージを作成
+    let message = match idempotency_key {
+        Some(key) => {
+            let (message, created) =
+                DbMessage::create_idempotent(pool, new_message, &key, IDEMPOTENCY_KEY_TTL)
+                    .await
+                    .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            // 再送: 元のメッセージをそのまま返す（インデックス登録も済んでいる）
+            if !created {
+                return Ok(Json(SendMessageResponse {
+                    message_id: message.id.to_string(),
+                    seq: message.seq,
+                    timestamp: message.created_at,
+                })
+                .into_response());
+            }
+            message
+        }
+        None => DbMessage::create(pool, new_message)
+            .await
+            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?,
+    };
+    if let Some(reservation) = slow_mode_reservation {
+        reservation.confirm();
     }
+    crate::metrics::message_sent("http");
+
+    // ユーザー情報を取得
+    let user_info = crate::models::User::find_by_id(pool, user_id)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
 
-    // ルームを作成
-    let room = Room::create(
-        &pool,
-        payload.name.clone(),
-        payload.description.clone(),
+    crate::ws::index_message(
+        meili_client,
+        &message,
+        &room,
+        user_id,
+        &user_info.username,
+        false,
+    )
+    .await;
+
+    // WebSocket で接続中のクライアントにもブロードキャスト（WS から送信した場合と同じ）
+    crate::ws::broadcast_to_room(
+        &room.name,
+        crate::ws::message_event(&message, &room.name, &user_info, false, quoted, &state.3),
+        Some(user_id),
+        None,
+        &state.1,
+    )
+    .await;
+
+    // URL を含む場合はリンクプレビューをバックグラウンドで取得
+    crate::link_preview::spawn_link_previews(
+        pool.clone(),
+        state.1.clone(),
+        state.3.clone(),
+        room.name.clone(),
+        message.id,
         user_id,
-        payload.is_public,
+        &content,
+    );
+
+    // 送信 Webhook へ転送（配信はバックグラウンドのワーカーが行う）
+    crate::webhooks::message_created(&room, &message, &user_info, &content);
+
+    // メンションされたユーザーに通知
+    crate::mentions::spawn_mention_notifications(
+        pool.clone(),
+        state.1.clone(),
+        room,
+        &message,
+        &user_info,
+        &content,
+    );
+
+    Ok(Json(SendMessageResponse {
+        message_id: message.id.to_string(),
+        seq: message.seq,
+        timestamp: message.created_at,
+    })
+    .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/rooms",
+    request_body = CreateRoomRequest,
+    responses(
+        (status = 200, description = "Room created successfully", body = CreateRoomResponse),
+        (status = 400, description = "Invalid room name (allowed: a-z, 0-9, '_', '-'; 2-100 characters)"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "The caller has already created the maximum number of rooms (MAX_ROOMS_PER_USER)"),
+        (status = 409, description = "Room name already exists")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn create_room(
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<CreateRoomRequest>,
+) -> AppResult<Json<CreateRoomResponse>> {
+    let pool = &state.0;
+    // ルーム名を正規化してバリデーション
+    let name = validate_room_name(&payload.name).map_err(AppError::validation)?;
+
+    if let Some(seconds) = payload.slow_mode_seconds {
+        if !(1..=MAX_SLOW_MODE_SECONDS).contains(&seconds) {
+            return Err(AppError::validation(format!(
+                "slow_mode_seconds must be between 1 and {}",
+                MAX_SLOW_MODE_SECONDS
+            )));
+        }
+    }
+
+    let max_room_message_length = state.3.max_room_message_length;
+    if let Some(length) = payload.max_message_length {
+        if !(1..=max_room_message_length as i64).contains(&i64::from(length)) {
+            return Err(AppError::validation(format!(
+                "max_message_length must be between 1 and {}",
+                max_room_message_length
+            )));
+        }
+    }
+
+    // ユーザーIDをUUIDにパース
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    // ルームを作成（重複はDBの一意制約で検出し、同時作成の競合も防ぐ。
+    // 作成数の上限は作成と同じトランザクションで確認する）
+    let max_rooms = state.3.max_rooms_per_user;
+    let room = match Room::create_within_quota(
+        pool,
+        NewRoom {
+            name: name.clone(),
+            description: payload.description.clone(),
+            created_by: user_id,
+            is_public: payload.is_public,
+            log_membership_events: payload.log_membership_events,
+            slow_mode_seconds: payload.slow_mode_seconds,
+            max_message_length: payload.max_message_length,
+        },
+        max_rooms as i64,
     )
     .await
-    .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    {
+        Ok(Some(room)) => room,
+        Ok(None) => {
+            return Err(AppError::forbidden(format!(
+                "作成できるルームは1人{}件までです",
+                max_rooms
+            )))
+        }
+        Err(e) if is_unique_violation(&e) => return Err(AppError::room_exists(name)),
+        Err(e) => return Err(e.into()),
+    };
 
-    // プライベートルームの場合、作成者をメンバーに追加
+    // プライベートルームの場合、作成者をオーナーとしてメンバーに追加
     if !payload.is_public {
-        room.add_member(&pool, user_id)
-            .await
-            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+        room.add_member_with_role(pool, user_id, "owner").await?;
     }
 
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "room.create",
+        None,
+        serde_json::json!({ "name": room.name, "is_public": room.is_public }),
+    )
+    .await;
+
     Ok(Json(CreateRoomResponse {
         id: room.id.to_string(),
         name: room.name,
         description: room.description,
         is_public: room.is_public,
+        log_membership_events: room.log_membership_events,
+        slow_mode_seconds: room.slow_mode_seconds,
+        max_message_length: room.max_message_length,
         created_at: room.created_at,
     }))
 }
 
+#[utoipa::path(
+    patch,
+    path = "/chat/{room}",
+    params(
+        ("room" = String, Path, description = "Room name")
+    ),
+    request_body = UpdateRoomRequest,
+    responses(
+        (status = 200, description = "Room updated successfully", body = CreateRoomResponse),
+        (status = 400, description = "Invalid room name or no field to update"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Only the room owner can update the room"),
+        (status = 404, description = "Room not found"),
+        (status = 409, description = "Room name already exists")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn update_room(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<UpdateRoomRequest>,
+) -> AppResult<Json<CreateRoomResponse>> {
+    let pool = &state.0;
+    let ws_state = &state.1;
+
+    let name = payload
+        .name
+        .as_deref()
+        .map(validate_room_name)
+        .transpose()
+        .map_err(AppError::validation)?;
+    let description = payload.description.map(|description| {
+        let description = description.trim();
+        (!description.is_empty()).then(|| description.to_string())
+    });
+    if name.is_none() && description.is_none() && payload.is_public.is_none() {
+        return Err(AppError::validation(
+            "name / description / is_public のいずれかを指定してください",
+        ));
+    }
+
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    let room = Room::find_by_name(pool, &room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
+
+    ensure_room_visible(&room, pool, user_id, &state.3).await?;
+    if !room.is_owner(pool, user_id).await? {
+        return Err(AppError::forbidden(
+            "ルームの設定を変更できるのはオーナーのみです",
+        ));
+    }
+
+    // 名前の重複はDBの一意制約で検出する（同時に同じ名前へ変更された場合も一方だけが成功する）
+    let updated = match room
+        .update(pool, name.clone(), description, payload.is_public)
+        .await
+    {
+        Ok(updated) => updated,
+        Err(e) if is_unique_violation(&e) => {
+            return Err(AppError::room_exists(name.unwrap_or_default()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let made_private = room.is_public && !updated.is_public;
+
+    // パブリックルームの作成者はメンバーとして登録されていないため、非公開にする前に owner として加える
+    if made_private && !updated.is_member(pool, updated.created_by).await? {
+        updated
+            .add_member_with_role(pool, updated.created_by, "owner")
+            .await?;
+    }
+
+    record_audit(
+        &updated,
+        pool,
+        user_id,
+        "room.update",
+        None,
+        serde_json::json!({
+            "old_name": room.name,
+            "name": updated.name,
+            "description": updated.description,
+            "is_public": updated.is_public,
+        }),
+    )
+    .await;
+
+    // 参加中の接続に知らせてから、新しい名前で届くよう AppState のキーを付け替える
+    crate::ws::broadcast_to_room(
+        &room.name,
+        WsMessage::RoomUpdated {
+            old_name: room.name.clone(),
+            new_name: updated.name.clone(),
+            description: updated.description.clone(),
+            is_public: updated.is_public,
+        },
+        None,
+        None,
+        ws_state,
+    )
+    .await;
+    if updated.name != room.name {
+        crate::ws::rename_room(&room.name, &updated.name, ws_state).await;
+    }
+
+    // 非公開にした場合、メンバー以外の接続はルームから外す
+    if made_private {
+        let mut non_members = Vec::new();
+        for connected_user in crate::ws::connected_user_ids(&updated.name, ws_state).await {
+            if !updated.is_member(pool, connected_user).await? {
+                non_members.push(connected_user);
+            }
+        }
+        crate::ws::remove_users_from_room(&updated.name, &non_members, ws_state).await;
+        for removed in non_members {
+            if let Some(removed) = crate::models::User::find_by_id(pool, removed).await? {
+                crate::ws::log_membership_event(
+                    &updated,
+                    format!(
+                        "{}はメンバーでないためルームから外されました",
+                        removed.username
+                    ),
+                    pool,
+                    ws_state,
+                    state.2.as_ref(),
+                )
+                .await;
+            }
+        }
+    }
+
+    Ok(Json(CreateRoomResponse {
+        id: updated.id.to_string(),
+        name: updated.name,
+        description: updated.description,
+        is_public: updated.is_public,
+        log_membership_events: updated.log_membership_events,
+        slow_mode_seconds: updated.slow_mode_seconds,
+        max_message_length: updated.max_message_length,
+        created_at: updated.created_at,
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/chat/{room}/members",
     params(
-        ("room" = String, Path, description = "Room ID or name")
+        ("room" = String, Path, description = "Room ID or name"),
+        MembersQuery
     ),
     responses(
         (status = 200, description = "Room members retrieved successfully", body = RoomMembersResponse),
+        (status = 400, description = "Invalid pagination parameters (strict mode)"),
         (status = 404, description = "Room not found"),
         (status = 403, description = "Access denied")
     ),
@@ -398,39 +1359,37 @@ async fn create_room(
 )]
 async fn get_room_members(
     Path(room_name): Path<String>,
-    State(state): State<(PgPool, crate::ws::AppState, MeilisearchClient)>,
+    Query(params): Query<MembersQuery>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
     user: AuthUser,
-) -> Result<Json<RoomMembersResponse>, axum::http::StatusCode> {
+) -> AppResult<Json<RoomMembersResponse>> {
     let pool = &state.0;
+    let strict = params.strict.unwrap_or(false);
+    let limit = resolve_limit(params.limit, MEMBER_PAGE_LIMITS, strict)? as i64;
+    let offset = resolve_offset(params.offset, strict)? as i64;
+
     // ユーザーIDをUUIDにパース
     let user_id = user
         .user_id
         .parse::<uuid::Uuid>()
-        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
 
     // ルーム名からルームを検索
-    let room = Room::find_by_name(&pool, &room_name)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    let room = Room::find_by_name(pool, &room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
 
     // プライベートルームの場合、ユーザーがメンバーかチェック
-    if !room.is_public {
-        let is_member = room
-            .is_member(&pool, user_id)
-            .await
-            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    ensure_room_visible(&room, pool, user_id, &state.3).await?;
 
-        if !is_member {
-            return Err(axum::http::StatusCode::FORBIDDEN);
-        }
-    }
-
-    // ルームメンバーを取得
-    let members = room
-        .get_members(&pool)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    // ルームメンバーを参加順に取得
+    let (members, total) = room.get_members(pool, limit, offset).await?;
+    let has_more = offset + (members.len() as i64) < total;
 
     let response_members: Vec<RoomMember> = members
         .into_iter()
@@ -443,12 +1402,83 @@ async fn get_room_members(
 
     Ok(Json(RoomMembersResponse {
         members: response_members,
+        total,
+        has_more,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/chat/{room}/messages/{message_id}/seen",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("message_id" = String, Path, description = "Message ID")
+    ),
+    responses(
+        (status = 200, description = "Members whose read marker is at or past the message", body = MessageSeenResponse),
+        (status = 400, description = "Invalid message ID"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a member of the private room"),
+        (status = 404, description = "Room or message not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_message_seen(
+    Path((room_name, message_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<MessageSeenResponse>> {
+    let pool = &state.0;
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+    let message_id = message_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid message ID"))?;
+
+    let room = Room::find_by_name(pool, &room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
+
+    // プライベートルームの場合、ユーザーがメンバーかチェック
+    ensure_room_visible(&room, pool, user_id, &state.3).await?;
+
+    // メッセージがこのルームに属しているかチェック
+    let message = DbMessage::find_by_id(pool, message_id)
+        .await?
+        .filter(|message| message.room_id == room.id)
+        .ok_or_else(|| AppError::not_found("メッセージ"))?;
+
+    let (readers, total) = room
+        .get_readers(pool, message.seq, MAX_SEEN_BY_USERS)
+        .await?;
+
+    Ok(Json(MessageSeenResponse {
+        users: readers
+            .into_iter()
+            .map(|reader| MessageReader {
+                user_id: reader.user_id.to_string(),
+                username: reader.username,
+                avatar_url: reader.avatar_url,
+            })
+            .collect(),
+        total,
     }))
 }
 
 #[utoipa::path(
     get,
     path = "/chat/rooms",
+    params(RoomsQuery),
     responses(
         (status = 200, description = "Rooms retrieved successfully", body = RoomsResponse),
         (status = 401, description = "Unauthorized")
@@ -459,34 +1489,67 @@ async fn get_room_members(
     )
 )]
 async fn get_rooms(
-    State(state): State<(PgPool, crate::ws::AppState, MeilisearchClient)>,
+    Query(params): Query<RoomsQuery>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
     user: AuthUser,
 ) -> Result<Json<RoomsResponse>, axum::http::StatusCode> {
     let pool = &state.0;
+    let limit = params.limit.unwrap_or(50).min(100) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+    let q = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
     // ユーザーIDをUUIDにパース
     let user_id = user
         .user_id
         .parse::<uuid::Uuid>()
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
 
-    // ユーザーがアクセス可能なルームを取得
-    let rooms = Room::get_accessible_rooms(&pool, user_id)
+    // ユーザーがアクセス可能なルームを最新アクティビティ順に取得
+    let (rooms, total) = Room::search_accessible(pool, user_id, q, limit, offset)
         .await
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let has_more = offset + (rooms.len() as i64) < total;
+
     let response_rooms: Vec<RoomInfo> = rooms
         .into_iter()
-        .map(|room| RoomInfo {
-            id: room.id.to_string(),
-            name: room.name,
-            description: room.description,
-            is_public: room.is_public,
-            created_at: room.created_at,
+        .map(|room| {
+            let last_message = match (
+                room.last_message_content,
+                room.last_message_username,
+                room.last_message_at,
+            ) {
+                (Some(content), Some(username), Some(timestamp)) => Some(LastMessagePreview {
+                    content,
+                    username,
+                    timestamp,
+                }),
+                _ => None,
+            };
+
+            RoomInfo {
+                id: room.id.to_string(),
+                name: room.name,
+                description: room.description,
+                is_public: room.is_public,
+                created_at: room.created_at,
+                last_message,
+                last_activity_at: room.last_activity_at,
+                member_count: room.member_count,
+                message_count: room.message_count,
+            }
         })
         .collect();
 
     Ok(Json(RoomsResponse {
         rooms: response_rooms,
+        total,
+        has_more,
     }))
 }
 
@@ -499,10 +1562,10 @@ async fn get_rooms(
     request_body = InviteUserRequest,
     responses(
         (status = 200, description = "User invited successfully", body = InviteUserResponse),
-        (status = 400, description = "Invalid request"),
+        (status = 400, description = "Public room, self-invite or invalid request"),
         (status = 403, description = "Access denied"),
         (status = 404, description = "Room or user not found"),
-        (status = 409, description = "User is already a member")
+        (status = 409, description = "User is already a member, or username matches multiple users")
     ),
     tag = "Chat",
     security(
@@ -511,69 +1574,227 @@ async fn get_rooms(
 )]
 async fn invite_user(
     Path(room_name): Path<String>,
-    State(state): State<(PgPool, crate::ws::AppState, MeilisearchClient)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
     user: AuthUser,
     Json(payload): Json<InviteUserRequest>,
-) -> Result<Json<InviteUserResponse>, axum::http::StatusCode> {
+) -> AppResult<Json<InviteUserResponse>> {
     let pool = &state.0;
-    // ユーザーIDをUUIDにパース
-    let user_id = user
-        .user_id
-        .parse::<uuid::Uuid>()
-        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    let (room, user_id) = find_room_for_invite(pool, &room_name, &user, &state.3).await?;
 
-    // ルームを検索
-    let room = Room::find_by_name(&pool, &room_name)
+    // パブリックルームには招待できない
+    if room.is_public {
+        return Err(AppError::bad_request(
+            "パブリックルームには招待は必要ありません",
+        ));
+    }
+
+    let (status, target_user) = invite_one(
+        &room,
+        pool,
+        &state.1,
+        state.2.as_ref(),
+        (user_id, &user.username),
+        &payload.username,
+    )
+    .await?;
+
+    match (status, target_user) {
+        (InviteStatus::Invited, Some(target_user)) => Ok(Json(InviteUserResponse {
+            success: true,
+            message: format!("{}をルームに招待しました", target_user.username),
+        })),
+        // 自分自身は招待できない（招待する本人は必ずメンバーなので AlreadyMember になる）
+        (InviteStatus::AlreadyMember, Some(target_user)) if target_user.id == user_id => {
+            Err(AppError::bad_request("自分自身を招待することはできません"))
+        }
+        (InviteStatus::AlreadyMember, Some(target_user)) => Err(AppError::conflict(format!(
+            "{}は既にメンバーです",
+            target_user.username
+        ))),
+        (InviteStatus::Ambiguous, _) => Err(AppError::conflict(format!(
+            "ユーザー名 \"{}\" に一致するユーザーが複数います",
+            payload.username.trim()
+        ))),
+        _ => Err(AppError::not_found("ユーザー")),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/{room}/invite-bulk",
+    params(
+        ("room" = String, Path, description = "Room name")
+    ),
+    request_body = BulkInviteRequest,
+    responses(
+        (status = 200, description = "Per-username invite results", body = BulkInviteResponse),
+        (status = 400, description = "Empty or too many usernames, or public room"),
+        (status = 403, description = "Access denied"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn invite_users_bulk(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<BulkInviteRequest>,
+) -> Result<Json<BulkInviteResponse>, axum::http::StatusCode> {
+    let pool = &state.0;
+    if payload.usernames.is_empty() || payload.usernames.len() > MAX_BULK_INVITE_USERNAMES {
+        return Err(axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    let (room, user_id) = find_room_for_invite(pool, &room_name, &user, &state.3)
         .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+        .map_err(|e| e.status_code())?;
 
     // パブリックルームには招待できない
     if room.is_public {
-        return Ok(Json(InviteUserResponse {
-            success: false,
-            message: "パブリックルームには招待は必要ありません".to_string(),
-        }));
+        return Err(axum::http::StatusCode::BAD_REQUEST);
     }
 
-    // 現在のユーザーがルームのメンバーかチェック
-    let is_member = room
-        .is_member(&pool, user_id)
+    // 1件ごとに結果を返し、一部の失敗で全体を失敗させない
+    let mut results = Vec::with_capacity(payload.usernames.len());
+    for username in payload.usernames {
+        let (status, target_user) = invite_one(
+            &room,
+            pool,
+            &state.1,
+            state.2.as_ref(),
+            (user_id, &user.username),
+            &username,
+        )
         .await
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    if !is_member {
-        return Err(axum::http::StatusCode::FORBIDDEN);
+        results.push(BulkInviteResult {
+            username,
+            status,
+            user_id: target_user.map(|target| target.id.to_string()),
+        });
     }
 
-    // 招待対象ユーザーを検索
-    let target_user = crate::models::User::find_by_username(&pool, &payload.username)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or_else(|| axum::http::StatusCode::NOT_FOUND)?;
+    Ok(Json(BulkInviteResponse { results }))
+}
 
-    // 既にメンバーかどうかチェック
-    let is_already_member = room
-        .is_member(&pool, target_user.id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+// 招待先ルームを検索し、招待する本人がメンバーであることを確認
+async fn find_room_for_invite(
+    pool: &PgPool,
+    room_name: &str,
+    user: &AuthUser,
+    config: &Config,
+) -> AppResult<(Room, uuid::Uuid)> {
+    // ユーザーIDをUUIDにパース
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    // ルームを検索
+    let room = Room::find_by_name(pool, room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
 
-    if is_already_member {
-        return Ok(Json(InviteUserResponse {
-            success: false,
-            message: format!("{}は既にメンバーです", payload.username),
-        }));
+    // 現在のユーザーがルームのメンバーかチェック（パブリックルームは各ハンドラーで扱う）
+    ensure_room_visible(&room, pool, user_id, config).await?;
+
+    Ok((room, user_id))
+}
+
+// ユーザー名で1人を招待する（大文字小文字は区別しない）
+//
+// 招待できたらルームに接続中のクライアントへ `member_added` を送り、メンバー一覧を更新させる。
+// プライベートルームでは、招待されたユーザーの接続に `invited_to_room` を送ってルームに参加させる。
+// ルームの `log_membership_events` が有効なら、招待をシステムメッセージとして履歴にも残す。
+async fn invite_one(
+    room: &Room,
+    pool: &PgPool,
+    ws_state: &crate::ws::AppState,
+    meili_client: Option<&MeilisearchClient>,
+    (inviter_id, inviter_name): (uuid::Uuid, &str),
+    username: &str,
+) -> anyhow::Result<(InviteStatus, Option<crate::models::User>)> {
+    let target_user = match crate::models::User::find_by_username_ci(pool, username).await {
+        Ok(Some(target_user)) => target_user,
+        Ok(None) => return Ok((InviteStatus::NotFound, None)),
+        Err(e) if e.is::<crate::models::AmbiguousUsername>() => {
+            return Ok((InviteStatus::Ambiguous, None))
+        }
+        Err(e) => return Err(e),
+    };
+
+    // 既にメンバーかどうかチェック
+    if room.is_member(pool, target_user.id).await? {
+        return Ok((InviteStatus::AlreadyMember, Some(target_user)));
     }
 
     // ユーザーをルームに追加
-    room.add_member(&pool, target_user.id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    room.add_member(pool, target_user.id).await?;
+
+    record_audit(
+        room,
+        pool,
+        inviter_id,
+        "member.invite",
+        Some(target_user.id),
+        serde_json::json!({ "username": target_user.username }),
+    )
+    .await;
+
+    crate::ws::broadcast_to_room(
+        &room.name,
+        WsMessage::MemberAdded {
+            room: room.name.clone(),
+            user_id: target_user.id.to_string(),
+            username: target_user.username.clone(),
+        },
+        None,
+        None,
+        ws_state,
+    )
+    .await;
 
-    Ok(Json(InviteUserResponse {
-        success: true,
-        message: format!("{}をルームに招待しました", payload.username),
-    }))
+    // 接続中の招待されたユーザーに知らせ、参加し直さなくてもルームのイベントが届くようにする
+    if !room.is_public {
+        crate::ws::send_to_user(
+            target_user.id,
+            WsMessage::InvitedToRoom {
+                room: room.name.clone(),
+                by_username: inviter_name.to_string(),
+            },
+            ws_state,
+        )
+        .await;
+        crate::ws::join_user_connections(&room.name, target_user.id, ws_state).await;
+    }
+
+    crate::ws::log_membership_event(
+        room,
+        format!(
+            "{}が{}をルームに招待しました",
+            inviter_name, target_user.username
+        ),
+        pool,
+        ws_state,
+        meili_client,
+    )
+    .await;
+
+    Ok((InviteStatus::Invited, Some(target_user)))
 }
 
 #[utoipa::path(
@@ -589,27 +1810,26 @@ async fn invite_user(
     )
 )]
 async fn get_online_users(
-    State(state): State<(PgPool, crate::ws::AppState, MeilisearchClient)>,
-    user: AuthUser, // 認証チェック
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    _user: AuthUser, // 認証チェック
 ) -> Result<Json<OnlineUsersResponse>, axum::http::StatusCode> {
     let ws_state = &state.1;
     // WebSocket状態から実際のオンラインユーザー情報を取得
-    let online_users_info = crate::ws::get_online_users_info(&ws_state).await;
+    let online_users_info = crate::ws::get_online_users_info(ws_state).await;
 
     let online_users: Vec<OnlineUser> = online_users_info
         .into_iter()
-        .map(|(user_id, username, rooms, connected_at)| {
-            // std::time::Instant を chrono::DateTime<Utc> に変換
-            let connected_at_utc = chrono::Utc::now()
-                - chrono::Duration::from_std(connected_at.elapsed())
-                    .unwrap_or_else(|_| chrono::Duration::zero());
-
-            OnlineUser {
-                user_id: user_id.to_string(),
-                username,
-                connected_rooms: rooms,
-                connected_at: connected_at_utc,
-            }
+        .map(|info| OnlineUser {
+            user_id: info.user_id.to_string(),
+            username: info.username,
+            connected_rooms: info.rooms,
+            connected_at: info.connected_at,
+            connection_count: info.connection_count,
         })
         .collect();
 
@@ -620,3 +1840,594 @@ async fn get_online_users(
         total_count,
     }))
 }
+
+#[utoipa::path(
+    get,
+    path = "/chat/{room}/pins",
+    params(
+        ("room" = String, Path, description = "Room name")
+    ),
+    responses(
+        (status = 200, description = "Pinned messages retrieved successfully (newest first)", body = PinnedMessagesResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Access denied"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_pinned_messages(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> Result<Json<PinnedMessagesResponse>, axum::http::StatusCode> {
+    let pool = &state.0;
+    // ユーザーIDをUUIDにパース
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+
+    // ルーム名からルームを検索
+    let room = Room::find_by_name(pool, &room_name)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+
+    // プライベートルームの場合、ユーザーがメンバーかチェック
+    ensure_room_visible(&room, pool, user_id, &state.3)
+        .await
+        .map_err(|e| e.status_code())?;
+
+    let pinned = room
+        .get_pinned(pool)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let pins: Vec<PinnedMessage> = pinned
+        .into_iter()
+        .map(|pin| PinnedMessage {
+            message: Message {
+                rendered_html: crate::markdown::rendered_html(
+                    &state.3,
+                    &pin.message_type,
+                    &pin.content,
+                ),
+                id: pin.message_id.to_string(),
+                room_id: pin.room_id.to_string(),
+                author_id: pin.user_id.to_string(),
+                author_name: pin.username,
+                author_avatar: pin.avatar_url,
+                author_is_bot: pin.is_bot,
+                content: pin.content,
+                created_at: pin.created_at,
+                updated_at: pin.updated_at,
+                edited: pin.updated_at > pin.created_at,
+                message_type: pin.message_type.into(),
+                seq: pin.seq,
+                quoted: None,
+            },
+            pinned_by: pin.pinned_by.to_string(),
+            pinned_by_name: pin.pinned_by_username,
+            pinned_at: pin.pinned_at,
+        })
+        .collect();
+
+    Ok(Json(PinnedMessagesResponse { pins }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/{room}/pins/{message_id}",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("message_id" = String, Path, description = "Message ID to pin")
+    ),
+    responses(
+        (status = 200, description = "Message pinned successfully", body = PinMessageResponse),
+        (status = 400, description = "Invalid message ID"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Moderator role required"),
+        (status = 404, description = "Room or message not found"),
+        (status = 409, description = "Message already pinned or pin limit reached")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn pin_message(
+    Path((room_name, message_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> Result<Json<PinMessageResponse>, axum::http::StatusCode> {
+    let pool = &state.0;
+    let ws_state = &state.1;
+    // ユーザーIDとメッセージIDをUUIDにパース
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    let message_id = message_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+
+    // ルーム名からルームを検索
+    let room = Room::find_by_name(pool, &room_name)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+
+    // モデレーター権限をチェック（メンバー以外には先にメンバーシップのエラーを返す）
+    ensure_room_visible(&room, pool, user_id, &state.3)
+        .await
+        .map_err(|e| e.status_code())?;
+    let is_moderator = room
+        .is_moderator(pool, user_id)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !is_moderator {
+        return Err(axum::http::StatusCode::FORBIDDEN);
+    }
+
+    // メッセージがこのルームに属しているかチェック
+    let message = DbMessage::find_by_id(pool, message_id)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+
+    if message.room_id != room.id {
+        return Err(axum::http::StatusCode::NOT_FOUND);
+    }
+
+    // 既にピン留め済みの場合と上限に達している場合は 409
+    let pinned = room
+        .pin_message(pool, message_id, user_id, MAX_PINS_PER_ROOM)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::CONFLICT)?;
+
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "message.pin",
+        Some(message_id),
+        serde_json::json!({}),
+    )
+    .await;
+
+    // ルーム内のクライアントに通知
+    crate::ws::broadcast_to_room(
+        &room.name,
+        WsMessage::MessagePinned {
+            room: room.name.clone(),
+            message_id: pinned.message_id.to_string(),
+            pinned_by: user_id.to_string(),
+            pinned_at: pinned.pinned_at,
+        },
+        None,
+        None,
+        ws_state,
+    )
+    .await;
+
+    Ok(Json(PinMessageResponse {
+        message_id: pinned.message_id.to_string(),
+        pinned_by: pinned.pinned_by.to_string(),
+        pinned_at: pinned.pinned_at,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/chat/{room}/pins/{message_id}",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("message_id" = String, Path, description = "Message ID to unpin")
+    ),
+    responses(
+        (status = 200, description = "Message unpinned successfully", body = UnpinMessageResponse),
+        (status = 400, description = "Invalid message ID"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Moderator role required"),
+        (status = 404, description = "Room or pin not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn unpin_message(
+    Path((room_name, message_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> Result<Json<UnpinMessageResponse>, axum::http::StatusCode> {
+    let pool = &state.0;
+    let ws_state = &state.1;
+    // ユーザーIDとメッセージIDをUUIDにパース
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    let message_id = message_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+
+    // ルーム名からルームを検索
+    let room = Room::find_by_name(pool, &room_name)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+
+    // モデレーター権限をチェック（メンバー以外には先にメンバーシップのエラーを返す）
+    ensure_room_visible(&room, pool, user_id, &state.3)
+        .await
+        .map_err(|e| e.status_code())?;
+    let is_moderator = room
+        .is_moderator(pool, user_id)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !is_moderator {
+        return Err(axum::http::StatusCode::FORBIDDEN);
+    }
+
+    let removed = room
+        .unpin_message(pool, message_id)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !removed {
+        return Err(axum::http::StatusCode::NOT_FOUND);
+    }
+
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "message.unpin",
+        Some(message_id),
+        serde_json::json!({}),
+    )
+    .await;
+
+    // ルーム内のクライアントに通知
+    crate::ws::broadcast_to_room(
+        &room.name,
+        WsMessage::MessageUnpinned {
+            room: room.name.clone(),
+            message_id: message_id.to_string(),
+            unpinned_by: user_id.to_string(),
+        },
+        None,
+        None,
+        ws_state,
+    )
+    .await;
+
+    Ok(Json(UnpinMessageResponse {
+        success: true,
+        message_id: message_id.to_string(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/chat/{room}/audit",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        AuditLogQuery
+    ),
+    responses(
+        (status = 200, description = "Audit log retrieved successfully", body = AuditLogResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Only room owners can view the audit log"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_audit_log(
+    Path(room_name): Path<String>,
+    Query(params): Query<AuditLogQuery>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> Result<Json<AuditLogResponse>, axum::http::StatusCode> {
+    let pool = &state.0;
+    let limit = params.limit.unwrap_or(50).min(100) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+
+    // ユーザーIDをUUIDにパース
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+
+    // ルーム名からルームを検索
+    let room = Room::find_by_name(pool, &room_name)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+
+    // オーナーのみ閲覧可能（メンバー以外には先にメンバーシップのエラーを返す）
+    ensure_room_visible(&room, pool, user_id, &state.3)
+        .await
+        .map_err(|e| e.status_code())?;
+    let is_owner = room
+        .is_owner(pool, user_id)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !is_owner {
+        return Err(axum::http::StatusCode::FORBIDDEN);
+    }
+
+    let (entries, total) = room
+        .get_audit_log(pool, limit, offset)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let has_more = offset + (entries.len() as i64) < total;
+
+    let entries = entries
+        .into_iter()
+        .map(|entry| AuditLogEntry {
+            id: entry.id.to_string(),
+            action: entry.action,
+            actor_id: entry.actor_id.to_string(),
+            actor_name: entry.actor_username,
+            target_id: entry.target_id.map(|id| id.to_string()),
+            metadata: entry.metadata,
+            created_at: entry.created_at,
+        })
+        .collect();
+
+    Ok(Json(AuditLogResponse {
+        entries,
+        total,
+        has_more,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/blocks/{username}",
+    params(
+        ("username" = String, Path, description = "Username to block")
+    ),
+    responses(
+        (status = 200, description = "User blocked (idempotent)", body = BlockUserResponse),
+        (status = 400, description = "Cannot block yourself"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Username matches several users case-insensitively")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn block_user(
+    Path(username): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<BlockUserResponse>> {
+    set_blocked(&state, &user, &username, true).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/chat/blocks/{username}",
+    params(
+        ("username" = String, Path, description = "Username to unblock")
+    ),
+    responses(
+        (status = 200, description = "User unblocked (idempotent)", body = BlockUserResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Username matches several users case-insensitively")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn unblock_user(
+    Path(username): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<BlockUserResponse>> {
+    set_blocked(&state, &user, &username, false).await
+}
+
+// ブロック・解除の共通処理（DB と接続中のWebSocketのブロック一覧を更新）
+async fn set_blocked(
+    state: &(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    ),
+    user: &AuthUser,
+    username: &str,
+    blocked: bool,
+) -> AppResult<Json<BlockUserResponse>> {
+    let pool = &state.0;
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    // 招待と同じく大文字小文字を区別せずに探し、ボットは対象外
+    let target = match crate::models::User::find_by_username_ci(pool, username).await {
+        Ok(Some(target)) => target,
+        Ok(None) => return Err(AppError::not_found("ユーザー")),
+        Err(e) if e.is::<crate::models::AmbiguousUsername>() => {
+            return Err(AppError::conflict(
+                "大文字小文字違いの同名ユーザーが複数いるため特定できません",
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if blocked {
+        if target.id == user_id {
+            return Err(AppError::validation("自分自身はブロックできません"));
+        }
+        crate::models::User::block(pool, user_id, target.id).await?;
+    } else {
+        crate::models::User::unblock(pool, user_id, target.id).await?;
+    }
+
+    crate::ws::update_blocked_user(user_id, target.id, blocked, &state.1).await;
+
+    Ok(Json(BlockUserResponse {
+        username: target.username,
+        blocked,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/chat/notification-prefs",
+    responses(
+        (status = 200, description = "Notification preferences", body = NotificationPrefsResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn get_notification_prefs(
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<NotificationPrefsResponse>> {
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    Ok(Json(notification_prefs_response(&state.0, user_id).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/chat/notification-prefs",
+    request_body = UpdateNotificationPrefRequest,
+    responses(
+        (status = 200, description = "Preference updated", body = NotificationPrefsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a member of the private room"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn update_notification_prefs(
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<UpdateNotificationPrefRequest>,
+) -> AppResult<Json<NotificationPrefsResponse>> {
+    let pool = &state.0;
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    // ルーム指定時は閲覧できるルームに限る
+    let room_id = match &payload.room {
+        Some(room_name) => {
+            let room = Room::find_by_name(pool, room_name)
+                .await?
+                .ok_or_else(|| AppError::not_found("ルーム"))?;
+            ensure_room_visible(&room, pool, user_id, &state.3).await?;
+            Some(room.id)
+        }
+        None => None,
+    };
+
+    match payload.mode {
+        Some(mode) => NotificationPref::set(pool, user_id, room_id, mode.into()).await?,
+        None => {
+            NotificationPref::clear(pool, user_id, room_id).await?;
+        }
+    }
+
+    Ok(Json(notification_prefs_response(pool, user_id).await?))
+}
+
+async fn notification_prefs_response(
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+) -> AppResult<NotificationPrefsResponse> {
+    let prefs = NotificationPref::find_by_user(pool, user_id).await?;
+
+    let global = prefs
+        .iter()
+        .find(|pref| pref.room_id.is_none())
+        .map(|pref| pref.mode)
+        .unwrap_or_default();
+    let rooms = prefs
+        .into_iter()
+        .filter_map(|pref| {
+            Some(RoomNotificationPref {
+                room: pref.room_name?,
+                mode: pref.mode.into(),
+            })
+        })
+        .collect();
+
+    Ok(NotificationPrefsResponse {
+        global: global.into(),
+        rooms,
+    })
+}
@@ -1,18 +1,61 @@
-use axum::Router;
+use axum::{middleware, Router};
 use meilisearch_sdk::client::Client as MeilisearchClient;
 use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::config::Config;
 
 pub mod auth;
+pub mod avatar;
 pub mod chat;
+pub mod pagination;
+pub mod rate_limit;
+pub mod reports;
 pub mod response;
 pub mod search;
+pub mod webhooks;
+pub mod ws_stats;
+
+/// ログインのルートは接続元 IP ごと、検索は認証ユーザーごとにレート制限する（上限は `config` から）
+pub fn create_router(
+    config: Arc<Config>,
+) -> Router<(PgPool, Option<MeilisearchClient>, Arc<Config>)> {
+    let auth_limiter = rate_limit::RateLimiter::new(config.auth_rate_limit);
+    let search_limiter = rate_limit::RateLimiter::new(config.search_rate_limit);
 
-pub fn create_router() -> Router<(PgPool, MeilisearchClient)> {
     Router::new()
-        .nest("/auth", auth::router())
-        .nest("/search", search::router())
+        .nest(
+            "/auth",
+            auth::login_router()
+                .route_layer(middleware::from_fn_with_state(
+                    (auth_limiter, config.clone()),
+                    rate_limit::limit_by_ip,
+                ))
+                .merge(auth::router()),
+        )
+        .nest("/avatar", avatar::router())
+        .nest(
+            "/search",
+            search::router().route_layer(middleware::from_fn_with_state(
+                (search_limiter, config),
+                rate_limit::limit_by_user,
+            )),
+        )
 }
 
-pub fn create_chat_router() -> Router<(PgPool, crate::ws::AppState, MeilisearchClient)> {
-    Router::new().nest("/api/chat", chat::router())
+pub fn create_chat_router() -> Router<(
+    PgPool,
+    crate::ws::AppState,
+    Option<MeilisearchClient>,
+    Arc<Config>,
+)> {
+    Router::new()
+        .nest(
+            "/api/chat",
+            chat::router()
+                .merge(webhooks::router())
+                .merge(reports::router()),
+        )
+        .nest("/api/auth", auth::sessions_router())
+        .nest("/api/ws", ws_stats::router())
 }
@@ -0,0 +1,102 @@
+//! `limit` / `offset` クエリパラメーターの解釈
+//!
+//! 既定では範囲外の値を黙って丸めるが、`?strict=true` が指定された場合は
+//! 範囲外の値を `AppError::validation` として 400 で返す。
+//! 既定値と上限はエンドポイントごとに `Config` から渡す。
+
+use serde_json::json;
+
+use crate::error::{AppError, AppResult};
+
+/// `limit` の既定値と上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimits {
+    /// `limit` 未指定時の件数
+    pub default: u32,
+    /// 1回のリクエストで取得できる件数の上限
+    pub max: u32,
+}
+
+/// `limit` を解釈する（未指定時は `limits.default`）
+///
+/// 非 strict モードでは `1..=limits.max` に丸める。
+pub fn resolve_limit(limit: Option<i64>, limits: PageLimits, strict: bool) -> AppResult<u32> {
+    let Some(limit) = limit else {
+        return Ok(limits.default);
+    };
+
+    if strict && !(1..=limits.max as i64).contains(&limit) {
+        return Err(AppError::validation_with_details(
+            format!("limit は 1 から {} の範囲で指定してください", limits.max),
+            json!({ "field": "limit", "value": limit, "min": 1, "max": limits.max }),
+        ));
+    }
+
+    Ok(limit.clamp(1, limits.max as i64) as u32)
+}
+
+/// `offset` を解釈する（未指定時は 0）
+///
+/// 非 strict モードでは負の値を 0 に丸める。
+pub fn resolve_offset(offset: Option<i64>, strict: bool) -> AppResult<u32> {
+    let offset = offset.unwrap_or(0);
+
+    if strict && offset < 0 {
+        return Err(AppError::validation_with_details(
+            "offset には 0 以上の値を指定してください",
+            json!({ "field": "offset", "value": offset, "min": 0 }),
+        ));
+    }
+
+    Ok(offset.clamp(0, u32::MAX as i64) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: PageLimits = PageLimits {
+        default: 50,
+        max: 100,
+    };
+
+    #[test]
+    fn test_resolve_limit_clamps_by_default() {
+        assert_eq!(resolve_limit(None, LIMITS, false).unwrap(), 50);
+        assert_eq!(resolve_limit(Some(10), LIMITS, false).unwrap(), 10);
+        assert_eq!(resolve_limit(Some(500), LIMITS, false).unwrap(), LIMITS.max);
+        assert_eq!(resolve_limit(Some(0), LIMITS, false).unwrap(), 1);
+        assert_eq!(resolve_limit(Some(-5), LIMITS, false).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_resolve_limit_strict_rejects_out_of_range() {
+        assert_eq!(resolve_limit(None, LIMITS, true).unwrap(), 50);
+        assert_eq!(resolve_limit(Some(100), LIMITS, true).unwrap(), 100);
+
+        for limit in [0, 101, -1] {
+            let err = resolve_limit(Some(limit), LIMITS, true).unwrap_err();
+            assert_eq!(err.code(), "VALIDATION_ERROR");
+            assert_eq!(err.details().unwrap()["max"], LIMITS.max);
+        }
+    }
+
+    #[test]
+    fn test_resolve_limit_uses_given_cap() {
+        let limits = PageLimits {
+            default: 5,
+            max: 10,
+        };
+        assert_eq!(resolve_limit(None, limits, false).unwrap(), 5);
+        assert_eq!(resolve_limit(Some(50), limits, false).unwrap(), 10);
+        assert!(resolve_limit(Some(11), limits, true).is_err());
+    }
+
+    #[test]
+    fn test_resolve_offset() {
+        assert_eq!(resolve_offset(None, true).unwrap(), 0);
+        assert_eq!(resolve_offset(Some(20), true).unwrap(), 20);
+        assert_eq!(resolve_offset(Some(-1), false).unwrap(), 0);
+        assert!(resolve_offset(Some(-1), true).is_err());
+    }
+}
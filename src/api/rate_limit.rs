@@ -0,0 +1,179 @@
+//! HTTP エンドポイントのレート制限
+//!
+//! 認証ルートは接続元 IP ごと、検索は認証ユーザーごとに固定ウィンドウで回数を数え、
+//! 上限を超えたリクエストには `Retry-After` 付きの 429 を返す。
+//! カウンターはルーターごとに持つため、アプリを作り直すとリセットされる。
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, Extensions, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use super::auth::verify_jwt;
+use crate::config::{Config, HTTP_RATE_LIMIT_WINDOW};
+use crate::error::AppError;
+
+/// キーごとの固定ウィンドウのカウンター
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    windows: Mutex<HashMap<String, (Instant, usize)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: usize) -> Arc<Self> {
+        Arc::new(Self {
+            limit,
+            window: HTTP_RATE_LIMIT_WINDOW,
+            windows: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 1回分を消費する。上限を超えている場合は再試行できるまでの秒数を返す
+    fn check(&self, key: &str) -> Result<(), u64> {
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        // 期限切れのウィンドウは捨てる（一度きりのクライアントの分が残り続けないように）
+        windows.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+
+        let (started, count) = windows.entry(key.to_string()).or_insert((now, 0));
+        if *count >= self.limit {
+            let remaining = self.window.saturating_sub(now.duration_since(*started));
+            return Err(remaining.as_secs().max(1));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// 接続元 IP ごとのレート制限（認証ルート用）
+pub async fn limit_by_ip(
+    State((limiter, config)): State<(Arc<RateLimiter>, Arc<Config>)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = client_ip(request.headers(), request.extensions(), &config);
+    match limiter.check(&key) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            tracing::warn!(
+                "HTTP rate limit exceeded for {} on {}",
+                key,
+                request.uri().path()
+            );
+            too_many_requests(retry_after)
+        }
+    }
+}
+
+/// 認証ユーザーごとのレート制限（検索用）
+///
+/// トークンが無効なリクエストはそのまま通し、ハンドラーで 401 にする。
+pub async fn limit_by_user(
+    State((limiter, config)): State<(Arc<RateLimiter>, Arc<Config>)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let user_id = bearer_token(request.headers())
+        .and_then(|token| verify_jwt(token, &config).ok())
+        .map(|claims| claims.sub);
+
+    if let Some(user_id) = user_id {
+        if let Err(retry_after) = limiter.check(&user_id) {
+            tracing::warn!(
+                "HTTP rate limit exceeded for user {} on {}",
+                user_id,
+                request.uri().path()
+            );
+            return too_many_requests(retry_after);
+        }
+    }
+    next.run(request).await
+}
+
+fn too_many_requests(retry_after: u64) -> Response {
+    (
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        AppError::RateLimit,
+    )
+        .into_response()
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// 接続元 IP
+//
+// `TRUSTED_PROXY_COUNT` が 1 以上の場合は X-Forwarded-For の右から数えて、信頼できるプロキシが
+// 追加した末尾の要素を使う。左側はクライアントが自由に書けるため信用しない。
+// ヘッダーの要素が足りない・IP でない場合は接続元のアドレスを使う。
+pub(crate) fn client_ip(headers: &HeaderMap, extensions: &Extensions, config: &Config) -> String {
+    if let Some(ip) = forwarded_ip(headers, config.trusted_proxy_count) {
+        return ip.to_string();
+    }
+
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn forwarded_ip(headers: &HeaderMap, trusted_proxy_count: usize) -> Option<IpAddr> {
+    if trusted_proxy_count == 0 {
+        return None;
+    }
+    // 複数の X-Forwarded-For ヘッダーは順に連結したものとして扱う
+    let mut entries = Vec::new();
+    for value in headers.get_all("x-forwarded-for") {
+        entries.extend(value.to_str().ok()?.split(',').map(str::trim));
+    }
+    let index = entries.len().checked_sub(trusted_proxy_count)?;
+    entries[index].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_counts_per_key() {
+        let limiter = RateLimiter::new(2);
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_ok());
+        let retry_after = limiter.check("a").unwrap_err();
+        assert!((1..=HTTP_RATE_LIMIT_WINDOW.as_secs()).contains(&retry_after));
+
+        // 別のキーは独立して数える
+        assert!(limiter.check("b").is_ok());
+    }
+
+    #[test]
+    fn test_forwarded_ip_uses_rightmost_trusted_entry() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", "1.1.1.1, 2.2.2.2".parse().unwrap());
+        headers.append("x-forwarded-for", "3.3.3.3".parse().unwrap());
+
+        assert_eq!(forwarded_ip(&headers, 0), None);
+        assert_eq!(forwarded_ip(&headers, 1), Some("3.3.3.3".parse().unwrap()));
+        assert_eq!(forwarded_ip(&headers, 2), Some("2.2.2.2".parse().unwrap()));
+        assert_eq!(forwarded_ip(&headers, 4), None);
+
+        // 偽装された先頭の値や IP でない値は使わない
+        headers.insert("x-forwarded-for", "spoofed, 4.4.4.4".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers, 2), None);
+        assert_eq!(forwarded_ip(&headers, 1), Some("4.4.4.4".parse().unwrap()));
+    }
+}
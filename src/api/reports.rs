@@ -0,0 +1,322 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use meilisearch_sdk::client::Client as MeilisearchClient;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+use super::auth::AuthUser;
+use super::chat::{ensure_room_visible, record_audit};
+use super::pagination::resolve_limit;
+use crate::config::{Config, MAX_REPORT_REASON_LENGTH, REPORT_PAGE_LIMITS};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    is_unique_violation, Message as DbMessage, MessageReport, MessageReportWithDetails, Room,
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReportMessageRequest {
+    /// 通報の理由（1〜1000文字）
+    pub reason: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReportMessageResponse {
+    pub id: String,
+    pub message_id: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 未解決の通報（モデレーター向け）
+#[derive(Serialize, ToSchema)]
+pub struct MessageReportInfo {
+    pub id: String,
+    pub message_id: String,
+    pub message_content: String,
+    pub message_author_id: String,
+    pub message_author_name: String,
+    pub reporter_id: String,
+    pub reporter_name: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReportsQuery {
+    pub limit: Option<i64>,
+    /// 前のページの `next_cursor`（指定した通報より後に作られた通報を返す）
+    pub after: Option<String>,
+    /// trueの場合、範囲外の limit を丸めずに 400 を返す
+    pub strict: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReportsResponse {
+    /// 未解決の通報（古い順）
+    pub reports: Vec<MessageReportInfo>,
+    pub has_more: bool,
+    /// 続きを取得するためのカーソル（返したうちの最も新しい通報のID）。次の `after` に渡す
+    pub next_cursor: Option<String>,
+}
+
+impl From<MessageReportWithDetails> for MessageReportInfo {
+    fn from(report: MessageReportWithDetails) -> Self {
+        Self {
+            id: report.id.to_string(),
+            message_id: report.message_id.to_string(),
+            message_content: report.message_content,
+            message_author_id: report.message_author_id.to_string(),
+            message_author_name: report.message_author_name,
+            reporter_id: report.reporter_id.to_string(),
+            reporter_name: report.reporter_name,
+            reason: report.reason,
+            created_at: report.created_at,
+        }
+    }
+}
+
+pub fn router() -> Router<(
+    PgPool,
+    crate::ws::AppState,
+    Option<MeilisearchClient>,
+    Arc<Config>,
+)> {
+    Router::new()
+        .route("/{room}/messages/{message_id}/report", post(report_message))
+        .route("/{room}/reports", get(list_reports))
+        .route("/{room}/reports/{report_id}/resolve", post(resolve_report))
+}
+
+fn parse_user_id(user: &AuthUser) -> AppResult<uuid::Uuid> {
+    user.user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))
+}
+
+// ルームを取得し、操作するユーザーがモデレーターであることを確認する
+async fn find_moderated_room(
+    pool: &PgPool,
+    room_name: &str,
+    user: &AuthUser,
+    config: &Config,
+) -> AppResult<(Room, uuid::Uuid)> {
+    let user_id = parse_user_id(user)?;
+
+    let room = Room::find_by_name(pool, room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
+
+    ensure_room_visible(&room, pool, user_id, config).await?;
+    if !room.is_moderator(pool, user_id).await? {
+        return Err(AppError::forbidden(
+            "通報の確認はルームのモデレーターのみ可能です",
+        ));
+    }
+
+    Ok((room, user_id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/{room}/messages/{message_id}/report",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("message_id" = String, Path, description = "Message ID to report")
+    ),
+    request_body = ReportMessageRequest,
+    responses(
+        (status = 200, description = "Message reported", body = ReportMessageResponse),
+        (status = 400, description = "Invalid message ID or empty / too long reason"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a member of the private room"),
+        (status = 404, description = "Room or message not found"),
+        (status = 409, description = "Already reported this message")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn report_message(
+    Path((room_name, message_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<ReportMessageRequest>,
+) -> AppResult<Json<ReportMessageResponse>> {
+    let pool = &state.0;
+    let user_id = parse_user_id(&user)?;
+    let message_id = message_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid message ID"))?;
+
+    let reason = payload.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REPORT_REASON_LENGTH {
+        return Err(AppError::validation(format!(
+            "通報の理由は1〜{}文字で入力してください",
+            MAX_REPORT_REASON_LENGTH
+        )));
+    }
+
+    let room = Room::find_by_name(pool, &room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
+
+    // プライベートルームの場合、ユーザーがメンバーかチェック
+    ensure_room_visible(&room, pool, user_id, &state.3).await?;
+
+    // メッセージがこのルームに属しているかチェック
+    DbMessage::find_by_id(pool, message_id)
+        .await?
+        .filter(|message| message.room_id == room.id)
+        .ok_or_else(|| AppError::not_found("メッセージ"))?;
+
+    let report = match MessageReport::create(pool, message_id, user_id, reason).await {
+        Ok(report) => report,
+        Err(e) if is_unique_violation(&e) => {
+            return Err(AppError::conflict("このメッセージは既に通報済みです"))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    tracing::info!(
+        "User {} reported message {} in room {}",
+        user.username,
+        message_id,
+        room.name
+    );
+
+    Ok(Json(ReportMessageResponse {
+        id: report.id.to_string(),
+        message_id: report.message_id.to_string(),
+        reason: report.reason,
+        created_at: report.created_at,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/chat/{room}/reports",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ReportsQuery
+    ),
+    responses(
+        (status = 200, description = "Open reports of the room", body = ReportsResponse),
+        (status = 400, description = "Invalid cursor or limit out of range (strict mode)"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Moderator role required"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn list_reports(
+    Path(room_name): Path<String>,
+    Query(params): Query<ReportsQuery>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<ReportsResponse>> {
+    let pool = &state.0;
+    let limit = resolve_limit(
+        params.limit,
+        REPORT_PAGE_LIMITS,
+        params.strict.unwrap_or(false),
+    )? as i64;
+    let after = params
+        .after
+        .as_deref()
+        .map(|cursor| {
+            cursor
+                .parse::<uuid::Uuid>()
+                .map_err(|_| AppError::bad_request("Invalid cursor"))
+        })
+        .transpose()?;
+    let (room, _) = find_moderated_room(pool, &room_name, &user, &state.3).await?;
+
+    let reports = MessageReport::find_open_in_room(pool, room.id, limit, after).await?;
+    let has_more = reports.len() == limit as usize;
+    let next_cursor = reports.last().map(|report| report.id.to_string());
+
+    Ok(Json(ReportsResponse {
+        reports: reports.into_iter().map(MessageReportInfo::from).collect(),
+        has_more,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/{room}/reports/{report_id}/resolve",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("report_id" = String, Path, description = "Report ID")
+    ),
+    responses(
+        (status = 204, description = "Report resolved"),
+        (status = 400, description = "Invalid report ID"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Moderator role required"),
+        (status = 404, description = "Room or report not found"),
+        (status = 409, description = "Report already resolved")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn resolve_report(
+    Path((room_name, report_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<StatusCode> {
+    let pool = &state.0;
+    let (room, user_id) = find_moderated_room(pool, &room_name, &user, &state.3).await?;
+    let report_id = report_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid report ID"))?;
+
+    let report = MessageReport::find_in_room(pool, room.id, report_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("通報"))?;
+
+    if !MessageReport::resolve(pool, report.id, user_id).await? {
+        return Err(AppError::conflict("この通報は既に解決済みです"));
+    }
+
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "report.resolve",
+        Some(report.id),
+        serde_json::json!({ "message_id": report.message_id }),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
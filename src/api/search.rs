@@ -4,21 +4,62 @@ use axum::{
     routing::get,
     Router,
 };
-use meilisearch_sdk::client::Client as MeilisearchClient;
+use meilisearch_sdk::{client::Client as MeilisearchClient, settings::Settings};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 use utoipa::{IntoParams, ToSchema};
 
 use super::auth::AuthUser;
-use super::chat::{Message, MessageType};
+use super::chat::{ensure_room_visible, Message};
+use super::pagination::{resolve_limit, resolve_offset};
+use crate::config::{Config, MAX_SEARCH_SNIPPET_CROP_LENGTH, SEARCH_SNIPPET_CROP_LENGTH};
+use crate::error::{AppError, AppResult};
+use crate::models::{DbMessageType, Message as DbMessage, MessageSearchFilter, Room};
 
 #[derive(Deserialize, IntoParams)]
 pub struct SearchQuery {
     pub q: String,
     pub room: Option<String>,
     pub author: Option<String>,
-    pub limit: Option<u32>,
-    pub offset: Option<u32>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// trueの場合、範囲外の limit / offset を丸めずに 400 を返す
+    pub strict: Option<bool>,
+    /// ハイライトとして切り出す単語数（既定: 30、1〜200に丸める）
+    pub crop: Option<usize>,
+    /// 並び順: `relevance`（既定、関連度順）または `recent`（新しい順）
+    pub sort: Option<String>,
+    /// この日時（unix 秒）以降に投稿されたメッセージに絞り込む
+    pub after: Option<i64>,
+    /// この日時（unix 秒）以前に投稿されたメッセージに絞り込む
+    pub before: Option<i64>,
+}
+
+/// メッセージインデックスでフィルターに使う属性
+const MESSAGES_FILTERABLE_ATTRIBUTES: [&str; 4] =
+    ["room_id", "room_name", "author_name", "created_at"];
+/// メッセージインデックスでソートに使う属性
+const MESSAGES_SORTABLE_ATTRIBUTES: [&str; 2] = ["created_at", "seq"];
+
+/// メッセージインデックスの設定（フィルター・ソートに使う属性）を反映する
+///
+/// 設定の反映は Meilisearch 側で非同期に行われるため、完了まで待つ。
+pub async fn configure_messages_index(client: &MeilisearchClient) -> anyhow::Result<()> {
+    let settings = Settings::new()
+        .with_filterable_attributes(MESSAGES_FILTERABLE_ATTRIBUTES)
+        .with_sortable_attributes(MESSAGES_SORTABLE_ATTRIBUTES);
+
+    let task = client
+        .index("messages")
+        .set_settings(&settings)
+        .await?
+        .wait_for_completion(client, None, None)
+        .await?;
+    if task.is_failure() {
+        anyhow::bail!("Failed to update messages index settings: {:?}", task);
+    }
+    Ok(())
 }
 
 #[derive(Serialize, ToSchema)]
@@ -32,11 +73,17 @@ pub struct SearchResult {
 pub struct SearchResponse {
     pub results: Vec<SearchResult>,
     pub total_hits: u64,
+    /// `total_hits` が推定値か（Meilisearch の検索では推定値、フォールバック時は正確な件数）
+    pub total_hits_estimated: bool,
     pub query_time_ms: u32,
     pub has_more: bool,
+    /// Meilisearch障害時にPostgresフォールバックで検索した場合はtrue
+    pub degraded: bool,
+    /// 応答時のサーバー時刻（クライアントは時計のずれの補正に使う）
+    pub server_time: chrono::DateTime<chrono::Utc>,
 }
 
-pub fn router() -> Router<(PgPool, MeilisearchClient)> {
+pub fn router() -> Router<(PgPool, Option<MeilisearchClient>, Arc<Config>)> {
     Router::new().route("/messages", get(search_messages))
 }
 
@@ -47,7 +94,8 @@ pub fn router() -> Router<(PgPool, MeilisearchClient)> {
     responses(
         (status = 200, description = "Search completed successfully", body = SearchResponse),
         (status = 400, description = "Invalid search parameters"),
-        (status = 401, description = "Unauthorized")
+        (status = 401, description = "Unauthorized"),
+        (status = 503, description = "Search is disabled (the Meilisearch client could not be created at startup)")
     ),
     tag = "Search",
     security(
@@ -56,28 +104,83 @@ pub fn router() -> Router<(PgPool, MeilisearchClient)> {
 )]
 async fn search_messages(
     Query(params): Query<SearchQuery>,
-    State((_pool, meili_client)): State<(PgPool, MeilisearchClient)>,
-    _user: AuthUser, // 認証チェック
-) -> Result<Json<SearchResponse>, axum::http::StatusCode> {
-    let limit = params.limit.unwrap_or(20).min(100);
-    let offset = params.offset.unwrap_or(0);
+    State((pool, meili_client, config)): State<(PgPool, Option<MeilisearchClient>, Arc<Config>)>,
+    user: AuthUser, // 認証チェック
+) -> AppResult<Json<SearchResponse>> {
+    let strict = params.strict.unwrap_or(false);
+    let limit = resolve_limit(params.limit, config.search_page_limits, strict)?;
+    let offset = resolve_offset(params.offset, strict)?;
+    let recent_first = match params.sort.as_deref() {
+        None | Some("relevance") => false,
+        Some("recent") => true,
+        Some(_) => return Err(AppError::validation("sort must be 'relevance' or 'recent'")),
+    };
+    let created_after = params
+        .after
+        .map(|ts| parse_timestamp("after", ts))
+        .transpose()?;
+    let created_before = params
+        .before
+        .map(|ts| parse_timestamp("before", ts))
+        .transpose()?;
+    if let (Some(after), Some(before)) = (created_after, created_before) {
+        if after > before {
+            return Err(AppError::validation("after must not be later than before"));
+        }
+    }
+
+    // 起動時に Meilisearch クライアントを作れなかった場合は検索自体を無効にしている
+    let Some(meili_client) = meili_client else {
+        return Err(AppError::unavailable("検索"));
+    };
+
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    // 検索できるのは閲覧できるルームのメッセージのみ
+    // ルーム名は変更されうるため、インデックスの room_id で絞り込む
+    let room_ids = match &params.room {
+        Some(room) => {
+            let room = Room::find_by_name(&pool, room)
+                .await?
+                .ok_or_else(|| AppError::not_found("ルーム"))?;
+            ensure_room_visible(&room, &pool, user_id, &config).await?;
+            vec![room.id]
+        }
+        None => Room::accessible_ids(&pool, user_id).await?,
+    };
 
     // Meilisearchで検索実行
     let index = meili_client.index("messages");
 
     // フィルター条件を構築
-    let mut filters = Vec::new();
-    if let Some(room) = &params.room {
-        filters.push(format!("room_name = '{}'", room));
-    }
+    let mut filters = vec![format!(
+        "room_id IN [{}]",
+        room_ids
+            .iter()
+            .map(|id| format!("'{}'", id))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )];
     if let Some(author) = &params.author {
-        filters.push(format!("author_name = '{}'", author));
+        filters.push(format!("author_name = '{}'", escape_filter_value(author)));
     }
-    let filter_string = if !filters.is_empty() {
-        Some(filters.join(" AND "))
-    } else {
-        None
-    };
+    // created_at は unix 秒でインデックスしている
+    if let Some(after) = params.after {
+        filters.push(format!("created_at >= {}", after));
+    }
+    if let Some(before) = params.before {
+        filters.push(format!("created_at <= {}", before));
+    }
+    let filter_string = filters.join(" AND ");
+
+    // 長いメッセージでも一致箇所の周辺だけをハイライトとして返す（全文は message.content）
+    let crop_length = params
+        .crop
+        .unwrap_or(SEARCH_SNIPPET_CROP_LENGTH)
+        .clamp(1, MAX_SEARCH_SNIPPET_CROP_LENGTH);
 
     let mut search_query = index.search();
     search_query
@@ -85,18 +188,33 @@ async fn search_messages(
         .with_limit(limit as usize)
         .with_offset(offset as usize)
         .with_attributes_to_highlight(meilisearch_sdk::search::Selectors::Some(&["content"]))
+        .with_attributes_to_crop(meilisearch_sdk::search::Selectors::Some(&[(
+            "content", None,
+        )]))
+        .with_crop_length(crop_length)
         .with_highlight_pre_tag("<mark>")
         .with_highlight_post_tag("</mark>");
 
-    if let Some(filter) = &filter_string {
-        search_query.with_filter(filter);
+    search_query.with_filter(&filter_string);
+    if recent_first {
+        search_query.with_sort(&["created_at:desc", "seq:desc"]);
     }
 
     let search_results = match search_query.execute::<serde_json::Value>().await {
         Ok(results) => results,
         Err(e) => {
-            tracing::error!("Meilisearch error: {}", e);
-            return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+            tracing::error!("Meilisearch error, falling back to Postgres search: {}", e);
+            let filter = MessageSearchFilter {
+                room_name: params.room.as_deref(),
+                author_name: params.author.as_deref(),
+                created_after,
+                created_before,
+            };
+            return search_messages_fallback(
+                &pool, &config, &params.q, filter, user_id, limit, offset,
+            )
+            .await
+            .map(Json);
         }
     };
 
@@ -104,6 +222,35 @@ async fn search_messages(
 
     for hit in &search_results.hits {
         // hit.resultがドキュメントデータを含む
+        let created_at = chrono::DateTime::from_timestamp(
+            hit.result
+                .get("created_at")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0),
+            0,
+        )
+        .unwrap_or_default();
+        // インデックスに updated_at がない古いドキュメントは created_at で代用
+        let updated_at = hit
+            .result
+            .get("updated_at")
+            .and_then(|v| v.as_i64())
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .unwrap_or(created_at);
+
+        let content = hit
+            .result
+            .get("content")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let message_type = hit
+            .result
+            .get("message_type")
+            .and_then(|v| v.as_str())
+            .and_then(|v| v.parse::<DbMessageType>().ok())
+            .unwrap_or(DbMessageType::Text);
+
         let message_data = Message {
             id: hit
                 .result
@@ -134,31 +281,21 @@ async fn search_messages(
                 .get("author_avatar")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string()),
-            content: hit
-                .result
-                .get("content")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string(),
-            created_at: chrono::DateTime::from_timestamp(
-                hit.result
-                    .get("created_at")
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0),
-                0,
-            )
-            .unwrap_or_default(),
-            message_type: match hit
+            author_is_bot: hit
                 .result
-                .get("message_type")
-                .and_then(|v| v.as_str())
-                .unwrap_or("text")
-            {
-                "image" => MessageType::Image,
-                "file" => MessageType::File,
-                "system" => MessageType::System,
-                _ => MessageType::Text,
-            },
+                .get("author_is_bot")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            rendered_html: crate::markdown::rendered_html(&config, &message_type, &content),
+            content,
+            created_at,
+            updated_at,
+            edited: updated_at > created_at,
+            message_type: message_type.into(),
+            // インデックスに seq がない古いドキュメントは 0
+            seq: hit.result.get("seq").and_then(|v| v.as_i64()).unwrap_or(0),
+            // 引用元はインデックスしていない
+            quoted: None,
         };
 
         let highlights = if let Some(formatted) = &hit.formatted_result {
@@ -178,15 +315,77 @@ async fn search_messages(
         });
     }
 
-    let total_hits = search_results
-        .estimated_total_hits
-        .map(|h| h as u64)
-        .unwrap_or(search_results.hits.len() as u64);
+    // offset / limit で検索した場合、Meilisearch が返すのは推定件数のみ
+    let (total_hits, total_hits_estimated) = match search_results.total_hits {
+        Some(total) => (total as u64, false),
+        None => (
+            search_results
+                .estimated_total_hits
+                .unwrap_or(search_results.hits.len()) as u64,
+            true,
+        ),
+    };
 
     Ok(Json(SearchResponse {
         results,
         total_hits,
+        total_hits_estimated,
         query_time_ms: search_results.processing_time_ms as u32,
-        has_more: (offset + limit) < total_hits as u32,
+        has_more: (offset as u64 + limit as u64) < total_hits,
+        degraded: false,
+        server_time: chrono::Utc::now(),
     }))
 }
+
+// フィルターの文字列値（シングルクォートで囲む）に埋め込めるようエスケープする
+fn escape_filter_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+// unix 秒の検索パラメータを日時に変換する
+fn parse_timestamp(name: &str, ts: i64) -> AppResult<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .ok_or_else(|| AppError::validation(format!("{} is out of range", name)))
+}
+
+// Meilisearchが利用できない場合のPostgresによる検索（ハイライトなし）
+async fn search_messages_fallback(
+    pool: &PgPool,
+    config: &Config,
+    query: &str,
+    filter: MessageSearchFilter<'_>,
+    user_id: uuid::Uuid,
+    limit: u32,
+    offset: u32,
+) -> AppResult<SearchResponse> {
+    let started_at = std::time::Instant::now();
+
+    let (db_messages, total) =
+        DbMessage::search_accessible(pool, user_id, query, filter, limit as i64, offset as i64)
+            .await
+            .inspect_err(|e| {
+                tracing::error!("Postgres fallback search failed: {}", e);
+            })?;
+
+    let results = db_messages
+        .into_iter()
+        .map(|msg| SearchResult {
+            message: Message::rendered(msg, config),
+            highlights: vec![],
+            score: 0.0,
+        })
+        .collect();
+
+    let total_hits = total.max(0) as u64;
+
+    // フォールバックは常に新しい順で、件数も正確
+    Ok(SearchResponse {
+        results,
+        total_hits,
+        total_hits_estimated: false,
+        query_time_ms: started_at.elapsed().as_millis() as u32,
+        has_more: (offset as u64 + limit as u64) < total_hits,
+        degraded: true,
+        server_time: chrono::Utc::now(),
+    })
+}
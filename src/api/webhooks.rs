@@ -0,0 +1,699 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::{delete, get, post, put},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use meilisearch_sdk::client::Client as MeilisearchClient;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use super::auth::AuthUser;
+use super::chat::{ensure_room_visible, record_audit, SendMessageResponse};
+use crate::config::{Config, MAX_BOT_NAME_LENGTH, MAX_WEBHOOKS_PER_ROOM};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    sanitize_content, validate_message_content, DbMessageType, Message as DbMessage, NewMessage,
+    Room, RoomWebhook, User, WebhookToken, WEBHOOK_EVENTS, WEBHOOK_EVENT_MESSAGE_CREATED,
+};
+use crate::webhooks::{
+    check_incoming_rate_limit, generate_incoming_token, generate_secret, hash_token,
+    is_webhook_url_allowed,
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    /// 送信先 URL（https かつ `WEBHOOK_ALLOWED_DOMAINS` のドメインのみ）
+    pub url: String,
+    /// 購読するイベント（省略時は `message.created`）
+    pub events: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WebhookInfo {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WebhooksResponse {
+    pub webhooks: Vec<WebhookInfo>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: WebhookInfo,
+    /// 署名検証用のシークレット（作成時のみ返す）
+    pub secret: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateIncomingWebhookRequest {
+    /// 投稿者として表示するボット名
+    pub name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IncomingWebhookInfo {
+    pub id: String,
+    pub bot_name: String,
+    pub bot_user_id: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IncomingWebhooksResponse {
+    pub webhooks: Vec<IncomingWebhookInfo>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateIncomingWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: IncomingWebhookInfo,
+    /// 投稿用 URL に含めるトークン（作成時のみ返す）
+    pub token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct IncomingWebhookMessageRequest {
+    pub content: String,
+}
+
+impl From<WebhookToken> for IncomingWebhookInfo {
+    fn from(token: WebhookToken) -> Self {
+        Self {
+            id: token.id.to_string(),
+            bot_name: token.bot_name,
+            bot_user_id: token.bot_user_id.to_string(),
+            created_by: token.created_by.to_string(),
+            created_at: token.created_at,
+        }
+    }
+}
+
+impl From<RoomWebhook> for WebhookInfo {
+    fn from(webhook: RoomWebhook) -> Self {
+        Self {
+            id: webhook.id.to_string(),
+            url: webhook.url,
+            events: webhook.events,
+            created_by: webhook.created_by.to_string(),
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+pub fn router() -> Router<(
+    PgPool,
+    crate::ws::AppState,
+    Option<MeilisearchClient>,
+    Arc<Config>,
+)> {
+    Router::new()
+        .route("/{room}/webhooks", get(list_webhooks).post(create_webhook))
+        .route(
+            "/{room}/webhooks/{webhook_id}",
+            put(update_webhook).delete(delete_webhook),
+        )
+        .route(
+            "/{room}/incoming-webhooks",
+            get(list_incoming_webhooks).post(create_incoming_webhook),
+        )
+        .route(
+            "/{room}/incoming-webhooks/{webhook_id}",
+            delete(delete_incoming_webhook),
+        )
+        .route("/{room}/webhook/{token}", post(post_incoming_webhook))
+}
+
+// ルームを取得し、操作するユーザーがオーナーであることを確認する
+async fn find_owned_room(
+    pool: &PgPool,
+    room_name: &str,
+    user: &AuthUser,
+    config: &Config,
+) -> AppResult<(Room, uuid::Uuid)> {
+    let user_id = user
+        .user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid user ID"))?;
+
+    let room = Room::find_by_name(pool, room_name)
+        .await?
+        .ok_or_else(|| AppError::not_found("ルーム"))?;
+
+    ensure_room_visible(&room, pool, user_id, config).await?;
+    if !room.is_owner(pool, user_id).await? {
+        return Err(AppError::forbidden(
+            "Webhook の管理はルームのオーナーのみ可能です",
+        ));
+    }
+
+    Ok((room, user_id))
+}
+
+fn validate_url(url: &str, config: &Config) -> AppResult<String> {
+    let url = url.trim();
+    if !is_webhook_url_allowed(url, config) {
+        return Err(AppError::validation(
+            "Webhook URL は https で、許可されたドメインである必要があります",
+        ));
+    }
+    Ok(url.to_string())
+}
+
+fn validate_events(events: Vec<String>) -> AppResult<Vec<String>> {
+    let mut validated: Vec<String> = Vec::new();
+    for event in events {
+        if !WEBHOOK_EVENTS.contains(&event.as_str()) {
+            return Err(AppError::validation(format!(
+                "不明なイベントです: {}",
+                event
+            )));
+        }
+        if !validated.contains(&event) {
+            validated.push(event);
+        }
+    }
+    if validated.is_empty() {
+        return Err(AppError::validation("イベントを1つ以上指定してください"));
+    }
+    Ok(validated)
+}
+
+fn parse_webhook_id(webhook_id: &str) -> AppResult<uuid::Uuid> {
+    webhook_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| AppError::bad_request("Invalid webhook ID"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/chat/{room}/webhooks",
+    params(
+        ("room" = String, Path, description = "Room name")
+    ),
+    responses(
+        (status = 200, description = "Webhooks of the room (secrets are not included)", body = WebhooksResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Owner role required"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn list_webhooks(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<WebhooksResponse>> {
+    let pool = &state.0;
+    let (room, _) = find_owned_room(pool, &room_name, &user, &state.3).await?;
+
+    let webhooks = RoomWebhook::find_by_room(pool, room.id)
+        .await?
+        .into_iter()
+        .map(WebhookInfo::from)
+        .collect();
+
+    Ok(Json(WebhooksResponse { webhooks }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/{room}/webhooks",
+    params(
+        ("room" = String, Path, description = "Room name")
+    ),
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 200, description = "Webhook created", body = CreateWebhookResponse),
+        (status = 400, description = "URL not allowed, unknown event, or webhook limit reached"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Owner role required"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn create_webhook(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> AppResult<Json<CreateWebhookResponse>> {
+    let pool = &state.0;
+    let (room, user_id) = find_owned_room(pool, &room_name, &user, &state.3).await?;
+
+    let url = validate_url(&payload.url, &state.3)?;
+    let events = validate_events(
+        payload
+            .events
+            .unwrap_or_else(|| vec![WEBHOOK_EVENT_MESSAGE_CREATED.to_string()]),
+    )?;
+
+    if RoomWebhook::count_by_room(pool, room.id).await? >= MAX_WEBHOOKS_PER_ROOM {
+        return Err(AppError::validation(format!(
+            "Webhook は1ルームにつき{}件までです",
+            MAX_WEBHOOKS_PER_ROOM
+        )));
+    }
+
+    let secret = generate_secret();
+    let webhook = RoomWebhook::create(pool, room.id, &url, &secret, &events, user_id).await?;
+
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "webhook.create",
+        Some(webhook.id),
+        serde_json::json!({ "url": webhook.url }),
+    )
+    .await;
+
+    Ok(Json(CreateWebhookResponse {
+        webhook: webhook.into(),
+        secret,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/chat/{room}/webhooks/{webhook_id}",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("webhook_id" = String, Path, description = "Webhook ID")
+    ),
+    request_body = UpdateWebhookRequest,
+    responses(
+        (status = 200, description = "Webhook updated", body = WebhookInfo),
+        (status = 400, description = "URL not allowed or unknown event"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Owner role required"),
+        (status = 404, description = "Room or webhook not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn update_webhook(
+    Path((room_name, webhook_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<UpdateWebhookRequest>,
+) -> AppResult<Json<WebhookInfo>> {
+    let pool = &state.0;
+    let webhook_id = parse_webhook_id(&webhook_id)?;
+    let (room, user_id) = find_owned_room(pool, &room_name, &user, &state.3).await?;
+
+    let url = payload
+        .url
+        .map(|url| validate_url(&url, &state.3))
+        .transpose()?;
+    let events = payload.events.map(validate_events).transpose()?;
+
+    let webhook = RoomWebhook::update(pool, room.id, webhook_id, url.as_deref(), events.as_deref())
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook"))?;
+
+    // 変更した項目だけを記録する
+    let mut changes = serde_json::Map::new();
+    if url.is_some() {
+        changes.insert("url".to_string(), serde_json::json!(webhook.url));
+    }
+    if events.is_some() {
+        changes.insert("events".to_string(), serde_json::json!(webhook.events));
+    }
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "webhook.update",
+        Some(webhook.id),
+        serde_json::Value::Object(changes),
+    )
+    .await;
+
+    Ok(Json(webhook.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/chat/{room}/webhooks/{webhook_id}",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("webhook_id" = String, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Owner role required"),
+        (status = 404, description = "Room or webhook not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn delete_webhook(
+    Path((room_name, webhook_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<axum::http::StatusCode> {
+    let pool = &state.0;
+    let webhook_id = parse_webhook_id(&webhook_id)?;
+    let (room, user_id) = find_owned_room(pool, &room_name, &user, &state.3).await?;
+
+    if !RoomWebhook::delete(pool, room.id, webhook_id).await? {
+        return Err(AppError::not_found("Webhook"));
+    }
+
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "webhook.delete",
+        Some(webhook_id),
+        serde_json::json!({}),
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/chat/{room}/incoming-webhooks",
+    params(
+        ("room" = String, Path, description = "Room name")
+    ),
+    responses(
+        (status = 200, description = "Incoming webhooks of the room (tokens are not included)", body = IncomingWebhooksResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Owner role required"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn list_incoming_webhooks(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<Json<IncomingWebhooksResponse>> {
+    let pool = &state.0;
+    let (room, _) = find_owned_room(pool, &room_name, &user, &state.3).await?;
+
+    let webhooks = WebhookToken::find_by_room(pool, room.id)
+        .await?
+        .into_iter()
+        .map(IncomingWebhookInfo::from)
+        .collect();
+
+    Ok(Json(IncomingWebhooksResponse { webhooks }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/{room}/incoming-webhooks",
+    params(
+        ("room" = String, Path, description = "Room name")
+    ),
+    request_body = CreateIncomingWebhookRequest,
+    responses(
+        (status = 200, description = "Incoming webhook created", body = CreateIncomingWebhookResponse),
+        (status = 400, description = "Invalid bot name or webhook limit reached"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Owner role required"),
+        (status = 404, description = "Room not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn create_incoming_webhook(
+    Path(room_name): Path<String>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+    Json(payload): Json<CreateIncomingWebhookRequest>,
+) -> AppResult<Json<CreateIncomingWebhookResponse>> {
+    let pool = &state.0;
+    let (room, user_id) = find_owned_room(pool, &room_name, &user, &state.3).await?;
+
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_BOT_NAME_LENGTH {
+        return Err(AppError::validation(format!(
+            "ボット名は1〜{}文字で指定してください",
+            MAX_BOT_NAME_LENGTH
+        )));
+    }
+
+    if WebhookToken::find_by_room(pool, room.id).await?.len() as i64 >= MAX_WEBHOOKS_PER_ROOM {
+        return Err(AppError::validation(format!(
+            "受信 Webhook は1ルームにつき{}件までです",
+            MAX_WEBHOOKS_PER_ROOM
+        )));
+    }
+
+    let token = generate_incoming_token();
+    let webhook = WebhookToken::create(pool, room.id, name, &hash_token(&token), user_id).await?;
+
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "incoming_webhook.create",
+        Some(webhook.id),
+        serde_json::json!({ "bot_name": webhook.bot_name }),
+    )
+    .await;
+
+    Ok(Json(CreateIncomingWebhookResponse {
+        webhook: webhook.into(),
+        token,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/chat/{room}/incoming-webhooks/{webhook_id}",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("webhook_id" = String, Path, description = "Incoming webhook ID")
+    ),
+    responses(
+        (status = 204, description = "Incoming webhook revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Owner role required"),
+        (status = 404, description = "Room or webhook not found")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+async fn delete_incoming_webhook(
+    Path((room_name, webhook_id)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    user: AuthUser,
+) -> AppResult<axum::http::StatusCode> {
+    let pool = &state.0;
+    let webhook_id = parse_webhook_id(&webhook_id)?;
+    let (room, user_id) = find_owned_room(pool, &room_name, &user, &state.3).await?;
+
+    if !WebhookToken::delete(pool, room.id, webhook_id).await? {
+        return Err(AppError::not_found("Webhook"));
+    }
+
+    record_audit(
+        &room,
+        pool,
+        user_id,
+        "incoming_webhook.delete",
+        Some(webhook_id),
+        serde_json::json!({}),
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/{room}/webhook/{token}",
+    params(
+        ("room" = String, Path, description = "Room name"),
+        ("token" = String, Path, description = "Incoming webhook token")
+    ),
+    request_body = IncomingWebhookMessageRequest,
+    responses(
+        (status = 200, description = "Message posted as the webhook's bot", body = SendMessageResponse),
+        (status = 400, description = "Invalid message content"),
+        (status = 404, description = "Room not found or invalid token"),
+        (status = 429, description = "Rate limit exceeded for this token")
+    ),
+    tag = "Chat"
+)]
+async fn post_incoming_webhook(
+    Path((room_name, token)): Path<(String, String)>,
+    State(state): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    Json(payload): Json<IncomingWebhookMessageRequest>,
+) -> AppResult<Json<SendMessageResponse>> {
+    let pool = &state.0;
+
+    // トークンが不正な場合もルームが存在しない場合と同じく 404 にする
+    let webhook = WebhookToken::find_by_token_hash(pool, &hash_token(&token))
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook"))?;
+    let room = Room::find_by_id(pool, webhook.room_id)
+        .await?
+        .filter(|room| room.name == room_name)
+        .ok_or_else(|| AppError::not_found("Webhook"))?;
+
+    if !check_incoming_rate_limit(webhook.id) {
+        return Err(AppError::RateLimit);
+    }
+
+    let content = sanitize_content(&payload.content);
+    let content = state.3.moderator.moderate(&content)?;
+    validate_message_content(
+        &content,
+        room.message_length_limit(state.3.max_room_message_length),
+    )
+    .map_err(AppError::validation)?;
+
+    let bot = User::find_by_id(pool, webhook.bot_user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook"))?;
+
+    let message = DbMessage::create(
+        pool,
+        NewMessage {
+            room_id: room.id,
+            user_id: bot.id,
+            content: content.clone(),
+            message_type: DbMessageType::Text,
+            reply_to_message_id: None,
+        },
+    )
+    .await?;
+    crate::metrics::message_sent("webhook");
+
+    crate::ws::index_message(
+        state.2.as_ref(),
+        &message,
+        &room,
+        bot.id,
+        &bot.username,
+        true,
+    )
+    .await;
+
+    // 全クライアントにブロードキャスト
+    crate::ws::broadcast_to_room(
+        &room.name,
+        crate::ws::message_event(&message, &room.name, &bot, true, None, &state.3),
+        Some(bot.id),
+        None,
+        &state.1,
+    )
+    .await;
+
+    // ボットの投稿は送信 Webhook には転送しない（ブリッジ間での投稿のループを防ぐ）
+    crate::mentions::spawn_mention_notifications(
+        pool.clone(),
+        state.1.clone(),
+        room,
+        &message,
+        &bot,
+        &content,
+    );
+
+    Ok(Json(SendMessageResponse {
+        message_id: message.id.to_string(),
+        seq: message.seq,
+        timestamp: message.created_at,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_events() {
+        assert_eq!(
+            validate_events(vec![
+                "message.created".to_string(),
+                "message.created".to_string()
+            ])
+            .unwrap(),
+            vec!["message.created"]
+        );
+        assert!(validate_events(vec!["message.deleted".to_string()]).is_err());
+        assert!(validate_events(Vec::new()).is_err());
+    }
+}
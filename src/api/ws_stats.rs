@@ -0,0 +1,103 @@
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use meilisearch_sdk::client::Client as MeilisearchClient;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+use super::pagination::resolve_limit;
+use crate::config::{Config, WS_STATS_ROOM_LIMITS};
+use crate::metrics::check_metrics_token;
+
+#[derive(Deserialize, IntoParams)]
+pub struct WsStatsQuery {
+    /// 返すルーム数（接続数の多い順、既定: 10、上限: 100）
+    pub limit: Option<i64>,
+    /// trueの場合、範囲外の limit を丸めずに 400 を返す
+    pub strict: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RoomConnectionCount {
+    pub room: String,
+    pub connections: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WsStatsResponse {
+    /// 接続数（複数のルームに参加している接続も1件）
+    pub total_connections: usize,
+    pub online_users: usize,
+    /// 接続が1件以上あるルーム数
+    pub active_rooms: usize,
+    /// 接続数の多いルーム（同数はルーム名順）
+    pub rooms: Vec<RoomConnectionCount>,
+    /// 起動以降にルームへブロードキャストしたメッセージ数
+    pub messages_broadcast: u64,
+}
+
+pub fn router() -> Router<(
+    PgPool,
+    crate::ws::AppState,
+    Option<MeilisearchClient>,
+    Arc<Config>,
+)> {
+    Router::new().route("/stats", get(get_ws_stats))
+}
+
+/// WebSocket サーバーの集計情報を返す
+///
+/// 運用者向けのため `/metrics` と同じ `METRICS_TOKEN` が必要（未設定なら 404）。
+#[utoipa::path(
+    get,
+    path = "/ws/stats",
+    params(WsStatsQuery),
+    responses(
+        (status = 200, description = "WebSocket statistics", body = WsStatsResponse),
+        (status = 400, description = "Invalid limit (strict mode)"),
+        (status = 401, description = "Missing or invalid METRICS_TOKEN"),
+        (status = 404, description = "METRICS_TOKEN is not configured")
+    ),
+    tag = "Chat",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_ws_stats(
+    Query(params): Query<WsStatsQuery>,
+    State((_pool, app_state, _meili, config)): State<(
+        PgPool,
+        crate::ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+    headers: HeaderMap,
+) -> Result<Json<WsStatsResponse>, Response> {
+    check_metrics_token(&headers, &config).map_err(IntoResponse::into_response)?;
+    let limit = resolve_limit(
+        params.limit,
+        WS_STATS_ROOM_LIMITS,
+        params.strict.unwrap_or(false),
+    )
+    .map_err(IntoResponse::into_response)?;
+
+    let stats = crate::ws::get_ws_stats(&app_state, limit as usize).await;
+
+    Ok(Json(WsStatsResponse {
+        total_connections: stats.total_connections,
+        online_users: stats.online_users,
+        active_rooms: stats.active_rooms,
+        rooms: stats
+            .top_rooms
+            .into_iter()
+            .map(|(room, connections)| RoomConnectionCount { room, connections })
+            .collect(),
+        messages_broadcast: stats.messages_broadcast,
+    }))
+}
@@ -0,0 +1,331 @@
+//! ユーザーアバターの取得とキャッシュ
+//!
+//! GitHub のアバター画像をサーバーで取得してメモリにキャッシュし、
+//! `GET /api/avatar/{user_id}` から配信する。クライアントは外部の URL を参照しなくてよい。
+//! アバターが未設定・取得できない場合は、ユーザー ID から生成したアイデンティコン（SVG）を返す。
+//!
+//! 取得するのはユーザーのプロフィールに登録された URL だけで、リクエストから URL は受け取らない。
+//! 取得先は `AVATAR_ALLOWED_DOMAIN` に限り、`crate::net_guard` の SSRF 対策を行う。
+//! 取得に失敗した場合もアイデンティコンを `AVATAR_FAILURE_CACHE_TTL` の間キャッシュし、
+//! 認証なしのリクエストで外部への取得を繰り返させない。
+//! 自サイトのオリジンから配信するため、スクリプトを含みうる外部の SVG は受け付けない。
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use axum::body::Bytes;
+use reqwest::{header, Url};
+use sha2::{Digest, Sha256};
+use tokio::time::timeout;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::config::{
+    AVATAR_ALLOWED_DOMAIN, AVATAR_CACHE_CAPACITY, AVATAR_CACHE_TTL, AVATAR_FAILURE_CACHE_TTL,
+    AVATAR_FETCH_TIMEOUT, AVATAR_MAX_BYTES,
+};
+use crate::net_guard::{is_url_allowed, pinned_client};
+
+/// 取得したアバターとして配信する Content-Type（ラスター画像のみ）
+const ALLOWED_CONTENT_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// 配信するアバター画像
+#[derive(Debug, Clone)]
+pub struct Avatar {
+    pub body: Bytes,
+    pub content_type: String,
+    /// 本文のハッシュから作る ETag（引用符を含む）
+    pub etag: String,
+}
+
+impl Avatar {
+    fn new(body: Bytes, content_type: impl Into<String>) -> Self {
+        let digest = Sha256::digest(&body);
+        Self {
+            etag: format!("\"{}\"", hex::encode(&digest[..16])),
+            body,
+            content_type: content_type.into(),
+        }
+    }
+}
+
+struct CacheEntry {
+    // 取得元の URL（ユーザーのアバターが変わったら取り直す）
+    source_url: String,
+    avatar: Avatar,
+    fetched_at: Instant,
+    // キャッシュの有効期間（取得に失敗したエントリは短い）
+    ttl: Duration,
+    last_used: u64,
+}
+
+/// ユーザー ID をキーにした件数上限つきの LRU キャッシュ
+struct AvatarCache {
+    entries: HashMap<Uuid, CacheEntry>,
+    capacity: usize,
+    // 参照順を記録するための単調増加カウンタ
+    clock: u64,
+}
+
+impl AvatarCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    /// 取得元が同じで期限内のエントリを返す（古いエントリは捨てる）
+    fn get(&mut self, user_id: Uuid, source_url: &str, now: Instant) -> Option<Avatar> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(&user_id)?;
+        if entry.source_url != source_url || now.duration_since(entry.fetched_at) >= entry.ttl {
+            self.entries.remove(&user_id);
+            return None;
+        }
+        entry.last_used = self.clock;
+        Some(entry.avatar.clone())
+    }
+
+    /// 上限に達していれば最も長く参照されていないエントリを捨ててから追加する
+    fn insert(
+        &mut self,
+        user_id: Uuid,
+        source_url: String,
+        avatar: Avatar,
+        ttl: Duration,
+        now: Instant,
+    ) {
+        self.clock += 1;
+        if !self.entries.contains_key(&user_id) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(
+            user_id,
+            CacheEntry {
+                source_url,
+                avatar,
+                fetched_at: now,
+                ttl,
+                last_used: self.clock,
+            },
+        );
+    }
+}
+
+fn cache() -> &'static Mutex<AvatarCache> {
+    static CACHE: OnceLock<Mutex<AvatarCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(AvatarCache::new(AVATAR_CACHE_CAPACITY)))
+}
+
+/// ユーザーのアバターを返す（キャッシュになければ取得し、取得できなければアイデンティコン）
+pub async fn avatar_for(user_id: Uuid, avatar_url: Option<&str>) -> Avatar {
+    let Some(avatar_url) = avatar_url.filter(|url| !url.is_empty()) else {
+        return identicon(user_id);
+    };
+
+    let cached = cache().lock().unwrap_or_else(PoisonError::into_inner).get(
+        user_id,
+        avatar_url,
+        Instant::now(),
+    );
+    if let Some(avatar) = cached {
+        return avatar;
+    }
+
+    // 取得できなかった場合はアイデンティコンを短い期間キャッシュしてから再度試す
+    let (avatar, ttl) = match timeout(AVATAR_FETCH_TIMEOUT, fetch_avatar(avatar_url)).await {
+        Ok(Ok(avatar)) => (avatar, AVATAR_CACHE_TTL),
+        Ok(Err(e)) => {
+            debug!("Avatar fetch failed for user {}: {}", user_id, e);
+            (identicon(user_id), AVATAR_FAILURE_CACHE_TTL)
+        }
+        Err(_) => {
+            debug!("Avatar fetch timed out for user {}", user_id);
+            (identicon(user_id), AVATAR_FAILURE_CACHE_TTL)
+        }
+    };
+    cache()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(
+            user_id,
+            avatar_url.to_string(),
+            avatar.clone(),
+            ttl,
+            Instant::now(),
+        );
+    avatar
+}
+
+// アバター画像を取得する（許可外の URL・画像以外・上限超過はエラー）
+async fn fetch_avatar(avatar_url: &str) -> anyhow::Result<Avatar> {
+    let url = Url::parse(avatar_url)?;
+    if url.scheme() != "https" || !is_url_allowed(&url, &[AVATAR_ALLOWED_DOMAIN.to_string()], &[]) {
+        anyhow::bail!("URL is not allowed for avatars");
+    }
+
+    let client = pinned_client(&url, AVATAR_FETCH_TIMEOUT, "miuchi-chat-avatar/0.1").await?;
+    let mut response = client
+        .get(url)
+        .header(header::ACCEPT, "image/*")
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        anyhow::bail!("Avatar server returned {}", response.status());
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    if !ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+        anyhow::bail!("Unsupported avatar content type: {:?}", content_type);
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length > AVATAR_MAX_BYTES as u64)
+    {
+        anyhow::bail!("Avatar is too large");
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > AVATAR_MAX_BYTES {
+            anyhow::bail!("Avatar is too large");
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(Avatar::new(Bytes::from(body), content_type))
+}
+
+/// ユーザー ID から決まるアイデンティコン（左右対称の 5x5 マスの SVG）
+pub fn identicon(user_id: Uuid) -> Avatar {
+    let hash = Sha256::digest(user_id.as_bytes());
+    let hue = u16::from_be_bytes([hash[0], hash[1]]) % 360;
+
+    let cell = |x: usize, y: usize| format!(r#"<rect x="{}" y="{}" width="1" height="1"/>"#, x, y);
+    let mut cells = String::new();
+    for row in 0..5 {
+        // 左3列を決め、右2列は左右反転して写す
+        for col in 0..3 {
+            if hash[2 + row * 3 + col] & 1 == 0 {
+                continue;
+            }
+            cells.push_str(&cell(col, row));
+            if col != 2 {
+                cells.push_str(&cell(4 - col, row));
+            }
+        }
+    }
+
+    let svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 6 6" width="120" height="120" shape-rendering="crispEdges"><rect width="6" height="6" fill="#f0f0f0"/><g fill="hsl({}, 55%, 50%)" transform="translate(0.5 0.5)">{}</g></svg>"##,
+        hue, cells
+    );
+    Avatar::new(Bytes::from(svg), "image/svg+xml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn avatar(body: &'static str) -> Avatar {
+        Avatar::new(Bytes::from_static(body.as_bytes()), "image/png")
+    }
+
+    #[test]
+    fn test_identicon_is_deterministic_svg() {
+        let user_id = Uuid::new_v4();
+        let first = identicon(user_id);
+        let second = identicon(user_id);
+
+        assert_eq!(first.content_type, "image/svg+xml");
+        assert_eq!(first.body, second.body);
+        assert_eq!(first.etag, second.etag);
+        assert!(first.body.starts_with(b"<svg "));
+        assert!(first.etag.starts_with('"') && first.etag.ends_with('"'));
+
+        let other = identicon(Uuid::new_v4());
+        assert_ne!(first.etag, other.etag);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = AvatarCache::new(2);
+        let now = Instant::now();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        cache.insert(a, "https://a".into(), avatar("a"), AVATAR_CACHE_TTL, now);
+        cache.insert(b, "https://b".into(), avatar("b"), AVATAR_CACHE_TTL, now);
+        // a を参照したので、次に追加すると b が捨てられる
+        assert!(cache.get(a, "https://a", now).is_some());
+        cache.insert(c, "https://c".into(), avatar("c"), AVATAR_CACHE_TTL, now);
+
+        assert!(cache.get(a, "https://a", now).is_some());
+        assert!(cache.get(b, "https://b", now).is_none());
+        assert!(cache.get(c, "https://c", now).is_some());
+    }
+
+    #[test]
+    fn test_cache_misses_on_changed_url_or_expiry() {
+        let mut cache = AvatarCache::new(10);
+        let now = Instant::now();
+        let user_id = Uuid::new_v4();
+
+        cache.insert(
+            user_id,
+            "https://old".into(),
+            avatar("old"),
+            AVATAR_CACHE_TTL,
+            now,
+        );
+        assert!(cache.get(user_id, "https://new", now).is_none());
+        // URL が違うエントリは捨てられている
+        assert!(cache.get(user_id, "https://old", now).is_none());
+
+        cache.insert(
+            user_id,
+            "https://new".into(),
+            avatar("new"),
+            AVATAR_CACHE_TTL,
+            now,
+        );
+        assert!(cache
+            .get(user_id, "https://new", now + AVATAR_CACHE_TTL)
+            .is_none());
+    }
+
+    #[test]
+    fn test_cache_keeps_failures_for_shorter_ttl() {
+        let mut cache = AvatarCache::new(10);
+        let now = Instant::now();
+        let user_id = Uuid::new_v4();
+
+        cache.insert(
+            user_id,
+            "https://broken".into(),
+            identicon(user_id),
+            AVATAR_FAILURE_CACHE_TTL,
+            now,
+        );
+        let cached = cache.get(user_id, "https://broken", now).unwrap();
+        assert_eq!(cached.content_type, "image/svg+xml");
+        assert!(cache
+            .get(user_id, "https://broken", now + AVATAR_FAILURE_CACHE_TTL)
+            .is_none());
+    }
+}
@@ -0,0 +1,234 @@
+//! スラッシュコマンドの処理
+//!
+//! `/` で始まるメッセージを保存前にコマンドとして解釈する。REST / WebSocket の送信処理は
+//! [`process_message`] を呼ぶだけなので、新しいコマンドは [`Command`] を実装して
+//! [`CommandRegistry::with_builtins`] に登録すれば送信処理を変更せずに追加できる。
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::models::DbMessageType;
+
+/// コマンド実行時のコンテキスト
+pub struct CommandContext<'a> {
+    pub username: &'a str,
+}
+
+/// コマンドの実行結果（保存・配信されるメッセージ）
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOutput {
+    pub content: String,
+    pub message_type: DbMessageType,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum CommandError {
+    #[error("Unknown command: /{0}")]
+    Unknown(String),
+    #[error("Usage: {0}")]
+    InvalidArguments(&'static str),
+}
+
+pub trait Command: Send + Sync {
+    /// 先頭の `/` を除いたコマンド名
+    fn name(&self) -> &'static str;
+
+    fn execute(&self, ctx: &CommandContext, args: &str) -> Result<CommandOutput, CommandError>;
+}
+
+/// コマンド名から [`Command`] を引いて実行するディスパッチャー
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: HashMap<&'static str, Box<dyn Command>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 組み込みコマンドを登録したレジストリ
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register(MeCommand);
+        registry.register(ShrugCommand);
+        registry
+    }
+
+    pub fn register(&mut self, command: impl Command + 'static) {
+        self.commands.insert(command.name(), Box::new(command));
+    }
+
+    /// メッセージを処理する
+    ///
+    /// `/` で始まらないメッセージはそのまま返す。`//` で始まる場合は先頭の `/` を1つ外した
+    /// 通常のメッセージとして扱う（`/` から始まる文章を送るためのエスケープ）。
+    pub fn process(
+        &self,
+        content: &str,
+        message_type: DbMessageType,
+        ctx: &CommandContext,
+    ) -> Result<CommandOutput, CommandError> {
+        let Some(command_line) = content.strip_prefix('/') else {
+            return Ok(CommandOutput {
+                content: content.to_string(),
+                message_type,
+            });
+        };
+
+        if command_line.starts_with('/') {
+            return Ok(CommandOutput {
+                content: command_line.to_string(),
+                message_type,
+            });
+        }
+
+        let (name, args) = command_line
+            .split_once(char::is_whitespace)
+            .unwrap_or((command_line, ""));
+        let command = self
+            .commands
+            .get(name.to_ascii_lowercase().as_str())
+            .ok_or_else(|| CommandError::Unknown(name.to_string()))?;
+
+        command.execute(ctx, args.trim())
+    }
+}
+
+/// 組み込みコマンドを登録した共有レジストリ
+pub fn registry() -> &'static CommandRegistry {
+    static REGISTRY: OnceLock<CommandRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(CommandRegistry::with_builtins)
+}
+
+/// 共有レジストリでメッセージを処理する（REST/WebSocket 共通）
+pub fn process_message(
+    content: &str,
+    message_type: DbMessageType,
+    ctx: &CommandContext,
+) -> Result<CommandOutput, CommandError> {
+    registry().process(content, message_type, ctx)
+}
+
+/// `/me <action>`: 三人称の動作表現（システムメッセージとして保存）
+struct MeCommand;
+
+impl Command for MeCommand {
+    fn name(&self) -> &'static str {
+        "me"
+    }
+
+    fn execute(&self, ctx: &CommandContext, args: &str) -> Result<CommandOutput, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::InvalidArguments("/me <action>"));
+        }
+
+        Ok(CommandOutput {
+            content: format!("* {} {}", ctx.username, args),
+            message_type: DbMessageType::System,
+        })
+    }
+}
+
+/// `/shrug [message]`: 末尾に ¯\_(ツ)_/¯ を付ける
+struct ShrugCommand;
+
+impl Command for ShrugCommand {
+    fn name(&self) -> &'static str {
+        "shrug"
+    }
+
+    fn execute(&self, _ctx: &CommandContext, args: &str) -> Result<CommandOutput, CommandError> {
+        let shrug = r"¯\_(ツ)_/¯";
+        let content = if args.is_empty() {
+            shrug.to_string()
+        } else {
+            format!("{} {}", args, shrug)
+        };
+
+        Ok(CommandOutput {
+            content,
+            message_type: DbMessageType::Text,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> CommandContext<'static> {
+        CommandContext {
+            username: "octocat",
+        }
+    }
+
+    #[test]
+    fn test_me_formats_emote_as_system_message() {
+        let output = process_message("/me waves  ", DbMessageType::Text, &ctx()).unwrap();
+        assert_eq!(output.content, "* octocat waves");
+        assert_eq!(output.message_type, DbMessageType::System);
+    }
+
+    #[test]
+    fn test_me_requires_action() {
+        assert_eq!(
+            process_message("/me", DbMessageType::Text, &ctx()),
+            Err(CommandError::InvalidArguments("/me <action>"))
+        );
+    }
+
+    #[test]
+    fn test_shrug() {
+        let output = process_message("/shrug", DbMessageType::Text, &ctx()).unwrap();
+        assert_eq!(output.content, r"¯\_(ツ)_/¯");
+
+        let output = process_message("/SHRUG oh well", DbMessageType::Text, &ctx()).unwrap();
+        assert_eq!(output.content, r"oh well ¯\_(ツ)_/¯");
+    }
+
+    #[test]
+    fn test_plain_and_escaped_messages_pass_through() {
+        let output = process_message("hello /me", DbMessageType::Image, &ctx()).unwrap();
+        assert_eq!(output.content, "hello /me");
+        assert_eq!(output.message_type, DbMessageType::Image);
+
+        let output = process_message("//me is literal", DbMessageType::Text, &ctx()).unwrap();
+        assert_eq!(output.content, "/me is literal");
+    }
+
+    #[test]
+    fn test_unknown_command_is_rejected() {
+        assert_eq!(
+            process_message("/nope x", DbMessageType::Text, &ctx()),
+            Err(CommandError::Unknown("nope".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_registry_accepts_custom_commands() {
+        struct Echo;
+        impl Command for Echo {
+            fn name(&self) -> &'static str {
+                "echo"
+            }
+            fn execute(
+                &self,
+                _ctx: &CommandContext,
+                args: &str,
+            ) -> Result<CommandOutput, CommandError> {
+                Ok(CommandOutput {
+                    content: args.to_string(),
+                    message_type: DbMessageType::Text,
+                })
+            }
+        }
+
+        let mut registry = CommandRegistry::new();
+        registry.register(Echo);
+        let output = registry
+            .process("/echo hi", DbMessageType::Text, &ctx())
+            .unwrap();
+        assert_eq!(output.content, "hi");
+    }
+}
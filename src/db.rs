@@ -0,0 +1,72 @@
+//! PostgreSQL の接続プール
+//!
+//! プールの大きさとタイムアウトは設定（`DB_MAX_CONNECTIONS` など）から決める。
+//! 起動時にデータベースがまだ立ち上がっていない場合に備え、最初の接続は
+//! `DB_CONNECT_MAX_RETRIES` 回まで指数バックオフで再試行する。
+
+use std::time::Duration;
+
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use tracing::{info, warn};
+
+use crate::config::{Config, DB_CONNECT_RETRY_BASE_DELAY, DB_CONNECT_RETRY_MAX_DELAY};
+
+/// 設定に従った接続プールのオプション
+pub fn pool_options(config: &Config) -> PgPoolOptions {
+    PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .acquire_timeout(config.db_acquire_timeout)
+        .idle_timeout(config.db_idle_timeout)
+}
+
+/// 接続プールを作成する（接続できるまで設定の回数だけ再試行する）
+pub async fn connect(config: &Config) -> anyhow::Result<PgPool> {
+    let max_attempts = config.db_connect_max_retries + 1;
+    let mut attempt = 1;
+    loop {
+        info!(
+            "Connecting to database (attempt {}/{})",
+            attempt, max_attempts
+        );
+        match pool_options(config).connect(&config.database_url).await {
+            Ok(pool) => return Ok(pool),
+            Err(e) if attempt < max_attempts => {
+                let delay = retry_delay(attempt);
+                warn!(
+                    "Database connection failed (attempt {}/{}), retrying in {:?}: {}",
+                    attempt, max_attempts, delay, e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                anyhow::bail!(
+                    "Failed to connect to database after {} attempts: {}",
+                    attempt,
+                    e
+                )
+            }
+        }
+    }
+}
+
+// attempt 回目の失敗のあとに待つ時間（試行ごとに2倍、上限あり）
+fn retry_delay(attempt: u32) -> Duration {
+    DB_CONNECT_RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(DB_CONNECT_RETRY_MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_the_cap() {
+        assert_eq!(retry_delay(1), DB_CONNECT_RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), DB_CONNECT_RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(3), DB_CONNECT_RETRY_BASE_DELAY * 4);
+        assert_eq!(retry_delay(10), DB_CONNECT_RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), DB_CONNECT_RETRY_MAX_DELAY);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::api::response::ErrorResponse;
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -13,7 +13,11 @@ pub enum AppError {
     Database(#[from] sqlx::Error),
 
     #[error("Authentication failed: {message}")]
-    Authentication { message: String },
+    Authentication {
+        message: String,
+        /// 失敗理由（`details.reason` で返し、クライアントが再認証の方法を選べるようにする）
+        reason: Option<&'static str>,
+    },
 
     #[error("Authorization failed: {message}")]
     Authorization { message: String },
@@ -30,15 +34,27 @@ pub enum AppError {
         details: Option<serde_json::Value>,
     },
 
+    #[error("Room already exists: {name}")]
+    RoomExists { name: String },
+
+    #[error("Conflict: {message}")]
+    Conflict { message: String },
+
     #[error("Rate limit exceeded")]
     RateLimit,
 
+    #[error("Slow mode: retry after {retry_after}s")]
+    SlowMode { retry_after: u64 },
+
     #[error("WebSocket error: {message}")]
     WebSocket { message: String },
 
     #[error("External service error: {service}")]
     ExternalService { service: String, message: String },
 
+    #[error("Service unavailable: {service}")]
+    ServiceUnavailable { service: String },
+
     #[error("Internal server error")]
     Internal(#[from] anyhow::Error),
 }
@@ -53,9 +69,13 @@ impl AppError {
             Self::NotFound { .. } => "NOT_FOUND",
             Self::BadRequest { .. } => "BAD_REQUEST",
             Self::Validation { .. } => "VALIDATION_ERROR",
+            Self::RoomExists { .. } => "ROOM_EXISTS",
+            Self::Conflict { .. } => "CONFLICT",
             Self::RateLimit => "RATE_LIMIT_EXCEEDED",
+            Self::SlowMode { .. } => "SLOW_MODE",
             Self::WebSocket { .. } => "WEBSOCKET_ERROR",
             Self::ExternalService { .. } => "EXTERNAL_SERVICE_ERROR",
+            Self::ServiceUnavailable { .. } => "SERVICE_UNAVAILABLE",
             Self::Internal(_) => "INTERNAL_ERROR",
         }
     }
@@ -68,9 +88,11 @@ impl AppError {
             Self::Authorization { .. } => StatusCode::FORBIDDEN,
             Self::NotFound { .. } => StatusCode::NOT_FOUND,
             Self::BadRequest { .. } | Self::Validation { .. } => StatusCode::BAD_REQUEST,
-            Self::RateLimit => StatusCode::TOO_MANY_REQUESTS,
+            Self::RoomExists { .. } | Self::Conflict { .. } => StatusCode::CONFLICT,
+            Self::RateLimit | Self::SlowMode { .. } => StatusCode::TOO_MANY_REQUESTS,
             Self::WebSocket { .. } => StatusCode::BAD_REQUEST,
             Self::ExternalService { .. } => StatusCode::BAD_GATEWAY,
+            Self::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
@@ -81,6 +103,15 @@ impl AppError {
             Self::Database(e) => Some(json!({
                 "database_error": e.to_string()
             })),
+            Self::Authentication {
+                reason: Some(reason),
+                ..
+            } => Some(json!({
+                "reason": reason
+            })),
+            Self::SlowMode { retry_after } => Some(json!({
+                "retry_after": retry_after
+            })),
             Self::ExternalService { service, message } => Some(json!({
                 "service": service,
                 "error_message": message
@@ -96,20 +127,27 @@ impl AppError {
                 "データベースエラーが発生しました。しばらく時間をおいて再試行してください。"
                     .to_string()
             }
-            Self::Authentication { .. } => {
-                "認証に失敗しました。再度ログインしてください。".to_string()
-            }
+            Self::Authentication { message, .. } => message.clone(),
             Self::Authorization { message } => message.clone(),
             Self::NotFound { resource } => format!("{}が見つかりません。", resource),
             Self::BadRequest { message } => message.clone(),
             Self::Validation { message, .. } => message.clone(),
+            Self::RoomExists { name } => format!("ルーム名 \"{}\" は既に使用されています。", name),
+            Self::Conflict { message } => message.clone(),
             Self::RateLimit => {
                 "送信回数が制限を超えました。しばらく時間をおいて再試行してください。".to_string()
             }
+            Self::SlowMode { retry_after } => format!(
+                "スローモード中です。あと{}秒待ってから送信してください。",
+                retry_after
+            ),
             Self::WebSocket { message } => format!("接続エラー: {}", message),
             Self::ExternalService { .. } => {
                 "外部サービスとの通信でエラーが発生しました。".to_string()
             }
+            Self::ServiceUnavailable { service } => {
+                format!("{}は現在利用できません。", service)
+            }
             Self::Internal(_) => {
                 "内部エラーが発生しました。管理者にお問い合わせください。".to_string()
             }
@@ -120,10 +158,16 @@ impl AppError {
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         // ログ出力（開発環境では詳細、本番環境では簡略化）
-        if cfg!(debug_assertions) {
-            tracing::error!("API Error: {:?}", self);
+        let summary = if cfg!(debug_assertions) {
+            format!("{:?}", self)
         } else {
-            tracing::error!("API Error [{}]: {}", self.code(), self.user_message());
+            format!("[{}]: {}", self.code(), self.user_message())
+        };
+        // サーバー側の障害だけを error にする（認証失敗はトークン切れなどで日常的に起きる）
+        match self {
+            Self::Authentication { .. } => tracing::debug!("API Error: {}", summary),
+            _ if self.status_code().is_server_error() => tracing::error!("API Error: {}", summary),
+            _ => tracing::warn!("API Error: {}", summary),
         }
 
         let response = if let Some(details) = self.details() {
@@ -132,7 +176,14 @@ impl IntoResponse for AppError {
             ErrorResponse::new(self.code(), &self.user_message())
         };
 
-        (self.status_code(), Json(response)).into_response()
+        let mut response = (self.status_code(), Json(response)).into_response();
+        // スローモードは再送できるまでの秒数をヘッダーでも返す
+        if let Self::SlowMode { retry_after } = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
     }
 }
 
@@ -141,6 +192,14 @@ impl AppError {
     pub fn auth(message: impl Into<String>) -> Self {
         Self::Authentication {
             message: message.into(),
+            reason: None,
+        }
+    }
+
+    pub fn auth_with_reason(reason: &'static str, message: impl Into<String>) -> Self {
+        Self::Authentication {
+            message: message.into(),
+            reason: Some(reason),
         }
     }
 
@@ -176,6 +235,22 @@ impl AppError {
         }
     }
 
+    pub fn room_exists(name: impl Into<String>) -> Self {
+        Self::RoomExists { name: name.into() }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict {
+            message: message.into(),
+        }
+    }
+
+    pub fn unavailable(service: impl Into<String>) -> Self {
+        Self::ServiceUnavailable {
+            service: service.into(),
+        }
+    }
+
     pub fn ws_error(message: impl Into<String>) -> Self {
         Self::WebSocket {
             message: message.into(),
@@ -210,6 +285,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_auth_reason_in_details() {
+        let error = AppError::auth_with_reason("token_expired", "トークンの有効期限が切れています");
+        assert_eq!(error.status_code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(error.user_message(), "トークンの有効期限が切れています");
+        assert_eq!(error.details(), Some(json!({ "reason": "token_expired" })));
+
+        assert_eq!(AppError::auth("invalid").details(), None);
+    }
+
+    #[test]
+    fn test_room_exists() {
+        let error = AppError::room_exists("general");
+        assert_eq!(error.code(), "ROOM_EXISTS");
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+    }
+
     #[test]
     fn test_status_codes() {
         assert_eq!(
@@ -224,6 +316,10 @@ mod tests {
             AppError::forbidden("denied").status_code(),
             StatusCode::FORBIDDEN
         );
+        assert_eq!(
+            AppError::unavailable("検索").status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
     }
 
     #[test]
@@ -235,6 +331,13 @@ mod tests {
         assert_eq!(error.user_message(), "ユーザー名が無効です");
     }
 
+    #[test]
+    fn test_slow_mode_sets_retry_after() {
+        let response = AppError::SlowMode { retry_after: 12 }.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "12");
+    }
+
     #[test]
     fn test_validation_with_details() {
         let details = json!({"field": "username", "min_length": 3});
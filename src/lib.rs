@@ -1,39 +1,63 @@
-use axum::{
-    response::{Html, Json},
-    routing::get,
-    Router,
-};
+use axum::{response::Json, routing::get, Router};
 use meilisearch_sdk::client::Client as MeilisearchClient;
 use serde_json::{json, Value};
 use sqlx::PgPool;
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 
 pub mod api;
+pub mod avatars;
+pub mod commands;
+pub mod config;
+pub mod db;
 pub mod error;
+pub mod health;
+pub mod link_preview;
+pub mod markdown;
+pub mod mentions;
+pub mod metrics;
 pub mod models;
+pub mod moderation;
+pub mod net_guard;
+pub mod observability;
+pub mod slow_mode;
+pub mod webhooks;
 pub mod ws;
 
 pub use error::{AppError, AppResult};
 
 /// テスト用のアプリケーション作成関数
-pub async fn create_app(pool: PgPool, meili_client: MeilisearchClient) -> Router {
+///
+/// `meili_client` に `None` を渡すと検索を無効にした状態で起動する。
+pub async fn create_app(
+    pool: PgPool,
+    meili_client: impl Into<Option<MeilisearchClient>>,
+    config: Arc<config::Config>,
+) -> Router {
+    let meili_client = meili_client.into();
     // WebSocket用の状態管理を初期化
-    let ws_state: ws::AppState = Arc::new(RwLock::new(HashMap::new()));
+    let ws_state = ws::AppState::default();
+    metrics::install();
 
-    Router::new()
+    let app = Router::new()
         .route("/", get(root))
         .route("/health", get(health_check))
         .route("/db-health", get(db_health_check))
+        .route("/ready", get(health::ready_check))
+        .route("/metrics", get(metrics::metrics_handler))
         .nest(
             "/api",
-            api::create_router().with_state((pool.clone(), meili_client.clone())),
+            api::create_router(config.clone()).with_state((
+                pool.clone(),
+                meili_client.clone(),
+                config.clone(),
+            )),
         )
         .merge(api::create_chat_router())
         .route("/ws", get(ws::websocket_handler))
-        .with_state((pool, ws_state, meili_client))
-        .layer(CorsLayer::permissive())
+        .with_state((pool, ws_state, meili_client, config));
+
+    observability::with_request_tracing(app).layer(CorsLayer::permissive())
 }
 
 async fn root() -> Json<Value> {
@@ -51,7 +75,12 @@ async fn health_check() -> Json<Value> {
 }
 
 async fn db_health_check(
-    axum::extract::State((pool, _, _)): axum::extract::State<(PgPool, ws::AppState, MeilisearchClient)>,
+    axum::extract::State((pool, _, _, _)): axum::extract::State<(
+        PgPool,
+        ws::AppState,
+        Option<MeilisearchClient>,
+        Arc<config::Config>,
+    )>,
 ) -> Json<Value> {
     match sqlx::query("SELECT 1").execute(&pool).await {
         Ok(_) => Json(json!({
@@ -59,11 +88,13 @@ async fn db_health_check(
             "database": "connected",
             "timestamp": chrono::Utc::now()
         })),
-        Err(e) => Json(json!({
-            "status": "unhealthy",
-            "database": "disconnected",
-            "error": e.to_string(),
-            "timestamp": chrono::Utc::now()
-        })),
+        Err(e) => {
+            tracing::error!("Database health check failed: {}", e);
+            Json(json!({
+                "status": "unhealthy",
+                "database": "disconnected",
+                "timestamp": chrono::Utc::now()
+            }))
+        }
     }
 }
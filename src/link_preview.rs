@@ -0,0 +1,373 @@
+//! メッセージ内 URL のリンクプレビュー（Open Graph）
+//!
+//! メッセージの保存後にバックグラウンドで OG タグを取得し、保存できたものを
+//! `WsMessage::LinkPreview` としてルームに配信する。送信処理は取得を待たない。
+//!
+//! SSRF 対策として、設定の許可・拒否リストに加えて [`crate::net_guard`] の検査を行う。
+//! リダイレクトは自動では追わず、各ホップで同じ検査を行う。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use reqwest::{header, Url};
+use sqlx::PgPool;
+use tokio::time::timeout;
+use tracing::{debug, warn, Instrument};
+use uuid::Uuid;
+
+use crate::config::{
+    Config, LINK_PREVIEW_MAX_BYTES, LINK_PREVIEW_MAX_REDIRECTS, LINK_PREVIEW_TIMEOUT,
+    MAX_LINK_PREVIEWS_PER_MESSAGE,
+};
+use crate::models::LinkPreview;
+use crate::net_guard::{is_url_allowed, pinned_client};
+use crate::ws::{broadcast_to_room, AppState, WsMessage};
+
+const MAX_TITLE_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 500;
+
+#[derive(Debug, Default, PartialEq)]
+struct OpenGraph {
+    title: Option<String>,
+    description: Option<String>,
+    image_url: Option<String>,
+}
+
+/// メッセージ保存後にリンクプレビューの取得を開始する（無効時や URL がない場合は何もしない）
+pub fn spawn_link_previews(
+    pool: PgPool,
+    app_state: AppState,
+    config: Arc<Config>,
+    room: String,
+    message_id: Uuid,
+    author_id: Uuid,
+    content: &str,
+) {
+    if !config.link_preview_enabled {
+        return;
+    }
+    let urls = extract_urls(content);
+    if urls.is_empty() {
+        return;
+    }
+
+    let fetch_previews = async move {
+        for url in urls {
+            let og =
+                match timeout(LINK_PREVIEW_TIMEOUT, fetch_open_graph(url.clone(), &config)).await {
+                    Ok(Ok(Some(og))) => og,
+                    Ok(Ok(None)) => continue,
+                    Ok(Err(e)) => {
+                        debug!("Link preview skipped for {}: {}", url, e);
+                        continue;
+                    }
+                    Err(_) => {
+                        debug!("Link preview timed out for {}", url);
+                        continue;
+                    }
+                };
+
+            let preview = match LinkPreview::create(
+                &pool,
+                message_id,
+                url.as_str(),
+                og.title.as_deref(),
+                og.description.as_deref(),
+                og.image_url.as_deref(),
+            )
+            .await
+            {
+                Ok(Some(preview)) => preview,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed to store link preview for {}: {}", url, e);
+                    continue;
+                }
+            };
+
+            broadcast_to_room(
+                &room,
+                WsMessage::LinkPreview {
+                    room: room.clone(),
+                    message_id: message_id.to_string(),
+                    url: preview.url,
+                    title: preview.title,
+                    description: preview.description,
+                    image_url: preview.image_url,
+                },
+                Some(author_id),
+                None,
+                &app_state,
+            )
+            .await;
+        }
+    };
+    tokio::spawn(fetch_previews.in_current_span());
+}
+
+/// メッセージ本文から http(s) の URL を取り出す（重複は除き、最大 `MAX_LINK_PREVIEWS_PER_MESSAGE` 件）
+fn extract_urls(content: &str) -> Vec<Url> {
+    let mut urls: Vec<Url> = Vec::new();
+
+    for token in content.split_whitespace() {
+        let token = token.trim_start_matches(['<', '(', '[', '"', '\'']);
+        if !(token.starts_with("http://") || token.starts_with("https://")) {
+            continue;
+        }
+        // 文末の句読点や括弧は URL に含めない
+        let token =
+            token.trim_end_matches(['>', ')', ']', '"', '\'', '.', ',', '!', '?', ';', ':']);
+
+        let Ok(url) = Url::parse(token) else {
+            continue;
+        };
+        if url.host_str().is_none() || urls.contains(&url) {
+            continue;
+        }
+        urls.push(url);
+        if urls.len() == MAX_LINK_PREVIEWS_PER_MESSAGE {
+            break;
+        }
+    }
+
+    urls
+}
+
+// URL を取得して OG タグを読む（HTML 以外や OG タグがない場合は `None`）
+async fn fetch_open_graph(mut url: Url, config: &Config) -> anyhow::Result<Option<OpenGraph>> {
+    for _ in 0..=LINK_PREVIEW_MAX_REDIRECTS {
+        if !is_url_allowed(
+            &url,
+            &config.link_preview_allowed_domains,
+            &config.link_preview_denied_domains,
+        ) {
+            anyhow::bail!("URL is not allowed for link previews");
+        }
+
+        let client =
+            pinned_client(&url, LINK_PREVIEW_TIMEOUT, "miuchi-chat-link-preview/0.1").await?;
+        let mut response = client
+            .get(url.clone())
+            .header(header::ACCEPT, "text/html")
+            .send()
+            .await?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| anyhow::anyhow!("Redirect without Location header"))?;
+            url = url.join(location)?;
+            continue;
+        }
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let is_html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        if !is_html {
+            return Ok(None);
+        }
+
+        // 上限を超える部分は読み込まない（OG タグは通常 <head> にある）
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            let remaining = LINK_PREVIEW_MAX_BYTES - body.len();
+            body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+            if body.len() >= LINK_PREVIEW_MAX_BYTES {
+                break;
+            }
+        }
+
+        let og = parse_open_graph(&String::from_utf8_lossy(&body), &url);
+        if og == OpenGraph::default() {
+            return Ok(None);
+        }
+        return Ok(Some(og));
+    }
+
+    anyhow::bail!("Too many redirects")
+}
+
+// HTML から OG タグを読む（og:title がなければ <title>、og:description がなければ description）
+fn parse_open_graph(html: &str, base: &Url) -> OpenGraph {
+    // ASCII の小文字化はバイト位置を変えないため、位置は元の HTML にもそのまま使える
+    let lower = html.to_ascii_lowercase();
+    let mut meta: HashMap<String, String> = HashMap::new();
+
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find("<meta") {
+        let start = pos + start;
+        let Some(end) = lower[start..].find('>') else {
+            break;
+        };
+        let end = start + end;
+        pos = end + 1;
+
+        let attrs = parse_attributes(&html[start + "<meta".len()..end]);
+        let Some(key) = attrs.get("property").or_else(|| attrs.get("name")) else {
+            continue;
+        };
+        if let Some(content) = attrs.get("content") {
+            meta.entry(key.to_ascii_lowercase())
+                .or_insert_with(|| content.clone());
+        }
+    }
+
+    let title_tag = lower.find("<title").and_then(|start| {
+        let open_end = start + lower[start..].find('>')? + 1;
+        let close = open_end + lower[open_end..].find("</title")?;
+        Some(decode_entities(html[open_end..close].trim()))
+    });
+
+    let non_empty = |value: Option<String>| value.filter(|value| !value.trim().is_empty());
+    let title = non_empty(meta.remove("og:title"))
+        .or_else(|| non_empty(title_tag))
+        .map(|title| truncate(title.trim(), MAX_TITLE_LENGTH));
+    let description = non_empty(meta.remove("og:description"))
+        .or_else(|| non_empty(meta.remove("description")))
+        .map(|description| truncate(description.trim(), MAX_DESCRIPTION_LENGTH));
+    let image_url = non_empty(meta.remove("og:image"))
+        .and_then(|image| base.join(image.trim()).ok())
+        .filter(|image| matches!(image.scheme(), "http" | "https"))
+        .map(String::from);
+
+    OpenGraph {
+        title,
+        description,
+        image_url,
+    }
+}
+
+// タグ内の属性を読む（名前は小文字化、値は文字参照をデコード）
+fn parse_attributes(input: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace() || *c == '/') {
+            chars.next();
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '=' || c == '/' {
+                break;
+            }
+            name.push(c.to_ascii_lowercase());
+            chars.next();
+        }
+        if name.is_empty() {
+            break;
+        }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek() != Some(&'=') {
+            attrs.entry(name).or_insert_with(String::new);
+            continue;
+        }
+        chars.next();
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let mut value = String::new();
+        match chars.peek().copied() {
+            Some(quote @ ('"' | '\'')) => {
+                chars.next();
+                for c in chars.by_ref() {
+                    if c == quote {
+                        break;
+                    }
+                    value.push(c);
+                }
+            }
+            _ => {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+            }
+        }
+        attrs.entry(name).or_insert_with(|| decode_entities(&value));
+    }
+
+    attrs
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(content: &str) -> Vec<String> {
+        extract_urls(content)
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_urls() {
+        assert_eq!(
+            urls("見て https://example.com/a?b=1. と (http://example.org/x) と https://example.com/a?b=1"),
+            vec!["https://example.com/a?b=1", "http://example.org/x"]
+        );
+        assert!(urls("ftp://example.com example.com http://").is_empty());
+        assert_eq!(
+            urls("https://a.com https://b.com https://c.com https://d.com").len(),
+            MAX_LINK_PREVIEWS_PER_MESSAGE
+        );
+    }
+
+    #[test]
+    fn test_parse_open_graph() {
+        let base = Url::parse("https://example.com/posts/1").unwrap();
+        let html = r#"<html><head>
+            <title>Fallback</title>
+            <META property="og:title" content="Tom &amp; Jerry">
+            <meta name=description content='A "classic"' />
+            <meta property="og:image" content="/img/cover.png">
+            </head></html>"#;
+
+        assert_eq!(
+            parse_open_graph(html, &base),
+            OpenGraph {
+                title: Some("Tom & Jerry".to_string()),
+                description: Some("A \"classic\"".to_string()),
+                image_url: Some("https://example.com/img/cover.png".to_string()),
+            }
+        );
+
+        let og = parse_open_graph("<title> Only title </title>", &base);
+        assert_eq!(og.title.as_deref(), Some("Only title"));
+        assert_eq!(og.image_url, None);
+        assert_eq!(
+            parse_open_graph("<p>nothing</p>", &base),
+            OpenGraph::default()
+        );
+    }
+}
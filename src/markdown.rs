@@ -0,0 +1,144 @@
+//! メッセージ本文の Markdown を表示用の安全な HTML に変換する
+//!
+//! 保存・配信の正は生の `content` で、ここで作る `rendered_html` はクライアントの表示を揃えるための補助。
+//! Markdown を HTML にしたあと許可リスト方式のサニタイザー（ammonia）に通すため、本文に書かれた
+//! `<script>` やイベントハンドラー属性、`javascript:` などの URL は取り除かれる。
+//! `RENDER_MARKDOWN=true` のときだけ、テキストメッセージに対して作る。
+
+use std::borrow::Cow;
+
+use pulldown_cmark::{html, Options, Parser};
+
+use crate::config::Config;
+use crate::models::DbMessageType;
+
+/// Markdown を HTML に変換し、サニタイズして返す
+pub fn render(content: &str) -> String {
+    let parser = Parser::new_ext(
+        content,
+        Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES,
+    );
+    let mut unsafe_html = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, parser);
+
+    ammonia::Builder::default()
+        // コードブロックの言語（`language-rust` など）はシンタックスハイライト用に残す
+        .add_tag_attributes("code", &["class"])
+        .attribute_filter(|element, attribute, value| {
+            if element == "code" && attribute == "class" && !is_language_class(value) {
+                return None;
+            }
+            Some(Cow::Borrowed(value))
+        })
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+// `language-[a-z0-9+-]+` の形のクラスか（スタイルを当てる任意のクラスは残さない）
+fn is_language_class(class: &str) -> bool {
+    class.strip_prefix("language-").is_some_and(|language| {
+        !language.is_empty()
+            && language
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'+' || b == b'-')
+    })
+}
+
+/// 設定で有効な場合のみ、テキストメッセージの表示用 HTML を作る
+///
+/// 本文と合わせて `Config::max_message_content_bytes` を超える HTML は付けない（配信時のフレームに収めるため）。
+pub fn rendered_html(
+    config: &Config,
+    message_type: &DbMessageType,
+    content: &str,
+) -> Option<String> {
+    (config.render_markdown && *message_type == DbMessageType::Text)
+        .then(|| render(content))
+        .filter(|html| content.len() + html.len() <= config.max_message_content_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_basic_markdown() {
+        assert_eq!(
+            render("**bold** and _em_"),
+            "<p><strong>bold</strong> and <em>em</em></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_strips_scripts_and_event_handlers() {
+        let payloads = [
+            "<script>alert(1)</script>",
+            "<img src=x onerror=alert(1)>",
+            "<a href=\"javascript:alert(1)\">click</a>",
+            "[click](javascript:alert(1))",
+            "<svg onload=alert(1)></svg>",
+            "<iframe src=\"https://evil.example.com\"></iframe>",
+            "<div style=\"background:url(javascript:alert(1))\">x</div>",
+        ];
+        for payload in payloads {
+            let html = render(payload);
+            let lower = html.to_ascii_lowercase();
+            assert!(!lower.contains("<script"), "{} -> {}", payload, html);
+            assert!(!lower.contains("onerror"), "{} -> {}", payload, html);
+            assert!(!lower.contains("onload"), "{} -> {}", payload, html);
+            assert!(!lower.contains("javascript:"), "{} -> {}", payload, html);
+            assert!(!lower.contains("<iframe"), "{} -> {}", payload, html);
+            assert!(!lower.contains("style="), "{} -> {}", payload, html);
+        }
+    }
+
+    #[test]
+    fn test_render_preserves_code_blocks() {
+        let html = render("```rust\nlet x = \"<script>\";\n```");
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-rust\">let x = \"&lt;script&gt;\";\n</code></pre>\n"
+        );
+
+        assert_eq!(
+            render("`<b>inline</b>`"),
+            "<p><code>&lt;b&gt;inline&lt;/b&gt;</code></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_drops_non_language_code_classes() {
+        assert_eq!(
+            render("```c++\nx\n```"),
+            "<pre><code class=\"language-c++\">x\n</code></pre>\n"
+        );
+        for class in ["hidden", "language-", "language-Rust", "language-x y"] {
+            let html = render(&format!("<code class=\"{}\">x</code>", class));
+            assert!(!html.contains("class="), "{} -> {}", class, html);
+        }
+    }
+
+    #[test]
+    fn test_links_get_safe_rel() {
+        let html = render("[site](https://example.com)");
+        assert!(html.contains("href=\"https://example.com\""));
+        assert!(html.contains("rel=\"noopener noreferrer\""));
+    }
+
+    #[test]
+    fn test_rendered_html_only_when_enabled_for_text() {
+        let mut config =
+            Config::from_lookup(|key| (key == "DEV_MODE").then(|| "true".to_string())).unwrap();
+        assert_eq!(rendered_html(&config, &DbMessageType::Text, "*hi*"), None);
+
+        config.render_markdown = true;
+        assert_eq!(
+            rendered_html(&config, &DbMessageType::Text, "*hi*").as_deref(),
+            Some("<p><em>hi</em></p>\n")
+        );
+        assert_eq!(
+            rendered_html(&config, &DbMessageType::System, "a_b_ joined"),
+            None
+        );
+    }
+}
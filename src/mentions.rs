@@ -0,0 +1,137 @@
+//! メッセージ内の `@username` メンション通知
+//!
+//! メッセージの保存後にバックグラウンドでメンションされたユーザーを解決し、
+//! 通知設定で許可されている場合に `WsMessage::Mentioned` をそのユーザーの全接続へ送る。
+//! ルームを閲覧できないユーザーや、送信者をブロックしているユーザーには送らない。
+
+use sqlx::PgPool;
+use tracing::{debug, warn, Instrument};
+
+use crate::config::MAX_MENTIONS_PER_MESSAGE;
+use crate::models::{Message, NotificationPref, Room, User};
+use crate::ws::{self, AppState, WsMessage};
+
+/// メンション通知をバックグラウンドで送る（メンションがなければ何もしない）
+pub fn spawn_mention_notifications(
+    pool: PgPool,
+    app_state: AppState,
+    room: Room,
+    message: &Message,
+    author: &User,
+    content: &str,
+) {
+    let usernames = extract_mentions(content);
+    if usernames.is_empty() {
+        return;
+    }
+
+    let event = WsMessage::Mentioned {
+        room: room.name.clone(),
+        message_id: message.id.to_string(),
+        user_id: author.id.to_string(),
+        username: author.username.clone(),
+        content: content.to_string(),
+        timestamp: message.created_at,
+    };
+    let author_id = author.id;
+
+    let notify = async move {
+        for username in usernames {
+            let target = match User::find_by_username_ci(&pool, &username).await {
+                Ok(Some(user)) if user.id != author_id => user,
+                Ok(_) => continue,
+                Err(e) => {
+                    debug!("Mention of @{} skipped: {}", username, e);
+                    continue;
+                }
+            };
+
+            match should_notify(&pool, &room, &target, author_id).await {
+                Ok(true) => {
+                    let delivered = ws::send_to_user(target.id, event.clone(), &app_state).await;
+                    debug!(
+                        "Mention of @{} delivered to {} connection(s)",
+                        target.username, delivered
+                    );
+                }
+                Ok(false) => {}
+                Err(e) => warn!("Failed to check mention for @{}: {}", target.username, e),
+            }
+        }
+    };
+    tokio::spawn(notify.in_current_span());
+}
+
+// ルームを閲覧でき、送信者をブロックしておらず、通知設定が "none" でない場合のみ通知する
+async fn should_notify(
+    pool: &PgPool,
+    room: &Room,
+    target: &User,
+    author_id: uuid::Uuid,
+) -> anyhow::Result<bool> {
+    if !room.is_public && !room.is_member(pool, target.id).await? {
+        return Ok(false);
+    }
+    if User::is_blocked(pool, target.id, author_id).await? {
+        return Ok(false);
+    }
+
+    let mode = NotificationPref::effective_mode(pool, target.id, room.id).await?;
+    Ok(mode.allows_mentions())
+}
+
+/// 本文から `@username` を抽出する（小文字に正規化・重複除去、最大 `MAX_MENTIONS_PER_MESSAGE` 件）
+///
+/// メールアドレスのように直前が英数字の `@` はメンションとみなさない。
+fn extract_mentions(content: &str) -> Vec<String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+
+    let mut mentions: Vec<String> = Vec::new();
+    let mut prev: Option<char> = None;
+    for (index, c) in content.char_indices() {
+        if c == '@' && !prev.is_some_and(|p| is_name_char(p) || p == '.') {
+            let rest = &content[index + 1..];
+            let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+            let name = rest[..end].trim_end_matches('-').to_ascii_lowercase();
+
+            if !name.is_empty() && !mentions.contains(&name) {
+                mentions.push(name);
+                if mentions.len() == MAX_MENTIONS_PER_MESSAGE {
+                    break;
+                }
+            }
+        }
+        prev = Some(c);
+    }
+
+    mentions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_mentions() {
+        assert_eq!(
+            extract_mentions("@Octocat ping @hubot, and @octocat again"),
+            vec!["octocat", "hubot"]
+        );
+        assert_eq!(extract_mentions("(@dev_user1)"), vec!["dev_user1"]);
+    }
+
+    #[test]
+    fn test_extract_mentions_ignores_emails_and_bare_at() {
+        assert!(extract_mentions("mail me at user@example.com").is_empty());
+        assert!(extract_mentions("@ @- nobody").is_empty());
+    }
+
+    #[test]
+    fn test_extract_mentions_is_capped() {
+        let content = (0..20)
+            .map(|i| format!("@user{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(extract_mentions(&content).len(), MAX_MENTIONS_PER_MESSAGE);
+    }
+}
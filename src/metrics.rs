@@ -0,0 +1,132 @@
+//! Prometheus 形式のメトリクス
+//!
+//! カウンターは各処理（`ws.rs` / `api`）から記録し、ルーム数やオンラインユーザー数などの
+//! ゲージは `GET /metrics` の取得時に WebSocket の状態から計算する。
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use meilisearch_sdk::client::Client as MeilisearchClient;
+use metrics::{counter, gauge};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::{Arc, OnceLock};
+use subtle::ConstantTimeEq;
+
+use crate::config::Config;
+use crate::ws::AppState;
+
+pub const WS_CONNECTIONS_ACTIVE: &str = "miuchi_ws_connections_active";
+pub const WS_ROOMS_ACTIVE: &str = "miuchi_ws_rooms_active";
+pub const ONLINE_USERS: &str = "miuchi_online_users";
+pub const MESSAGES_SENT_TOTAL: &str = "miuchi_messages_sent_total";
+pub const BROADCAST_FAILURES_TOTAL: &str = "miuchi_broadcast_failures_total";
+pub const AUTH_FAILURES_TOTAL: &str = "miuchi_auth_failures_total";
+pub const WS_SLOW_CONSUMER_TOTAL: &str = "miuchi_ws_slow_consumer_total";
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// グローバルレコーダーを一度だけインストールし、描画用のハンドルを返す
+///
+/// テストなどで `create_app` が複数回呼ばれても同じレコーダーを使い回す。
+pub fn install() -> PrometheusHandle {
+    HANDLE
+        .get_or_init(|| {
+            let recorder = PrometheusBuilder::new().build_recorder();
+            let handle = recorder.handle();
+            if metrics::set_global_recorder(recorder).is_err() {
+                tracing::warn!("A global metrics recorder is already installed");
+            }
+            handle
+        })
+        .clone()
+}
+
+pub fn connection_opened() {
+    gauge!(WS_CONNECTIONS_ACTIVE).increment(1.0);
+}
+
+pub fn connection_closed() {
+    gauge!(WS_CONNECTIONS_ACTIVE).decrement(1.0);
+}
+
+/// `transport` は "ws" または "http"
+pub fn message_sent(transport: &'static str) {
+    counter!(MESSAGES_SENT_TOTAL, "transport" => transport).increment(1);
+}
+
+pub fn broadcast_failed() {
+    counter!(BROADCAST_FAILURES_TOTAL).increment(1);
+}
+
+/// `transport` は "ws" または "http"、`reason` は失敗理由（missing_token など）
+pub fn auth_failed(transport: &'static str, reason: &'static str) {
+    counter!(AUTH_FAILURES_TOTAL, "transport" => transport, "reason" => reason).increment(1);
+}
+
+/// `action` は "dropped"（送信キューが満杯で破棄）または "disconnected"（満杯が続いて切断）
+pub fn slow_consumer(action: &'static str) {
+    counter!(WS_SLOW_CONSUMER_TOTAL, "action" => action).increment(1);
+}
+
+// WebSocket の状態からルーム数とオンラインユーザー数のゲージを更新
+async fn record_ws_state(app_state: &AppState) {
+    let state = app_state.read().await;
+    gauge!(WS_ROOMS_ACTIVE).set(state.rooms.len() as f64);
+    gauge!(ONLINE_USERS).set(state.connections.len() as f64);
+}
+
+/// 運用者向けエンドポイントの `Authorization: Bearer <METRICS_TOKEN>` を確認する
+///
+/// `METRICS_TOKEN` が未設定なら 404、トークンが一致しなければ 401 を返す。
+pub(crate) fn check_metrics_token(headers: &HeaderMap, config: &Config) -> Result<(), StatusCode> {
+    let Some(expected) = config.metrics_token.as_deref() else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| tokens_match(token, expected));
+    if !authorized {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(())
+}
+
+// トークンを定数時間で比較する（長さも漏らさないようハッシュ同士を比べる）
+fn tokens_match(token: &str, expected: &str) -> bool {
+    Sha256::digest(token.as_bytes())
+        .ct_eq(&Sha256::digest(expected.as_bytes()))
+        .into()
+}
+
+/// Prometheus のテキスト形式でメトリクスを返す
+///
+/// `METRICS_TOKEN` が未設定の場合は無効（404）。設定されている場合は
+/// `Authorization: Bearer <METRICS_TOKEN>` が必要。
+pub async fn metrics_handler(
+    headers: HeaderMap,
+    State((_, app_state, _, config)): State<(
+        PgPool,
+        AppState,
+        Option<MeilisearchClient>,
+        Arc<Config>,
+    )>,
+) -> Response {
+    if let Err(status) = check_metrics_token(&headers, &config) {
+        return status.into_response();
+    }
+
+    record_ws_state(&app_state).await;
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        install().render(),
+    )
+        .into_response()
+}
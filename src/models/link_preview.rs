@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LinkPreview {
+    pub message_id: Uuid,
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LinkPreview {
+    /// プレビューを保存する（同じメッセージ・URL の組が既にある場合は `None`）
+    pub async fn create(
+        pool: &PgPool,
+        message_id: Uuid,
+        url: &str,
+        title: Option<&str>,
+        description: Option<&str>,
+        image_url: Option<&str>,
+    ) -> anyhow::Result<Option<LinkPreview>> {
+        let preview = sqlx::query_as::<_, LinkPreview>(
+            r#"
+            INSERT INTO link_previews (message_id, url, title, description, image_url)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (message_id, url) DO NOTHING
+            RETURNING message_id, url, title, description, image_url, created_at
+            "#,
+        )
+        .bind(message_id)
+        .bind(url)
+        .bind(title)
+        .bind(description)
+        .bind(image_url)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(preview)
+    }
+}
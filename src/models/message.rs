@@ -1,9 +1,22 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool, Type};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Once;
+use std::time::Duration;
+use tokio::time::interval;
+use unicode_normalization::UnicodeNormalization;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+use crate::config::{
+    IDEMPOTENCY_KEY_CLEANUP_INTERVAL, IDEMPOTENCY_KEY_TTL, MAX_CONSECUTIVE_NEWLINES,
+    MAX_CONSECUTIVE_SPACES, MAX_IDEMPOTENCY_KEY_LENGTH, QUOTE_PREVIEW_LENGTH,
+};
+
+static IDEMPOTENCY_KEY_CLEANUP_TASK: Once = Once::new();
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[sqlx(type_name = "message_type", rename_all = "lowercase")]
 pub enum DbMessageType {
     Text,
@@ -12,6 +25,42 @@ pub enum DbMessageType {
     System,
 }
 
+/// 未知のメッセージタイプ
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown message type {0:?}")]
+pub struct UnknownMessageType(pub String);
+
+impl DbMessageType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DbMessageType::Text => "text",
+            DbMessageType::Image => "image",
+            DbMessageType::File => "file",
+            DbMessageType::System => "system",
+        }
+    }
+}
+
+impl fmt::Display for DbMessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DbMessageType {
+    type Err = UnknownMessageType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(DbMessageType::Text),
+            "image" => Ok(DbMessageType::Image),
+            "file" => Ok(DbMessageType::File),
+            "system" => Ok(DbMessageType::System),
+            _ => Err(UnknownMessageType(s.to_string())),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Message {
     pub id: Uuid,
@@ -19,10 +68,33 @@ pub struct Message {
     pub user_id: Uuid,
     pub content: String,
     pub message_type: DbMessageType,
+    /// 引用している同じルームのメッセージ（引用元が削除されると NULL）
+    pub reply_to_message_id: Option<Uuid>,
+    /// ルーム内の投稿順の連番（並び順とカーソルの基準）
+    pub seq: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// 保存するメッセージ
+#[derive(Debug, Clone)]
+pub struct NewMessage {
+    pub room_id: Uuid,
+    pub user_id: Uuid,
+    pub content: String,
+    pub message_type: DbMessageType,
+    pub reply_to_message_id: Option<Uuid>,
+}
+
+/// メッセージ検索の絞り込み条件（日時の範囲は両端を含む）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageSearchFilter<'a> {
+    pub room_name: Option<&'a str>,
+    pub author_name: Option<&'a str>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct MessageWithUser {
     pub id: Uuid,
@@ -30,41 +102,332 @@ pub struct MessageWithUser {
     pub user_id: Uuid,
     pub username: String,
     pub avatar_url: Option<String>,
+    /// 受信 Webhook などのボットによる投稿か
+    pub is_bot: bool,
     pub content: String,
     pub message_type: DbMessageType,
+    /// ルーム内の投稿順の連番（並び順とカーソルの基準）
+    pub seq: i64,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub reply_to_message_id: Option<Uuid>,
+    /// 引用元の投稿者名と本文（引用していなければ None）
+    pub quoted_username: Option<String>,
+    pub quoted_content: Option<String>,
+}
+
+/// 引用元メッセージの表示用の要約
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotePreview {
+    pub id: Uuid,
+    pub username: String,
+    pub content_preview: String,
+}
+
+impl QuotePreview {
+    /// 引用されるメッセージから要約を作る
+    pub fn of(message: &MessageWithUser) -> Self {
+        Self {
+            id: message.id,
+            username: message.username.clone(),
+            content_preview: quote_preview(&message.content),
+        }
+    }
+}
+
+impl MessageWithUser {
+    /// このメッセージが引用しているメッセージの要約
+    pub fn quote(&self) -> Option<QuotePreview> {
+        match (
+            self.reply_to_message_id,
+            &self.quoted_username,
+            &self.quoted_content,
+        ) {
+            (Some(id), Some(username), Some(content)) => Some(QuotePreview {
+                id,
+                username: username.clone(),
+                content_preview: quote_preview(content),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// 引用元の本文を表示用に切り詰める（文字数で数え、切り詰めた場合は末尾に `…`）
+pub fn quote_preview(content: &str) -> String {
+    let mut chars = content.chars();
+    let preview: String = chars.by_ref().take(QUOTE_PREVIEW_LENGTH).collect();
+    if chars.next().is_some() {
+        format!("{}…", preview.trim_end())
+    } else {
+        preview
+    }
+}
+
+/// ルームのメッセージ一覧の状態（一覧の ETag の元）
+///
+/// 新規投稿で `latest_id`、削除で `message_count`、編集で `last_updated_at`、
+/// 投稿者のユーザー名・アバターの変更で `authors_updated_at` が変わる。
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct RoomMessagesVersion {
+    pub latest_id: Option<Uuid>,
+    pub message_count: i64,
+    pub last_updated_at: Option<DateTime<Utc>>,
+    pub authors_updated_at: Option<DateTime<Utc>>,
+}
+
+/// メッセージ本文を保存できる形に整える（REST/WebSocket/受信 Webhook 共通、検証の前に行う）
+///
+/// NFC に正規化したうえで、改行とタブ以外の制御文字、双方向テキストの制御文字、
+/// ゼロ幅スペースなどを取り除き、連続する空白・改行を上限までに詰める。
+/// 絵文字の結合に使われるゼロ幅接合子（ZWJ/ZWNJ）は文字の間にある1つだけ残す。
+pub fn sanitize_content(content: &str) -> String {
+    let mut sanitized = String::with_capacity(content.len());
+    let mut spaces = 0;
+    let mut newlines = 0;
+
+    for c in content.nfc() {
+        match c {
+            '\n' => {
+                spaces = 0;
+                newlines += 1;
+                if newlines > MAX_CONSECUTIVE_NEWLINES {
+                    continue;
+                }
+            }
+            '\u{200C}' | '\u{200D}' => {
+                // 先頭・空白の直後・連続した接合子は表示を崩すだけなので落とす
+                match sanitized.chars().last() {
+                    Some(prev) if !prev.is_whitespace() && !is_joiner(prev) => {}
+                    _ => continue,
+                }
+            }
+            c if is_disallowed_char(c) => continue,
+            c if c.is_whitespace() => {
+                newlines = 0;
+                spaces += 1;
+                if spaces > MAX_CONSECUTIVE_SPACES {
+                    continue;
+                }
+            }
+            _ => {
+                spaces = 0;
+                newlines = 0;
+            }
+        }
+        sanitized.push(c);
+    }
+
+    // 末尾に残った接合子は結合先がないので落とす
+    while sanitized.ends_with(is_joiner) {
+        sanitized.pop();
+    }
+    sanitized
+}
+
+fn is_joiner(c: char) -> bool {
+    matches!(c, '\u{200C}' | '\u{200D}')
+}
+
+// 改行・タブ以外の制御文字、双方向テキストの制御文字、ゼロ幅スペースの類
+fn is_disallowed_char(c: char) -> bool {
+    (c.is_control() && c != '\n' && c != '\t')
+        || matches!(
+            c,
+            '\u{061C}'
+                | '\u{200B}'
+                | '\u{200E}'
+                | '\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{FEFF}'
+        )
+}
+
+/// メッセージ本文を検証する（REST/WebSocket 共通）
+///
+/// 長さはバイト数ではなく文字数で数えるため、日本語などのマルチバイト文字も
+/// ASCII と同じ上限まで送信できる。上限は送信先ルームの `Room::message_length_limit`。
+pub fn validate_message_content(content: &str, max_length: usize) -> Result<(), &'static str> {
+    if content.is_empty() {
+        return Err("Message content cannot be empty");
+    }
+    if content.chars().count() > max_length {
+        return Err("Message content too long");
+    }
+    Ok(())
+}
+
+/// 冪等キーを検証する（前後の空白は除去）
+pub fn validate_idempotency_key(key: &str) -> Result<&str, &'static str> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("Idempotency key cannot be empty");
+    }
+    if key.chars().count() > MAX_IDEMPOTENCY_KEY_LENGTH {
+        return Err("Idempotency key too long");
+    }
+    Ok(key)
+}
+
+/// 期限切れの冪等キーを定期的に削除するタスクを開始（複数回呼ばれても1つだけ起動）
+pub fn start_idempotency_key_cleanup_task(pool: PgPool) {
+    IDEMPOTENCY_KEY_CLEANUP_TASK.call_once(|| {
+        tokio::spawn(async move {
+            let mut interval = interval(IDEMPOTENCY_KEY_CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                match Message::delete_expired_idempotency_keys(&pool, IDEMPOTENCY_KEY_TTL).await {
+                    Ok(0) => {}
+                    Ok(deleted) => tracing::debug!("Deleted {} expired idempotency keys", deleted),
+                    Err(e) => tracing::warn!("Failed to delete expired idempotency keys: {}", e),
+                }
+            }
+        });
+    });
 }
 
 impl Message {
-    pub async fn create(
+    pub async fn create(pool: &PgPool, new_message: NewMessage) -> anyhow::Result<Message> {
+        let message = sqlx::query_as::<_, Message>(
+            r#"
+            INSERT INTO messages (room_id, user_id, content, message_type, reply_to_message_id)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, room_id, user_id, content, message_type, reply_to_message_id, seq, created_at, updated_at
+            "#,
+        )
+        .bind(new_message.room_id)
+        .bind(new_message.user_id)
+        .bind(new_message.content)
+        .bind(new_message.message_type)
+        .bind(new_message.reply_to_message_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(message)
+    }
+
+    /// 冪等キー付きでメッセージを作成する
+    ///
+    /// 同じユーザーが TTL 内に同じキーで送信済みの場合は新規作成せず、元のメッセージを
+    /// `(message, false)` として返す。期限切れのキーは新しいメッセージで上書きする。
+    pub async fn create_idempotent(
         pool: &PgPool,
-        room_id: Uuid,
-        user_id: Uuid,
-        content: String,
-        message_type: DbMessageType,
-    ) -> anyhow::Result<Message> {
+        new_message: NewMessage,
+        idempotency_key: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<(Message, bool)> {
+        let user_id = new_message.user_id;
+        let mut tx = pool.begin().await?;
+
         let message = sqlx::query_as::<_, Message>(
             r#"
-            INSERT INTO messages (room_id, user_id, content, message_type)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, room_id, user_id, content, message_type, created_at, updated_at
+            INSERT INTO messages (room_id, user_id, content, message_type, reply_to_message_id)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, room_id, user_id, content, message_type, reply_to_message_id, seq, created_at, updated_at
+            "#,
+        )
+        .bind(new_message.room_id)
+        .bind(user_id)
+        .bind(new_message.content)
+        .bind(new_message.message_type)
+        .bind(new_message.reply_to_message_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // 同時リクエストは一意制約で待たされ、先行トランザクションのコミット後に競合として扱われる
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO message_idempotency_keys (user_id, idempotency_key, message_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, idempotency_key) DO UPDATE
+                SET message_id = EXCLUDED.message_id, created_at = now()
+                WHERE message_idempotency_keys.created_at < now() - make_interval(secs => $4)
+            RETURNING message_id
+            "#,
+        )
+        .bind(user_id)
+        .bind(idempotency_key)
+        .bind(message.id)
+        .bind(ttl.as_secs_f64())
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+
+        if claimed {
+            tx.commit().await?;
+            return Ok((message, true));
+        }
+
+        // 送信済みのキー: 作成したメッセージは破棄して元のメッセージを返す
+        tx.rollback().await?;
+        let original = sqlx::query_as::<_, Message>(
+            r#"
+            SELECT m.id, m.room_id, m.user_id, m.content, m.message_type, m.reply_to_message_id, m.seq, m.created_at, m.updated_at
+            FROM message_idempotency_keys k
+            JOIN messages m ON m.id = k.message_id
+            WHERE k.user_id = $1 AND k.idempotency_key = $2
             "#,
         )
-        .bind(room_id)
         .bind(user_id)
-        .bind(content)
-        .bind(message_type)
+        .bind(idempotency_key)
         .fetch_one(pool)
         .await?;
 
+        Ok((original, false))
+    }
+
+    /// TTL 内に同じユーザーが同じ冪等キーで送信したメッセージを返す
+    pub async fn find_by_idempotency_key(
+        pool: &PgPool,
+        user_id: Uuid,
+        idempotency_key: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<Option<Message>> {
+        let message = sqlx::query_as::<_, Message>(
+            r#"
+            SELECT m.id, m.room_id, m.user_id, m.content, m.message_type, m.reply_to_message_id, m.seq, m.created_at, m.updated_at
+            FROM message_idempotency_keys k
+            JOIN messages m ON m.id = k.message_id
+            WHERE k.user_id = $1 AND k.idempotency_key = $2
+              AND k.created_at >= now() - make_interval(secs => $3)
+            "#,
+        )
+        .bind(user_id)
+        .bind(idempotency_key)
+        .bind(ttl.as_secs_f64())
+        .fetch_optional(pool)
+        .await?;
+
         Ok(message)
     }
 
+    /// TTL を過ぎた冪等キーを削除し、削除件数を返す
+    pub async fn delete_expired_idempotency_keys(
+        pool: &PgPool,
+        ttl: Duration,
+    ) -> anyhow::Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM message_idempotency_keys WHERE created_at < now() - make_interval(secs => $1)",
+        )
+        .bind(ttl.as_secs_f64())
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// ルームのメッセージを新しい順に取得する
+    ///
+    /// `viewer_id` を指定した場合、そのユーザーがブロックしている著者のメッセージは除外する。
     pub async fn find_by_room_with_users(
         pool: &PgPool,
         room_id: Uuid,
         limit: i64,
         before_id: Option<Uuid>,
+        viewer_id: Option<Uuid>,
     ) -> anyhow::Result<Vec<MessageWithUser>> {
         let sql = if before_id.is_some() {
             r#"
@@ -74,13 +437,26 @@ impl Message {
                 m.user_id,
                 u.username,
                 u.avatar_url,
+                u.is_bot,
                 m.content,
                 m.message_type,
-                m.created_at
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                m.reply_to_message_id,
+                qu.username AS quoted_username,
+                q.content AS quoted_content
             FROM messages m
             JOIN users u ON m.user_id = u.id
-            WHERE m.room_id = $1 AND m.id < $2
-            ORDER BY m.created_at DESC
+            LEFT JOIN messages q ON q.id = m.reply_to_message_id
+            LEFT JOIN users qu ON qu.id = q.user_id
+            WHERE m.room_id = $1
+              AND m.seq < (SELECT c.seq FROM messages c WHERE c.id = $2)
+              AND NOT EXISTS (
+                  SELECT 1 FROM user_blocks b
+                  WHERE b.blocker_id = $4 AND b.blocked_id = m.user_id
+              )
+            ORDER BY m.seq DESC
             LIMIT $3
             "#
         } else {
@@ -91,13 +467,25 @@ impl Message {
                 m.user_id,
                 u.username,
                 u.avatar_url,
+                u.is_bot,
                 m.content,
                 m.message_type,
-                m.created_at
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                m.reply_to_message_id,
+                qu.username AS quoted_username,
+                q.content AS quoted_content
             FROM messages m
             JOIN users u ON m.user_id = u.id
+            LEFT JOIN messages q ON q.id = m.reply_to_message_id
+            LEFT JOIN users qu ON qu.id = q.user_id
             WHERE m.room_id = $1
-            ORDER BY m.created_at DESC
+              AND NOT EXISTS (
+                  SELECT 1 FROM user_blocks b
+                  WHERE b.blocker_id = $3 AND b.blocked_id = m.user_id
+              )
+            ORDER BY m.seq DESC
             LIMIT $2
             "#
         };
@@ -108,14 +496,213 @@ impl Message {
             query = query.bind(before_id);
         }
 
-        let messages = query.bind(limit).fetch_all(pool).await?;
+        let messages = query.bind(limit).bind(viewer_id).fetch_all(pool).await?;
+
+        Ok(messages)
+    }
+
+    /// カーソルのメッセージより新しいメッセージを古い順に取得する（前方ページング用）
+    ///
+    /// `viewer_id` を指定した場合、そのユーザーがブロックしている著者のメッセージは除外する。
+    pub async fn find_by_room_after(
+        pool: &PgPool,
+        room_id: Uuid,
+        after_id: Uuid,
+        limit: i64,
+        viewer_id: Option<Uuid>,
+    ) -> anyhow::Result<Vec<MessageWithUser>> {
+        let messages = sqlx::query_as::<_, MessageWithUser>(
+            r#"
+            SELECT
+                m.id,
+                m.room_id,
+                m.user_id,
+                u.username,
+                u.avatar_url,
+                u.is_bot,
+                m.content,
+                m.message_type,
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                m.reply_to_message_id,
+                qu.username AS quoted_username,
+                q.content AS quoted_content
+            FROM messages m
+            JOIN users u ON m.user_id = u.id
+            LEFT JOIN messages q ON q.id = m.reply_to_message_id
+            LEFT JOIN users qu ON qu.id = q.user_id
+            WHERE m.room_id = $1
+              AND m.seq > (SELECT c.seq FROM messages c WHERE c.id = $2)
+              AND NOT EXISTS (
+                  SELECT 1 FROM user_blocks b
+                  WHERE b.blocker_id = $4 AND b.blocked_id = m.user_id
+              )
+            ORDER BY m.seq ASC
+            LIMIT $3
+            "#,
+        )
+        .bind(room_id)
+        .bind(after_id)
+        .bind(limit)
+        .bind(viewer_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(messages)
+    }
+
+    // 指定メッセージより後に投稿されたメッセージを古い順に取得（再接続時の取りこぼし補完用）
+    pub async fn find_after_in_room(
+        pool: &PgPool,
+        room_id: Uuid,
+        since: &Message,
+        limit: i64,
+    ) -> anyhow::Result<Vec<MessageWithUser>> {
+        let messages = sqlx::query_as::<_, MessageWithUser>(
+            r#"
+            SELECT
+                m.id,
+                m.room_id,
+                m.user_id,
+                u.username,
+                u.avatar_url,
+                u.is_bot,
+                m.content,
+                m.message_type,
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                m.reply_to_message_id,
+                qu.username AS quoted_username,
+                q.content AS quoted_content
+            FROM messages m
+            JOIN users u ON m.user_id = u.id
+            LEFT JOIN messages q ON q.id = m.reply_to_message_id
+            LEFT JOIN users qu ON qu.id = q.user_id
+            WHERE m.room_id = $1 AND m.seq > $2
+            ORDER BY m.seq ASC
+            LIMIT $3
+            "#,
+        )
+        .bind(room_id)
+        .bind(since.seq)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(messages)
+    }
+
+    /// 複数ルームの最新メッセージをルームごとに `limit_per_room` 件ずつ取得する（新しい順）
+    ///
+    /// ルームごとに `(room_id, seq)` のインデックスを上から読むだけなので、ルーム数に比例した
+    /// 1回のクエリで済む。`viewer_id` がブロックしている著者のメッセージは除外する。
+    pub async fn find_latest_in_rooms(
+        pool: &PgPool,
+        room_ids: &[Uuid],
+        limit_per_room: i64,
+        viewer_id: Uuid,
+    ) -> anyhow::Result<Vec<MessageWithUser>> {
+        let messages = sqlx::query_as::<_, MessageWithUser>(
+            r#"
+            SELECT latest.*
+            FROM unnest($1::UUID[]) AS target(room_id)
+            CROSS JOIN LATERAL (
+                SELECT
+                    m.id,
+                    m.room_id,
+                    m.user_id,
+                    u.username,
+                    u.avatar_url,
+                    u.is_bot,
+                    m.content,
+                    m.message_type,
+                    m.seq,
+                    m.created_at,
+                    m.updated_at,
+                    m.reply_to_message_id,
+                    qu.username AS quoted_username,
+                    q.content AS quoted_content
+                FROM messages m
+                JOIN users u ON m.user_id = u.id
+                LEFT JOIN messages q ON q.id = m.reply_to_message_id
+                LEFT JOIN users qu ON qu.id = q.user_id
+                WHERE m.room_id = target.room_id
+                  AND NOT EXISTS (
+                      SELECT 1 FROM user_blocks b
+                      WHERE b.blocker_id = $3 AND b.blocked_id = m.user_id
+                  )
+                ORDER BY m.seq DESC
+                LIMIT $2
+            ) latest
+            ORDER BY latest.room_id, latest.seq DESC
+            "#,
+        )
+        .bind(room_ids)
+        .bind(limit_per_room)
+        .bind(viewer_id)
+        .fetch_all(pool)
+        .await?;
 
         Ok(messages)
     }
 
+    /// ルームのメッセージ一覧の状態を取得する（`viewer_id` がブロックしている著者の分は除く）
+    ///
+    /// ポーリングのたびに呼ばれるため、それぞれ (room_id, seq)・(room_id, user_id)・
+    /// (room_id, updated_at) のインデックスだけで求まる問い合わせにしている。
+    pub async fn room_version(
+        pool: &PgPool,
+        room_id: Uuid,
+        viewer_id: Uuid,
+    ) -> anyhow::Result<RoomMessagesVersion> {
+        let version = sqlx::query_as::<_, RoomMessagesVersion>(
+            r#"
+            SELECT
+                (
+                    SELECT m.id FROM messages m
+                    WHERE m.room_id = $1
+                      AND NOT EXISTS (
+                          SELECT 1 FROM user_blocks b
+                          WHERE b.blocker_id = $2 AND b.blocked_id = m.user_id
+                      )
+                    ORDER BY m.seq DESC LIMIT 1
+                ) AS latest_id,
+                (
+                    SELECT COUNT(*) FROM messages m
+                    WHERE m.room_id = $1
+                      AND NOT EXISTS (
+                          SELECT 1 FROM user_blocks b
+                          WHERE b.blocker_id = $2 AND b.blocked_id = m.user_id
+                      )
+                ) AS message_count,
+                (
+                    SELECT m.updated_at FROM messages m
+                    WHERE m.room_id = $1
+                      AND NOT EXISTS (
+                          SELECT 1 FROM user_blocks b
+                          WHERE b.blocker_id = $2 AND b.blocked_id = m.user_id
+                      )
+                    ORDER BY m.updated_at DESC LIMIT 1
+                ) AS last_updated_at,
+                (
+                    SELECT MAX(u.updated_at) FROM users u
+                    WHERE u.id IN (SELECT m.user_id FROM messages m WHERE m.room_id = $1)
+                ) AS authors_updated_at
+            "#,
+        )
+        .bind(room_id)
+        .bind(viewer_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(version)
+    }
+
     pub async fn find_by_id(pool: &PgPool, id: Uuid) -> anyhow::Result<Option<Message>> {
         let message = sqlx::query_as::<_, Message>(
-            "SELECT id, room_id, user_id, content, message_type, created_at, updated_at 
+            "SELECT id, room_id, user_id, content, message_type, reply_to_message_id, seq, created_at, updated_at
              FROM messages WHERE id = $1",
         )
         .bind(id)
@@ -124,4 +711,314 @@ impl Message {
 
         Ok(message)
     }
+
+    /// ルーム内のメッセージを投稿者の情報付きで取得する（別のルームのメッセージなら None）
+    pub async fn find_with_user_in_room(
+        pool: &PgPool,
+        room_id: Uuid,
+        id: Uuid,
+    ) -> anyhow::Result<Option<MessageWithUser>> {
+        let message = sqlx::query_as::<_, MessageWithUser>(
+            r#"
+            SELECT
+                m.id,
+                m.room_id,
+                m.user_id,
+                u.username,
+                u.avatar_url,
+                u.is_bot,
+                m.content,
+                m.message_type,
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                m.reply_to_message_id,
+                qu.username AS quoted_username,
+                q.content AS quoted_content
+            FROM messages m
+            JOIN users u ON m.user_id = u.id
+            LEFT JOIN messages q ON q.id = m.reply_to_message_id
+            LEFT JOIN users qu ON qu.id = q.user_id
+            WHERE m.id = $1 AND m.room_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(room_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(message)
+    }
+
+    /// 閲覧できるルームのメッセージを投稿者の情報付きで取得する
+    ///
+    /// 存在しない場合と、参加していない非公開ルームのメッセージの場合は区別せず None を返す。
+    pub async fn find_accessible_with_user(
+        pool: &PgPool,
+        id: Uuid,
+        viewer_id: Uuid,
+    ) -> anyhow::Result<Option<MessageWithUser>> {
+        let message = sqlx::query_as::<_, MessageWithUser>(
+            r#"
+            SELECT
+                m.id,
+                m.room_id,
+                m.user_id,
+                u.username,
+                u.avatar_url,
+                u.is_bot,
+                m.content,
+                m.message_type,
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                m.reply_to_message_id,
+                qu.username AS quoted_username,
+                q.content AS quoted_content
+            FROM messages m
+            JOIN users u ON m.user_id = u.id
+            LEFT JOIN messages q ON q.id = m.reply_to_message_id
+            LEFT JOIN users qu ON qu.id = q.user_id
+            JOIN rooms r ON m.room_id = r.id
+            LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $2
+            WHERE m.id = $1
+              AND (r.is_public = true OR rm.user_id IS NOT NULL)
+            "#,
+        )
+        .bind(id)
+        .bind(viewer_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(message)
+    }
+
+    // アクセス可能なルーム内のメッセージを部分一致検索（Meilisearch障害時のフォールバック）
+    pub async fn search_accessible(
+        pool: &PgPool,
+        user_id: Uuid,
+        query: &str,
+        filter: MessageSearchFilter<'_>,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<(Vec<MessageWithUser>, i64)> {
+        let pattern = super::like_pattern(query);
+
+        let messages = sqlx::query_as::<_, MessageWithUser>(
+            r#"
+            SELECT
+                m.id,
+                m.room_id,
+                m.user_id,
+                u.username,
+                u.avatar_url,
+                u.is_bot,
+                m.content,
+                m.message_type,
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                m.reply_to_message_id,
+                qu.username AS quoted_username,
+                q.content AS quoted_content
+            FROM messages m
+            JOIN users u ON m.user_id = u.id
+            LEFT JOIN messages q ON q.id = m.reply_to_message_id
+            LEFT JOIN users qu ON qu.id = q.user_id
+            JOIN rooms r ON m.room_id = r.id
+            LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $1
+            WHERE (r.is_public = true OR rm.user_id IS NOT NULL)
+              AND m.content ILIKE $2 ESCAPE '\'
+              AND ($3::TEXT IS NULL OR r.name = $3)
+              AND ($4::TEXT IS NULL OR u.username = $4)
+              AND ($5::TIMESTAMPTZ IS NULL OR m.created_at >= $5)
+              AND ($6::TIMESTAMPTZ IS NULL OR m.created_at <= $6)
+            ORDER BY m.created_at DESC
+            LIMIT $7 OFFSET $8
+            "#,
+        )
+        .bind(user_id)
+        .bind(&pattern)
+        .bind(filter.room_name)
+        .bind(filter.author_name)
+        .bind(filter.created_after)
+        .bind(filter.created_before)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        let total = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*)
+            FROM messages m
+            JOIN users u ON m.user_id = u.id
+            JOIN rooms r ON m.room_id = r.id
+            LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $1
+            WHERE (r.is_public = true OR rm.user_id IS NOT NULL)
+              AND m.content ILIKE $2 ESCAPE '\'
+              AND ($3::TEXT IS NULL OR r.name = $3)
+              AND ($4::TEXT IS NULL OR u.username = $4)
+              AND ($5::TIMESTAMPTZ IS NULL OR m.created_at >= $5)
+              AND ($6::TIMESTAMPTZ IS NULL OR m.created_at <= $6)
+            "#,
+        )
+        .bind(user_id)
+        .bind(&pattern)
+        .bind(filter.room_name)
+        .bind(filter.author_name)
+        .bind(filter.created_after)
+        .bind(filter.created_before)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((messages, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MAX_MESSAGE_CONTENT_LENGTH;
+
+    #[test]
+    fn test_validate_message_content_counts_characters() {
+        // 3バイト文字 3999 文字（約12KB）はバイト数では上限を超えるが許可される
+        let content = "あ".repeat(MAX_MESSAGE_CONTENT_LENGTH - 1);
+        assert!(content.len() > MAX_MESSAGE_CONTENT_LENGTH);
+        assert!(validate_message_content(&content, MAX_MESSAGE_CONTENT_LENGTH).is_ok());
+
+        let content = "あ".repeat(MAX_MESSAGE_CONTENT_LENGTH);
+        assert!(validate_message_content(&content, MAX_MESSAGE_CONTENT_LENGTH).is_ok());
+    }
+
+    #[test]
+    fn test_validate_message_content_rejects_empty_and_too_long() {
+        assert!(validate_message_content("", MAX_MESSAGE_CONTENT_LENGTH).is_err());
+        let content = "a".repeat(MAX_MESSAGE_CONTENT_LENGTH + 1);
+        assert!(validate_message_content(&content, MAX_MESSAGE_CONTENT_LENGTH).is_err());
+        // ルームで上限を上げていれば通る
+        assert!(validate_message_content(&content, 10000).is_ok());
+    }
+
+    #[test]
+    fn test_validate_message_content_counts_multibyte_chars() {
+        // マルチバイト文字もルームの上限の文字数まで送れる
+        assert!(validate_message_content(&"あ".repeat(16000), 16000).is_ok());
+        assert!(validate_message_content(&"𠮷".repeat(16000), 16000).is_ok());
+        assert!(validate_message_content(&"𠮷".repeat(16001), 16000).is_err());
+    }
+
+    #[test]
+    fn test_sanitize_content_strips_bidi_overrides() {
+        // RLO で拡張子を偽装するファイル名
+        assert_eq!(
+            sanitize_content("invoice\u{202E}fdp.exe\u{202C}"),
+            "invoicefdp.exe"
+        );
+        assert_eq!(sanitize_content("\u{2066}admin\u{2069}\u{200F}"), "admin");
+    }
+
+    #[test]
+    fn test_sanitize_content_strips_zero_width_spaces() {
+        let content = "\u{200B}".repeat(100);
+        assert_eq!(sanitize_content(&content), "");
+        assert!(
+            validate_message_content(&sanitize_content(&content), MAX_MESSAGE_CONTENT_LENGTH)
+                .is_err()
+        );
+        assert_eq!(
+            sanitize_content("he\u{200B}\u{FEFF}llo\u{200D}\u{200D}"),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_content_strips_control_chars_but_keeps_newlines_and_tabs() {
+        assert_eq!(
+            sanitize_content("a\u{0000}b\u{0007}c\r\n\td\u{009B}"),
+            "abc\n\td"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_content_limits_consecutive_whitespace() {
+        let content = format!("a{}b{}c", " ".repeat(100), "\n".repeat(100));
+        assert_eq!(
+            sanitize_content(&content),
+            format!(
+                "a{}b{}c",
+                " ".repeat(MAX_CONSECUTIVE_SPACES),
+                "\n".repeat(MAX_CONSECUTIVE_NEWLINES)
+            )
+        );
+    }
+
+    #[test]
+    fn test_sanitize_content_preserves_japanese_and_emoji() {
+        for content in [
+            "こんにちは、世界！",
+            "👨\u{200D}👩\u{200D}👧 家族",
+            "🇯🇵 👍🏽",
+            "コード:\n\tfn main() {}",
+        ] {
+            assert_eq!(sanitize_content(content), content);
+        }
+    }
+
+    #[test]
+    fn test_sanitize_content_normalizes_to_nfc() {
+        // 「が」の分解形（か + 濁点）は合成済みの1文字になる
+        assert_eq!(sanitize_content("\u{304B}\u{3099}"), "\u{304C}");
+    }
+
+    #[test]
+    fn test_quote_preview_truncates_by_characters() {
+        assert_eq!(quote_preview("short"), "short");
+
+        let content = "あ".repeat(QUOTE_PREVIEW_LENGTH);
+        assert_eq!(quote_preview(&content), content);
+
+        let content = format!("{} tail", "あ".repeat(QUOTE_PREVIEW_LENGTH - 1));
+        assert_eq!(
+            quote_preview(&content),
+            format!("{}…", "あ".repeat(QUOTE_PREVIEW_LENGTH - 1))
+        );
+    }
+
+    #[test]
+    fn test_validate_idempotency_key() {
+        assert_eq!(validate_idempotency_key("  retry-1 "), Ok("retry-1"));
+        assert!(validate_idempotency_key("   ").is_err());
+        let key = "k".repeat(MAX_IDEMPOTENCY_KEY_LENGTH + 1);
+        assert!(validate_idempotency_key(&key).is_err());
+    }
+
+    #[test]
+    fn test_message_type_from_str_rejects_unknown_values() {
+        assert_eq!(
+            "image".parse::<DbMessageType>().unwrap(),
+            DbMessageType::Image
+        );
+        assert!("video".parse::<DbMessageType>().is_err());
+        // 大文字は受け付けない（プロトコル上は小文字のみ）
+        assert!("Text".parse::<DbMessageType>().is_err());
+    }
+
+    #[test]
+    fn test_message_type_string_round_trip() {
+        for message_type in [
+            DbMessageType::Text,
+            DbMessageType::Image,
+            DbMessageType::File,
+            DbMessageType::System,
+        ] {
+            let parsed = message_type.to_string().parse::<DbMessageType>().unwrap();
+            assert_eq!(parsed, message_type);
+        }
+        assert_eq!(
+            "image".parse::<DbMessageType>().unwrap().to_string(),
+            "image"
+        );
+    }
 }
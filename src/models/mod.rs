@@ -1,7 +1,35 @@
+pub mod link_preview;
 pub mod message;
+pub mod notification_pref;
+pub mod oauth;
+pub mod report;
 pub mod room;
+pub mod session;
 pub mod user;
+pub mod webhook;
 
+pub use link_preview::*;
 pub use message::*;
+pub use notification_pref::*;
+pub use oauth::*;
+pub use report::*;
 pub use room::*;
+pub use session::*;
 pub use user::*;
+pub use webhook::*;
+
+// LIKE/ILIKE 用に部分一致パターンを作成（メタ文字は '\' でエスケープ）
+pub(crate) fn like_pattern(query: &str) -> String {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// 一意制約違反によるエラーかどうか（同時作成の競合検出用）
+pub(crate) fn is_unique_violation(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .is_some_and(|e| e.is_unique_violation())
+}
@@ -0,0 +1,161 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Type};
+use uuid::Uuid;
+
+/// 通知モード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "notification_mode", rename_all = "lowercase")]
+pub enum DbNotificationMode {
+    /// すべて通知する（既定）
+    #[default]
+    All,
+    /// メンションのみ通知する
+    Mentions,
+    /// 通知しない
+    None,
+}
+
+impl DbNotificationMode {
+    /// メンション通知（`Mentioned` イベント）を送るか
+    pub fn allows_mentions(self) -> bool {
+        self != Self::None
+    }
+
+    /// ルームの設定を優先し、なければ全体の設定、どちらもなければ既定値を使う
+    pub fn resolve(global: Option<Self>, room: Option<Self>) -> Self {
+        room.or(global).unwrap_or_default()
+    }
+}
+
+/// ユーザーの通知設定（`room_id` が `None` の行は全体の既定値）
+#[derive(Debug, Clone, FromRow)]
+pub struct NotificationPref {
+    pub room_id: Option<Uuid>,
+    pub room_name: Option<String>,
+    pub mode: DbNotificationMode,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NotificationPref {
+    /// ユーザーの設定一覧（全体の設定が先頭、ルームの設定はルーム名順）
+    pub async fn find_by_user(
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> anyhow::Result<Vec<NotificationPref>> {
+        let prefs = sqlx::query_as::<_, NotificationPref>(
+            r#"
+            SELECT p.room_id, r.name AS room_name, p.mode, p.updated_at
+            FROM notification_prefs p
+            LEFT JOIN rooms r ON r.id = p.room_id
+            WHERE p.user_id = $1
+            ORDER BY p.room_id IS NOT NULL, r.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(prefs)
+    }
+
+    /// 設定を保存する（`room_id` が `None` なら全体の既定値）
+    pub async fn set(
+        pool: &PgPool,
+        user_id: Uuid,
+        room_id: Option<Uuid>,
+        mode: DbNotificationMode,
+    ) -> anyhow::Result<()> {
+        // 部分一意インデックスごとに競合対象を指定する
+        let query = if room_id.is_some() {
+            r#"
+            INSERT INTO notification_prefs (user_id, room_id, mode)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, room_id) WHERE room_id IS NOT NULL
+            DO UPDATE SET mode = EXCLUDED.mode, updated_at = now()
+            "#
+        } else {
+            r#"
+            INSERT INTO notification_prefs (user_id, room_id, mode)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) WHERE room_id IS NULL
+            DO UPDATE SET mode = EXCLUDED.mode, updated_at = now()
+            "#
+        };
+
+        sqlx::query(query)
+            .bind(user_id)
+            .bind(room_id)
+            .bind(mode)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 設定を削除して既定値に戻す。削除した場合は true
+    pub async fn clear(
+        pool: &PgPool,
+        user_id: Uuid,
+        room_id: Option<Uuid>,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            "DELETE FROM notification_prefs WHERE user_id = $1 AND room_id IS NOT DISTINCT FROM $2",
+        )
+        .bind(user_id)
+        .bind(room_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// ルームでの実際の通知モード（ルームの設定 > 全体の設定 > 既定値）
+    pub async fn effective_mode(
+        pool: &PgPool,
+        user_id: Uuid,
+        room_id: Uuid,
+    ) -> anyhow::Result<DbNotificationMode> {
+        let rows = sqlx::query_as::<_, (Option<Uuid>, DbNotificationMode)>(
+            "SELECT room_id, mode FROM notification_prefs
+             WHERE user_id = $1 AND (room_id = $2 OR room_id IS NULL)",
+        )
+        .bind(user_id)
+        .bind(room_id)
+        .fetch_all(pool)
+        .await?;
+
+        let global = rows.iter().find(|(id, _)| id.is_none()).map(|(_, m)| *m);
+        let room = rows.iter().find(|(id, _)| id.is_some()).map(|(_, m)| *m);
+        Ok(DbNotificationMode::resolve(global, room))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_override_takes_precedence() {
+        let (all, mentions, none) = (
+            DbNotificationMode::All,
+            DbNotificationMode::Mentions,
+            DbNotificationMode::None,
+        );
+
+        assert_eq!(DbNotificationMode::resolve(None, None), all);
+        assert_eq!(DbNotificationMode::resolve(Some(none), None), none);
+        assert_eq!(
+            DbNotificationMode::resolve(Some(none), Some(mentions)),
+            mentions
+        );
+        assert_eq!(DbNotificationMode::resolve(None, Some(none)), none);
+    }
+
+    #[test]
+    fn test_only_none_suppresses_mentions() {
+        assert!(DbNotificationMode::All.allows_mentions());
+        assert!(DbNotificationMode::Mentions.allows_mentions());
+        assert!(!DbNotificationMode::None.allows_mentions());
+    }
+}
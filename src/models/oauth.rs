@@ -0,0 +1,52 @@
+use sqlx::PgPool;
+
+/// ログイン中の OAuth フローの PKCE code verifier（state トークンの nonce ごとに1回だけ使える）
+///
+/// state トークンは認可コードと一緒にリダイレクト URL に載るため、verifier はサーバー側に保存する。
+pub struct PkceVerifier;
+
+impl PkceVerifier {
+    /// verifier を保存する。ついでに期限切れ（放棄されたログイン）の行を削除する
+    pub async fn store(
+        pool: &PgPool,
+        nonce: &str,
+        verifier: &str,
+        ttl_minutes: i64,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "DELETE FROM oauth_pkce_verifiers WHERE created_at < now() - make_interval(mins => $1)",
+        )
+        .bind(ttl_minutes as i32)
+        .execute(pool)
+        .await?;
+
+        sqlx::query("INSERT INTO oauth_pkce_verifiers (nonce, verifier) VALUES ($1, $2)")
+            .bind(nonce)
+            .bind(verifier)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// verifier を取り出して削除する。存在しない・期限切れ・使用済みなら `None`
+    pub async fn take(
+        pool: &PgPool,
+        nonce: &str,
+        ttl_minutes: i64,
+    ) -> anyhow::Result<Option<String>> {
+        let verifier = sqlx::query_scalar::<_, String>(
+            r#"
+            DELETE FROM oauth_pkce_verifiers
+            WHERE nonce = $1 AND created_at >= now() - make_interval(mins => $2)
+            RETURNING verifier
+            "#,
+        )
+        .bind(nonce)
+        .bind(ttl_minutes as i32)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(verifier)
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// メッセージの通報（モデレーターが確認して解決済みにする）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MessageReport {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub reporter_id: Uuid,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub resolved: bool,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// 通報と、対象メッセージ・通報者の情報
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MessageReportWithDetails {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub message_content: String,
+    pub message_author_id: Uuid,
+    pub message_author_name: String,
+    pub reporter_id: Uuid,
+    pub reporter_name: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MessageReport {
+    /// 通報を記録する（同じユーザーが同じメッセージを通報済みなら一意制約違反）
+    pub async fn create(
+        pool: &PgPool,
+        message_id: Uuid,
+        reporter_id: Uuid,
+        reason: &str,
+    ) -> anyhow::Result<MessageReport> {
+        let report = sqlx::query_as::<_, MessageReport>(
+            r#"
+            INSERT INTO message_reports (message_id, reporter_id, reason)
+            VALUES ($1, $2, $3)
+            RETURNING id, message_id, reporter_id, reason, created_at, resolved, resolved_by, resolved_at
+            "#,
+        )
+        .bind(message_id)
+        .bind(reporter_id)
+        .bind(reason)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(report)
+    }
+
+    /// ルーム内のメッセージへの通報（別のルームなら None）
+    pub async fn find_in_room(
+        pool: &PgPool,
+        room_id: Uuid,
+        id: Uuid,
+    ) -> anyhow::Result<Option<MessageReport>> {
+        let report = sqlx::query_as::<_, MessageReport>(
+            r#"
+            SELECT r.id, r.message_id, r.reporter_id, r.reason, r.created_at,
+                   r.resolved, r.resolved_by, r.resolved_at
+            FROM message_reports r
+            JOIN messages m ON m.id = r.message_id
+            WHERE r.id = $1 AND m.room_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(room_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(report)
+    }
+
+    /// ルームの未解決の通報を古い順に取得
+    ///
+    /// `after` を指定した場合は、その通報（解決済みでもよい）より後に作られた通報を返す。
+    pub async fn find_open_in_room(
+        pool: &PgPool,
+        room_id: Uuid,
+        limit: i64,
+        after: Option<Uuid>,
+    ) -> anyhow::Result<Vec<MessageReportWithDetails>> {
+        let reports = sqlx::query_as::<_, MessageReportWithDetails>(
+            r#"
+            SELECT
+                r.id,
+                r.message_id,
+                m.content AS message_content,
+                m.user_id AS message_author_id,
+                author.username AS message_author_name,
+                r.reporter_id,
+                reporter.username AS reporter_name,
+                r.reason,
+                r.created_at
+            FROM message_reports r
+            JOIN messages m ON m.id = r.message_id
+            JOIN users author ON author.id = m.user_id
+            JOIN users reporter ON reporter.id = r.reporter_id
+            WHERE m.room_id = $1 AND NOT r.resolved
+              AND ($3::uuid IS NULL OR (r.created_at, r.id) > (
+                  SELECT c.created_at, c.id FROM message_reports c WHERE c.id = $3
+              ))
+            ORDER BY r.created_at ASC, r.id ASC
+            LIMIT $2
+            "#,
+        )
+        .bind(room_id)
+        .bind(limit)
+        .bind(after)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(reports)
+    }
+
+    /// 未解決の通報を解決済みにする（解決済みなら false）
+    pub async fn resolve(pool: &PgPool, id: Uuid, resolved_by: Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE message_reports
+            SET resolved = true, resolved_by = $2, resolved_at = now()
+            WHERE id = $1 AND NOT resolved
+            "#,
+        )
+        .bind(id)
+        .bind(resolved_by)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
 use uuid::Uuid;
 
+use super::{DbMessageType, SYSTEM_USER_ID};
+use crate::config::{MAX_MESSAGE_CONTENT_LENGTH, MAX_ROOM_NAME_LENGTH, MIN_ROOM_NAME_LENGTH};
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Room {
     pub id: Uuid,
@@ -10,15 +13,34 @@ pub struct Room {
     pub description: Option<String>,
     pub created_by: Uuid,
     pub is_public: bool,
+    /// メンバーの追加をシステムメッセージとして履歴に残すか
+    pub log_membership_events: bool,
+    /// 同じユーザーの連続投稿の最小間隔（秒、`None` ならスローモードなし）
+    pub slow_mode_seconds: Option<i32>,
+    /// メッセージ本文の文字数上限（`None` ならサーバー既定の上限）
+    pub max_message_length: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// 作成するルーム
+#[derive(Debug, Clone)]
+pub struct NewRoom {
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: Uuid,
+    pub is_public: bool,
+    pub log_membership_events: bool,
+    pub slow_mode_seconds: Option<i32>,
+    pub max_message_length: Option<i32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct RoomMember {
     pub id: Uuid,
     pub room_id: Uuid,
     pub user_id: Uuid,
+    pub role: String,
     pub joined_at: DateTime<Utc>,
 }
 
@@ -29,10 +51,104 @@ pub struct RoomMemberWithUser {
     pub joined_at: DateTime<Utc>,
 }
 
+// メッセージを既読にしたメンバー
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RoomReader {
+    pub user_id: Uuid,
+    pub username: String,
+    pub avatar_url: Option<String>,
+}
+
+// 最新メッセージのプレビューとアクティビティ日時付きのルーム
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RoomWithActivity {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: Uuid,
+    pub is_public: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_message_content: Option<String>,
+    pub last_message_username: Option<String>,
+    pub last_message_at: Option<DateTime<Utc>>,
+    pub last_activity_at: DateTime<Utc>,
+    // プライベートルームはメンバー数、パブリックルームは発言したユーザー数
+    pub member_count: i64,
+    pub message_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PinnedMessage {
+    pub room_id: Uuid,
+    pub message_id: Uuid,
+    pub pinned_by: Uuid,
+    pub pinned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PinnedMessageWithDetails {
+    pub message_id: Uuid,
+    pub room_id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub avatar_url: Option<String>,
+    pub is_bot: bool,
+    pub content: String,
+    pub message_type: DbMessageType,
+    pub seq: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub pinned_by: Uuid,
+    pub pinned_by_username: String,
+    pub pinned_at: DateTime<Utc>,
+}
+
+// ルームの管理操作の監査ログ（操作者のユーザー名付き）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RoomAuditEntry {
+    pub id: Uuid,
+    pub room_id: Uuid,
+    pub actor_id: Uuid,
+    pub actor_username: String,
+    pub action: String,
+    pub target_id: Option<Uuid>,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// ルーム名を正規化（前後の空白除去・小文字化）して検証する
+///
+/// ルーム名はURLパス（`/api/chat/{room}/messages`）やWebSocketのルームキーに
+/// そのまま使われるため、`[a-z0-9_-]` のみ・2〜100文字に制限する。
+pub fn validate_room_name(name: &str) -> Result<String, String> {
+    let normalized = name.trim().to_ascii_lowercase();
+
+    if !normalized
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "Room name may only contain lowercase letters, digits, '_' and '-': {:?}",
+            name
+        ));
+    }
+
+    let len = normalized.len();
+    if !(MIN_ROOM_NAME_LENGTH..=MAX_ROOM_NAME_LENGTH).contains(&len) {
+        return Err(format!(
+            "Room name must be between {} and {} characters",
+            MIN_ROOM_NAME_LENGTH, MAX_ROOM_NAME_LENGTH
+        ));
+    }
+
+    Ok(normalized)
+}
+
 impl Room {
     pub async fn find_by_name(pool: &PgPool, name: &str) -> anyhow::Result<Option<Room>> {
         let room = sqlx::query_as::<_, Room>(
-            "SELECT id, name, description, created_by, is_public, created_at, updated_at 
+            "SELECT id, name, description, created_by, is_public, log_membership_events, slow_mode_seconds, max_message_length, created_at, updated_at 
              FROM rooms WHERE name = $1",
         )
         .bind(name)
@@ -44,7 +160,7 @@ impl Room {
 
     pub async fn find_by_id(pool: &PgPool, id: Uuid) -> anyhow::Result<Option<Room>> {
         let room = sqlx::query_as::<_, Room>(
-            "SELECT id, name, description, created_by, is_public, created_at, updated_at 
+            "SELECT id, name, description, created_by, is_public, log_membership_events, slow_mode_seconds, max_message_length, created_at, updated_at 
              FROM rooms WHERE id = $1",
         )
         .bind(id)
@@ -54,23 +170,75 @@ impl Room {
         Ok(room)
     }
 
-    pub async fn create(
+    /// 作成者のルームが `max_rooms` 件未満の場合だけルームを作成する（上限に達していれば None）
+    ///
+    /// 作成者の行をロックしてから数えるため、同じユーザーの同時作成でも上限を超えない。
+    pub async fn create_within_quota(
         pool: &PgPool,
-        name: String,
-        description: Option<String>,
-        created_by: Uuid,
-        is_public: bool,
+        new_room: NewRoom,
+        max_rooms: i64,
+    ) -> anyhow::Result<Option<Room>> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+            .bind(new_room.created_by)
+            .execute(&mut *tx)
+            .await?;
+        let count =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM rooms WHERE created_by = $1")
+                .bind(new_room.created_by)
+                .fetch_one(&mut *tx)
+                .await?;
+        if count >= max_rooms {
+            return Ok(None);
+        }
+
+        let room = sqlx::query_as::<_, Room>(
+            r#"
+            INSERT INTO rooms (name, description, created_by, is_public, log_membership_events, slow_mode_seconds, max_message_length)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, name, description, created_by, is_public, log_membership_events, slow_mode_seconds, max_message_length, created_at, updated_at
+            "#,
+        )
+        .bind(new_room.name)
+        .bind(new_room.description)
+        .bind(new_room.created_by)
+        .bind(new_room.is_public)
+        .bind(new_room.log_membership_events)
+        .bind(new_room.slow_mode_seconds)
+        .bind(new_room.max_message_length)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(room))
+    }
+
+    /// 名前・説明・公開設定を更新する（`None` の項目は変更しない）
+    ///
+    /// `description` は `Some(None)` で説明を消す。名前の重複は一意制約違反のエラーになる。
+    pub async fn update(
+        &self,
+        pool: &PgPool,
+        name: Option<String>,
+        description: Option<Option<String>>,
+        is_public: Option<bool>,
     ) -> anyhow::Result<Room> {
         let room = sqlx::query_as::<_, Room>(
             r#"
-            INSERT INTO rooms (name, description, created_by, is_public)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, name, description, created_by, is_public, created_at, updated_at
+            UPDATE rooms
+            SET name = COALESCE($2, name),
+                description = CASE WHEN $3 THEN $4 ELSE description END,
+                is_public = COALESCE($5, is_public),
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, name, description, created_by, is_public, log_membership_events, slow_mode_seconds, max_message_length, created_at, updated_at
             "#,
         )
+        .bind(self.id)
         .bind(name)
-        .bind(description)
-        .bind(created_by)
+        .bind(description.is_some())
+        .bind(description.flatten())
         .bind(is_public)
         .fetch_one(pool)
         .await?;
@@ -78,6 +246,17 @@ impl Room {
         Ok(room)
     }
 
+    /// このルームのメッセージ本文の文字数上限
+    ///
+    /// ルームの設定があればそれを `server_max`（`MAX_ROOM_MESSAGE_LENGTH`）までに丸めて使い、
+    /// なければ既定の上限を使う。設定後にサーバーの上限を下げた場合も超えないよう、読むたびに丸める。
+    pub fn message_length_limit(&self, server_max: usize) -> usize {
+        match self.max_message_length {
+            Some(length) => usize::try_from(length).unwrap_or(0).clamp(1, server_max),
+            None => MAX_MESSAGE_CONTENT_LENGTH,
+        }
+    }
+
     pub async fn is_member(&self, pool: &PgPool, user_id: Uuid) -> anyhow::Result<bool> {
         let exists = sqlx::query_scalar::<_, bool>(
             "SELECT EXISTS(SELECT 1 FROM room_members WHERE room_id = $1 AND user_id = $2)",
@@ -91,54 +270,555 @@ impl Room {
     }
 
     pub async fn add_member(&self, pool: &PgPool, user_id: Uuid) -> anyhow::Result<RoomMember> {
+        self.add_member_with_role(pool, user_id, "member").await
+    }
+
+    pub async fn add_member_with_role(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        role: &str,
+    ) -> anyhow::Result<RoomMember> {
         let member = sqlx::query_as::<_, RoomMember>(
             r#"
-            INSERT INTO room_members (room_id, user_id)
-            VALUES ($1, $2)
+            INSERT INTO room_members (room_id, user_id, role)
+            VALUES ($1, $2, $3)
             ON CONFLICT (room_id, user_id) DO NOTHING
-            RETURNING id, room_id, user_id, joined_at
+            RETURNING id, room_id, user_id, role, joined_at
             "#,
         )
         .bind(self.id)
         .bind(user_id)
+        .bind(role)
         .fetch_one(pool)
         .await?;
 
         Ok(member)
     }
 
-    pub async fn get_members(&self, pool: &PgPool) -> anyhow::Result<Vec<RoomMemberWithUser>> {
+    // ルーム作成者または owner/moderator ロールを持つメンバーかどうか
+    pub async fn is_moderator(&self, pool: &PgPool, user_id: Uuid) -> anyhow::Result<bool> {
+        if self.created_by == user_id {
+            return Ok(true);
+        }
+
+        let exists = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM room_members
+                WHERE room_id = $1 AND user_id = $2 AND role IN ('owner', 'moderator')
+            )
+            "#,
+        )
+        .bind(self.id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    // ルーム作成者または owner ロールを持つメンバーかどうか
+    pub async fn is_owner(&self, pool: &PgPool, user_id: Uuid) -> anyhow::Result<bool> {
+        if self.created_by == user_id {
+            return Ok(true);
+        }
+
+        let exists = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM room_members
+                WHERE room_id = $1 AND user_id = $2 AND role = 'owner'
+            )
+            "#,
+        )
+        .bind(self.id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    // メンバーを参加順にページング取得し、総数と合わせて返す（同時刻の参加は user_id 順）
+    pub async fn get_members(
+        &self,
+        pool: &PgPool,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<(Vec<RoomMemberWithUser>, i64)> {
         let members = sqlx::query_as::<_, RoomMemberWithUser>(
             r#"
             SELECT rm.user_id, u.username, rm.joined_at
             FROM room_members rm
             JOIN users u ON rm.user_id = u.id
             WHERE rm.room_id = $1
-            ORDER BY rm.joined_at ASC
+            ORDER BY rm.joined_at ASC, rm.user_id ASC
+            LIMIT $2 OFFSET $3
             "#,
         )
         .bind(self.id)
+        .bind(limit)
+        .bind(offset)
         .fetch_all(pool)
         .await?;
 
-        Ok(members)
+        let total =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM room_members WHERE room_id = $1")
+                .bind(self.id)
+                .fetch_one(pool)
+                .await?;
+
+        Ok((members, total))
+    }
+
+    // メンバーの既読位置をこのルームのメッセージまで進める
+    //
+    // 既読位置より前（seq が小さい）のメッセージや、メンバーでない場合は何もせず false を返す。
+    pub async fn mark_read(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        message_id: Uuid,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE room_members rm
+            SET last_read_message_id = m.id
+            FROM messages m
+            WHERE rm.room_id = $1 AND rm.user_id = $2
+              AND m.id = $3 AND m.room_id = $1
+              AND (
+                  rm.last_read_message_id IS NULL
+                  OR m.seq > (SELECT seq FROM messages WHERE id = rm.last_read_message_id)
+              )
+            "#,
+        )
+        .bind(self.id)
+        .bind(user_id)
+        .bind(message_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // メンバーの既読位置（メンバーでない・まだ既読にしていない場合は None）
+    pub async fn last_read_message_id(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> anyhow::Result<Option<Uuid>> {
+        let last_read = sqlx::query_scalar::<_, Option<Uuid>>(
+            "SELECT last_read_message_id FROM room_members WHERE room_id = $1 AND user_id = $2",
+        )
+        .bind(self.id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(last_read.flatten())
+    }
+
+    // 既読位置が指定した seq 以降のメンバーを名前順に上限まで取得し、総数と合わせて返す
+    pub async fn get_readers(
+        &self,
+        pool: &PgPool,
+        seq: i64,
+        limit: i64,
+    ) -> anyhow::Result<(Vec<RoomReader>, i64)> {
+        let readers = sqlx::query_as::<_, RoomReader>(
+            r#"
+            SELECT rm.user_id, u.username, u.avatar_url
+            FROM room_members rm
+            JOIN users u ON rm.user_id = u.id
+            JOIN messages r ON r.id = rm.last_read_message_id
+            WHERE rm.room_id = $1 AND r.seq >= $2
+            ORDER BY u.username ASC
+            LIMIT $3
+            "#,
+        )
+        .bind(self.id)
+        .bind(seq)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        let total = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*)
+            FROM room_members rm
+            JOIN messages r ON r.id = rm.last_read_message_id
+            WHERE rm.room_id = $1 AND r.seq >= $2
+            "#,
+        )
+        .bind(self.id)
+        .bind(seq)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((readers, total))
     }
 
     // ユーザーがアクセス可能なルーム一覧を取得（パブリック + メンバーのプライベート）
-    pub async fn get_accessible_rooms(pool: &PgPool, user_id: Uuid) -> anyhow::Result<Vec<Room>> {
+    pub async fn get_accessible_rooms(
+        pool: &PgPool,
+        user_id: Uuid,
+        limit: i64,
+    ) -> anyhow::Result<Vec<Room>> {
         let rooms = sqlx::query_as::<_, Room>(
             r#"
-            SELECT DISTINCT r.id, r.name, r.description, r.created_by, r.is_public, r.created_at, r.updated_at
+            SELECT DISTINCT r.id, r.name, r.description, r.created_by, r.is_public, r.log_membership_events, r.slow_mode_seconds, r.max_message_length, r.created_at, r.updated_at
             FROM rooms r
             LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $1
             WHERE r.is_public = true OR rm.user_id IS NOT NULL
             ORDER BY r.created_at ASC
+            LIMIT $2
             "#
         )
         .bind(user_id)
+        .bind(limit)
         .fetch_all(pool)
         .await?;
 
         Ok(rooms)
     }
+
+    // ユーザーがアクセス可能な全ルームのID（パブリックルームと参加しているルーム）
+    pub async fn accessible_ids(pool: &PgPool, user_id: Uuid) -> anyhow::Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT r.id
+            FROM rooms r
+            LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $1
+            WHERE r.is_public = true OR rm.user_id IS NOT NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    // 指定した名前のルームのうち、ユーザーがアクセス可能なもの（存在しない名前は無視）
+    pub async fn find_accessible_by_names(
+        pool: &PgPool,
+        user_id: Uuid,
+        names: &[String],
+    ) -> anyhow::Result<Vec<Room>> {
+        let rooms = sqlx::query_as::<_, Room>(
+            r#"
+            SELECT r.id, r.name, r.description, r.created_by, r.is_public, r.log_membership_events, r.slow_mode_seconds, r.max_message_length, r.created_at, r.updated_at
+            FROM rooms r
+            LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $1
+            WHERE r.name = ANY($2)
+              AND (r.is_public = true OR rm.user_id IS NOT NULL)
+            "#,
+        )
+        .bind(user_id)
+        .bind(names)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rooms)
+    }
+
+    // アクセス可能なルームを名前で絞り込み、最新アクティビティ順にページング取得
+    // 最新メッセージのプレビューは120文字に切り詰める
+    pub async fn search_accessible(
+        pool: &PgPool,
+        user_id: Uuid,
+        q: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<(Vec<RoomWithActivity>, i64)> {
+        let pattern = q.map(super::like_pattern);
+
+        let rooms = sqlx::query_as::<_, RoomWithActivity>(
+            r#"
+            SELECT
+                r.id,
+                r.name,
+                r.description,
+                r.created_by,
+                r.is_public,
+                r.created_at,
+                r.updated_at,
+                lm.content AS last_message_content,
+                lm.username AS last_message_username,
+                lm.created_at AS last_message_at,
+                COALESCE(lm.created_at, r.created_at) AS last_activity_at,
+                CASE WHEN r.is_public THEN mc.participant_count ELSE mb.member_count END
+                    AS member_count,
+                mc.message_count
+            FROM rooms r
+            LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $1
+            LEFT JOIN LATERAL (
+                SELECT LEFT(m.content, 120) AS content, u.username, m.created_at
+                FROM messages m
+                JOIN users u ON m.user_id = u.id
+                WHERE m.room_id = r.id
+                ORDER BY m.created_at DESC
+                LIMIT 1
+            ) lm ON true
+            CROSS JOIN LATERAL (
+                -- システムユーザーによる参加通知などは件数・参加者数に含めない
+                SELECT COUNT(*) AS message_count, COUNT(DISTINCT m.user_id) AS participant_count
+                FROM messages m
+                WHERE m.room_id = r.id AND m.user_id <> $5
+            ) mc
+            CROSS JOIN LATERAL (
+                SELECT COUNT(*) AS member_count
+                FROM room_members members
+                WHERE members.room_id = r.id
+            ) mb
+            WHERE (r.is_public = true OR rm.user_id IS NOT NULL)
+              AND ($2::TEXT IS NULL OR r.name ILIKE $2 ESCAPE '\')
+            ORDER BY last_activity_at DESC, r.id
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(pattern.as_deref())
+        .bind(limit)
+        .bind(offset)
+        .bind(SYSTEM_USER_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let total = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*)
+            FROM rooms r
+            LEFT JOIN room_members rm ON r.id = rm.room_id AND rm.user_id = $1
+            WHERE (r.is_public = true OR rm.user_id IS NOT NULL)
+              AND ($2::TEXT IS NULL OR r.name ILIKE $2 ESCAPE '\')
+            "#,
+        )
+        .bind(user_id)
+        .bind(pattern.as_deref())
+        .fetch_one(pool)
+        .await?;
+
+        Ok((rooms, total))
+    }
+
+    // 既にピン留め済みの場合や、ピン留めが `max_pins` 件に達している場合は None を返す
+    //
+    // ルームの行をロックしてから数えるため、同時にピン留めしても上限を超えない。
+    pub async fn pin_message(
+        &self,
+        pool: &PgPool,
+        message_id: Uuid,
+        pinned_by: Uuid,
+        max_pins: i64,
+    ) -> anyhow::Result<Option<PinnedMessage>> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("SELECT id FROM rooms WHERE id = $1 FOR UPDATE")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+        let count =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM pinned_messages WHERE room_id = $1")
+                .bind(self.id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if count >= max_pins {
+            return Ok(None);
+        }
+
+        let pinned = sqlx::query_as::<_, PinnedMessage>(
+            r#"
+            INSERT INTO pinned_messages (room_id, message_id, pinned_by)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (room_id, message_id) DO NOTHING
+            RETURNING room_id, message_id, pinned_by, pinned_at
+            "#,
+        )
+        .bind(self.id)
+        .bind(message_id)
+        .bind(pinned_by)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(pinned)
+    }
+
+    // ピン留めが存在しなかった場合は false を返す
+    pub async fn unpin_message(&self, pool: &PgPool, message_id: Uuid) -> anyhow::Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM pinned_messages WHERE room_id = $1 AND message_id = $2")
+                .bind(self.id)
+                .bind(message_id)
+                .execute(pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ピン留めされたメッセージを新しい順に取得
+    pub async fn get_pinned(&self, pool: &PgPool) -> anyhow::Result<Vec<PinnedMessageWithDetails>> {
+        let pins = sqlx::query_as::<_, PinnedMessageWithDetails>(
+            r#"
+            SELECT
+                m.id AS message_id,
+                m.room_id,
+                m.user_id,
+                u.username,
+                u.avatar_url,
+                u.is_bot,
+                m.content,
+                m.message_type,
+                m.seq,
+                m.created_at,
+                m.updated_at,
+                p.pinned_by,
+                pu.username AS pinned_by_username,
+                p.pinned_at
+            FROM pinned_messages p
+            JOIN messages m ON p.message_id = m.id
+            JOIN users u ON m.user_id = u.id
+            JOIN users pu ON p.pinned_by = pu.id
+            WHERE p.room_id = $1
+            ORDER BY p.pinned_at DESC
+            "#,
+        )
+        .bind(self.id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(pins)
+    }
+
+    // 管理操作を監査ログに記録
+    pub async fn log_action(
+        &self,
+        pool: &PgPool,
+        actor_id: Uuid,
+        action: &str,
+        target_id: Option<Uuid>,
+        metadata: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO room_audit_log (room_id, actor_id, action, target_id, metadata)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(self.id)
+        .bind(actor_id)
+        .bind(action)
+        .bind(target_id)
+        .bind(metadata)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // 監査ログを新しい順に取得（総件数付き）
+    pub async fn get_audit_log(
+        &self,
+        pool: &PgPool,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<(Vec<RoomAuditEntry>, i64)> {
+        let entries = sqlx::query_as::<_, RoomAuditEntry>(
+            r#"
+            SELECT
+                l.id,
+                l.room_id,
+                l.actor_id,
+                u.username AS actor_username,
+                l.action,
+                l.target_id,
+                l.metadata,
+                l.created_at
+            FROM room_audit_log l
+            JOIN users u ON l.actor_id = u.id
+            WHERE l.room_id = $1
+            ORDER BY l.created_at DESC, l.id
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(self.id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        let total =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM room_audit_log WHERE room_id = $1")
+                .bind(self.id)
+                .fetch_one(pool)
+                .await?;
+
+        Ok((entries, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_room_name_normalizes() {
+        assert_eq!(validate_room_name("general").unwrap(), "general");
+        assert_eq!(
+            validate_room_name("  Project-Alpha_2 ").unwrap(),
+            "project-alpha_2"
+        );
+    }
+
+    #[test]
+    fn test_validate_room_name_rejects_spaces_and_slashes() {
+        assert!(validate_room_name("my room").is_err());
+        assert!(validate_room_name("a/b").is_err());
+        assert!(validate_room_name("room?x=1").is_err());
+    }
+
+    #[test]
+    fn test_validate_room_name_rejects_unicode() {
+        assert!(validate_room_name("雑談").is_err());
+        assert!(validate_room_name("party🎉").is_err());
+        // 小文字化すると ASCII になる文字（ケルビン記号）も拒否
+        assert!(validate_room_name("\u{212A}eep").is_err());
+    }
+
+    #[test]
+    fn test_validate_room_name_length() {
+        assert!(validate_room_name("").is_err());
+        assert!(validate_room_name("   ").is_err());
+        assert!(validate_room_name("a").is_err());
+        assert!(validate_room_name("ab").is_ok());
+        assert!(validate_room_name(&"a".repeat(100)).is_ok());
+        assert!(validate_room_name(&"a".repeat(101)).is_err());
+    }
+
+    #[test]
+    fn test_message_length_limit_uses_override_clamped_to_server_max() {
+        let mut room = Room {
+            id: Uuid::new_v4(),
+            name: "paste".to_string(),
+            description: None,
+            created_by: Uuid::new_v4(),
+            is_public: true,
+            log_membership_events: false,
+            slow_mode_seconds: None,
+            max_message_length: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        assert_eq!(room.message_length_limit(16000), MAX_MESSAGE_CONTENT_LENGTH);
+
+        room.max_message_length = Some(10000);
+        assert_eq!(room.message_length_limit(16000), 10000);
+        assert_eq!(room.message_length_limit(8000), 8000);
+
+        room.max_message_length = Some(100);
+        assert_eq!(room.message_length_limit(16000), 100);
+    }
 }
@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// 発行済みのアクセストークン（ログインした端末）
+///
+/// 行を削除するとそのトークンは失効する（`jti` を持つトークンは行が残っている間だけ有効）。
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Session {
+    pub jti: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    /// セッションを記録する。ついでにユーザーの期限切れのセッションを削除する
+    pub async fn create(
+        pool: &PgPool,
+        jti: Uuid,
+        user_id: Uuid,
+        user_agent: Option<&str>,
+        ip: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<Session> {
+        sqlx::query("DELETE FROM sessions WHERE user_id = $1 AND expires_at < now()")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            INSERT INTO sessions (jti, user_id, user_agent, ip, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING jti, user_id, user_agent, ip, created_at, last_seen, expires_at
+            "#,
+        )
+        .bind(jti)
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    /// ユーザーの有効なセッション（最近使われた順）
+    ///
+    /// `before` を指定した場合は、そのセッションより後に並ぶセッションを返す。
+    /// 存在しない（失効した）セッションを指定した場合は空になる。
+    pub async fn find_active_by_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        limit: i64,
+        before: Option<Uuid>,
+    ) -> anyhow::Result<Vec<Session>> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT jti, user_id, user_agent, ip, created_at, last_seen, expires_at
+            FROM sessions
+            WHERE user_id = $1 AND expires_at > now()
+              AND ($3::uuid IS NULL OR (last_seen, jti) < (
+                  SELECT c.last_seen, c.jti FROM sessions c WHERE c.jti = $3 AND c.user_id = $1
+              ))
+            ORDER BY last_seen DESC, jti DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .bind(before)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// `last_seen` を更新する。セッションが失効している（削除済み）場合は false
+    pub async fn touch(pool: &PgPool, jti: Uuid, user_id: Uuid) -> anyhow::Result<bool> {
+        let result =
+            sqlx::query("UPDATE sessions SET last_seen = now() WHERE jti = $1 AND user_id = $2")
+                .bind(jti)
+                .bind(user_id)
+                .execute(pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// ユーザー自身のセッションを失効させる。削除した場合は true
+    pub async fn revoke(pool: &PgPool, jti: Uuid, user_id: Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM sessions WHERE jti = $1 AND user_id = $2")
+            .bind(jti)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
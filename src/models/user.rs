@@ -3,6 +3,15 @@ use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
 use uuid::Uuid;
 
+/// システムメッセージ（メンバーの参加通知など）の投稿者（マイグレーション 021 で作成）
+pub const SYSTEM_USER_ID: Uuid = Uuid::nil();
+pub const SYSTEM_USERNAME: &str = "system";
+
+/// 大文字小文字を無視したユーザー名検索で複数のユーザーが一致した
+#[derive(Debug, thiserror::Error)]
+#[error("Username {0:?} matches multiple users")]
+pub struct AmbiguousUsername(pub String);
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: Uuid,
@@ -80,4 +89,92 @@ impl User {
 
         Ok(user)
     }
+
+    // ユーザー名で検索（大文字小文字を区別せず、前後の空白は無視）
+    //
+    // GitHubのユーザー名は大文字小文字を区別せずに一意だが、users.username には
+    // 一意制約がないため複数一致し得る。その場合は完全一致を優先し、
+    // 決められなければ AmbiguousUsername を返す。
+    // ボット（受信 Webhook・system ユーザー）は実在のユーザーと同名になり得るため対象外。
+    pub async fn find_by_username_ci(
+        pool: &PgPool,
+        username: &str,
+    ) -> anyhow::Result<Option<User>> {
+        let username = username.trim();
+        let users = sqlx::query_as::<_, User>(
+            "SELECT id, github_id, username, email, avatar_url, created_at, updated_at
+             FROM users WHERE LOWER(username) = LOWER($1) AND NOT is_bot",
+        )
+        .bind(username)
+        .fetch_all(pool)
+        .await?;
+
+        if users.len() <= 1 {
+            return Ok(users.into_iter().next());
+        }
+
+        users
+            .into_iter()
+            .find(|user| user.username == username)
+            .map(Some)
+            .ok_or_else(|| AmbiguousUsername(username.to_string()).into())
+    }
+
+    /// `blocker_id` が `blocked_id` をブロック（ミュート）する。新たにブロックした場合は true
+    pub async fn block(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO user_blocks (blocker_id, blocked_id) VALUES ($1, $2)
+             ON CONFLICT (blocker_id, blocked_id) DO NOTHING",
+        )
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// ブロックを解除する。ブロックしていた場合は true
+    pub async fn unblock(
+        pool: &PgPool,
+        blocker_id: Uuid,
+        blocked_id: Uuid,
+    ) -> anyhow::Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2")
+                .bind(blocker_id)
+                .bind(blocked_id)
+                .execute(pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn is_blocked(
+        pool: &PgPool,
+        blocker_id: Uuid,
+        blocked_id: Uuid,
+    ) -> anyhow::Result<bool> {
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2)",
+        )
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// `blocker_id` がブロックしているユーザーの ID 一覧
+    pub async fn blocked_user_ids(pool: &PgPool, blocker_id: Uuid) -> anyhow::Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            "SELECT blocked_id FROM user_blocks WHERE blocker_id = $1",
+        )
+        .bind(blocker_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids)
+    }
 }
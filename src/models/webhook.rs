@@ -0,0 +1,252 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// 新しいメッセージが投稿されたときのイベント名
+pub const WEBHOOK_EVENT_MESSAGE_CREATED: &str = "message.created";
+
+/// 購読できるイベントの一覧
+pub const WEBHOOK_EVENTS: &[&str] = &[WEBHOOK_EVENT_MESSAGE_CREATED];
+
+/// ルームの送信 Webhook（新しいメッセージを外部 URL へ転送する）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RoomWebhook {
+    pub id: Uuid,
+    pub room_id: Uuid,
+    pub url: String,
+    /// 署名（`X-Signature`）用の共有シークレット
+    pub secret: String,
+    pub events: Vec<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RoomWebhook {
+    pub async fn create(
+        pool: &PgPool,
+        room_id: Uuid,
+        url: &str,
+        secret: &str,
+        events: &[String],
+        created_by: Uuid,
+    ) -> anyhow::Result<RoomWebhook> {
+        let webhook = sqlx::query_as::<_, RoomWebhook>(
+            r#"
+            INSERT INTO room_webhooks (room_id, url, secret, events, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, room_id, url, secret, events, created_by, created_at
+            "#,
+        )
+        .bind(room_id)
+        .bind(url)
+        .bind(secret)
+        .bind(events)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    pub async fn find_by_room(pool: &PgPool, room_id: Uuid) -> anyhow::Result<Vec<RoomWebhook>> {
+        let webhooks = sqlx::query_as::<_, RoomWebhook>(
+            "SELECT id, room_id, url, secret, events, created_by, created_at
+             FROM room_webhooks WHERE room_id = $1 ORDER BY created_at",
+        )
+        .bind(room_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    /// ルームで `event` を購読している Webhook
+    pub async fn find_subscribed(
+        pool: &PgPool,
+        room_id: Uuid,
+        event: &str,
+    ) -> anyhow::Result<Vec<RoomWebhook>> {
+        let webhooks = sqlx::query_as::<_, RoomWebhook>(
+            "SELECT id, room_id, url, secret, events, created_by, created_at
+             FROM room_webhooks WHERE room_id = $1 AND $2 = ANY(events)",
+        )
+        .bind(room_id)
+        .bind(event)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn count_by_room(pool: &PgPool, room_id: Uuid) -> anyhow::Result<i64> {
+        let count =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM room_webhooks WHERE room_id = $1")
+                .bind(room_id)
+                .fetch_one(pool)
+                .await?;
+
+        Ok(count)
+    }
+
+    /// URL・購読イベントを更新する（`None` の項目は変更しない）。見つからなければ `None`
+    pub async fn update(
+        pool: &PgPool,
+        room_id: Uuid,
+        id: Uuid,
+        url: Option<&str>,
+        events: Option<&[String]>,
+    ) -> anyhow::Result<Option<RoomWebhook>> {
+        let webhook = sqlx::query_as::<_, RoomWebhook>(
+            r#"
+            UPDATE room_webhooks
+            SET url = COALESCE($3, url), events = COALESCE($4, events)
+            WHERE room_id = $1 AND id = $2
+            RETURNING id, room_id, url, secret, events, created_by, created_at
+            "#,
+        )
+        .bind(room_id)
+        .bind(id)
+        .bind(url)
+        .bind(events)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    /// Webhook を削除する。削除した場合は true
+    pub async fn delete(pool: &PgPool, room_id: Uuid, id: Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM room_webhooks WHERE room_id = $1 AND id = $2")
+            .bind(room_id)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 再試行しても配信できなかったイベントを記録する
+    pub async fn record_dead_letter(
+        &self,
+        pool: &PgPool,
+        event: &str,
+        payload: &serde_json::Value,
+        last_error: &str,
+        attempts: u32,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO webhook_dead_letters (webhook_id, event, payload, last_error, attempts)
+             VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(self.id)
+        .bind(event)
+        .bind(payload)
+        .bind(last_error)
+        .bind(attempts as i32)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// ルームの受信 Webhook のトークン（投稿は紐づくボットユーザーの名前で行われる）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookToken {
+    pub id: Uuid,
+    pub room_id: Uuid,
+    pub bot_user_id: Uuid,
+    pub bot_name: String,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookToken {
+    /// ボットユーザーを作成し、トークン（のハッシュ）を登録する
+    pub async fn create(
+        pool: &PgPool,
+        room_id: Uuid,
+        bot_name: &str,
+        token_hash: &str,
+        created_by: Uuid,
+    ) -> anyhow::Result<WebhookToken> {
+        let mut tx = pool.begin().await?;
+
+        let bot_user_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO users (github_id, username, is_bot)
+            VALUES (-nextval('bot_github_id_seq'), $1, true)
+            RETURNING id
+            "#,
+        )
+        .bind(bot_name)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let token = sqlx::query_as::<_, WebhookToken>(
+            r#"
+            INSERT INTO webhook_tokens (room_id, bot_user_id, token_hash, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, room_id, bot_user_id, $5::TEXT AS bot_name, created_by, created_at
+            "#,
+        )
+        .bind(room_id)
+        .bind(bot_user_id)
+        .bind(token_hash)
+        .bind(created_by)
+        .bind(bot_name)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(token)
+    }
+
+    pub async fn find_by_token_hash(
+        pool: &PgPool,
+        token_hash: &str,
+    ) -> anyhow::Result<Option<WebhookToken>> {
+        let token = sqlx::query_as::<_, WebhookToken>(
+            r#"
+            SELECT t.id, t.room_id, t.bot_user_id, u.username AS bot_name, t.created_by, t.created_at
+            FROM webhook_tokens t
+            JOIN users u ON t.bot_user_id = u.id
+            WHERE t.token_hash = $1
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    pub async fn find_by_room(pool: &PgPool, room_id: Uuid) -> anyhow::Result<Vec<WebhookToken>> {
+        let tokens = sqlx::query_as::<_, WebhookToken>(
+            r#"
+            SELECT t.id, t.room_id, t.bot_user_id, u.username AS bot_name, t.created_by, t.created_at
+            FROM webhook_tokens t
+            JOIN users u ON t.bot_user_id = u.id
+            WHERE t.room_id = $1
+            ORDER BY t.created_at
+            "#,
+        )
+        .bind(room_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    /// トークンを無効化する（ボットユーザーと過去の投稿は残す）。削除した場合は true
+    pub async fn delete(pool: &PgPool, room_id: Uuid, id: Uuid) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM webhook_tokens WHERE room_id = $1 AND id = $2")
+            .bind(room_id)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
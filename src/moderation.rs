@@ -0,0 +1,296 @@
+//! 投稿前のメッセージのモデレーション（禁止語フィルター）
+//!
+//! REST・WebSocket・受信 Webhook の投稿は、本文の整形と検証のあと保存の前に [`moderate`] を通す。
+//! 既定の [`BannedWordsModerator`] は `BANNED_WORDS_FILE` の禁止語（1行1語、`#` 以降はコメント）に
+//! 一致した投稿を、`MODERATION_MODE` に従って拒否するか一致部分を `*` で伏せる。
+//! 照合は NFKC で正規化して大文字小文字を区別せずに行い（全角英数字も一致する）、
+//! 英数字で始まる・終わる禁止語は英単語の途中には一致させない。
+//! モデレーターは起動時に [`Config`](crate::config::Config) の `moderator` として組み立て、状態として共有する。
+//! Unix では SIGHUP を受けると、再起動せずに禁止語ファイルを読み直す。
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use tracing::{info, warn};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::config::ModerationMode;
+use crate::error::{AppError, AppResult};
+
+/// 保存前のメッセージ本文を検査する
+pub trait Moderator: Send + Sync + fmt::Debug {
+    /// 保存する本文を返す（伏せ字にした場合は置き換え後）。拒否する場合は `AppError::validation`
+    fn moderate(&self, content: &str) -> AppResult<String>;
+
+    /// 設定元（禁止語ファイルなど）を読み直す。SIGHUP で呼ばれ、既定では何もしない
+    fn reload(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// 禁止語の一覧で投稿を拒否するか伏せ字にするモデレーター
+#[derive(Debug)]
+pub struct BannedWordsModerator {
+    // 正規化済みの禁止語（再読み込みで丸ごと入れ替える）
+    words: RwLock<HashSet<String>>,
+    mode: ModerationMode,
+    // 禁止語ファイル（`reload` で読み直す）
+    path: Option<String>,
+}
+
+impl BannedWordsModerator {
+    pub fn new(words: impl IntoIterator<Item = String>, mode: ModerationMode) -> Self {
+        Self {
+            words: RwLock::new(normalize_words(words)),
+            mode,
+            path: None,
+        }
+    }
+
+    /// 禁止語ファイルから作る（未指定なら何もフィルターしない）
+    pub fn load(path: Option<&str>, mode: ModerationMode) -> anyhow::Result<Self> {
+        let words = match path {
+            Some(path) => load_words(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read BANNED_WORDS_FILE {}: {}", path, e))?,
+            None => Vec::new(),
+        };
+        info!("Loaded {} banned words ({:?} mode)", words.len(), mode);
+        Ok(Self {
+            path: path.map(str::to_string),
+            ..Self::new(words, mode)
+        })
+    }
+
+    /// 禁止語を入れ替える（処理中の投稿は古い一覧か新しい一覧のどちらかで検査される）
+    pub fn replace_words(&self, words: impl IntoIterator<Item = String>) {
+        let words = normalize_words(words);
+        *self.words.write().unwrap_or_else(PoisonError::into_inner) = words;
+    }
+
+    // 禁止語に一致した文字の位置（元の本文の文字単位）
+    fn matched_chars(&self, content: &str) -> Vec<bool> {
+        let original: Vec<char> = content.chars().collect();
+        let mut matched = vec![false; original.len()];
+
+        // 結合文字（半角の濁点なども）は直前の文字とまとめて正規化する
+        let mut groups: Vec<(usize, usize)> = Vec::new();
+        for (index, c) in original.iter().enumerate() {
+            let combining = c.to_string().nfkc().next().is_some_and(is_combining_mark);
+            match groups.last_mut() {
+                Some((_, end)) if combining => *end = index + 1,
+                _ => groups.push((index, index + 1)),
+            }
+        }
+
+        // 正規化後の文字と、それが何番目のまとまりから来たか
+        let folded: Vec<(char, usize)> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group, &(start, end))| {
+                original[start..end]
+                    .iter()
+                    .collect::<String>()
+                    .nfkc()
+                    .flat_map(char::to_lowercase)
+                    .map(move |folded| (folded, group))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let is_word_char = |position: Option<&(char, usize)>| {
+            position.is_some_and(|(c, _)| c.is_ascii_alphanumeric())
+        };
+
+        let words = self.words.read().unwrap_or_else(PoisonError::into_inner);
+        for word in words.iter() {
+            let word: Vec<char> = word.chars().collect();
+            if word.len() > folded.len() {
+                continue;
+            }
+            for from in 0..=folded.len() - word.len() {
+                let to = from + word.len();
+                if !folded[from..to].iter().zip(&word).all(|((c, _), w)| c == w) {
+                    continue;
+                }
+                // "ass" が "class" に一致しないよう、英数字の境界では前後も英数字でないことを確認
+                if (word[0].is_ascii_alphanumeric()
+                    && from > 0
+                    && is_word_char(folded.get(from - 1)))
+                    || (word[word.len() - 1].is_ascii_alphanumeric()
+                        && is_word_char(folded.get(to)))
+                {
+                    continue;
+                }
+                for (_, group) in &folded[from..to] {
+                    let (start, end) = groups[*group];
+                    matched[start..end].fill(true);
+                }
+            }
+        }
+
+        matched
+    }
+}
+
+impl Moderator for BannedWordsModerator {
+    fn moderate(&self, content: &str) -> AppResult<String> {
+        let matched = self.matched_chars(content);
+        if !matched.contains(&true) {
+            return Ok(content.to_string());
+        }
+
+        match self.mode {
+            ModerationMode::Reject => Err(AppError::validation(
+                "メッセージに禁止されている語句が含まれています",
+            )),
+            ModerationMode::Mask => Ok(content
+                .chars()
+                .zip(matched)
+                .map(|(c, matched)| if matched { '*' } else { c })
+                .collect()),
+        }
+    }
+
+    // 読めなければ前の一覧を使い続ける
+    fn reload(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let words = load_words(path)?;
+        info!("Reloaded {} banned words from {}", words.len(), path);
+        self.replace_words(words);
+        Ok(())
+    }
+}
+
+// 照合用に禁止語を正規化する（空行は捨てる）
+fn normalize_words(words: impl IntoIterator<Item = String>) -> HashSet<String> {
+    words
+        .into_iter()
+        .map(|word| word.trim().nfkc().flat_map(char::to_lowercase).collect())
+        .filter(|word: &String| !word.is_empty())
+        .collect()
+}
+
+/// 禁止語ファイルを読み込む（1行1語、`#` 以降はコメント）
+pub fn load_words(path: &str) -> anyhow::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// SIGHUP を受けるたびにモデレーターの設定元を読み直す
+#[cfg(unix)]
+pub fn reload_on_hangup(moderator: Arc<dyn Moderator>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!(
+                "Failed to listen for SIGHUP; banned words will not be reloaded: {}",
+                e
+            );
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            if let Err(e) = moderator.reload() {
+                warn!(
+                    "Failed to reload banned words; keeping the previous list: {}",
+                    e
+                );
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_hangup(_moderator: Arc<dyn Moderator>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moderator(words: &[&str], mode: ModerationMode) -> BannedWordsModerator {
+        BannedWordsModerator::new(words.iter().map(|word| word.to_string()), mode)
+    }
+
+    #[test]
+    fn test_reject_mode() {
+        let moderator = moderator(&["spam"], ModerationMode::Reject);
+        assert_eq!(moderator.moderate("hello").unwrap(), "hello");
+
+        let err = moderator.moderate("buy SPAM now").unwrap_err();
+        assert_eq!(err.code(), "VALIDATION_ERROR");
+    }
+
+    #[test]
+    fn test_mask_mode() {
+        let moderator = moderator(&["spam", "ばか"], ModerationMode::Mask);
+        assert_eq!(
+            moderator.moderate("Spam and spam").unwrap(),
+            "**** and ****"
+        );
+        assert_eq!(moderator.moderate("このばかもの").unwrap(), "この**もの");
+    }
+
+    #[test]
+    fn test_matching_is_unicode_aware() {
+        let moderator = moderator(&["spam", "ﾊﾞｶ"], ModerationMode::Mask);
+        // 全角英字・半角カナも NFKC で同じ語として扱い、伏せ字は元の文字数のまま
+        assert_eq!(moderator.moderate("ＳＰＡＭ!").unwrap(), "****!");
+        assert_eq!(moderator.moderate("バカだ").unwrap(), "**だ");
+        assert_eq!(moderator.moderate("ﾊﾞｶだ").unwrap(), "***だ");
+        assert_eq!(moderator.moderate("🙂spam🙂").unwrap(), "🙂****🙂");
+    }
+
+    #[test]
+    fn test_ascii_words_do_not_match_inside_words() {
+        let moderator = moderator(&["ass"], ModerationMode::Mask);
+        assert_eq!(moderator.moderate("classic pass").unwrap(), "classic pass");
+        assert_eq!(moderator.moderate("ass-kicking").unwrap(), "***-kicking");
+    }
+
+    #[test]
+    fn test_replace_words() {
+        let moderator = moderator(&["old"], ModerationMode::Reject);
+        moderator.replace_words(vec!["new".to_string(), "  ".to_string()]);
+
+        assert!(moderator.moderate("old").is_ok());
+        assert!(moderator.moderate("new").is_err());
+    }
+
+    #[test]
+    fn test_reload_reads_file_again() {
+        let path = std::env::temp_dir().join(format!("banned_words_{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "old\n").unwrap();
+        let moderator = BannedWordsModerator::load(path.to_str(), ModerationMode::Reject).unwrap();
+        assert!(moderator.moderate("old").is_err());
+
+        std::fs::write(&path, "new\n").unwrap();
+        moderator.reload().unwrap();
+        assert!(moderator.moderate("old").is_ok());
+        assert!(moderator.moderate("new").is_err());
+
+        // 読めなければ前の一覧のまま
+        std::fs::remove_file(&path).unwrap();
+        assert!(moderator.reload().is_err());
+        assert!(moderator.moderate("new").is_err());
+    }
+
+    #[test]
+    fn test_load_words_skips_comments_and_blank_lines() {
+        let path = std::env::temp_dir().join(format!("banned_words_{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "# comment\nspam\n\n  eggs  # trailing\n").unwrap();
+
+        let words = load_words(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(words, vec!["spam", "eggs"]);
+    }
+}
@@ -0,0 +1,196 @@
+//! 外部 URL を取得する際の SSRF 対策
+//!
+//! リンクプレビュー・Webhook 配信・アバター取得で共通して使う。
+//! IP アドレス直指定とローカルホストを拒否し、名前解決した全アドレスが公開アドレスの
+//! 場合だけ取得する。検査したアドレスをクライアントに固定するため、検査後に DNS の
+//! 応答が変わっても（DNS リバインディング）内部アドレスには接続しない。
+//! 環境変数のプロキシ設定は使わず、リダイレクトは自動では追わない。
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use reqwest::{redirect, Client, Url};
+
+// `host` が `domain` 自身またはそのサブドメインか
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// スキーム・ホスト名と許可・拒否リストから取得してよい URL か判定する
+pub(crate) fn is_url_allowed(url: &Url, allowed: &[String], denied: &[String]) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    // IP アドレス直指定とローカルホストは許可しない
+    if host.starts_with('[') || host.parse::<IpAddr>().is_ok() {
+        return false;
+    }
+    if host == "localhost" || host.ends_with(".localhost") {
+        return false;
+    }
+
+    if denied.iter().any(|domain| domain_matches(&host, domain)) {
+        return false;
+    }
+    allowed.is_empty() || allowed.iter().any(|domain| domain_matches(&host, domain))
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8（「このネットワーク」）
+        || a == 0
+        // 100.64.0.0/10（キャリアグレード NAT）
+        || (a == 100 && (b & 0xc0) == 64)
+        // 198.18.0.0/15（ベンチマーク用）
+        || (a == 198 && (b & 0xfe) == 18)
+        // 240.0.0.0/4（予約済み）
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    // IPv4 射影（::ffff:0:0/96）と IPv4 互換（::/96）は埋め込まれた IPv4 で判定する
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(v4);
+    }
+    if segments[..6] == [0; 6] {
+        return !ip.is_loopback() && !ip.is_unspecified() && is_public_ipv4(embedded_ipv4(ip));
+    }
+    // 64:ff9b::/96（NAT64）と 2002::/16（6to4）は IPv4 への変換経路になるため許可しない
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] || segments[0] == 0x2002 {
+        return false;
+    }
+    !(ip.is_multicast()
+        // fc00::/7（ユニークローカル）と fe80::/10（リンクローカル）
+        || (segments[0] & 0xfe00) == 0xfc00
+        || (segments[0] & 0xffc0) == 0xfe80)
+}
+
+fn embedded_ipv4(ip: Ipv6Addr) -> Ipv4Addr {
+    let [.., a, b, c, d] = ip.octets();
+    Ipv4Addr::new(a, b, c, d)
+}
+
+/// インターネット上のアドレスか（プライベート・ループバック・リンクローカル・予約済みなどを除外）
+pub(crate) fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+/// ホストを名前解決し、全アドレスが公開アドレスならそのアドレスを返す
+pub(crate) async fn resolve_public_addrs(url: &Url) -> Option<Vec<SocketAddr>> {
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return None;
+    };
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await.ok()?.collect();
+    (!addrs.is_empty() && addrs.iter().all(|addr| is_public_ip(addr.ip()))).then_some(addrs)
+}
+
+/// `url` の取得に使うクライアントを作る
+///
+/// 名前解決の結果を検査し、そのアドレスだけに接続するよう固定する。
+/// 内部アドレスに解決されるホストはエラー。リダイレクトは追わないため、
+/// 呼び出し側は各ホップで URL を検査してからこの関数を呼び直すこと。
+pub(crate) async fn pinned_client(
+    url: &Url,
+    timeout: Duration,
+    user_agent: &str,
+) -> anyhow::Result<Client> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("URL has no host"))?;
+    let addrs = resolve_public_addrs(url)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Host does not resolve to public addresses"))?;
+
+    Ok(Client::builder()
+        .redirect(redirect::Policy::none())
+        .no_proxy()
+        .resolve_to_addrs(host, &addrs)
+        .timeout(timeout)
+        .user_agent(user_agent)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_policy() {
+        let allowed = |url: &str, allow: &[&str], deny: &[&str]| {
+            let allow: Vec<String> = allow.iter().map(|d| d.to_string()).collect();
+            let deny: Vec<String> = deny.iter().map(|d| d.to_string()).collect();
+            is_url_allowed(&Url::parse(url).unwrap(), &allow, &deny)
+        };
+
+        assert!(allowed("https://example.com/", &[], &[]));
+        assert!(!allowed("http://127.0.0.1/", &[], &[]));
+        assert!(!allowed("http://[::1]/", &[], &[]));
+        assert!(!allowed("http://localhost:3000/", &[], &[]));
+        assert!(!allowed("file:///etc/passwd", &[], &[]));
+
+        assert!(allowed("https://docs.github.com/", &["github.com"], &[]));
+        assert!(!allowed("https://notgithub.com/", &["github.com"], &[]));
+        assert!(!allowed(
+            "https://internal.example.com/",
+            &[],
+            &["example.com"]
+        ));
+        assert!(!allowed(
+            "https://github.com/",
+            &["github.com"],
+            &["github.com"]
+        ));
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in [
+            "10.0.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.1.2.3",
+            "198.18.0.1",
+            "198.19.255.255",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::a00:1",
+            "2002:a00:1::",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "93.184.216.34",
+            "198.20.0.1",
+            "2606:2800:220:1::",
+            "::ffff:93.184.216.34",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
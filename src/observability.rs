@@ -0,0 +1,41 @@
+//! リクエスト単位のトレーシング
+//!
+//! 各 HTTP リクエストに `x-request-id` を付与（クライアントが指定した場合はそれを使う）し、
+//! そのIDを持つスパンの中でハンドラーを実行する。レスポンスにも同じIDを返すため、
+//! クライアントの報告からサーバーログを追える。
+
+use axum::{body::Body, http::Request, Router};
+use tower::ServiceBuilder;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::Span;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// リクエストIDの付与・伝播と、リクエストごとのスパンを追加する
+pub fn with_request_tracing(router: Router) -> Router {
+    router.layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+            .layer(TraceLayer::new_for_http().make_span_with(make_request_span))
+            .layer(PropagateRequestIdLayer::x_request_id()),
+    )
+}
+
+// クエリ文字列には旧方式の WebSocket トークンが含まれうるため、パスだけを記録する
+fn make_request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "http_request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id = %request_id,
+    )
+}
@@ -0,0 +1,276 @@
+//! ルームのスローモード（同じユーザーの連続投稿の最小間隔）
+//!
+//! レート制限とは別に、`rooms.slow_mode_seconds` が設定されたルームでは、同じユーザーが前回の投稿から
+//! 指定の秒数が経つまで次の投稿を受け付けない。REST と WebSocket の投稿で共有し、
+//! ルームのモデレーター（作成者・owner・moderator）は対象外。
+//! 同時に送られた投稿が揃って間隔の確認を通らないよう、確認と同時に投稿時刻を予約し、
+//! 保存に失敗した場合は予約を取り消す。最終投稿時刻はプロセスのメモリに持つため、再起動するとリセットされる。
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::MAX_SLOW_MODE_SECONDS;
+use crate::error::AppError;
+use crate::models::Room;
+
+/// スローモードの間隔内の投稿（`retry_after` 秒後に送信できる）
+#[derive(Debug, thiserror::Error)]
+#[error("Slow mode is enabled in this room; retry after {retry_after}s")]
+pub struct SlowModeActive {
+    pub retry_after: u64,
+}
+
+impl From<SlowModeActive> for AppError {
+    fn from(error: SlowModeActive) -> Self {
+        Self::SlowMode {
+            retry_after: error.retry_after,
+        }
+    }
+}
+
+// 記録がこの件数を超えたら古い記録を捨てる（以降は捨てた後の件数の2倍が次の目安）
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// ルームとユーザーの組ごとの最終投稿時刻
+struct SlowModeTracker {
+    state: Mutex<TrackerState>,
+}
+
+struct TrackerState {
+    last_sent: HashMap<(Uuid, Uuid), Instant>,
+    prune_at: usize,
+}
+
+impl Default for SlowModeTracker {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(TrackerState {
+                last_sent: HashMap::new(),
+                prune_at: PRUNE_THRESHOLD,
+            }),
+        }
+    }
+}
+
+impl SlowModeTracker {
+    /// 前回の投稿から間隔が空いていれば、同じロックの中で投稿時刻として `now` を記録する
+    ///
+    /// 空いていなければ送信できるまでの秒数（切り上げ）を返す。
+    fn reserve(
+        &self,
+        room_id: Uuid,
+        user_id: Uuid,
+        interval: Duration,
+        now: Instant,
+    ) -> Result<(), u64> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(sent) = state.last_sent.get(&(room_id, user_id)) {
+            let remaining = interval.saturating_sub(now.duration_since(*sent));
+            if !remaining.is_zero() {
+                return Err(remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0));
+            }
+        }
+        state.last_sent.insert((room_id, user_id), now);
+
+        // 最長の間隔を過ぎた記録は捨てる（投稿しなくなったユーザーの分が残り続けないように）
+        if state.last_sent.len() > state.prune_at {
+            let max_interval = Duration::from_secs(MAX_SLOW_MODE_SECONDS as u64);
+            state
+                .last_sent
+                .retain(|_, sent| now.duration_since(*sent) < max_interval);
+            state.prune_at = PRUNE_THRESHOLD.max(state.last_sent.len() * 2);
+        }
+        Ok(())
+    }
+
+    /// 保存できなかった投稿の予約を取り消す（その後に別の投稿が予約されていれば何もしない）
+    fn release(&self, room_id: Uuid, user_id: Uuid, reserved_at: Instant) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.last_sent.get(&(room_id, user_id)) == Some(&reserved_at) {
+            state.last_sent.remove(&(room_id, user_id));
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .last_sent
+            .len()
+    }
+}
+
+fn tracker() -> &'static SlowModeTracker {
+    static TRACKER: OnceLock<SlowModeTracker> = OnceLock::new();
+    TRACKER.get_or_init(SlowModeTracker::default)
+}
+
+/// 予約した投稿枠
+///
+/// 投稿を保存できたら [`Reservation::confirm`] を呼ぶ。呼ばずに破棄すると予約を取り消し、
+/// 保存に失敗した投稿は間隔に数えない。
+#[must_use]
+pub struct Reservation {
+    reserved: Option<(Uuid, Uuid, Instant)>,
+}
+
+impl Reservation {
+    /// 投稿を保存できたので、予約した時刻から次の投稿までの間隔を始める
+    pub fn confirm(mut self) {
+        self.reserved = None;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some((room_id, user_id, reserved_at)) = self.reserved.take() {
+            tracker().release(room_id, user_id, reserved_at);
+        }
+    }
+}
+
+/// スローモードのルームで投稿できるか確認し、投稿枠を予約する
+///
+/// 間隔内の場合は [`SlowModeActive`] を返す。スローモードでないルームとモデレーターは常に投稿できる。
+pub async fn reserve(pool: &PgPool, room: &Room, user_id: Uuid) -> anyhow::Result<Reservation> {
+    let Some(seconds) = room.slow_mode_seconds.filter(|&seconds| seconds > 0) else {
+        return Ok(Reservation { reserved: None });
+    };
+    if room.is_moderator(pool, user_id).await? {
+        return Ok(Reservation { reserved: None });
+    }
+
+    let now = Instant::now();
+    tracker()
+        .reserve(room.id, user_id, Duration::from_secs(seconds as u64), now)
+        .map_err(|retry_after| SlowModeActive { retry_after })?;
+    Ok(Reservation {
+        reserved: Some((room.id, user_id, now)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_boundary() {
+        let tracker = SlowModeTracker::default();
+        let (room_id, user_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let interval = Duration::from_secs(30);
+        let now = Instant::now();
+
+        assert_eq!(tracker.reserve(room_id, user_id, interval, now), Ok(()));
+        // 残りは切り上げて返す
+        assert_eq!(
+            tracker.reserve(room_id, user_id, interval, now + Duration::from_millis(100)),
+            Err(30)
+        );
+        assert_eq!(
+            tracker.reserve(
+                room_id,
+                user_id,
+                interval,
+                now + interval - Duration::from_millis(1)
+            ),
+            Err(1)
+        );
+        // ちょうど間隔が経てば送信でき、そこから次の間隔が始まる
+        assert_eq!(
+            tracker.reserve(room_id, user_id, interval, now + interval),
+            Ok(())
+        );
+        assert_eq!(
+            tracker.reserve(
+                room_id,
+                user_id,
+                interval,
+                now + interval + Duration::from_secs(1)
+            ),
+            Err(29)
+        );
+    }
+
+    #[test]
+    fn test_rejected_sends_do_not_extend_the_interval() {
+        let tracker = SlowModeTracker::default();
+        let (room_id, user_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let interval = Duration::from_secs(10);
+        let now = Instant::now();
+
+        assert!(tracker.reserve(room_id, user_id, interval, now).is_ok());
+        assert!(tracker
+            .reserve(room_id, user_id, interval, now + Duration::from_secs(5))
+            .is_err());
+        assert!(tracker
+            .reserve(room_id, user_id, interval, now + interval)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_unsaved_sends_do_not_start_the_interval() {
+        let tracker = SlowModeTracker::default();
+        let (room_id, user_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let interval = Duration::from_secs(10);
+        let now = Instant::now();
+
+        // 予約して保存に失敗した場合は、取り消せばすぐに再送できる
+        assert!(tracker.reserve(room_id, user_id, interval, now).is_ok());
+        tracker.release(room_id, user_id, now);
+        assert!(tracker
+            .reserve(room_id, user_id, interval, now + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_concurrent_sends_reserve_one_slot() {
+        let tracker = SlowModeTracker::default();
+        let (room_id, user_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let interval = Duration::from_secs(10);
+        let now = Instant::now();
+
+        // 保存を待たずに届いた2件目は、1件目の予約で間隔内になる
+        assert!(tracker.reserve(room_id, user_id, interval, now).is_ok());
+        assert_eq!(tracker.reserve(room_id, user_id, interval, now), Err(10));
+    }
+
+    #[test]
+    fn test_tracked_per_room_and_user() {
+        let tracker = SlowModeTracker::default();
+        let (room_id, user_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let interval = Duration::from_secs(30);
+        let now = Instant::now();
+
+        assert!(tracker.reserve(room_id, user_id, interval, now).is_ok());
+        assert!(tracker
+            .reserve(Uuid::new_v4(), user_id, interval, now)
+            .is_ok());
+        assert!(tracker
+            .reserve(room_id, Uuid::new_v4(), interval, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_expired_records_pruned_past_threshold() {
+        let tracker = SlowModeTracker::default();
+        let now = Instant::now();
+        for _ in 0..PRUNE_THRESHOLD {
+            tracker
+                .reserve(Uuid::new_v4(), Uuid::new_v4(), Duration::ZERO, now)
+                .unwrap();
+        }
+        assert_eq!(tracker.len(), PRUNE_THRESHOLD);
+
+        // 閾値を超えた時点で、最長の間隔を過ぎた記録がまとめて捨てられる
+        let later = now + Duration::from_secs(MAX_SLOW_MODE_SECONDS as u64);
+        tracker
+            .reserve(Uuid::new_v4(), Uuid::new_v4(), Duration::ZERO, later)
+            .unwrap();
+        assert_eq!(tracker.len(), 1);
+    }
+}
@@ -0,0 +1,349 @@
+//! ルームの Webhook
+//!
+//! 送信 Webhook: メッセージ送信処理はイベントをキューに積むだけで、配信はバックグラウンドのワーカーが行う。
+//! そのため Webhook の失敗や遅延は WebSocket への配信に影響しない。失敗した配信は
+//! 指数バックオフで再試行し、それでも届かなければ `webhook_dead_letters` に記録する。
+//!
+//! 本文は `X-Signature: sha256=<hex>`（シークレットをキーとした本文の HMAC-SHA256）で署名する。
+//! 送信先は `WEBHOOK_ALLOWED_DOMAINS` のドメインに限り、配信のたびに名前解決の結果も検査する。
+//!
+//! 受信 Webhook（外部からボットとして投稿する）: トークンの生成とトークンごとのレート制限を扱う。
+//! 投稿の処理は `api::webhooks` にある。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use hmac::{Hmac, Mac};
+use reqwest::{header, Url};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::config::{
+    Config, INCOMING_WEBHOOK_RATE_LIMIT, INCOMING_WEBHOOK_RATE_WINDOW, WEBHOOK_MAX_ATTEMPTS,
+    WEBHOOK_QUEUE_CAPACITY, WEBHOOK_RETRY_BASE_DELAY, WEBHOOK_TIMEOUT,
+};
+use crate::models::{Message, Room, RoomWebhook, User, WEBHOOK_EVENT_MESSAGE_CREATED};
+use crate::net_guard::{is_url_allowed, pinned_client};
+
+/// 署名ヘッダー名
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+// ワーカーに渡すイベント
+struct WebhookJob {
+    room_id: Uuid,
+    event: &'static str,
+    payload: serde_json::Value,
+}
+
+static QUEUE: OnceLock<mpsc::Sender<WebhookJob>> = OnceLock::new();
+
+/// 配信ワーカーを開始する（複数回呼ばれても1つだけ起動）
+pub fn start_webhook_worker(pool: PgPool, config: Arc<Config>) {
+    let (tx, mut rx) = mpsc::channel(WEBHOOK_QUEUE_CAPACITY);
+    if QUEUE.set(tx).is_err() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        while let Some(job) = rx.recv().await {
+            let webhooks = match RoomWebhook::find_subscribed(&pool, job.room_id, job.event).await {
+                Ok(webhooks) => webhooks,
+                Err(e) => {
+                    warn!("Failed to load webhooks for room {}: {}", job.room_id, e);
+                    continue;
+                }
+            };
+
+            // 遅い送信先が他の Webhook を待たせないよう送信先ごとに並行して配信
+            for webhook in webhooks {
+                tokio::spawn(deliver(
+                    pool.clone(),
+                    config.clone(),
+                    webhook,
+                    job.event,
+                    job.payload.clone(),
+                ));
+            }
+        }
+    });
+}
+
+/// `message.created` イベントをキューに積む（ワーカー未起動やキューが満杯の場合は破棄）
+pub fn message_created(room: &Room, message: &Message, author: &User, content: &str) {
+    let payload = serde_json::json!({
+        "event": WEBHOOK_EVENT_MESSAGE_CREATED,
+        "room": room.name,
+        "message": {
+            "id": message.id.to_string(),
+            "user_id": author.id.to_string(),
+            "username": author.username,
+            "content": content,
+            "message_type": message.message_type.as_str(),
+            "seq": message.seq,
+            "timestamp": message.created_at,
+        },
+    });
+
+    enqueue(WebhookJob {
+        room_id: room.id,
+        event: WEBHOOK_EVENT_MESSAGE_CREATED,
+        payload,
+    });
+}
+
+fn enqueue(job: WebhookJob) {
+    let Some(queue) = QUEUE.get() else {
+        return;
+    };
+    if queue.try_send(job).is_err() {
+        warn!("Webhook queue is full; dropping event");
+    }
+}
+
+/// 送信先として登録できる URL か（https で、許可リストのドメインに一致するもののみ）
+pub fn is_webhook_url_allowed(url: &str, config: &Config) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    // 許可リストが空なら登録できない（空を「すべて許可」とするリンクプレビューとは逆）
+    !config.webhook_allowed_domains.is_empty()
+        && url.scheme() == "https"
+        && is_url_allowed(&url, &config.webhook_allowed_domains, &[])
+}
+
+/// 本文の署名（`sha256=<hex>`）
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// 新しい Webhook 用のシークレットを生成する
+pub fn generate_secret() -> String {
+    format!(
+        "whsec_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// 受信 Webhook 用のトークンを生成する（URL に含めて使う）
+pub fn generate_incoming_token() -> String {
+    format!(
+        "whin_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// 受信 Webhook トークンの保存・照合用ハッシュ（SHA-256 の16進文字列）
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// 固定ウィンドウのカウンター
+struct RateWindow {
+    started: Instant,
+    count: u32,
+}
+
+impl RateWindow {
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.started) >= INCOMING_WEBHOOK_RATE_WINDOW {
+            self.started = now;
+            self.count = 0;
+        }
+        if self.count >= INCOMING_WEBHOOK_RATE_LIMIT {
+            return false;
+        }
+        self.count += 1;
+        true
+    }
+}
+
+/// 受信 Webhook のトークンごとのレート制限。上限内なら true を返して1回分を消費する
+pub fn check_incoming_rate_limit(token_id: Uuid) -> bool {
+    static WINDOWS: OnceLock<Mutex<HashMap<Uuid, RateWindow>>> = OnceLock::new();
+    let mut windows = WINDOWS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    let now = Instant::now();
+    // 期限切れのウィンドウは捨てる（削除されたトークンの分が残り続けないように）
+    windows.retain(|_, window| now.duration_since(window.started) < INCOMING_WEBHOOK_RATE_WINDOW);
+    windows
+        .entry(token_id)
+        .or_insert(RateWindow {
+            started: now,
+            count: 0,
+        })
+        .try_acquire(now)
+}
+
+// 再試行しながら配信し、最後まで失敗したら dead letter に記録する
+async fn deliver(
+    pool: PgPool,
+    config: Arc<Config>,
+    webhook: RoomWebhook,
+    event: &'static str,
+    payload: serde_json::Value,
+) {
+    let body = payload.to_string();
+    let mut last_error = String::new();
+
+    for attempt in 1..=WEBHOOK_MAX_ATTEMPTS {
+        match send_once(&webhook, event, &body, &config).await {
+            Ok(()) => {
+                debug!("Webhook {} delivered (attempt {})", webhook.id, attempt);
+                return;
+            }
+            Err(e) => {
+                debug!(
+                    "Webhook {} delivery failed (attempt {}): {}",
+                    webhook.id, attempt, e
+                );
+                last_error = e.to_string();
+            }
+        }
+
+        if attempt < WEBHOOK_MAX_ATTEMPTS {
+            tokio::time::sleep(WEBHOOK_RETRY_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+        }
+    }
+
+    warn!(
+        "Webhook {} gave up after {} attempts: {}",
+        webhook.id, WEBHOOK_MAX_ATTEMPTS, last_error
+    );
+    if let Err(e) = webhook
+        .record_dead_letter(&pool, event, &payload, &last_error, WEBHOOK_MAX_ATTEMPTS)
+        .await
+    {
+        warn!("Failed to record webhook dead letter: {}", e);
+    }
+}
+
+async fn send_once(
+    webhook: &RoomWebhook,
+    event: &str,
+    body: &str,
+    config: &Config,
+) -> anyhow::Result<()> {
+    // 登録後に許可リストから外れたドメインや、内部アドレスに解決されるホストには送らない
+    let url = Url::parse(&webhook.url)?;
+    if !is_webhook_url_allowed(&webhook.url, config) {
+        anyhow::bail!("Webhook URL is not allowed");
+    }
+
+    let client = pinned_client(&url, WEBHOOK_TIMEOUT, "miuchi-chat-webhook/0.1").await?;
+    let response = client
+        .post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(&webhook.secret, body.as_bytes()))
+        .header("X-Webhook-Event", event)
+        .body(body.to_string())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("Webhook endpoint returned {}", response.status());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allowed: &[&str]) -> Config {
+        let allowed = allowed.join(",");
+        Config::from_lookup(|key| match key {
+            "DEV_MODE" => Some("true".to_string()),
+            "WEBHOOK_ALLOWED_DOMAINS" => Some(allowed.clone()),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sign_matches_known_hmac_sha256() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_webhook_url_requires_https_and_allowlisted_domain() {
+        let config = config(&["hooks.example.com"]);
+        assert!(is_webhook_url_allowed(
+            "https://hooks.example.com/abc",
+            &config
+        ));
+        assert!(is_webhook_url_allowed(
+            "https://eu.hooks.example.com/abc",
+            &config
+        ));
+        assert!(!is_webhook_url_allowed(
+            "http://hooks.example.com/abc",
+            &config
+        ));
+        assert!(!is_webhook_url_allowed("https://example.com/abc", &config));
+        assert!(!is_webhook_url_allowed("https://127.0.0.1/abc", &config));
+        assert!(!is_webhook_url_allowed("not a url", &config));
+    }
+
+    #[test]
+    fn test_empty_allowlist_rejects_everything() {
+        assert!(!is_webhook_url_allowed(
+            "https://hooks.example.com/abc",
+            &config(&[])
+        ));
+    }
+
+    #[test]
+    fn test_token_hash_is_stable_hex() {
+        let token = generate_incoming_token();
+        assert!(token.starts_with("whin_"));
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_eq!(hash_token(&token).len(), 64);
+        assert_ne!(hash_token(&token), hash_token(&generate_incoming_token()));
+    }
+
+    #[test]
+    fn test_incoming_rate_limit_per_token() {
+        let token_id = Uuid::new_v4();
+        for _ in 0..INCOMING_WEBHOOK_RATE_LIMIT {
+            assert!(check_incoming_rate_limit(token_id));
+        }
+        assert!(!check_incoming_rate_limit(token_id));
+        // 別のトークンには影響しない
+        assert!(check_incoming_rate_limit(Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_rate_window_resets_after_window() {
+        let start = Instant::now();
+        let mut window = RateWindow {
+            started: start,
+            count: INCOMING_WEBHOOK_RATE_LIMIT,
+        };
+        assert!(!window.try_acquire(start));
+        assert!(window.try_acquire(start + INCOMING_WEBHOOK_RATE_WINDOW));
+        assert_eq!(window.count, 1);
+    }
+
+    #[test]
+    fn test_generated_secrets_are_unique() {
+        let secret = generate_secret();
+        assert!(secret.starts_with("whsec_"));
+        assert_eq!(secret.len(), "whsec_".len() + 64);
+        assert_ne!(secret, generate_secret());
+    }
+}
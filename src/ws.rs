@@ -3,215 +3,442 @@ use axum::{
         ws::{CloseFrame, Message, WebSocket},
         Query, State, WebSocketUpgrade,
     },
+    http::{header, HeaderMap},
     response::Response,
 };
 use chrono::{DateTime, Utc};
 use futures_util::{SinkExt, StreamExt};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use sqlx::PgPool;
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU32, AtomicU64, Ordering},
         Arc,
     },
     time::{Duration, Instant},
 };
 use tokio::{
-    sync::{broadcast, RwLock, Semaphore},
+    sync::{mpsc, RwLock, Semaphore},
     time::{interval, timeout},
 };
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
-use crate::models::{DbMessageType, Message as DbMessage, Room, User};
-
-// WebSocketでやり取りするメッセージの形式
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum WsMessage {
-    // クライアントからサーバーへ
-    #[serde(rename = "join_room")]
-    JoinRoom { room: String },
-    #[serde(rename = "send_message")]
-    SendMessage {
-        room: String,
-        content: String,
-        message_type: Option<String>,
-    },
-    #[serde(rename = "leave_room")]
-    LeaveRoom { room: String },
-    #[serde(rename = "ping")]
-    Ping { timestamp: Option<u64> },
-
-    // WebRTC シグナリング用
-    #[serde(rename = "webrtc_offer")]
-    WebRtcOffer {
-        room: String,
-        to_user_id: String,
-        offer: serde_json::Value,
-    },
-    #[serde(rename = "webrtc_answer")]
-    WebRtcAnswer {
-        room: String,
-        to_user_id: String,
-        answer: serde_json::Value,
-    },
-    #[serde(rename = "webrtc_ice_candidate")]
-    WebRtcIceCandidate {
-        room: String,
-        to_user_id: String,
-        candidate: serde_json::Value,
-    },
-
-    // サーバーからクライアントへ
-    #[serde(rename = "room_joined")]
-    RoomJoined {
-        room: String,
-        user_id: String,
-        username: String,
-    },
-    #[serde(rename = "message")]
-    Message {
-        id: String,
-        room: String,
-        user_id: String,
-        username: String,
-        content: String,
-        message_type: String,
-        timestamp: DateTime<Utc>,
-    },
-    #[serde(rename = "user_joined")]
-    UserJoined {
-        room: String,
-        user_id: String,
-        username: String,
-    },
-    #[serde(rename = "user_left")]
-    UserLeft {
-        room: String,
-        user_id: String,
-        username: String,
-    },
-    #[serde(rename = "pong")]
-    Pong { timestamp: Option<u64> },
-    #[serde(rename = "error")]
-    Error { message: String, code: Option<u16> },
-    #[serde(rename = "auth_required")]
-    AuthRequired,
-    #[serde(rename = "rate_limited")]
-    RateLimited { retry_after: u64 },
+use crate::commands::{self, CommandContext, CommandOutput};
+use crate::config::{
+    Config, ConnectionLimitPolicy, MAX_JOIN_ALL_ROOMS, MAX_SYNC_MESSAGES, RATE_LIMIT_MESSAGES,
+    RATE_LIMIT_WINDOW, SLOW_CONSUMER_DISCONNECT_THRESHOLD, WS_CLOSE_LINGER,
+};
+use crate::link_preview;
+use crate::mentions;
+use crate::models::{
+    sanitize_content, validate_message_content, validate_room_name, DbMessageType,
+    Message as DbMessage, NewMessage, QuotePreview, Room, Session, User, SYSTEM_USERNAME,
+    SYSTEM_USER_ID,
+};
+use crate::slow_mode::SlowModeActive;
+use crate::webhooks;
+
+// WebSocketでやり取りするメッセージの形式（miuchi-protocol クレートで定義）
+pub use miuchi_protocol::{HistoryMessage, QuotedMessage, WsErrorCode, WsMessage};
+
+impl From<QuotePreview> for QuotedMessage {
+    fn from(quote: QuotePreview) -> Self {
+        QuotedMessage {
+            id: quote.id.to_string(),
+            username: quote.username,
+            content_preview: quote.content_preview,
+        }
+    }
 }
 
 // 接続中のクライアント情報
 #[derive(Debug)]
 pub struct ConnectedClient {
+    pub connection_id: Uuid,
     pub user_id: Uuid,
     pub username: String,
+    // 接続に使ったトークンのセッションID（`jti` を持たない古いトークンでは None）
+    pub session_id: Option<Uuid>,
     pub rooms: Vec<String>,
-    pub sender: broadcast::Sender<WsMessage>,
-    pub connected_at: Instant,
+    pub sender: ClientSender,
+    // Ping やクローズなどの制御フレームを送信タスクへ渡す
+    pub control: mpsc::Sender<Message>,
+    pub connected_at: DateTime<Utc>,
     pub last_activity: Arc<RwLock<Instant>>,
     pub message_count: AtomicU64,
     pub rate_limiter: Arc<Semaphore>,
+    // このユーザーがブロックしているユーザー（同じ接続のクローン間で共有）
+    pub blocked_users: BlockedUsers,
 }
 
 impl Clone for ConnectedClient {
     fn clone(&self) -> Self {
         Self {
+            connection_id: self.connection_id,
             user_id: self.user_id,
             username: self.username.clone(),
+            session_id: self.session_id,
             rooms: self.rooms.clone(),
             sender: self.sender.clone(),
+            control: self.control.clone(),
             connected_at: self.connected_at,
             last_activity: self.last_activity.clone(),
             message_count: AtomicU64::new(self.message_count.load(Ordering::Relaxed)),
             rate_limiter: self.rate_limiter.clone(),
+            blocked_users: self.blocked_users.clone(),
+        }
+    }
+}
+
+// 接続の送信キューへの書き込み側
+//
+// ブロードキャスト側は `try_send` するだけで待たないため、書き込みが詰まった
+// クライアントがいても他の受信者への配信は遅れない。キューが満杯のときは
+// メッセージを破棄し、連続して満杯になった接続にはクローズフレームを送る。
+#[derive(Debug, Clone)]
+pub struct ClientSender {
+    queue: mpsc::Sender<WsMessage>,
+    control: mpsc::Sender<Message>,
+    // 連続してキューが満杯だった回数（送信に成功すると0に戻る）
+    consecutive_full: Arc<AtomicU32>,
+    // 受信側に未通知の破棄件数
+    dropped: Arc<AtomicU64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientSendError {
+    #[error("send queue is full")]
+    Full,
+    #[error("connection closed")]
+    Closed,
+}
+
+impl ClientSender {
+    /// 容量 `capacity` の送信キューを作る。切断時のクローズフレームは `control` へ送る
+    pub fn channel(capacity: usize, control: mpsc::Sender<Message>) -> (Self, ClientReceiver) {
+        let (queue, rx) = mpsc::channel(capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let sender = Self {
+            queue,
+            control,
+            consecutive_full: Arc::new(AtomicU32::new(0)),
+            dropped: dropped.clone(),
+        };
+        (sender, ClientReceiver { queue: rx, dropped })
+    }
+
+    pub fn send(&self, message: WsMessage) -> Result<(), ClientSendError> {
+        match self.queue.try_send(message) {
+            Ok(()) => {
+                self.consecutive_full.store(0, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                crate::metrics::slow_consumer("dropped");
+
+                let full = self.consecutive_full.fetch_add(1, Ordering::Relaxed) + 1;
+                if full == SLOW_CONSUMER_DISCONNECT_THRESHOLD {
+                    warn!(
+                        "WebSocket send queue full {} times in a row, disconnecting slow consumer",
+                        full
+                    );
+                    crate::metrics::slow_consumer("disconnected");
+                    let _ = self.control.try_send(Message::Close(Some(CloseFrame {
+                        code: axum::extract::ws::close_code::AGAIN,
+                        reason: "Client is too slow".into(),
+                    })));
+                }
+                Err(ClientSendError::Full)
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => Err(ClientSendError::Closed),
+        }
+    }
+}
+
+// 接続の送信キューの読み出し側（送信タスクが持つ）
+#[derive(Debug)]
+pub struct ClientReceiver {
+    queue: mpsc::Receiver<WsMessage>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl ClientReceiver {
+    // キューが満杯で破棄されたメッセージがあれば、次のメッセージの前にその件数を通知する
+    pub async fn recv(&mut self) -> Option<WsMessage> {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            return Some(WsMessage::Error {
+                message: format!("{} messages were dropped", dropped),
+                code: Some(WsErrorCode::MessagesDropped),
+            });
         }
+        self.queue.recv().await
     }
 }
 
+// ルーム内の1ユーザーの接続（タブごとに connection_id で区別）
+pub type UserClients = HashMap<Uuid, ConnectedClient>;
+
+// 接続ユーザーがブロックしているユーザー
+pub type BlockedUsers = Arc<RwLock<HashSet<Uuid>>>;
+
 // 全体の状態管理
-pub type AppState = Arc<RwLock<HashMap<String, HashMap<Uuid, ConnectedClient>>>>;
+pub type AppState = Arc<RwLock<WsState>>;
+
+#[derive(Debug, Default)]
+pub struct WsState {
+    // ルーム名 -> ユーザーID -> 接続ID -> クライアント
+    pub rooms: HashMap<String, HashMap<Uuid, UserClients>>,
+    // ユーザーID -> 接続ID -> クライアント（どのルームにも参加していない接続を含む全接続）
+    pub connections: HashMap<Uuid, UserClients>,
+}
+
+impl WsState {
+    // 接続を全ルームから外し、退出したルームを名前順で返す
+    fn leave_all_rooms(&mut self, user_id: Uuid, connection_id: Uuid) -> Vec<String> {
+        let rooms_to_clean: Vec<String> = self.rooms.keys().cloned().collect();
+
+        let mut cleaned_rooms = Vec::new();
+        for room in rooms_to_clean {
+            if let Some(room_clients) = self.rooms.get_mut(&room) {
+                let Some(user_clients) = room_clients.get_mut(&user_id) else {
+                    continue;
+                };
+                if user_clients.remove(&connection_id).is_some() {
+                    info!("Removed user {} from room {}", user_id, room);
+
+                    if user_clients.is_empty() {
+                        room_clients.remove(&user_id);
+                    }
+
+                    // ルームが空になったら削除
+                    if room_clients.is_empty() {
+                        self.rooms.remove(&room);
+                        info!("Removed empty room: {}", room);
+                    }
+                    cleaned_rooms.push(room);
+                }
+            }
+        }
+        cleaned_rooms.sort();
+        cleaned_rooms
+    }
+
+    // 接続の登録を外す
+    fn remove_connection(&mut self, user_id: Uuid, connection_id: Uuid) {
+        if let Some(user_clients) = self.connections.get_mut(&user_id) {
+            user_clients.remove(&connection_id);
+            if user_clients.is_empty() {
+                self.connections.remove(&user_id);
+            }
+        }
+    }
+}
 
-// ユーザーベースの接続管理を追加
-pub type UserConnections = Arc<RwLock<HashMap<Uuid, usize>>>;
+// 起動以降にルームへブロードキャストしたメッセージ数（`GET /api/ws/stats` 用）
+static MESSAGES_BROADCAST: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Deserialize)]
 pub struct WsQuery {
+    // 後方互換のため残しているが非推奨（URLがログや履歴に残る）
     token: Option<String>,
 }
 
-// WebSocket接続の設定
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
-const MAX_MESSAGE_SIZE: usize = 64 * 1024; // 64KB
-const RATE_LIMIT_MESSAGES: usize = 10; // 10 messages per window
-const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
-const MAX_CONNECTIONS_PER_USER: usize = 5;
-const WEBSOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+// クライアントと合意するサブプロトコル（トークンを含まないのでそのまま返せる）
+pub const WS_SUBPROTOCOL: &str = "miuchi-chat";
+// `Sec-WebSocket-Protocol` でトークンを渡すときの接頭辞（例: "bearer.<JWT>"）
+pub const WS_AUTH_PROTOCOL_PREFIX: &str = "bearer.";
+
+// アップグレード要求からJWTを取り出す
+//
+// 優先順位: Authorization ヘッダー > Sec-WebSocket-Protocol > クエリ文字列（非推奨）
+fn extract_ws_token(headers: &HeaderMap, query_token: Option<String>) -> Option<String> {
+    if let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(token.trim().to_string());
+    }
+
+    if let Some(token) = headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|protocol| protocol.trim().strip_prefix(WS_AUTH_PROTOCOL_PREFIX))
+    {
+        return Some(token.to_string());
+    }
+
+    if query_token.is_some() {
+        warn!("WebSocket token passed via query string is deprecated; use the Sec-WebSocket-Protocol header");
+    }
+    query_token
+}
+
+// WebSocket認証の失敗理由（クライアントはクローズコードと理由で判別する）
+#[derive(Debug, thiserror::Error)]
+enum WsAuthError {
+    #[error("missing token")]
+    MissingToken,
+    #[error("invalid token")]
+    InvalidToken,
+    #[error("user not found")]
+    UserNotFound,
+    #[error("server error")]
+    Internal(#[source] anyhow::Error),
+}
+
+impl WsAuthError {
+    // メトリクスのラベルに使う理由
+    fn metric_reason(&self) -> &'static str {
+        match self {
+            Self::MissingToken => "missing_token",
+            Self::InvalidToken => "invalid_token",
+            Self::UserNotFound => "user_not_found",
+            Self::Internal(_) => "server_error",
+        }
+    }
+
+    // 認証の失敗は 1008 (Policy Violation)、サーバー側の障害は 1011 (Internal Error)
+    fn close_frame(&self) -> CloseFrame {
+        let code = match self {
+            Self::Internal(_) => axum::extract::ws::close_code::ERROR,
+            _ => axum::extract::ws::close_code::POLICY,
+        };
+        CloseFrame {
+            code,
+            reason: self.to_string().into(),
+        }
+    }
+}
+
+// 理由付きのクローズフレームを送ってから接続を閉じる
+fn reject_upgrade(ws: WebSocketUpgrade, err: WsAuthError) -> Response {
+    let frame = err.close_frame();
+    ws.on_upgrade(move |mut socket| async move {
+        let _ = socket.send(Message::Close(Some(frame))).await;
+    })
+}
 
 // WebSocket接続のアップグレード処理
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
     Query(query): Query<WsQuery>,
-    State((pool, app_state, meili_client)): State<(
+    State((pool, app_state, meili_client, config)): State<(
         PgPool,
         AppState,
-        meilisearch_sdk::client::Client,
+        Option<meilisearch_sdk::client::Client>,
+        Arc<Config>,
     )>,
 ) -> Response {
+    // 合意したサブプロトコルを応答ヘッダーで返す（トークン自体は返さない）
+    // 上限を超えるフレームはヘッダーの長さを見た時点で拒否され、本文をメモリに読み込まない
+    let ws = ws
+        .protocols([WS_SUBPROTOCOL])
+        .max_frame_size(config.max_ws_inbound_size())
+        .max_message_size(config.max_ws_inbound_size());
+
     // トークンが必要
-    let token = match query.token {
+    let token = match extract_ws_token(&headers, query.token) {
         Some(token) => token,
         None => {
             warn!("WebSocket connection attempt without token");
-            return ws.on_upgrade(|mut socket| async move {
-                let _ = socket.close().await;
-            });
+            crate::metrics::auth_failed("ws", WsAuthError::MissingToken.metric_reason());
+            return reject_upgrade(ws, WsAuthError::MissingToken);
         }
     };
 
     // 事前認証
-    let user = match verify_jwt_token(&token, &pool).await {
-        Ok(user) => user,
+    let (user, session_id) = match verify_jwt_token(&token, &pool, &config).await {
+        Ok(authenticated) => authenticated,
         Err(e) => {
-            warn!("WebSocket authentication failed: {}", e);
-            return ws.on_upgrade(|mut socket| async move {
-                let _ = socket.close().await;
-            });
+            crate::metrics::auth_failed("ws", e.metric_reason());
+            match &e {
+                WsAuthError::Internal(source) => {
+                    error!("WebSocket authentication failed: {:#}", source)
+                }
+                _ => warn!("WebSocket authentication failed: {}", e),
+            }
+            return reject_upgrade(ws, e);
         }
     };
 
+    // 接続中のログはすべてこのスパンの中で出力され、connection_id で1セッションを追える
+    let connection_id = Uuid::new_v4();
+    let span = info_span!(
+        "ws_connection",
+        %connection_id,
+        user_id = %user.id,
+        username = %user.username,
+    );
+
     info!(
+        parent: &span,
         "WebSocket connection established for user: {} ({})",
-        user.username, user.id
+        user.username,
+        user.id
     );
 
-    ws.on_upgrade(move |socket| websocket_connection(socket, user, pool, app_state, meili_client))
+    ws.on_upgrade(move |socket| {
+        websocket_connection(
+            socket,
+            connection_id,
+            user,
+            session_id,
+            pool,
+            app_state,
+            meili_client,
+            config,
+        )
+        .instrument(span)
+    })
 }
 
 // WebSocket接続の処理
+#[allow(clippy::too_many_arguments)]
 async fn websocket_connection(
     socket: WebSocket,
+    connection_id: Uuid,
     user: User,
+    session_id: Option<Uuid>,
     pool: PgPool,
     app_state: AppState,
-    meili_client: meilisearch_sdk::client::Client,
+    meili_client: Option<meilisearch_sdk::client::Client>,
+    config: Arc<Config>,
 ) {
     let (mut sender, mut receiver) = socket.split();
-    let (tx, mut rx) = broadcast::channel::<WsMessage>(100);
+    // Ping などの制御フレームを送信タスクへ渡すチャネル
+    let (control_tx, mut control_rx) = mpsc::channel::<Message>(8);
+    let (tx, mut rx) = ClientSender::channel(config.ws_broadcast_capacity, control_tx.clone());
+
+    // ブロック中のユーザーを読み込む（失敗しても接続は継続）
+    let blocked_users = User::blocked_user_ids(&pool, user.id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to load blocked users for {}: {}", user.username, e);
+            Vec::new()
+        });
 
     // クライアント情報を初期化
     let client = ConnectedClient {
+        connection_id,
         user_id: user.id,
         username: user.username.clone(),
+        session_id,
         rooms: Vec::new(),
         sender: tx.clone(),
-        connected_at: Instant::now(),
+        control: control_tx.clone(),
+        connected_at: Utc::now(),
         last_activity: Arc::new(RwLock::new(Instant::now())),
         message_count: AtomicU64::new(0),
         rate_limiter: Arc::new(Semaphore::new(RATE_LIMIT_MESSAGES)),
+        blocked_users: Arc::new(RwLock::new(blocked_users.into_iter().collect())),
     };
 
     let user_id = user.id;
@@ -220,8 +447,15 @@ async fn websocket_connection(
     let username_for_handler = username.clone();
     let username_for_cleanup = username.clone();
 
-    // 接続数制限をチェック
-    if let Err(e) = check_connection_limit(user_id, &app_state).await {
+    // 接続数制限を適用して登録する（設定により最も古い接続を切断する）
+    if let Err(e) = register_connection(
+        &client,
+        config.max_connections_per_user,
+        config.connection_limit_policy,
+        &app_state,
+    )
+    .await
+    {
         warn!(
             "Connection limit exceeded for user {}: {}",
             username_for_cleanup, e
@@ -234,48 +468,58 @@ async fn websocket_connection(
             .await;
         return;
     }
+    crate::metrics::connection_opened();
+
+    // クライアントが時計のずれを補正できるよう、最初にサーバー時刻を送る
+    let _ = tx.send(WsMessage::ServerTime {
+        server_time: Utc::now(),
+    });
 
     // ハートビートタスク
-    let heartbeat_tx = tx.clone();
-    let last_activity_heartbeat = client.last_activity.clone();
-    let heartbeat_task = tokio::spawn(async move {
-        let mut interval = interval(HEARTBEAT_INTERVAL);
-        loop {
-            interval.tick().await;
-
-            // 最後のアクティビティをチェック
-            let last_activity = *last_activity_heartbeat.read().await;
-            if last_activity.elapsed() > CLIENT_TIMEOUT {
-                warn!("Client {} timed out", username_for_heartbeat);
-                let _ = heartbeat_tx.send(WsMessage::Error {
-                    message: "Connection timed out".to_string(),
-                    code: Some(1001),
-                });
-                break;
-            }
+    let heartbeat = run_heartbeat(
+        username_for_heartbeat,
+        control_tx,
+        tx.clone(),
+        client.last_activity.clone(),
+        config.heartbeat_interval,
+        config.client_timeout,
+    );
+    let heartbeat_task = tokio::spawn(heartbeat.in_current_span());
 
-            // Pingを送信
-            if heartbeat_tx
-                .send(WsMessage::Ping {
-                    timestamp: Some(chrono::Utc::now().timestamp_millis() as u64),
-                })
-                .is_err()
-            {
-                break;
-            }
-        }
-    });
+    // レート制限の補充タスク（ルームに参加していない接続も対象にするため接続ごとに持つ）
+    let rate_limit_task = tokio::spawn(refill_rate_limit(client.rate_limiter.clone()));
 
     // メッセージ送信タスク
-    let send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+    let send_timeout = config.ws_send_timeout;
+    let max_outbound_size = config.max_ws_outbound_size();
+    let send_loop = async move {
+        loop {
+            let msg = tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                Some(frame) = control_rx.recv() => {
+                    // 制御フレームはそのまま送信し、クローズフレームなら送信を終える
+                    let is_close = matches!(frame, Message::Close(_));
+                    match timeout(send_timeout, sender.send(frame)).await {
+                        Ok(Ok(_)) if is_close => break,
+                        Ok(Ok(_)) => continue,
+                        Ok(Err(_)) | Err(_) => {
+                            debug!("WebSocket control frame send failed or timed out");
+                            break;
+                        }
+                    }
+                }
+            };
+
             let json_str = match serde_json::to_string(&msg) {
                 Ok(json) => json,
                 Err(e) => {
                     error!("Failed to serialize WebSocket message: {}", e);
                     match serde_json::to_string(&WsMessage::Error {
                         message: "Internal serialization error".to_string(),
-                        code: Some(1011),
+                        code: Some(WsErrorCode::Internal),
                     }) {
                         Ok(error_json) => error_json,
                         Err(_) => break, // If we can't even serialize an error, abort
@@ -283,19 +527,22 @@ async fn websocket_connection(
                 }
             };
 
-            // メッセージサイズチェック
-            if json_str.len() > MAX_MESSAGE_SIZE {
+            // メッセージサイズチェック（本文の検証で通常は起きないが、破棄したことはクライアントに伝える）
+            let json_str = if json_str.len() > max_outbound_size {
                 warn!("Message too large, dropping: {} bytes", json_str.len());
-                continue;
-            }
+                match serde_json::to_string(&WsMessage::Error {
+                    message: "Message too large to deliver".to_string(),
+                    code: Some(WsErrorCode::MessageTooLarge),
+                }) {
+                    Ok(error_json) => error_json,
+                    Err(_) => continue,
+                }
+            } else {
+                json_str
+            };
 
             // タイムアウト付きで送信
-            match timeout(
-                WEBSOCKET_TIMEOUT,
-                sender.send(Message::Text(json_str.into())),
-            )
-            .await
-            {
+            match timeout(send_timeout, sender.send(Message::Text(json_str.into()))).await {
                 Ok(Ok(_)) => {}
                 Ok(Err(_)) | Err(_) => {
                     debug!("WebSocket send failed or timed out");
@@ -303,18 +550,46 @@ async fn websocket_connection(
                 }
             }
         }
-    });
+    };
+    let mut send_task = tokio::spawn(send_loop.in_current_span());
 
     // メッセージ受信と処理
     let client_for_handler = client.clone();
-    while let Some(msg) = receiver.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            // 送信タスクが終了した（クローズフレームの送信や書き込みのタイムアウト）
+            _ = &mut send_task => {
+                debug!("WebSocket send task finished for user {}", username_for_handler);
+                break;
+            }
+        };
+
         // 最後のアクティビティを更新
         *client.last_activity.write().await = Instant::now();
 
+        // テキスト・バイナリフレームは内容を解釈する前にレート制限に数える（JSON の `ping` も含む）
+        if matches!(msg, Ok(Message::Text(_) | Message::Binary(_))) {
+            // 許可は返さずに消費し、補充タスクがウィンドウごとに戻す
+            if let Ok(permit) = client.rate_limiter.try_acquire() {
+                permit.forget();
+            } else {
+                warn!("Rate limit exceeded for user {}", username);
+                let _ = tx.send(WsMessage::RateLimited {
+                    retry_after: RATE_LIMIT_WINDOW.as_secs(),
+                });
+                continue;
+            }
+            client.message_count.fetch_add(1, Ordering::Relaxed);
+        }
+
         match msg {
             Ok(Message::Text(text)) => {
-                // メッセージサイズチェック
-                if text.len() > MAX_MESSAGE_SIZE {
+                // メッセージサイズチェック（プロトコル層でも拒否されるが念のため）
+                if text.len() > config.max_ws_inbound_size() {
                     warn!(
                         "Received message too large from {}: {} bytes",
                         username_for_handler,
@@ -322,23 +597,11 @@ async fn websocket_connection(
                     );
                     let _ = tx.send(WsMessage::Error {
                         message: "Message too large".to_string(),
-                        code: Some(1009),
-                    });
-                    continue;
-                }
-
-                // レート制限チェック
-                if client.rate_limiter.try_acquire().is_err() {
-                    warn!("Rate limit exceeded for user {}", username);
-                    let _ = tx.send(WsMessage::RateLimited {
-                        retry_after: RATE_LIMIT_WINDOW.as_secs(),
+                        code: Some(WsErrorCode::MessageTooLarge),
                     });
                     continue;
                 }
 
-                // メッセージカウント更新
-                client.message_count.fetch_add(1, Ordering::Relaxed);
-
                 match serde_json::from_str::<WsMessage>(&text) {
                     Ok(ws_msg) => {
                         match handle_websocket_message(
@@ -348,7 +611,8 @@ async fn websocket_connection(
                             &pool,
                             &app_state,
                             &tx,
-                            &meili_client,
+                            meili_client.as_ref(),
+                            &config,
                         )
                         .await
                         {
@@ -365,7 +629,7 @@ async fn websocket_connection(
                                 );
                                 let _ = tx.send(WsMessage::Error {
                                     message: err.to_string(),
-                                    code: Some(1002),
+                                    code: Some(WsErrorCode::RequestFailed),
                                 });
                             }
                         }
@@ -374,7 +638,7 @@ async fn websocket_connection(
                         warn!("Invalid JSON from user {}: {}", username_for_handler, e);
                         let _ = tx.send(WsMessage::Error {
                             message: "Invalid JSON format".to_string(),
-                            code: Some(1003),
+                            code: Some(WsErrorCode::InvalidJson),
                         });
                     }
                 }
@@ -386,7 +650,7 @@ async fn websocket_connection(
                 );
                 let _ = tx.send(WsMessage::Error {
                     message: "Binary messages not supported".to_string(),
-                    code: Some(1003),
+                    code: Some(WsErrorCode::UnsupportedFrame),
                 });
             }
             Ok(Message::Close(frame)) => {
@@ -397,16 +661,32 @@ async fn websocket_connection(
                 break;
             }
             Ok(Message::Pong(_)) => {
+                // ハートビートのPingへの応答。受信時点で last_activity は更新済み
                 debug!("Received pong from client {}", username_for_handler);
-                // クライアントが生きていることを確認
             }
             Ok(Message::Ping(_data)) => {
-                debug!(
-                    "Received ping from client {}, sending pong",
-                    username_for_handler
+                // 制御フレームの Ping には axum が Pong フレームで自動応答する
+                // （JSON の `pong` は `ping` メッセージへの応答だけに使う）
+                debug!("Received ping frame from client {}", username_for_handler);
+            }
+            Err(e) if is_message_too_large(&e) => {
+                warn!(
+                    "Received frame too large from {}: {}",
+                    username_for_handler, e
                 );
-                // Pongを送信
-                let _ = tx.send(WsMessage::Pong { timestamp: None });
+                // 送信タスクにクローズフレームを送らせ、送り終えるまで待つ
+                let _ = client
+                    .control
+                    .send(Message::Close(Some(CloseFrame {
+                        code: axum::extract::ws::close_code::SIZE,
+                        reason: "Message too large".into(),
+                    })))
+                    .await;
+                let _ = timeout(config.ws_send_timeout, &mut send_task).await;
+                // 読み残したフレームの本文があるまま閉じると RST になり、相手が未読の
+                // クローズフレームも破棄されるため、少し待ってから切断する
+                tokio::time::sleep(WS_CLOSE_LINGER).await;
+                break;
             }
             Err(e) => {
                 warn!("WebSocket error for user {}: {}", username_for_handler, e);
@@ -420,11 +700,14 @@ async fn websocket_connection(
         "Cleaning up WebSocket connection for user: {} ({})",
         username, user_id
     );
-    cleanup_user_connections(user_id, &app_state).await;
+    cleanup_connection(user_id, connection_id, &app_state).await;
+    unregister_connection(user_id, connection_id, &app_state).await;
+    crate::metrics::connection_closed();
 
     // タスクを停止
     send_task.abort();
     heartbeat_task.abort();
+    rate_limit_task.abort();
 
     info!(
         "WebSocket connection closed for user: {} ({})",
@@ -432,34 +715,85 @@ async fn websocket_connection(
     );
 }
 
+// `Config::max_ws_inbound_size` を超えるフレーム・メッセージを受信したことによるエラーか
+fn is_message_too_large(error: &axum::Error) -> bool {
+    std::error::Error::source(error)
+        .and_then(|source| source.downcast_ref::<tokio_tungstenite::tungstenite::Error>())
+        .is_some_and(|error| matches!(error, tokio_tungstenite::tungstenite::Error::Capacity(_)))
+}
+
+// ウィンドウごとにレート制限の許可を上限まで補充する
+async fn refill_rate_limit(rate_limiter: Arc<Semaphore>) {
+    let mut interval = interval(RATE_LIMIT_WINDOW);
+    loop {
+        interval.tick().await;
+        rate_limiter
+            .add_permits(RATE_LIMIT_MESSAGES.saturating_sub(rate_limiter.available_permits()));
+    }
+}
+
+// ハートビート: 定期的にPing制御フレームを送り、応答が途絶えたらタイムアウトさせる
+//
+// ブラウザはPingに自動でPongを返し、受信ループがPongを含む全フレームで last_activity を
+// 更新するため、メッセージを送らないクライアントでも応答している限り切断されない。
+async fn run_heartbeat(
+    username: String,
+    control_tx: mpsc::Sender<Message>,
+    message_tx: ClientSender,
+    last_activity: Arc<RwLock<Instant>>,
+    heartbeat_interval: Duration,
+    client_timeout: Duration,
+) {
+    let mut interval = interval(heartbeat_interval);
+    loop {
+        interval.tick().await;
+
+        // 最後のアクティビティをチェック
+        let last_activity = *last_activity.read().await;
+        if last_activity.elapsed() > client_timeout {
+            warn!("Client {} timed out", username);
+            let _ = message_tx.send(WsMessage::Error {
+                message: "Connection timed out".to_string(),
+                code: Some(WsErrorCode::Timeout),
+            });
+            break;
+        }
+
+        // Ping制御フレームを送信
+        let payload = chrono::Utc::now().timestamp_millis().to_be_bytes().to_vec();
+        if control_tx
+            .send(Message::Ping(payload.into()))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
 // WebSocketメッセージの処理
+#[allow(clippy::too_many_arguments)]
 async fn handle_websocket_message(
     msg: WsMessage,
     user: &User,
     client: &ConnectedClient,
     pool: &PgPool,
     app_state: &AppState,
-    sender: &broadcast::Sender<WsMessage>,
-    meili_client: &meilisearch_sdk::client::Client,
+    sender: &ClientSender,
+    meili_client: Option<&meilisearch_sdk::client::Client>,
+    config: &Arc<Config>,
 ) -> anyhow::Result<()> {
     match msg {
         WsMessage::JoinRoom { room } => {
             info!("User {} attempting to join room: {}", user.username, room);
 
-            // ルーム名のバリデーション
-            if room.is_empty() || room.len() > 100 {
-                return Err(anyhow::anyhow!("Invalid room name"));
-            }
+            // ルーム名のバリデーション（名前の場合は正規化したものを使う）
+            let room = normalize_room_key(&room)?;
 
             // ルームが存在するかチェック（IDまたは名前で検索）
-            let room_obj = if let Ok(room_uuid) = room.parse::<Uuid>() {
-                // UUIDの場合はIDで検索
-                Room::find_by_id(pool, room_uuid).await?
-            } else {
-                // UUIDでない場合は名前で検索
-                Room::find_by_name(pool, &room).await?
-            }
-            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+            let room_obj = find_room_by_key(&room, pool)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
 
             // パブリックルームでない場合はメンバーシップをチェック
             if !room_obj.is_public && !room_obj.is_member(pool, user.id).await? {
@@ -467,19 +801,32 @@ async fn handle_websocket_message(
                     "User {} attempted to join private room {} without permission",
                     user.username, room
                 );
-                return Err(anyhow::anyhow!("You are not a member of this private room"));
+                return Err(private_room_error(config));
             }
 
+            // ID で指定された場合も REST からの配信と同じルーム名で参加させる
+            let room = room_obj.name.clone();
+
             // アプリケーション状態にクライアントを追加
-            add_client_to_room(&room, user.clone(), client.clone(), app_state).await;
+            refresh_blocked_users(client, pool).await?;
+            let first_connection = !user_in_room(&room, user.id, app_state).await;
+            let newly_joined =
+                add_client_to_room(&room, user.clone(), client.clone(), app_state).await;
 
-            // 参加通知を送信
+            // 参加通知を送信（既読位置を含め、クライアントが追加の問い合わせなしに未読を表示できるようにする）
+            let last_read_message_id = room_obj.last_read_message_id(pool, user.id).await?;
             sender.send(WsMessage::RoomJoined {
                 room: room.clone(),
                 user_id: user.id.to_string(),
                 username: user.username.clone(),
+                last_read_message_id: last_read_message_id.map(|id| id.to_string()),
             })?;
 
+            // 再接続の競合などで参加済みのルームに再度 join した場合は、応答だけ返して参加通知はしない
+            if !newly_joined {
+                return Ok(());
+            }
+
             // 他のクライアントに参加を通知
             broadcast_to_room(
                 &room,
@@ -488,123 +835,283 @@ async fn handle_websocket_message(
                     user_id: user.id.to_string(),
                     username: user.username.clone(),
                 },
+                None,
                 Some(user.id),
                 app_state,
             )
             .await;
+            // 同じユーザーの他のタブが参加済みなら履歴には残さない
+            if first_connection {
+                log_membership_event(
+                    &room_obj,
+                    format!("{}がルームに参加しました", user.username),
+                    pool,
+                    app_state,
+                    meili_client,
+                )
+                .await;
+            }
 
             info!("User {} successfully joined room: {}", user.username, room);
         }
 
+        WsMessage::JoinAll => {
+            // アクセス可能な全ルーム（パブリック・メンバー）に上限付きで参加
+            let rooms = Room::get_accessible_rooms(pool, user.id, MAX_JOIN_ALL_ROOMS).await?;
+            let mut joined = Vec::with_capacity(rooms.len());
+            refresh_blocked_users(client, pool).await?;
+
+            for room_obj in rooms {
+                let first_connection = !user_in_room(&room_obj.name, user.id, app_state).await;
+                if add_client_to_room(&room_obj.name, user.clone(), client.clone(), app_state).await
+                {
+                    broadcast_to_room(
+                        &room_obj.name,
+                        WsMessage::UserJoined {
+                            room: room_obj.name.clone(),
+                            user_id: user.id.to_string(),
+                            username: user.username.clone(),
+                        },
+                        None,
+                        Some(user.id),
+                        app_state,
+                    )
+                    .await;
+                    if first_connection {
+                        log_membership_event(
+                            &room_obj,
+                            format!("{}がルームに参加しました", user.username),
+                            pool,
+                            app_state,
+                            meili_client,
+                        )
+                        .await;
+                    }
+                }
+
+                joined.push(room_obj.name);
+            }
+
+            info!("User {} joined {} rooms", user.username, joined.len());
+            sender.send(WsMessage::JoinedRooms { rooms: joined })?;
+        }
+
         WsMessage::SendMessage {
             room,
             content,
             message_type,
+            client_msg_id,
+            reply_to_message_id,
         } => {
-            // メッセージコンテンツのバリデーション
-            if content.is_empty() {
-                return Err(anyhow::anyhow!("Message content cannot be empty"));
-            }
-            if content.len() > 4000 {
-                return Err(anyhow::anyhow!("Message content too long"));
-            }
-
-            // ルームが存在するかチェック（IDまたは名前で検索）
-            let room_obj = if let Ok(room_uuid) = room.parse::<Uuid>() {
-                // UUIDの場合はIDで検索
-                Room::find_by_id(pool, room_uuid).await?
-            } else {
-                // UUIDでない場合は名前で検索
-                Room::find_by_name(pool, &room).await?
-            }
-            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
-
-            // パブリックルームでない場合はメンバーシップをチェック
-            if !room_obj.is_public && !room_obj.is_member(pool, user.id).await? {
-                return Err(anyhow::anyhow!("You are not a member of this private room"));
-            }
-
-            // メッセージタイプを変換
-            let db_message_type = match message_type.as_deref() {
-                Some("image") => DbMessageType::Image,
-                Some("file") => DbMessageType::File,
-                Some("system") => DbMessageType::System,
-                _ => DbMessageType::Text,
+            // 未知のメッセージタイプは text に読み替えず拒否する（省略時は text）
+            let message_type = match message_type
+                .as_deref()
+                .map(str::parse::<DbMessageType>)
+                .transpose()
+            {
+                Ok(message_type) => message_type.unwrap_or(DbMessageType::Text),
+                Err(err) => {
+                    warn!("Message from {} rejected: {}", user.username, err);
+                    if let Some(client_msg_id) = client_msg_id {
+                        sender.send(WsMessage::MessageNack {
+                            client_msg_id,
+                            reason: err.to_string(),
+                        })?;
+                    }
+                    sender.send(WsMessage::Error {
+                        message: err.to_string(),
+                        code: Some(WsErrorCode::InvalidMessage),
+                    })?;
+                    return Ok(());
+                }
             };
 
-            // メッセージをDBに保存
-            let message = DbMessage::create(
+            let result = send_room_message(
+                &room,
+                OutgoingMessage {
+                    content,
+                    message_type,
+                    reply_to_message_id,
+                },
+                user,
                 pool,
-                room_obj.id,
-                user.id,
-                content.clone(),
-                db_message_type.clone(),
+                app_state,
+                meili_client,
+                config,
             )
-            .await?;
+            .await;
 
-            // Meilisearchにインデックス追加
-            let index = meili_client.index("messages");
-            let search_document = serde_json::json!({
-                "id": message.id.to_string(),
-                "room_id": room_obj.id.to_string(),
-                "room_name": room_obj.name,
-                "author_id": user.id.to_string(),
-                "author_name": user.username,
-                "content": content,
-                "created_at": message.created_at.timestamp(),
-                "message_type": match db_message_type {
-                    DbMessageType::Text => "text",
-                    DbMessageType::Image => "image",
-                    DbMessageType::File => "file",
-                    DbMessageType::System => "system",
-                }
-            });
+            // スローモードの間隔内なら、送信できるまでの秒数を知らせる
+            let slow_mode = result
+                .as_ref()
+                .err()
+                .and_then(|err| err.downcast_ref::<SlowModeActive>())
+                .map(|slow_mode| slow_mode.retry_after);
+            if let Some(retry_after) = slow_mode {
+                sender.send(WsMessage::RateLimited { retry_after })?;
+            }
 
-            if let Err(e) = index.add_documents(&[search_document], Some("id")).await {
-                tracing::error!("Failed to index message in Meilisearch: {}", e);
-                // エラーをログに記録するが、メッセージ送信自体は成功とする
+            // client_msg_id がある場合のみ送信者に ACK/NACK を返す
+            match (result, client_msg_id) {
+                (Ok(message), Some(client_msg_id)) => {
+                    sender.send(WsMessage::MessageAck {
+                        client_msg_id,
+                        server_id: message.id.to_string(),
+                        timestamp: message.created_at,
+                    })?;
+                }
+                (Ok(_), None) => {}
+                (Err(err), Some(client_msg_id)) => {
+                    warn!(
+                        "Message from {} rejected ({}): {}",
+                        user.username, client_msg_id, err
+                    );
+                    sender.send(WsMessage::MessageNack {
+                        client_msg_id,
+                        reason: err.to_string(),
+                    })?;
+                }
+                (Err(_), None) if slow_mode.is_some() => {}
+                (Err(err), None) => return Err(err),
             }
+        }
 
-            // 全クライアントにブロードキャスト
-            let ws_message = WsMessage::Message {
-                id: message.id.to_string(),
-                room: room.clone(),
-                user_id: user.id.to_string(),
-                username: user.username.clone(),
-                content,
-                message_type: match db_message_type {
-                    DbMessageType::Text => "text".to_string(),
-                    DbMessageType::Image => "image".to_string(),
-                    DbMessageType::File => "file".to_string(),
-                    DbMessageType::System => "system".to_string(),
-                },
-                timestamp: message.created_at,
-            };
+        WsMessage::Sync {
+            room,
+            since_message_id,
+        } => {
+            let room_obj = find_accessible_room(&room, user, pool, config).await?;
+
+            // 基準メッセージはこのルームのものに限る
+            let since_id = since_message_id
+                .parse::<Uuid>()
+                .map_err(|_| anyhow::anyhow!("Invalid message ID"))?;
+            let since = DbMessage::find_by_id(pool, since_id)
+                .await?
+                .filter(|message| message.room_id == room_obj.id)
+                .ok_or_else(|| anyhow::anyhow!("Message not found in room"))?;
+
+            // 上限+1件取得して続きがあるかを判定
+            let mut messages =
+                DbMessage::find_after_in_room(pool, room_obj.id, &since, MAX_SYNC_MESSAGES + 1)
+                    .await?;
+            let has_more = messages.len() as i64 > MAX_SYNC_MESSAGES;
+            messages.truncate(MAX_SYNC_MESSAGES as usize);
+
+            debug!(
+                "Replaying {} messages in room {} to {}",
+                messages.len(),
+                room,
+                user.username
+            );
+
+            sender.send(WsMessage::History {
+                room,
+                messages: messages
+                    .into_iter()
+                    .map(|message| {
+                        let quoted = message.quote().map(QuotedMessage::from);
+                        let rendered_html = crate::markdown::rendered_html(
+                            config,
+                            &message.message_type,
+                            &message.content,
+                        );
+                        HistoryMessage {
+                            id: message.id.to_string(),
+                            user_id: message.user_id.to_string(),
+                            username: message.username,
+                            avatar_url: message.avatar_url,
+                            is_bot: message.is_bot,
+                            content: message.content,
+                            rendered_html,
+                            message_type: message.message_type.to_string(),
+                            seq: message.seq,
+                            timestamp: message.created_at,
+                            quoted,
+                        }
+                    })
+                    .collect(),
+                has_more,
+            })?;
+        }
 
-            broadcast_to_room(&room, ws_message, None, app_state).await;
-            debug!("Message sent by {} in room {}", user.username, room);
+        WsMessage::MarkRead { room, message_id } => {
+            let room_obj = find_accessible_room(&room, user, pool, config).await?;
+            let message_id = message_id
+                .parse::<Uuid>()
+                .map_err(|_| anyhow::anyhow!("Invalid message ID"))?;
+
+            // 既読位置が進んだときだけ通知する（メンバーでない場合や古いメッセージでは何もしない）
+            // ID で指定された場合もあるため、参加時と同じルーム名で配信する
+            if room_obj.mark_read(pool, user.id, message_id).await? {
+                broadcast_to_room(
+                    &room_obj.name,
+                    WsMessage::SeenUpdate {
+                        room: room_obj.name.clone(),
+                        message_id: message_id.to_string(),
+                        user_id: user.id.to_string(),
+                    },
+                    None,
+                    None,
+                    app_state,
+                )
+                .await;
+            }
         }
 
         WsMessage::LeaveRoom { room } => {
             info!("User {} leaving room: {}", user.username, room);
-            remove_client_from_room(&room, user.id, app_state).await;
+            let room = room_state_key(&room, pool).await?;
+            remove_client_from_room(&room, user.id, client.connection_id, app_state).await;
+
+            // 他のクライアントに退出を通知（同じユーザーの他のタブが残っている間は通知しない）
+            if !user_in_room(&room, user.id, app_state).await {
+                broadcast_to_room(
+                    &room,
+                    WsMessage::UserLeft {
+                        room: room.clone(),
+                        user_id: user.id.to_string(),
+                        username: user.username.clone(),
+                    },
+                    None,
+                    Some(user.id),
+                    app_state,
+                )
+                .await;
+                log_leave_event(&room, user, pool, app_state, meili_client).await;
+            }
+        }
 
-            // 他のクライアントに退出を通知
-            broadcast_to_room(
-                &room,
-                WsMessage::UserLeft {
-                    room: room.clone(),
-                    user_id: user.id.to_string(),
-                    username: user.username.clone(),
-                },
-                Some(user.id),
-                app_state,
-            )
-            .await;
+        WsMessage::LeaveAll => {
+            // 同じユーザーの他のタブは退出させず、この接続だけを全ルームから外す
+            let rooms = cleanup_connection(user.id, client.connection_id, app_state).await;
+            info!("User {} left all rooms: {:?}", user.username, rooms);
+
+            for room in &rooms {
+                // 他のタブがまだ参加しているルームでは退出を通知しない
+                if user_in_room(room, user.id, app_state).await {
+                    continue;
+                }
+                broadcast_to_room(
+                    room,
+                    WsMessage::UserLeft {
+                        room: room.clone(),
+                        user_id: user.id.to_string(),
+                        username: user.username.clone(),
+                    },
+                    None,
+                    Some(user.id),
+                    app_state,
+                )
+                .await;
+                log_leave_event(room, user, pool, app_state, meili_client).await;
+            }
+
+            sender.send(WsMessage::LeftAll { rooms })?;
         }
 
         WsMessage::Ping { timestamp } => {
-            // Pongで応答
+            // クライアントの時刻をそのまま返す（クライアントは受信時刻との差を往復遅延とする）
             sender.send(WsMessage::Pong { timestamp })?;
         }
 
@@ -681,201 +1188,899 @@ async fn handle_websocket_message(
     Ok(())
 }
 
-// JWT トークンを検証してユーザー情報を取得
-async fn verify_jwt_token(token: &str, pool: &PgPool) -> anyhow::Result<User> {
-    use crate::api::auth::Claims;
-    use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
-
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "development_secret_key_change_in_production".to_string());
-
-    let mut validation = Validation::new(Algorithm::HS256);
-    validation.set_audience(&["miuchi.chat"]);
-
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &validation,
-    )?;
-
-    let user_id = token_data.claims.sub.parse::<Uuid>()?;
-    let user = User::find_by_id(pool, user_id)
-        .await?
-        .ok_or_else(|| anyhow::anyhow!("User not found"))?;
-
-    Ok(user)
+// クライアントが指定したルームを検証する（UUIDはそのまま、名前は正規化する）
+fn normalize_room_key(room: &str) -> anyhow::Result<String> {
+    if room.parse::<Uuid>().is_ok() {
+        return Ok(room.to_string());
+    }
+    validate_room_name(room).map_err(|e| anyhow::anyhow!(e))
 }
 
-// 接続数制限をチェック（最適化版）
-async fn check_connection_limit(user_id: Uuid, app_state: &AppState) -> anyhow::Result<()> {
-    let state = app_state.read().await;
-    let mut connection_count = 0;
-
-    // より効率的な検索: 全ルームを走査してユーザーの存在をチェック
-    // 将来的にはユーザー別インデックスを実装予定
-    for room_clients in state.values() {
-        if room_clients.contains_key(&user_id) {
-            connection_count += 1;
-            // 早期終了: 制限を超えた時点で処理を停止
-            if connection_count >= MAX_CONNECTIONS_PER_USER {
-                return Err(anyhow::anyhow!("Maximum connections exceeded"));
-            }
+// 参加中のルームはルーム名で管理するため、ID で指定された場合は名前に読み替える
+async fn room_state_key(room: &str, pool: &PgPool) -> anyhow::Result<String> {
+    if let Ok(room_id) = room.parse::<Uuid>() {
+        if let Some(room_obj) = Room::find_by_id(pool, room_id).await? {
+            return Ok(room_obj.name);
         }
     }
+    Ok(room.to_string())
+}
 
-    Ok(())
+// ルームをIDまたは名前で検索
+async fn find_room_by_key(room: &str, pool: &PgPool) -> anyhow::Result<Option<Room>> {
+    match room.parse::<Uuid>() {
+        // UUIDの場合はIDで検索
+        Ok(room_uuid) => Room::find_by_id(pool, room_uuid).await,
+        // UUIDでない場合は名前で検索
+        Err(_) => Room::find_by_name(pool, room).await,
+    }
 }
 
-// レート制限のリセットタスク
-static RATE_LIMIT_RESET_TASK: std::sync::Once = std::sync::Once::new();
+// ルームをIDまたは名前で検索し、ユーザーがアクセスできることを確認
+async fn find_accessible_room(
+    room: &str,
+    user: &User,
+    pool: &PgPool,
+    config: &Config,
+) -> anyhow::Result<Room> {
+    let room_obj = find_room_by_key(room, pool)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
 
-pub fn start_rate_limit_reset_task(app_state: AppState) {
-    RATE_LIMIT_RESET_TASK.call_once(|| {
-        tokio::spawn(async move {
-            let mut interval = interval(RATE_LIMIT_WINDOW);
-            loop {
-                interval.tick().await;
+    // パブリックルームでない場合はメンバーシップをチェック
+    if !room_obj.is_public && !room_obj.is_member(pool, user.id).await? {
+        return Err(private_room_error(config));
+    }
 
-                let state = app_state.read().await;
-                for room_clients in state.values() {
-                    for client in room_clients.values() {
-                        // レート制限をリセット
-                        while client.rate_limiter.available_permits() < RATE_LIMIT_MESSAGES {
-                            client.rate_limiter.add_permits(1);
-                        }
-                    }
-                }
-            }
-        });
-    });
+    Ok(room_obj)
 }
 
-// クライアントをルームに追加
-async fn add_client_to_room(room: &str, user: User, client: ConnectedClient, app_state: &AppState) {
-    let mut state = app_state.write().await;
-    let room_clients = state.entry(room.to_string()).or_insert_with(HashMap::new);
-
-    let mut updated_client = client;
-    updated_client.rooms.push(room.to_string());
-    let room_count = updated_client.rooms.len();
+// プライベートルームのメンバー以外に返すエラー
+//
+// `hide_private_rooms` のときはルームの存在を知られないよう、存在しないルームと同じエラーにする。
+fn private_room_error(config: &Config) -> anyhow::Error {
+    if config.hide_private_rooms {
+        anyhow::anyhow!("Room not found")
+    } else {
+        anyhow::anyhow!("You are not a member of this private room")
+    }
+}
 
-    room_clients.insert(user.id, updated_client);
+// クライアントから送信された保存前のメッセージ
+struct OutgoingMessage {
+    content: String,
+    message_type: DbMessageType,
+    reply_to_message_id: Option<String>,
+}
+
+// メッセージを保存してルームにブロードキャスト
+async fn send_room_message(
+    room: &str,
+    outgoing: OutgoingMessage,
+    user: &User,
+    pool: &PgPool,
+    app_state: &AppState,
+    meili_client: Option<&meilisearch_sdk::client::Client>,
+    config: &Arc<Config>,
+) -> anyhow::Result<DbMessage> {
+    let room = &normalize_room_key(room)?;
+
+    // スラッシュコマンドを解釈（未知のコマンドは保存せずエラーにする）
+    let CommandOutput {
+        content,
+        message_type: db_message_type,
+    } = commands::process_message(
+        &outgoing.content,
+        outgoing.message_type,
+        &CommandContext {
+            username: &user.username,
+        },
+    )?;
+
+    // メッセージコンテンツの整形とバリデーション
+    let content = sanitize_content(&content);
+    let content = config
+        .moderator
+        .moderate(&content)
+        .map_err(|e| anyhow::anyhow!(e.user_message()))?;
+
+    let room_obj = find_accessible_room(room, user, pool, config).await?;
+    // ID で指定された場合も参加時と同じルーム名で配信する
+    let room = room_obj.name.as_str();
+
+    // 文字数の上限はルームの設定による
+    validate_message_content(
+        &content,
+        room_obj.message_length_limit(config.max_room_message_length),
+    )
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    // 引用できるのは同じルームのメッセージのみ
+    let quoted = match outgoing.reply_to_message_id.as_deref() {
+        Some(reply_to_message_id) => {
+            let reply_to_message_id = reply_to_message_id
+                .parse::<Uuid>()
+                .map_err(|_| anyhow::anyhow!("Invalid quoted message ID"))?;
+            let quoted = DbMessage::find_with_user_in_room(pool, room_obj.id, reply_to_message_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Quoted message not found in room"))?;
+            Some(QuotePreview::of(&quoted))
+        }
+        None => None,
+    };
+
+    // 保存に失敗した場合は予約が破棄され、間隔に数えない
+    let slow_mode_reservation = crate::slow_mode::reserve(pool, &room_obj, user.id).await?;
+
+    // メッセージをDBに保存
+    let message = DbMessage::create(
+        pool,
+        NewMessage {
+            room_id: room_obj.id,
+            user_id: user.id,
+            content: content.clone(),
+            message_type: db_message_type.clone(),
+            reply_to_message_id: quoted.as_ref().map(|quote| quote.id),
+        },
+    )
+    .await?;
+    slow_mode_reservation.confirm();
+    crate::metrics::message_sent("ws");
+
+    index_message(
+        meili_client,
+        &message,
+        &room_obj,
+        user.id,
+        &user.username,
+        false,
+    )
+    .await;
+
+    // 全クライアントにブロードキャスト
+    let ws_message = message_event(&message, room, user, false, quoted, config);
+    broadcast_to_room(room, ws_message, Some(user.id), None, app_state).await;
+    debug!("Message sent by {} in room {}", user.username, room);
+
+    // URL を含む場合はリンクプレビューをバックグラウンドで取得
+    link_preview::spawn_link_previews(
+        pool.clone(),
+        app_state.clone(),
+        config.clone(),
+        room.to_string(),
+        message.id,
+        user.id,
+        &content,
+    );
+
+    // 送信 Webhook へ転送（配信はバックグラウンドのワーカーが行う）
+    webhooks::message_created(&room_obj, &message, user, &content);
+
+    // メンションされたユーザーに通知
+    mentions::spawn_mention_notifications(
+        pool.clone(),
+        app_state.clone(),
+        room_obj,
+        &message,
+        user,
+        &content,
+    );
+
+    Ok(message)
+}
+
+/// ルームの `log_membership_events` が有効なら、参加・退出・招待などをシステムメッセージとして履歴に残す
+///
+/// 記録に失敗しても元の操作は成功とする。
+pub async fn log_membership_event(
+    room: &Room,
+    content: String,
+    pool: &PgPool,
+    app_state: &AppState,
+    meili_client: Option<&meilisearch_sdk::client::Client>,
+) {
+    if !room.log_membership_events {
+        return;
+    }
+    if let Err(e) = post_system_message(room, content, pool, app_state, meili_client).await {
+        tracing::error!("Failed to record membership event: {}", e);
+    }
+}
+
+// ユーザーの最後の接続がルームから退出したことを履歴に残す
+async fn log_leave_event(
+    room: &str,
+    user: &User,
+    pool: &PgPool,
+    app_state: &AppState,
+    meili_client: Option<&meilisearch_sdk::client::Client>,
+) {
+    match find_room_by_key(room, pool).await {
+        Ok(Some(room_obj)) => {
+            log_membership_event(
+                &room_obj,
+                format!("{}がルームから退出しました", user.username),
+                pool,
+                app_state,
+                meili_client,
+            )
+            .await
+        }
+        Ok(None) => {}
+        Err(e) => tracing::error!("Failed to record membership event: {}", e),
+    }
+}
+
+/// システムメッセージ（メンバーの参加通知など）を保存してルームにブロードキャストする
+///
+/// 投稿者はシステムユーザーで、検索には `message_type: system` としてインデックスする。
+pub async fn post_system_message(
+    room: &Room,
+    content: String,
+    pool: &PgPool,
+    app_state: &AppState,
+    meili_client: Option<&meilisearch_sdk::client::Client>,
+) -> anyhow::Result<DbMessage> {
+    let message = DbMessage::create(
+        pool,
+        NewMessage {
+            room_id: room.id,
+            user_id: SYSTEM_USER_ID,
+            content: content.clone(),
+            message_type: DbMessageType::System,
+            reply_to_message_id: None,
+        },
+    )
+    .await?;
+
+    index_message(
+        meili_client,
+        &message,
+        room,
+        SYSTEM_USER_ID,
+        SYSTEM_USERNAME,
+        true,
+    )
+    .await;
+
+    broadcast_to_room(
+        &room.name,
+        WsMessage::Message {
+            id: message.id.to_string(),
+            room: room.name.clone(),
+            user_id: SYSTEM_USER_ID.to_string(),
+            username: SYSTEM_USERNAME.to_string(),
+            avatar_url: None,
+            is_bot: true,
+            content,
+            rendered_html: None,
+            message_type: DbMessageType::System.to_string(),
+            seq: message.seq,
+            timestamp: message.created_at,
+            quoted: None,
+        },
+        None,
+        None,
+        app_state,
+    )
+    .await;
+
+    Ok(message)
+}
+
+/// メッセージを検索インデックスに登録する（検索が無効なら何もしない）
+///
+/// 登録に失敗してもメッセージの送信自体は成功とする。
+pub async fn index_message(
+    meili_client: Option<&meilisearch_sdk::client::Client>,
+    message: &DbMessage,
+    room: &Room,
+    author_id: Uuid,
+    author_name: &str,
+    author_is_bot: bool,
+) {
+    let Some(meili_client) = meili_client else {
+        return;
+    };
+    let search_document = serde_json::json!({
+        "id": message.id.to_string(),
+        "room_id": room.id.to_string(),
+        "room_name": room.name,
+        "author_id": author_id.to_string(),
+        "author_name": author_name,
+        "author_is_bot": author_is_bot,
+        "content": message.content,
+        "created_at": message.created_at.timestamp(),
+        "updated_at": message.updated_at.timestamp(),
+        "message_type": message.message_type.as_str(),
+        "seq": message.seq,
+    });
+    if let Err(e) = meili_client
+        .index("messages")
+        .add_documents(&[search_document], Some("id"))
+        .await
+    {
+        tracing::error!("Failed to index message in Meilisearch: {}", e);
+    }
+}
+
+/// ユーザー（受信 Webhook のボットを含む）が投稿したメッセージをルームへ配信する `message` イベント
+///
+/// WebSocket・REST・受信 Webhook のどこから送信した場合も同じ形で配信する。
+pub fn message_event(
+    message: &DbMessage,
+    room: &str,
+    author: &User,
+    is_bot: bool,
+    quoted: Option<QuotePreview>,
+    config: &Config,
+) -> WsMessage {
+    WsMessage::Message {
+        id: message.id.to_string(),
+        room: room.to_string(),
+        user_id: author.id.to_string(),
+        username: author.username.clone(),
+        avatar_url: author.avatar_url.clone(),
+        is_bot,
+        content: message.content.clone(),
+        rendered_html: crate::markdown::rendered_html(
+            config,
+            &message.message_type,
+            &message.content,
+        ),
+        message_type: message.message_type.to_string(),
+        seq: message.seq,
+        timestamp: message.created_at,
+        quoted: quoted.map(QuotedMessage::from),
+    }
+}
+
+// JWT トークンを検証してユーザー情報とセッションIDを取得
+async fn verify_jwt_token(
+    token: &str,
+    pool: &PgPool,
+    config: &Config,
+) -> Result<(User, Option<Uuid>), WsAuthError> {
+    let claims =
+        crate::api::auth::verify_jwt(token, config).map_err(|_| WsAuthError::InvalidToken)?;
+
+    let user_id = claims
+        .sub
+        .parse::<Uuid>()
+        .map_err(|_| WsAuthError::InvalidToken)?;
+
+    // 失効したセッションのトークンでは接続させない
+    if let Some(jti) = claims.jti {
+        if !Session::touch(pool, jti, user_id)
+            .await
+            .map_err(WsAuthError::Internal)?
+        {
+            return Err(WsAuthError::InvalidToken);
+        }
+    }
+
+    let user = User::find_by_id(pool, user_id)
+        .await
+        .map_err(WsAuthError::Internal)?
+        .ok_or(WsAuthError::UserNotFound)?;
+
+    Ok((user, claims.jti))
+}
+
+/// 失効したセッションのトークンで接続している WebSocket を閉じ、閉じた接続数を返す
+pub async fn close_session_connections(
+    user_id: Uuid,
+    session_id: Uuid,
+    app_state: &AppState,
+) -> usize {
+    let connections: Vec<(Uuid, mpsc::Sender<Message>)> = {
+        let state = app_state.read().await;
+        state
+            .connections
+            .get(&user_id)
+            .into_iter()
+            .flat_map(|user_clients| user_clients.values())
+            .filter(|client| client.session_id == Some(session_id))
+            .map(|client| (client.connection_id, client.control.clone()))
+            .collect()
+    };
+
+    for (connection_id, control) in &connections {
+        info!(
+            "Closing connection {} of user {} (session {} revoked)",
+            connection_id, user_id, session_id
+        );
+        disconnect(
+            user_id,
+            *connection_id,
+            control,
+            "Session revoked",
+            app_state,
+        )
+        .await;
+    }
+    connections.len()
+}
+
+// 接続を状態から外し、クローズフレームを送る（送信タスクが閉じると接続が終わる）
+async fn disconnect(
+    user_id: Uuid,
+    connection_id: Uuid,
+    control: &mpsc::Sender<Message>,
+    reason: &'static str,
+    app_state: &AppState,
+) {
+    cleanup_connection(user_id, connection_id, app_state).await;
+    unregister_connection(user_id, connection_id, app_state).await;
+    send_close(control, reason);
+}
+
+// 送信タスクにクローズフレームを渡す
+fn send_close(control: &mpsc::Sender<Message>, reason: &'static str) {
+    let _ = control.try_send(Message::Close(Some(CloseFrame {
+        code: axum::extract::ws::close_code::POLICY,
+        reason: reason.into(),
+    })));
+}
+
+// クライアントをルームに追加し、新たに参加した場合は true を返す
+// （同じ接続が既に参加している場合は何もしない）
+async fn add_client_to_room(
+    room: &str,
+    user: User,
+    client: ConnectedClient,
+    app_state: &AppState,
+) -> bool {
+    let mut state = app_state.write().await;
+    // 接続時に登録されていない接続（テストなど）もユーザーの接続として扱えるようにする
+    state
+        .connections
+        .entry(user.id)
+        .or_default()
+        .entry(client.connection_id)
+        .or_insert_with(|| client.clone());
+    let user_clients = state
+        .rooms
+        .entry(room.to_string())
+        .or_insert_with(HashMap::new)
+        .entry(user.id)
+        .or_default();
+
+    if user_clients.contains_key(&client.connection_id) {
+        info!("Client {} is already in room {}", user.username, room);
+        return false;
+    }
+
+    let mut updated_client = client;
+    updated_client.rooms.push(room.to_string());
+    let room_count = updated_client.rooms.len();
+
+    user_clients.insert(updated_client.connection_id, updated_client);
 
     info!(
         "Client {} added to room {}, total rooms: {}",
         user.username, room, room_count
     );
+    true
 }
 
-// クライアントをルームから削除
-async fn remove_client_from_room(room: &str, user_id: Uuid, app_state: &AppState) {
+// 接続をルームから削除（同じユーザーの他の接続は残す）
+async fn remove_client_from_room(
+    room: &str,
+    user_id: Uuid,
+    connection_id: Uuid,
+    app_state: &AppState,
+) {
     let mut state = app_state.write().await;
-    if let Some(room_clients) = state.get_mut(room) {
-        room_clients.remove(&user_id);
+    if let Some(room_clients) = state.rooms.get_mut(room) {
+        if let Some(user_clients) = room_clients.get_mut(&user_id) {
+            user_clients.remove(&connection_id);
+            if user_clients.is_empty() {
+                room_clients.remove(&user_id);
+            }
+        }
         if room_clients.is_empty() {
-            state.remove(room);
+            state.rooms.remove(room);
         }
     }
 }
 
-// ユーザーの全接続をクリーンアップ
-async fn cleanup_user_connections(user_id: Uuid, app_state: &AppState) {
-    let mut state = app_state.write().await;
-    let rooms_to_clean: Vec<String> = state.keys().cloned().collect();
-
-    let mut cleaned_rooms = 0;
-    for room in rooms_to_clean {
-        if let Some(room_clients) = state.get_mut(&room) {
-            if room_clients.remove(&user_id).is_some() {
-                cleaned_rooms += 1;
-                info!("Removed user {} from room {}", user_id, room);
-
-                // ルームが空になったら削除
-                if room_clients.is_empty() {
-                    state.remove(&room);
-                    info!("Removed empty room: {}", room);
-                }
+// ユーザーのいずれかの接続がルームに参加しているか
+async fn user_in_room(room: &str, user_id: Uuid, app_state: &AppState) -> bool {
+    app_state
+        .read()
+        .await
+        .rooms
+        .get(room)
+        .is_some_and(|room_clients| room_clients.contains_key(&user_id))
+}
+
+// 接続を全ルームからクリーンアップし、退出したルームを名前順で返す
+async fn cleanup_connection(
+    user_id: Uuid,
+    connection_id: Uuid,
+    app_state: &AppState,
+) -> Vec<String> {
+    let cleaned_rooms = app_state
+        .write()
+        .await
+        .leave_all_rooms(user_id, connection_id);
+
+    info!(
+        "Cleaned up {} room connections for user {} (connection {})",
+        cleaned_rooms.len(),
+        user_id,
+        connection_id
+    );
+    cleaned_rooms
+}
+
+// 接続をユーザーの接続として登録する（ルームに参加していなくても個別の通知を届けるため）
+//
+// 接続数制限を適用する（ユーザーの接続IDを重複なく数える）。同時に接続しても上限を超えないよう、
+// 数える・切断する・登録するまでを1回の書き込みロックの中で行う。
+// 上限に達している場合、Reject なら新しい接続を拒否し、EvictOldest なら
+// connected_at が最も古い接続から順に状態から外してクローズフレームを送る。
+async fn register_connection(
+    client: &ConnectedClient,
+    limit: usize,
+    policy: ConnectionLimitPolicy,
+    app_state: &AppState,
+) -> anyhow::Result<()> {
+    let user_id = client.user_id;
+    let evicted = {
+        let mut state = app_state.write().await;
+        let mut connections: Vec<(DateTime<Utc>, Uuid, mpsc::Sender<Message>)> = state
+            .connections
+            .get(&user_id)
+            .into_iter()
+            .flat_map(|user_clients| user_clients.values())
+            .map(|client| {
+                (
+                    client.connected_at,
+                    client.connection_id,
+                    client.control.clone(),
+                )
+            })
+            .collect();
+
+        let mut evicted = Vec::new();
+        if connections.len() >= limit {
+            if policy == ConnectionLimitPolicy::Reject {
+                return Err(anyhow::anyhow!("Maximum connections exceeded"));
+            }
+            connections
+                .sort_by_key(|(connected_at, connection_id, _)| (*connected_at, *connection_id));
+            let excess = connections.len() + 1 - limit;
+            for (_, connection_id, control) in connections.into_iter().take(excess) {
+                info!(
+                    "Evicting oldest connection {} of user {} (limit {})",
+                    connection_id, user_id, limit
+                );
+                state.leave_all_rooms(user_id, connection_id);
+                state.remove_connection(user_id, connection_id);
+                evicted.push(control);
             }
         }
+
+        state
+            .connections
+            .entry(user_id)
+            .or_default()
+            .insert(client.connection_id, client.clone());
+        evicted
+    };
+
+    for control in evicted {
+        send_close(&control, "Replaced by a newer connection");
     }
+    Ok(())
+}
 
-    info!(
-        "Cleaned up {} room connections for user {}",
-        cleaned_rooms, user_id
-    );
+// 切断した接続の登録を外す（ルームからの退出は `cleanup_connection` で行う）
+async fn unregister_connection(user_id: Uuid, connection_id: Uuid, app_state: &AppState) {
+    app_state
+        .write()
+        .await
+        .remove_connection(user_id, connection_id);
+}
+
+// ブロック中のユーザーを読み直す
+//
+// 接続時に読み込んだ一覧を、ルームへの参加時にも最新化する。
+async fn refresh_blocked_users(client: &ConnectedClient, pool: &PgPool) -> anyhow::Result<()> {
+    let blocked = User::blocked_user_ids(pool, client.user_id).await?;
+    *client.blocked_users.write().await = blocked.into_iter().collect();
+    Ok(())
+}
+
+/// ユーザーの全接続のブロック一覧を更新（REST のブロック・解除から呼ばれる）
+pub async fn update_blocked_user(
+    user_id: Uuid,
+    target_id: Uuid,
+    blocked: bool,
+    app_state: &AppState,
+) {
+    let state = app_state.read().await;
+    for client in state
+        .connections
+        .get(&user_id)
+        .into_iter()
+        .flat_map(|clients| clients.values())
+    {
+        let mut blocked_users = client.blocked_users.write().await;
+        if blocked {
+            blocked_users.insert(target_id);
+        } else {
+            blocked_users.remove(&target_id);
+        }
+    }
+}
+
+/// ユーザーの全接続にメッセージを送信し、送信できた接続数を返す
+///
+/// どのルームにも参加していない接続にも送る。
+pub async fn send_to_user(user_id: Uuid, message: WsMessage, app_state: &AppState) -> usize {
+    let state = app_state.read().await;
+    state
+        .connections
+        .get(&user_id)
+        .into_iter()
+        .flat_map(|clients| clients.values())
+        .filter(|client| client.sender.send(message.clone()).is_ok())
+        .count()
+}
+
+/// ユーザーの全接続をルームに参加させ、新たに参加させた接続数を返す
+///
+/// 招待されたプライベートルームのイベントを、参加し直さなくても受け取れるようにする。
+/// どのルームにも参加していない接続も対象。
+pub async fn join_user_connections(room: &str, user_id: Uuid, app_state: &AppState) -> usize {
+    let mut state = app_state.write().await;
+    let clients: Vec<ConnectedClient> = state
+        .connections
+        .get(&user_id)
+        .into_iter()
+        .flat_map(|clients| clients.values().cloned())
+        .collect();
+    if clients.is_empty() {
+        return 0;
+    }
+
+    let user_clients = state
+        .rooms
+        .entry(room.to_string())
+        .or_default()
+        .entry(user_id)
+        .or_default();
+    let mut joined = 0;
+    for mut client in clients {
+        if user_clients.contains_key(&client.connection_id) {
+            continue;
+        }
+        client.rooms.push(room.to_string());
+        user_clients.insert(client.connection_id, client);
+        joined += 1;
+    }
+    joined
+}
+
+/// ルーム名の変更を `AppState` に反映する（接続は新しい名前のルームに参加したまま）
+pub async fn rename_room(old_name: &str, new_name: &str, app_state: &AppState) {
+    let mut state = app_state.write().await;
+    if let Some(room_clients) = state.rooms.remove(old_name) {
+        state.rooms.insert(new_name.to_string(), room_clients);
+    }
+
+    for client in state
+        .rooms
+        .values_mut()
+        .flat_map(|room_clients| room_clients.values_mut())
+        .flat_map(|clients| clients.values_mut())
+    {
+        for room in client.rooms.iter_mut().filter(|room| *room == old_name) {
+            *room = new_name.to_string();
+        }
+    }
+}
+
+/// ルームに参加している接続を持つユーザー
+pub async fn connected_user_ids(room: &str, app_state: &AppState) -> Vec<Uuid> {
+    let state = app_state.read().await;
+    state
+        .rooms
+        .get(room)
+        .map(|room_clients| room_clients.keys().copied().collect())
+        .unwrap_or_default()
+}
+
+/// 指定したユーザーの全接続をルームから外す
+pub async fn remove_users_from_room(room: &str, user_ids: &[Uuid], app_state: &AppState) {
+    let mut state = app_state.write().await;
+    let Some(room_clients) = state.rooms.get_mut(room) else {
+        return;
+    };
+    for user_id in user_ids {
+        room_clients.remove(user_id);
+    }
+    if room_clients.is_empty() {
+        state.rooms.remove(room);
+    }
+
+    for client in state
+        .rooms
+        .values_mut()
+        .flat_map(|room_clients| room_clients.iter_mut())
+        .filter(|(user_id, _)| user_ids.contains(user_id))
+        .flat_map(|(_, clients)| clients.values_mut())
+    {
+        client.rooms.retain(|name| name != room);
+    }
 }
 
 // ルーム内の全クライアントにメッセージをブロードキャスト
-async fn broadcast_to_room(
+//
+// `sender_id` を指定した場合、送信者をブロックしている受信者には配信しない。
+pub async fn broadcast_to_room(
     room: &str,
     message: WsMessage,
+    sender_id: Option<Uuid>,
     exclude_user: Option<Uuid>,
     app_state: &AppState,
 ) {
-    let state = app_state.read().await;
-    if let Some(room_clients) = state.get(room) {
-        let mut success_count = 0;
-        let mut error_count = 0;
-
-        for (user_id, client) in room_clients {
-            if exclude_user.map_or(true, |exclude| exclude != *user_id) {
-                match client.sender.send(message.clone()) {
-                    Ok(_) => success_count += 1,
-                    Err(_) => {
-                        error_count += 1;
-                        warn!(
-                            "Failed to send message to user {} in room {}",
-                            user_id, room
-                        );
-                    }
-                }
+    // ブロックの確認は await を伴うため、ロックを保持したまま行わないよう送信先を先に取り出す
+    let recipients: Vec<(Uuid, ClientSender, BlockedUsers)> = {
+        let state = app_state.read().await;
+        let Some(room_clients) = state.rooms.get(room) else {
+            debug!("No subscribers to broadcast to in room: {}", room);
+            return;
+        };
+        room_clients
+            .iter()
+            .filter(|(user_id, _)| exclude_user != Some(**user_id))
+            .flat_map(|(user_id, clients)| {
+                clients.values().map(move |client| {
+                    (
+                        *user_id,
+                        client.sender.clone(),
+                        client.blocked_users.clone(),
+                    )
+                })
+            })
+            .collect()
+    };
+
+    MESSAGES_BROADCAST.fetch_add(1, Ordering::Relaxed);
+    let mut success_count = 0;
+    let mut error_count = 0;
+
+    for (user_id, sender, blocked_users) in recipients {
+        if let Some(sender_id) = sender_id {
+            if blocked_users.read().await.contains(&sender_id) {
+                continue;
             }
         }
 
-        debug!(
-            "Broadcast to room {}: {} successful, {} failed",
-            room, success_count, error_count
-        );
-    } else {
-        warn!("Attempted to broadcast to non-existent room: {}", room);
+        match sender.send(message.clone()) {
+            Ok(_) => success_count += 1,
+            Err(_) => {
+                error_count += 1;
+                crate::metrics::broadcast_failed();
+                warn!(
+                    "Failed to send message to user {} in room {}",
+                    user_id, room
+                );
+            }
+        }
     }
+
+    debug!(
+        "Broadcast to room {}: {} successful, {} failed",
+        room, success_count, error_count
+    );
+}
+
+// オンラインユーザー情報
+#[derive(Debug, Clone)]
+pub struct OnlineUserInfo {
+    pub user_id: Uuid,
+    pub username: String,
+    pub rooms: Vec<String>,
+    pub connected_at: DateTime<Utc>,
+    pub connection_count: usize,
 }
 
+// 集計中のオンラインユーザー（ユーザー名・参加ルーム・最も早い接続時刻・接続ID）
+type OnlineUserEntry = (String, BTreeSet<String>, DateTime<Utc>, HashSet<Uuid>);
+
 // オンラインユーザー情報を取得
-pub async fn get_online_users_info(
-    app_state: &AppState,
-) -> Vec<(uuid::Uuid, String, Vec<String>, std::time::Instant)> {
+//
+// ユーザーごとに参加ルームを重複なくソートし、最も早い接続時刻を採用する。
+// 結果はユーザー名（同名の場合はユーザーID）順に並べる。
+pub async fn get_online_users_info(app_state: &AppState) -> Vec<OnlineUserInfo> {
     let state = app_state.read().await;
-    let mut users_map: std::collections::HashMap<
-        uuid::Uuid,
-        (String, Vec<String>, std::time::Instant),
-    > = std::collections::HashMap::new();
+    let mut users_map: HashMap<Uuid, OnlineUserEntry> = HashMap::new();
 
     // 各ルームのクライアントを走査
-    for (room_name, room_clients) in state.iter() {
-        for (user_id, client) in room_clients.iter() {
-            if let Some((username, rooms, connected_at)) = users_map.get_mut(user_id) {
-                // 既存ユーザーにルームを追加
-                rooms.push(room_name.clone());
-            } else {
-                // 新しいユーザーを追加
-                users_map.insert(
-                    *user_id,
-                    (
-                        client.username.clone(),
-                        vec![room_name.clone()],
-                        client.connected_at,
-                    ),
-                );
+    for (room_name, room_clients) in state.rooms.iter() {
+        for (user_id, user_clients) in room_clients.iter() {
+            for client in user_clients.values() {
+                let (_, rooms, connected_at, connections) =
+                    users_map.entry(*user_id).or_insert_with(|| {
+                        (
+                            client.username.clone(),
+                            BTreeSet::new(),
+                            client.connected_at,
+                            HashSet::new(),
+                        )
+                    });
+                rooms.insert(room_name.clone());
+                connections.insert(client.connection_id);
+                *connected_at = (*connected_at).min(client.connected_at);
             }
         }
     }
 
-    // Vec形式で返す
-    users_map
+    let mut users: Vec<OnlineUserInfo> = users_map
         .into_iter()
-        .map(|(user_id, (username, rooms, connected_at))| (user_id, username, rooms, connected_at))
-        .collect()
+        .map(
+            |(user_id, (username, rooms, connected_at, connections))| OnlineUserInfo {
+                user_id,
+                username,
+                rooms: rooms.into_iter().collect(),
+                connected_at,
+                connection_count: connections.len(),
+            },
+        )
+        .collect();
+    users.sort_by(|a, b| a.username.cmp(&b.username).then(a.user_id.cmp(&b.user_id)));
+    users
+}
+
+// WebSocket サーバーの集計情報
+#[derive(Debug, Clone)]
+pub struct WsStats {
+    pub total_connections: usize,
+    pub online_users: usize,
+    pub active_rooms: usize,
+    // 接続数の多い順（同数はルーム名順）のルームと接続数
+    pub top_rooms: Vec<(String, usize)>,
+    pub messages_broadcast: u64,
+}
+
+// WebSocket サーバーの集計情報を取得（ルームは接続数の多い `top_rooms` 件まで）
+//
+// 読み取りロックを1回だけ取り、同じ時点の状態から集計する。並べ替えはロックの外で行う。
+pub async fn get_ws_stats(app_state: &AppState, top_rooms: usize) -> WsStats {
+    let (total_connections, online_users, mut rooms) = {
+        let state = app_state.read().await;
+        // 接続数・オンラインユーザー数はルームに参加していない接続も数える
+        let total_connections = state.connections.values().map(HashMap::len).sum();
+        let rooms: Vec<(String, usize)> = state
+            .rooms
+            .iter()
+            .map(|(room_name, room_clients)| {
+                (
+                    room_name.clone(),
+                    room_clients.values().map(HashMap::len).sum(),
+                )
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        (total_connections, state.connections.len(), rooms)
+    };
+
+    let active_rooms = rooms.len();
+    rooms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    rooms.truncate(top_rooms);
+
+    WsStats {
+        total_connections,
+        online_users,
+        active_rooms,
+        top_rooms: rooms,
+        messages_broadcast: MESSAGES_BROADCAST.load(Ordering::Relaxed),
+    }
 }
 
 // WebRTCシグナリングメッセージを特定のユーザーに中継
@@ -893,54 +2098,847 @@ async fn relay_webrtc_signal(
         _ => return Err(anyhow::anyhow!("Invalid WebRTC message type")),
     };
 
-    let state = app_state.read().await;
-    let mut message_sent = false;
-
-    // 対象ユーザーが現在接続している全ルームを検索
-    for room_clients in state.values() {
-        if let Some(target_client) = room_clients.get(&target_user_id) {
-            // メッセージに送信者の情報を追加
-            let enriched_message = match message.clone() {
-                WsMessage::WebRtcOffer { room, offer, .. } => WsMessage::WebRtcOffer {
-                    room,
-                    to_user_id: from_user_id.to_string(),
-                    offer,
-                },
-                WsMessage::WebRtcAnswer { room, answer, .. } => WsMessage::WebRtcAnswer {
-                    room,
-                    to_user_id: from_user_id.to_string(),
-                    answer,
-                },
-                WsMessage::WebRtcIceCandidate {
-                    room, candidate, ..
-                } => WsMessage::WebRtcIceCandidate {
-                    room,
-                    to_user_id: from_user_id.to_string(),
-                    candidate,
-                },
-                _ => message.clone(),
-            };
+    // メッセージに送信者の情報を追加
+    let enriched_message = match message {
+        WsMessage::WebRtcOffer { room, offer, .. } => WsMessage::WebRtcOffer {
+            room,
+            to_user_id: from_user_id.to_string(),
+            offer,
+        },
+        WsMessage::WebRtcAnswer { room, answer, .. } => WsMessage::WebRtcAnswer {
+            room,
+            to_user_id: from_user_id.to_string(),
+            answer,
+        },
+        WsMessage::WebRtcIceCandidate {
+            room, candidate, ..
+        } => WsMessage::WebRtcIceCandidate {
+            room,
+            to_user_id: from_user_id.to_string(),
+            candidate,
+        },
+        other => other,
+    };
 
-            match target_client.sender.send(enriched_message) {
-                Ok(_) => {
-                    debug!("WebRTC signal relayed to user {}", target_user_id);
-                    message_sent = true;
-                    break; // 1つの接続に送信したら終了
-                }
-                Err(e) => {
-                    warn!(
-                        "Failed to send WebRTC signal to user {}: {}",
-                        target_user_id, e
-                    );
+    // どのタブで通話を受けるかはサーバーでは分からないため、対象ユーザーの全接続に送る
+    let delivered = send_to_user(target_user_id, enriched_message, app_state).await;
+    if delivered == 0 {
+        warn!("Target user {} not found for WebRTC signal", target_user_id);
+        return Err(anyhow::anyhow!("Target user not found or offline"));
+    }
+    debug!(
+        "WebRTC signal relayed to {} connection(s) of user {}",
+        delivered, target_user_id
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_INTERVAL: Duration = Duration::from_millis(10);
+    const TEST_TIMEOUT: Duration = Duration::from_millis(40);
+
+    fn spawn_heartbeat() -> (
+        tokio::task::JoinHandle<()>,
+        mpsc::Receiver<Message>,
+        Arc<RwLock<Instant>>,
+    ) {
+        let (control_tx, control_rx) = mpsc::channel(8);
+        let (message_tx, _) = ClientSender::channel(8, control_tx.clone());
+        let last_activity = Arc::new(RwLock::new(Instant::now()));
+        let handle = tokio::spawn(run_heartbeat(
+            "tester".to_string(),
+            control_tx,
+            message_tx,
+            last_activity.clone(),
+            TEST_INTERVAL,
+            TEST_TIMEOUT,
+        ));
+        (handle, control_rx, last_activity)
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_keeps_pong_responding_client() {
+        let (handle, mut control_rx, last_activity) = spawn_heartbeat();
+
+        // 何も送信しないが、Pingには応答する（Pong受信でアクティビティ更新）クライアント
+        let client = tokio::spawn(async move {
+            while let Some(frame) = control_rx.recv().await {
+                assert!(matches!(frame, Message::Ping(_)));
+                *last_activity.write().await = Instant::now();
+            }
+        });
+
+        tokio::time::sleep(TEST_TIMEOUT * 5).await;
+        assert!(!handle.is_finished());
+
+        handle.abort();
+        client.abort();
+    }
+
+    fn test_client(user_id: Uuid, username: &str, connected_at: DateTime<Utc>) -> ConnectedClient {
+        let (control, _) = mpsc::channel(8);
+        let (sender, _) = ClientSender::channel(8, control.clone());
+        ConnectedClient {
+            connection_id: Uuid::new_v4(),
+            user_id,
+            username: username.to_string(),
+            session_id: None,
+            rooms: Vec::new(),
+            sender,
+            control,
+            connected_at,
+            last_activity: Arc::new(RwLock::new(Instant::now())),
+            message_count: AtomicU64::new(0),
+            rate_limiter: Arc::new(Semaphore::new(RATE_LIMIT_MESSAGES)),
+            blocked_users: Arc::new(RwLock::new(HashSet::new())),
+        }
+    }
+
+    // 送信キューの読み出し側を持つテスト用クライアント
+    fn test_client_with_receiver(
+        user_id: Uuid,
+        username: &str,
+    ) -> (ConnectedClient, mpsc::Receiver<WsMessage>) {
+        let mut client = test_client(user_id, username, Utc::now());
+        let (sender, receiver) = ClientSender::channel(8, client.control.clone());
+        client.sender = sender;
+        (client, receiver.queue)
+    }
+
+    // 同じユーザーの接続を古い順に2つ登録し、最も古い接続の制御チャネルを返す
+    async fn connect_two(user_id: Uuid, app_state: &AppState) -> (Uuid, mpsc::Receiver<Message>) {
+        let now = Utc::now();
+        let (control, oldest_rx) = mpsc::channel(8);
+        let mut oldest = test_client(user_id, "alice", now - chrono::Duration::minutes(10));
+        oldest.control = control;
+        let oldest_id = oldest.connection_id;
+        let newer = test_client(user_id, "alice", now);
+
+        add_client_to_room("general", test_user(user_id, "alice"), oldest, app_state).await;
+        add_client_to_room("general", test_user(user_id, "alice"), newer, app_state).await;
+        (oldest_id, oldest_rx)
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit_reject_policy() {
+        let alice = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+        let (_, mut oldest_rx) = connect_two(alice, &app_state).await;
+        let newest = test_client(alice, "alice", Utc::now());
+
+        assert!(
+            register_connection(&newest, 2, ConnectionLimitPolicy::Reject, &app_state)
+                .await
+                .is_err()
+        );
+        // 既存の接続はそのままで、新しい接続は登録しない
+        assert_eq!(app_state.read().await.connections[&alice].len(), 2);
+        assert!(oldest_rx.try_recv().is_err());
+
+        assert!(
+            register_connection(&newest, 3, ConnectionLimitPolicy::Reject, &app_state)
+                .await
+                .is_ok()
+        );
+        assert_eq!(app_state.read().await.connections[&alice].len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit_evict_oldest_policy() {
+        let alice = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+        let (oldest_id, mut oldest_rx) = connect_two(alice, &app_state).await;
+        let newest = test_client(alice, "alice", Utc::now());
+
+        assert!(
+            register_connection(&newest, 2, ConnectionLimitPolicy::EvictOldest, &app_state)
+                .await
+                .is_ok()
+        );
+
+        // 最も古い接続だけが外されて新しい接続が登録され、古い接続はクローズフレームを受け取る
+        let state = app_state.read().await;
+        let remaining = &state.rooms["general"][&alice];
+        assert_eq!(remaining.len(), 1);
+        assert!(!remaining.contains_key(&oldest_id));
+        assert_eq!(state.connections[&alice].len(), 2);
+        assert!(state.connections[&alice].contains_key(&newest.connection_id));
+        drop(state);
+
+        match oldest_rx.try_recv() {
+            Ok(Message::Close(Some(frame))) => {
+                assert_eq!(frame.code, axum::extract::ws::close_code::POLICY)
+            }
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_connections_respect_limit() {
+        let alice = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        let results = futures_util::future::join_all((0..10).map(|_| {
+            let app_state = app_state.clone();
+            tokio::spawn(async move {
+                let client = test_client(alice, "alice", Utc::now());
+                register_connection(&client, 3, ConnectionLimitPolicy::Reject, &app_state)
+                    .await
+                    .is_ok()
+            })
+        }))
+        .await;
+
+        assert_eq!(
+            results.into_iter().filter(|r| *r.as_ref().unwrap()).count(),
+            3
+        );
+        assert_eq!(app_state.read().await.connections[&alice].len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_online_users_info_is_deterministic() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let earliest = Utc::now() - chrono::Duration::minutes(10);
+        let later = Utc::now();
+
+        let app_state: AppState = AppState::default();
+        {
+            let mut state = app_state.write().await;
+            for (room, connected_at) in [("random", later), ("general", earliest), ("dev", later)] {
+                let client = test_client(alice, "alice", connected_at);
+                state
+                    .rooms
+                    .entry(room.to_string())
+                    .or_default()
+                    .entry(alice)
+                    .or_default()
+                    .insert(client.connection_id, client);
+            }
+            let client = test_client(bob, "bob", later);
+            state
+                .rooms
+                .entry("general".to_string())
+                .or_default()
+                .entry(bob)
+                .or_default()
+                .insert(client.connection_id, client);
+        }
+
+        let users = get_online_users_info(&app_state).await;
+        assert_eq!(users.len(), 2);
+
+        assert_eq!(users[0].user_id, alice);
+        assert_eq!(users[0].username, "alice");
+        assert_eq!(users[0].rooms, ["dev", "general", "random"]);
+        assert_eq!(users[0].connected_at, earliest);
+
+        assert_eq!(users[1].username, "bob");
+        assert_eq!(users[1].rooms, ["general"]);
+    }
+
+    #[tokio::test]
+    async fn test_ws_stats_counts_connections_and_top_rooms() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        // alice の1接続が general と random に参加し、bob は2接続で general に参加
+        let client = test_client(alice, "alice", Utc::now());
+        add_client_to_room(
+            "general",
+            test_user(alice, "alice"),
+            client.clone(),
+            &app_state,
+        )
+        .await;
+        add_client_to_room("random", test_user(alice, "alice"), client, &app_state).await;
+        for _ in 0..2 {
+            let client = test_client(bob, "bob", Utc::now());
+            add_client_to_room("general", test_user(bob, "bob"), client, &app_state).await;
+        }
+
+        let before = get_ws_stats(&app_state, 10).await.messages_broadcast;
+        broadcast_to_room(
+            "general",
+            WsMessage::Pong { timestamp: None },
+            None,
+            None,
+            &app_state,
+        )
+        .await;
+
+        let stats = get_ws_stats(&app_state, 1).await;
+        assert_eq!(stats.total_connections, 3);
+        assert_eq!(stats.online_users, 2);
+        assert_eq!(stats.active_rooms, 2);
+        assert_eq!(stats.top_rooms, [("general".to_string(), 3)]);
+        assert!(stats.messages_broadcast > before);
+    }
+
+    #[test]
+    fn test_auth_error_close_frames() {
+        let frame = WsAuthError::MissingToken.close_frame();
+        assert_eq!(frame.code, axum::extract::ws::close_code::POLICY);
+        assert_eq!(frame.reason.as_str(), "missing token");
+
+        let frame = WsAuthError::InvalidToken.close_frame();
+        assert_eq!(frame.code, 1008);
+        assert_eq!(frame.reason.as_str(), "invalid token");
+
+        let frame = WsAuthError::UserNotFound.close_frame();
+        assert_eq!(frame.reason.as_str(), "user not found");
+
+        let frame = WsAuthError::Internal(anyhow::anyhow!("db down")).close_frame();
+        assert_eq!(frame.code, axum::extract::ws::close_code::ERROR);
+        assert_eq!(frame.reason.as_str(), "server error");
+    }
+
+    #[test]
+    fn test_extract_ws_token_sources() {
+        // サブプロトコル
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            "miuchi-chat, bearer.abc.def.ghi".parse().unwrap(),
+        );
+        assert_eq!(
+            extract_ws_token(&headers, None).as_deref(),
+            Some("abc.def.ghi")
+        );
+
+        // Authorization ヘッダーが最優先
+        headers.insert(header::AUTHORIZATION, "Bearer from-header".parse().unwrap());
+        assert_eq!(
+            extract_ws_token(&headers, Some("from-query".to_string())).as_deref(),
+            Some("from-header")
+        );
+
+        // ヘッダーがなければクエリ文字列（後方互換）
+        assert_eq!(
+            extract_ws_token(&HeaderMap::new(), Some("from-query".to_string())).as_deref(),
+            Some("from-query")
+        );
+        assert_eq!(extract_ws_token(&HeaderMap::new(), None), None);
+    }
+
+    fn test_user(id: Uuid, username: &str) -> User {
+        User {
+            id,
+            github_id: 1,
+            username: username.to_string(),
+            email: None,
+            avatar_url: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping_echoes_client_timestamp() {
+        let user = test_user(Uuid::new_v4(), "alice");
+        let (client, mut rx) = test_client_with_receiver(user.id, "alice");
+        let app_state: AppState = AppState::default();
+        // Ping の処理は DB・Meilisearch に触れない
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let config = Arc::new(
+            Config::from_lookup(|key| (key == "DEV_MODE").then(|| "true".to_string())).unwrap(),
+        );
+
+        // JavaScript の Date.now() 相当のミリ秒
+        let ping: WsMessage =
+            serde_json::from_str(r#"{"type":"ping","timestamp":1767225600123}"#).unwrap();
+        for msg in [ping, WsMessage::Ping { timestamp: None }] {
+            handle_websocket_message(
+                msg,
+                &user,
+                &client,
+                &pool,
+                &app_state,
+                &client.sender,
+                None,
+                &config,
+            )
+            .await
+            .unwrap();
+        }
+
+        let pong = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(
+            pong,
+            serde_json::json!({ "type": "pong", "timestamp": 1767225600123u64 })
+        );
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(WsMessage::Pong { timestamp: None })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_client_to_room_is_idempotent_per_connection() {
+        let alice = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+        let client = test_client(alice, "alice", Utc::now());
+        let connection_id = client.connection_id;
+
+        assert!(
+            add_client_to_room(
+                "general",
+                test_user(alice, "alice"),
+                client.clone(),
+                &app_state
+            )
+            .await
+        );
+        assert!(
+            !add_client_to_room(
+                "general",
+                test_user(alice, "alice"),
+                client.clone(),
+                &app_state
+            )
+            .await
+        );
+
+        let state = app_state.read().await;
+        assert_eq!(state.rooms["general"][&alice].len(), 1);
+        assert_eq!(
+            state.rooms["general"][&alice][&connection_id].rooms,
+            ["general"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multiple_connections_of_same_user_receive_broadcast() {
+        let alice = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        // 同じユーザーが2つのタブから同じルームに参加
+        let (first, mut first_rx) = test_client_with_receiver(alice, "alice");
+        let (second, mut second_rx) = test_client_with_receiver(alice, "alice");
+        let second_connection = second.connection_id;
+
+        add_client_to_room("general", test_user(alice, "alice"), first, &app_state).await;
+        add_client_to_room("general", test_user(alice, "alice"), second, &app_state).await;
+
+        broadcast_to_room(
+            "general",
+            WsMessage::Pong { timestamp: Some(1) },
+            None,
+            None,
+            &app_state,
+        )
+        .await;
+
+        assert!(matches!(first_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+        assert!(matches!(second_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+
+        let users = get_online_users_info(&app_state).await;
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].connection_count, 2);
+
+        // 片方の切断でもう片方は残る
+        cleanup_connection(alice, second_connection, &app_state).await;
+        let users = get_online_users_info(&app_state).await;
+        assert_eq!(users[0].connection_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_leave_all_only_removes_issuing_connection() {
+        let alice = test_user(Uuid::new_v4(), "alice");
+        let bob = test_user(Uuid::new_v4(), "bob");
+        let app_state: AppState = AppState::default();
+        // LeaveAll の処理は DB・Meilisearch に触れない
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let config = Arc::new(
+            Config::from_lookup(|key| (key == "DEV_MODE").then(|| "true".to_string())).unwrap(),
+        );
+
+        // alice は2つのタブで general と random に、bob は general に参加
+        let (first, mut first_rx) = test_client_with_receiver(alice.id, "alice");
+        let (second, mut second_rx) = test_client_with_receiver(alice.id, "alice");
+        let (bob_client, mut bob_rx) = test_client_with_receiver(bob.id, "bob");
+        for room in ["general", "random"] {
+            add_client_to_room(room, alice.clone(), first.clone(), &app_state).await;
+            add_client_to_room(room, alice.clone(), second.clone(), &app_state).await;
+        }
+        add_client_to_room("general", bob.clone(), bob_client, &app_state).await;
+
+        handle_websocket_message(
+            WsMessage::LeaveAll,
+            &alice,
+            &first,
+            &pool,
+            &app_state,
+            &first.sender,
+            None,
+            &config,
+        )
+        .await
+        .unwrap();
+
+        match first_rx.try_recv() {
+            Ok(WsMessage::LeftAll { rooms }) => assert_eq!(rooms, vec!["general", "random"]),
+            other => panic!("expected left_all, got {:?}", other),
+        }
+        // もう一方のタブが残っているため、bob には退出を通知しない
+        assert!(bob_rx.try_recv().is_err());
+        assert!(second_rx.try_recv().is_err());
+
+        // もう一方のタブは両方のルームに残る
+        {
+            let state = app_state.read().await;
+            for room in ["general", "random"] {
+                let connections = &state.rooms[room][&alice.id];
+                assert_eq!(connections.len(), 1);
+                assert!(connections.contains_key(&second.connection_id));
+            }
+        }
+
+        // 最後のタブが退出したときに通知する
+        handle_websocket_message(
+            WsMessage::LeaveAll,
+            &alice,
+            &second,
+            &pool,
+            &app_state,
+            &second.sender,
+            None,
+            &config,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            bob_rx.try_recv(),
+            Ok(WsMessage::UserLeft { room, .. }) if room == "general"
+        ));
+        assert!(bob_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_webrtc_signal_reaches_every_connection_of_target() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        // bob は2つのタブで general に参加し、1つ目のタブは random にも参加している
+        let (first, mut first_rx) = test_client_with_receiver(bob, "bob");
+        let (second, mut second_rx) = test_client_with_receiver(bob, "bob");
+        add_client_to_room("general", test_user(bob, "bob"), first.clone(), &app_state).await;
+        add_client_to_room("random", test_user(bob, "bob"), first, &app_state).await;
+        add_client_to_room("general", test_user(bob, "bob"), second, &app_state).await;
+
+        let offer = WsMessage::WebRtcOffer {
+            room: "general".to_string(),
+            to_user_id: bob.to_string(),
+            offer: serde_json::json!({ "type": "offer", "sdp": "v=0" }),
+        };
+        relay_webrtc_signal(offer, alice, &app_state).await.unwrap();
+
+        for rx in [&mut first_rx, &mut second_rx] {
+            match rx.try_recv() {
+                Ok(WsMessage::WebRtcOffer { to_user_id, .. }) => {
+                    assert_eq!(to_user_id, alice.to_string())
                 }
+                other => panic!("expected webrtc_offer, got {:?}", other),
             }
+            // 複数のルームに参加している接続にも1回だけ届く
+            assert!(rx.try_recv().is_err());
         }
+
+        let offline = WsMessage::WebRtcOffer {
+            room: "general".to_string(),
+            to_user_id: Uuid::new_v4().to_string(),
+            offer: serde_json::json!({}),
+        };
+        assert!(relay_webrtc_signal(offline, alice, &app_state)
+            .await
+            .is_err());
     }
 
-    if !message_sent {
-        warn!("Target user {} not found for WebRTC signal", target_user_id);
-        return Err(anyhow::anyhow!("Target user not found or offline"));
+    #[tokio::test]
+    async fn test_join_user_connections_subscribes_every_connection() {
+        let alice = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        // 2つのタブがそれぞれ別のルームに参加している
+        let (first, mut first_rx) = test_client_with_receiver(alice, "alice");
+        let (second, mut second_rx) = test_client_with_receiver(alice, "alice");
+        add_client_to_room("general", test_user(alice, "alice"), first, &app_state).await;
+        add_client_to_room("random", test_user(alice, "alice"), second, &app_state).await;
+
+        assert_eq!(join_user_connections("secret", alice, &app_state).await, 2);
+        // 参加済みの接続は数えない
+        assert_eq!(join_user_connections("secret", alice, &app_state).await, 0);
+
+        broadcast_to_room(
+            "secret",
+            WsMessage::Pong { timestamp: Some(1) },
+            None,
+            None,
+            &app_state,
+        )
+        .await;
+        assert!(matches!(first_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+        assert!(matches!(second_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+
+        // 接続していないユーザーのためにルームを作らない
+        assert_eq!(
+            join_user_connections("other", Uuid::new_v4(), &app_state).await,
+            0
+        );
+        assert!(!app_state.read().await.rooms.contains_key("other"));
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn test_rename_room_and_remove_users_from_room() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        let (alice_client, mut alice_rx) = test_client_with_receiver(alice, "alice");
+        let (bob_client, _bob_rx) = test_client_with_receiver(bob, "bob");
+        add_client_to_room("old", test_user(alice, "alice"), alice_client, &app_state).await;
+        add_client_to_room("old", test_user(bob, "bob"), bob_client, &app_state).await;
+
+        rename_room("old", "new", &app_state).await;
+        {
+            let state = app_state.read().await;
+            assert!(!state.rooms.contains_key("old"));
+            assert_eq!(
+                state.rooms["new"][&alice].values().next().unwrap().rooms,
+                ["new"]
+            );
+        }
+        let mut users = connected_user_ids("new", &app_state).await;
+        users.sort();
+        let mut expected = vec![alice, bob];
+        expected.sort();
+        assert_eq!(users, expected);
+
+        // 新しい名前で配信される
+        broadcast_to_room(
+            "new",
+            WsMessage::Pong { timestamp: Some(1) },
+            None,
+            None,
+            &app_state,
+        )
+        .await;
+        assert!(matches!(alice_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+
+        remove_users_from_room("new", &[bob], &app_state).await;
+        assert_eq!(connected_user_ids("new", &app_state).await, [alice]);
+
+        // 最後のユーザーを外すとルームも消える
+        remove_users_from_room("new", &[alice], &app_state).await;
+        assert!(app_state.read().await.rooms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_skips_recipients_who_blocked_sender() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let carol = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        let (bob_client, mut bob_rx) = test_client_with_receiver(bob, "bob");
+        let (carol_client, mut carol_rx) = test_client_with_receiver(carol, "carol");
+
+        add_client_to_room("general", test_user(bob, "bob"), bob_client, &app_state).await;
+        add_client_to_room(
+            "general",
+            test_user(carol, "carol"),
+            carol_client,
+            &app_state,
+        )
+        .await;
+
+        // bob が alice をブロック
+        update_blocked_user(bob, alice, true, &app_state).await;
+
+        broadcast_to_room(
+            "general",
+            WsMessage::Pong { timestamp: Some(1) },
+            Some(alice),
+            None,
+            &app_state,
+        )
+        .await;
+
+        assert!(bob_rx.try_recv().is_err());
+        assert!(matches!(carol_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+
+        // 送信者を指定しないイベントはブロックに関係なく届く
+        broadcast_to_room(
+            "general",
+            WsMessage::Pong { timestamp: Some(2) },
+            None,
+            None,
+            &app_state,
+        )
+        .await;
+        assert!(matches!(bob_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+
+        // 解除後は再び届く
+        update_blocked_user(bob, alice, false, &app_state).await;
+        broadcast_to_room(
+            "general",
+            WsMessage::Pong { timestamp: Some(3) },
+            Some(alice),
+            None,
+            &app_state,
+        )
+        .await;
+        assert!(matches!(bob_rx.try_recv(), Ok(WsMessage::Pong { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_send_to_user_delivers_once_per_connection() {
+        let alice = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        // 同じ接続が2つのルームに参加している
+        let (client, mut rx) = test_client_with_receiver(alice, "alice");
+        add_client_to_room(
+            "general",
+            test_user(alice, "alice"),
+            client.clone(),
+            &app_state,
+        )
+        .await;
+        add_client_to_room("random", test_user(alice, "alice"), client, &app_state).await;
+
+        let delivered =
+            send_to_user(alice, WsMessage::Pong { timestamp: Some(1) }, &app_state).await;
+
+        assert_eq!(delivered, 1);
+        assert!(matches!(rx.try_recv(), Ok(WsMessage::Pong { .. })));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(
+            send_to_user(
+                Uuid::new_v4(),
+                WsMessage::Pong { timestamp: None },
+                &app_state
+            )
+            .await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_does_not_block_others_and_is_disconnected() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let app_state: AppState = AppState::default();
+
+        // alice の書き込みは詰まっていて、送信キューから一切読み出さない
+        let (control, mut alice_control_rx) = mpsc::channel(8);
+        let mut alice_client = test_client(alice, "alice", Utc::now());
+        let (sender, mut alice_rx) = ClientSender::channel(2, control.clone());
+        alice_client.sender = sender;
+        alice_client.control = control;
+        let (bob_client, mut bob_rx) = test_client_with_receiver(bob, "bob");
+
+        add_client_to_room(
+            "general",
+            test_user(alice, "alice"),
+            alice_client,
+            &app_state,
+        )
+        .await;
+        add_client_to_room("general", test_user(bob, "bob"), bob_client, &app_state).await;
+
+        let total = 2 + SLOW_CONSUMER_DISCONNECT_THRESHOLD as u64;
+        for i in 0..total {
+            broadcast_to_room(
+                "general",
+                WsMessage::Pong { timestamp: Some(i) },
+                None,
+                None,
+                &app_state,
+            )
+            .await;
+            // bob は毎回読み出しているので取りこぼさない
+            assert!(matches!(
+                bob_rx.try_recv(),
+                Ok(WsMessage::Pong { timestamp: Some(t) }) if t == i
+            ));
+        }
+
+        // 満杯が続いたので alice にはクローズフレームが送られる
+        match alice_control_rx.try_recv() {
+            Ok(Message::Close(Some(frame))) => {
+                assert_eq!(frame.code, axum::extract::ws::close_code::AGAIN)
+            }
+            other => panic!("expected close frame, got {:?}", other),
+        }
+        assert!(alice_control_rx.try_recv().is_err());
+
+        // 読み出しを再開すると、まず破棄された件数が通知される
+        match alice_rx.recv().await {
+            Some(WsMessage::Error { message, .. }) => assert_eq!(
+                message,
+                format!(
+                    "{} messages were dropped",
+                    SLOW_CONSUMER_DISCONNECT_THRESHOLD
+                )
+            ),
+            other => panic!("expected drop notice, got {:?}", other),
+        }
+        assert!(matches!(
+            alice_rx.recv().await,
+            Some(WsMessage::Pong { timestamp: Some(0) })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_times_out_unresponsive_client() {
+        let (handle, _control_rx, _last_activity) = spawn_heartbeat();
+
+        tokio::time::timeout(TEST_TIMEOUT * 5, handle)
+            .await
+            .expect("heartbeat should stop for an unresponsive client")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_message_at_content_cap_fits_in_frame() {
+        let mut config =
+            Config::from_lookup(|key| (key == "DEV_MODE").then(|| "true".to_string())).unwrap();
+        config.render_markdown = true;
+
+        // JSON でエスケープされる文字と4バイトの文字を混ぜて、文字数の上限ちょうどにする
+        let unit = "\"𠮷\\\n";
+        let content = unit.repeat(config.max_room_message_length / unit.chars().count());
+        assert!(validate_message_content(&content, config.max_room_message_length).is_ok());
+
+        let message = WsMessage::Message {
+            id: Uuid::new_v4().to_string(),
+            room: "r".repeat(crate::config::MAX_ROOM_NAME_LENGTH),
+            user_id: Uuid::new_v4().to_string(),
+            username: "u".repeat(64),
+            avatar_url: Some(format!(
+                "https://avatars.githubusercontent.com/{}",
+                "a".repeat(200)
+            )),
+            is_bot: false,
+            rendered_html: crate::markdown::rendered_html(&config, &DbMessageType::Text, &content),
+            content,
+            message_type: DbMessageType::Text.to_string(),
+            seq: i64::MAX,
+            timestamp: Utc::now(),
+            quoted: Some(QuotedMessage {
+                id: Uuid::new_v4().to_string(),
+                username: "u".repeat(64),
+                content_preview: "\"".repeat(crate::config::QUOTE_PREVIEW_LENGTH),
+            }),
+        };
+        assert!(serde_json::to_string(&message).unwrap().len() <= config.max_ws_outbound_size());
+    }
 }
@@ -3,38 +3,30 @@ mod common;
 use axum::{
     body::Body,
     http::{Request, StatusCode},
-    Router,
 };
+use http_body_util::BodyExt;
 use serde_json::json;
 use tower::ServiceExt;
 
 use common::TestContext;
 
-/// テスト用のアプリケーション作成
-async fn create_test_app(pool: sqlx::PgPool) -> Router {
-    use miuchi_chat::api;
-    use std::collections::HashMap;
-    use std::sync::Arc;
-    use tokio::sync::RwLock;
-
-    // ダミーのMeilisearchクライアント（テスト用）
-    let meili_client =
-        meilisearch_sdk::client::Client::new("http://localhost:7700", None::<String>).unwrap();
-    let ws_state = Arc::new(RwLock::new(HashMap::new()));
-
-    Router::new()
-        .nest(
-            "/api",
-            api::create_router().with_state((pool.clone(), meili_client.clone())),
-        )
-        .merge(api::create_chat_router())
-        .with_state((pool, ws_state, meili_client))
+/// テストごとに重ならない名前（ルーム名・ユーザー名・GitHub ID）
+fn unique(prefix: &str) -> (String, i64) {
+    let id = uuid::Uuid::new_v4();
+    (
+        format!("{}_{}", prefix, &id.simple().to_string()[..8]),
+        (id.as_u128() as i64).abs(),
+    )
 }
 
 #[tokio::test]
 async fn test_health_endpoint() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
     let response = app
         .oneshot(
@@ -48,7 +40,7 @@ async fn test_health_endpoint() {
 
     assert_eq!(response.status(), StatusCode::OK);
 
-    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
     assert_eq!(json["status"], "healthy");
@@ -58,7 +50,11 @@ async fn test_health_endpoint() {
 #[tokio::test]
 async fn test_get_rooms_unauthorized() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
     let response = app
         .oneshot(
@@ -76,15 +72,23 @@ async fn test_get_rooms_unauthorized() {
 #[tokio::test]
 async fn test_get_rooms_authorized() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
     // テストユーザー作成
-    let user_id = ctx.create_test_user(12345, "testuser").await;
+    let (username, github_id) = unique("testuser");
+    let user_id = ctx.create_test_user(github_id, &username).await;
     let token = common::create_test_jwt(&user_id.to_string());
 
     // テストルーム作成
-    ctx.create_test_room("general", true, user_id).await;
-    ctx.create_test_room("private", false, user_id).await;
+    let (general, _) = unique("general");
+    let (private, _) = unique("private");
+    ctx.create_test_room(&general, true, user_id).await;
+    let private_id = ctx.create_test_room(&private, false, user_id).await;
+    ctx.add_test_member(private_id, user_id, "owner").await;
 
     let response = app
         .oneshot(
@@ -99,7 +103,7 @@ async fn test_get_rooms_authorized() {
 
     assert_eq!(response.status(), StatusCode::OK);
 
-    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
     assert!(json["rooms"].is_array());
@@ -107,22 +111,34 @@ async fn test_get_rooms_authorized() {
     assert!(rooms.len() >= 2);
 
     // パブリックルームの確認
-    let general_room = rooms.iter().find(|r| r["name"] == "general").unwrap();
+    let general_room = rooms
+        .iter()
+        .find(|r| r["name"] == general.as_str())
+        .unwrap();
     assert_eq!(general_room["is_public"], true);
 
     // プライベートルームの確認
-    let private_room = rooms.iter().find(|r| r["name"] == "private").unwrap();
+    let private_room = rooms
+        .iter()
+        .find(|r| r["name"] == private.as_str())
+        .unwrap();
     assert_eq!(private_room["is_public"], false);
 }
 
 #[tokio::test]
 async fn test_get_messages() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
     // テストデータ準備
-    let user_id = ctx.create_test_user(12345, "testuser").await;
-    let room_id = ctx.create_test_room("testroom", true, user_id).await;
+    let (username, github_id) = unique("testuser");
+    let user_id = ctx.create_test_user(github_id, &username).await;
+    let (room_name, _) = unique("testroom");
+    let room_id = ctx.create_test_room(&room_name, true, user_id).await;
     let token = common::create_test_jwt(&user_id.to_string());
 
     // テストメッセージ作成
@@ -134,7 +150,7 @@ async fn test_get_messages() {
     let response = app
         .oneshot(
             Request::builder()
-                .uri("/api/chat/testroom/messages")
+                .uri(format!("/api/chat/{}/messages", room_name))
                 .header("Authorization", format!("Bearer {}", token))
                 .body(Body::empty())
                 .unwrap(),
@@ -144,7 +160,7 @@ async fn test_get_messages() {
 
     assert_eq!(response.status(), StatusCode::OK);
 
-    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
     assert!(json["messages"].is_array());
@@ -159,22 +175,28 @@ async fn test_get_messages() {
 #[tokio::test]
 async fn test_send_message() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
     // テストデータ準備
-    let user_id = ctx.create_test_user(12345, "testuser").await;
-    let _room_id = ctx.create_test_room("testroom", true, user_id).await;
+    let (username, github_id) = unique("testuser");
+    let user_id = ctx.create_test_user(github_id, &username).await;
+    let (room_name, _) = unique("testroom");
+    ctx.create_test_room(&room_name, true, user_id).await;
     let token = common::create_test_jwt(&user_id.to_string());
 
     let request_body = json!({
         "content": "New test message",
-        "message_type": "text"
+        "message_type": "Text"
     });
 
     let response = app
         .oneshot(
             Request::builder()
-                .uri("/api/chat/testroom/send")
+                .uri(format!("/api/chat/{}/send", room_name))
                 .method("POST")
                 .header("Authorization", format!("Bearer {}", token))
                 .header("Content-Type", "application/json")
@@ -186,7 +208,7 @@ async fn test_send_message() {
 
     assert_eq!(response.status(), StatusCode::OK);
 
-    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
     assert!(json["message_id"].is_string());
@@ -196,13 +218,19 @@ async fn test_send_message() {
 #[tokio::test]
 async fn test_create_room() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
-    let user_id = ctx.create_test_user(12345, "testuser").await;
+    let (username, github_id) = unique("testuser");
+    let user_id = ctx.create_test_user(github_id, &username).await;
     let token = common::create_test_jwt(&user_id.to_string());
 
+    let (room_name, _) = unique("newroom");
     let request_body = json!({
-        "name": "newroom",
+        "name": room_name,
         "description": "A new test room",
         "is_public": false
     });
@@ -222,21 +250,87 @@ async fn test_create_room() {
 
     assert_eq!(response.status(), StatusCode::OK);
 
-    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
-    assert_eq!(json["name"], "newroom");
+    assert_eq!(json["name"], room_name.as_str());
     assert_eq!(json["description"], "A new test room");
     assert_eq!(json["is_public"], false);
     assert!(json["id"].is_string());
 }
 
+#[tokio::test]
+async fn test_create_room_rejects_invalid_names() {
+    let ctx = TestContext::new().await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
+
+    let (username, github_id) = unique("testuser");
+    let user_id = ctx.create_test_user(github_id, &username).await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    for name in ["my room", "雑談", "a/b", "x"] {
+        let request_body = json!({ "name": name, "is_public": true });
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/chat/rooms")
+                    .method("POST")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "name: {}", name);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "VALIDATION_ERROR");
+        assert!(json["error"]["message"].is_string());
+    }
+
+    // 前後の空白と大文字は正規化される
+    let (room_name, _) = unique("new-room");
+    let request_body =
+        json!({ "name": format!("  {} ", room_name.to_uppercase()), "is_public": true });
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/chat/rooms")
+                .method("POST")
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(request_body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["name"], room_name.as_str());
+}
+
 #[tokio::test]
 async fn test_room_not_found() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
-    let user_id = ctx.create_test_user(12345, "testuser").await;
+    let (username, github_id) = unique("testuser");
+    let user_id = ctx.create_test_user(github_id, &username).await;
     let token = common::create_test_jwt(&user_id.to_string());
 
     let response = app
@@ -256,10 +350,16 @@ async fn test_room_not_found() {
 #[tokio::test]
 async fn test_pagination() {
     let ctx = TestContext::new().await;
-    let app = create_test_app(ctx.pool.clone()).await;
+    if ctx.pool.is_none() {
+        eprintln!("Skipping API test: database not available");
+        return;
+    }
+    let app = ctx.create_app().await;
 
-    let user_id = ctx.create_test_user(12345, "testuser").await;
-    let room_id = ctx.create_test_room("testroom", true, user_id).await;
+    let (username, github_id) = unique("testuser");
+    let user_id = ctx.create_test_user(github_id, &username).await;
+    let (room_name, _) = unique("testroom");
+    let room_id = ctx.create_test_room(&room_name, true, user_id).await;
     let token = common::create_test_jwt(&user_id.to_string());
 
     // 大量のメッセージ作成
@@ -272,7 +372,7 @@ async fn test_pagination() {
     let response = app
         .oneshot(
             Request::builder()
-                .uri("/api/chat/testroom/messages?limit=10")
+                .uri(format!("/api/chat/{}/messages?limit=10", room_name))
                 .header("Authorization", format!("Bearer {}", token))
                 .body(Body::empty())
                 .unwrap(),
@@ -282,7 +382,7 @@ async fn test_pagination() {
 
     assert_eq!(response.status(), StatusCode::OK);
 
-    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
     let messages = json["messages"].as_array().unwrap();
@@ -1,6 +1,14 @@
 use axum::Router;
 use meilisearch_sdk::client::Client as MeilisearchClient;
+use miuchi_chat::api::auth::JwtKeys;
+use miuchi_chat::api::pagination::PageLimits;
+use miuchi_chat::config::{
+    Config, ConnectionLimitPolicy, ModerationMode, DEFAULT_JWT_AUDIENCE, DEFAULT_JWT_ISSUER,
+    DEFAULT_MAX_ROOM_MESSAGE_LENGTH,
+};
+use miuchi_chat::moderation::BannedWordsModerator;
 use sqlx::PgPool;
+use std::sync::Arc;
 
 pub struct TestContext {
     pub pool: Option<PgPool>,
@@ -77,7 +85,7 @@ impl TestContext {
             }
         };
 
-        miuchi_chat::create_app(pool, self.meili_client.clone()).await
+        miuchi_chat::create_app(pool, self.meili_client.clone(), test_config()).await
     }
 
     /// テスト用のユーザーを作成
@@ -122,6 +130,19 @@ impl TestContext {
         }
     }
 
+    /// テスト用のルームメンバーを追加
+    pub async fn add_test_member(&self, room_id: uuid::Uuid, user_id: uuid::Uuid, role: &str) {
+        if let Some(ref pool) = self.pool {
+            sqlx::query("INSERT INTO room_members (room_id, user_id, role) VALUES ($1, $2, $3)")
+                .bind(room_id)
+                .bind(user_id)
+                .bind(role)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+
     /// テスト用のメッセージを作成
     pub async fn create_test_message(
         &self,
@@ -147,9 +168,79 @@ impl TestContext {
     }
 }
 
+/// テスト用の設定（create_test_jwt と同じシークレットを使用）
+pub fn test_config() -> Arc<Config> {
+    Arc::new(Config {
+        database_url: std::env::var("DATABASE_URL").unwrap_or_default(),
+        db_max_connections: 5,
+        db_acquire_timeout: std::time::Duration::from_secs(5),
+        db_idle_timeout: std::time::Duration::from_secs(60),
+        db_connect_max_retries: 0,
+        meili_url: "http://localhost:7700".to_string(),
+        meili_master_key: None,
+        jwt_secret: "test_secret".to_string(),
+        jwt_keys: JwtKeys::new("test_secret", DEFAULT_JWT_AUDIENCE, DEFAULT_JWT_ISSUER),
+        jwt_audience: DEFAULT_JWT_AUDIENCE.to_string(),
+        jwt_issuer: DEFAULT_JWT_ISSUER.to_string(),
+        dev_mode: true,
+        base_url: "http://localhost:3001".to_string(),
+        frontend_url: "http://localhost:5173".to_string(),
+        github_client_id: "dummy_client_id".to_string(),
+        github_client_secret: "dummy_client_secret".to_string(),
+        auth_rate_limit: 1000,
+        search_rate_limit: 1000,
+        message_page_limits: PageLimits {
+            default: 50,
+            max: 100,
+        },
+        search_page_limits: PageLimits {
+            default: 20,
+            max: 100,
+        },
+        trusted_proxy_count: 0,
+        github_allowed_org: None,
+        ws_broadcast_capacity: 100,
+        max_rooms_per_user: 100,
+        max_room_message_length: DEFAULT_MAX_ROOM_MESSAGE_LENGTH,
+        hide_private_rooms: false,
+        heartbeat_interval: std::time::Duration::from_secs(30),
+        client_timeout: std::time::Duration::from_secs(60),
+        ws_send_timeout: std::time::Duration::from_secs(5),
+        max_connections_per_user: 5,
+        connection_limit_policy: ConnectionLimitPolicy::Reject,
+        metrics_token: Some("test_metrics_token".to_string()),
+        link_preview_enabled: false,
+        render_markdown: false,
+        link_preview_allowed_domains: Vec::new(),
+        link_preview_denied_domains: Vec::new(),
+        webhook_allowed_domains: vec!["hooks.example.com".to_string()],
+        banned_words_file: None,
+        moderation_mode: ModerationMode::Reject,
+        moderator: Arc::new(BannedWordsModerator::new(
+            Vec::new(),
+            ModerationMode::Reject,
+        )),
+    })
+}
+
 /// テスト用JWT生成
 pub fn create_test_jwt(user_id: &str) -> String {
-    use chrono::{Duration, Utc};
+    create_test_jwt_with_issuer(user_id, DEFAULT_JWT_ISSUER)
+}
+
+/// `iss` を指定したテスト用JWT生成
+pub fn create_test_jwt_with_issuer(user_id: &str, issuer: &str) -> String {
+    encode_test_jwt(user_id, issuer, chrono::Duration::hours(24))
+}
+
+/// 有効期限切れのテスト用JWT生成
+#[allow(dead_code)]
+pub fn create_expired_test_jwt(user_id: &str) -> String {
+    encode_test_jwt(user_id, DEFAULT_JWT_ISSUER, -chrono::Duration::hours(1))
+}
+
+fn encode_test_jwt(user_id: &str, issuer: &str, expires_in: chrono::Duration) -> String {
+    use chrono::Utc;
     use jsonwebtoken::{encode, EncodingKey, Header};
     use serde::{Deserialize, Serialize};
 
@@ -158,14 +249,18 @@ pub fn create_test_jwt(user_id: &str) -> String {
         sub: String,
         username: String,
         exp: usize,
+        iat: usize,
         aud: String,
+        iss: String,
     }
 
     let claims = Claims {
         sub: user_id.to_string(),
         username: "test_user".to_string(),
-        exp: (Utc::now() + Duration::hours(24)).timestamp() as usize,
-        aud: "miuchi.chat".to_string(),
+        exp: (Utc::now() + expires_in).timestamp() as usize,
+        iat: Utc::now().timestamp() as usize,
+        aud: DEFAULT_JWT_AUDIENCE.to_string(),
+        iss: issuer.to_string(),
     };
 
     let secret = "test_secret";
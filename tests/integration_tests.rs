@@ -2,12 +2,12 @@
  * Production Ready Integration Tests
  * HTTP API エンドポイントの基本的な統合テスト
  */
-
 use axum::{
     body::Body,
     http::{Method, Request, StatusCode},
 };
 use http_body_util::BodyExt;
+use meilisearch_sdk::client::Client as MeilisearchClient;
 use serde_json::Value;
 use tower::ServiceExt;
 
@@ -57,6 +57,99 @@ async fn test_health_endpoints() {
     );
 }
 
+/// レスポンスには x-request-id が付き、クライアントが指定した値はそのまま返る
+#[tokio::test]
+async fn test_request_id_header() {
+    let ctx = TestContext::new().await;
+    let app = ctx.create_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/health")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let generated = response
+        .headers()
+        .get("x-request-id")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(generated.parse::<uuid::Uuid>().is_ok());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/health")
+                .header("x-request-id", "client-trace-1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers().get("x-request-id").unwrap(),
+        "client-trace-1"
+    );
+}
+
+/// ログインのルートは接続元ごと、検索はユーザーごとに上限を超えると Retry-After 付きの 429 を返す
+#[tokio::test]
+async fn test_http_rate_limits() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping HTTP rate limit test: database not available");
+        return;
+    };
+
+    let mut config = (*common::test_config()).clone();
+    config.auth_rate_limit = 2;
+    config.search_rate_limit = 1;
+    let app =
+        miuchi_chat::create_app(pool, ctx.meili_client.clone(), std::sync::Arc::new(config)).await;
+
+    let request = |uri: &str, token: Option<&str>| {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    for _ in 0..2 {
+        let response = request("/api/auth/login-url", None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = request("/api/auth/login-url", None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+
+    // ログイン以外の認証ルートは数えない
+    let response = request("/api/auth/me", None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // 検索はユーザーごとに数える
+    let alice = common::create_test_jwt(&uuid::Uuid::new_v4().to_string());
+    let bob = common::create_test_jwt(&uuid::Uuid::new_v4().to_string());
+    let response = request("/api/search/messages?q=hello", Some(&alice))
+        .await
+        .unwrap();
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = request("/api/search/messages?q=hello", Some(&alice))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let response = request("/api/search/messages?q=hello", Some(&bob))
+        .await
+        .unwrap();
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
 /// 認証フローの基本テスト
 #[tokio::test]
 async fn test_authentication_flow() {
@@ -93,14 +186,349 @@ async fn test_authentication_flow() {
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
+/// login-url は PKCE のチャレンジを含み、verifier が保存されていない state での callback は拒否される
+#[tokio::test]
+async fn test_oauth_callback_requires_pkce_verifier() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping PKCE test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/auth/login-url")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let login_url = json["login_url"].as_str().unwrap();
+    assert!(login_url.contains("code_challenge="));
+    assert!(login_url.contains("code_challenge_method=S256"));
+    let state = json["state"].as_str().unwrap().to_string();
+
+    // この state の verifier だけを消す（改ざん・使用済みの state と同じ扱いになる）
+    // verifier は state トークンの nonce をキーに保存されている
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    let payload = state.split('.').nth(1).unwrap();
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    let deleted = sqlx::query("DELETE FROM oauth_pkce_verifiers WHERE nonce = $1")
+        .bind(claims["nonce"].as_str().unwrap())
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(deleted.rows_affected(), 1);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/auth/callback?code=dummy&state={}", state))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// 開発用ユーザーがいないDBでも dev-login で使えるトークンが得られる
+#[tokio::test]
+async fn test_dev_login_creates_missing_dev_user() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping dev-login test: database not available");
+        return;
+    };
+
+    // 他のデータから参照されていて削除できない場合は、既存ユーザーのまま検証する
+    let _ = sqlx::query("DELETE FROM users WHERE github_id = 999999")
+        .execute(&pool)
+        .await;
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/auth/dev-login")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let token = json["access_token"].as_str().unwrap().to_string();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/auth/me")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["username"], "dev_user");
+}
+
+/// 名前付きの dev-login は名前ごとに別の（同じ名前なら同じ）ユーザーになる
+#[tokio::test]
+async fn test_dev_login_with_username() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping named dev-login test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let login = |username: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/auth/dev-login?username={}", username))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+    let user_id = |token: String| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/auth/me")
+                        .header("authorization", format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let json: Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            json["id"].as_str().unwrap().to_string()
+        }
+    };
+    let token = |response: axum::response::Response| async move {
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        json["access_token"].as_str().unwrap().to_string()
+    };
+
+    let alice = user_id(token(login("alice").await.unwrap()).await).await;
+    let alice_again = user_id(token(login("alice").await.unwrap()).await).await;
+    let bob = user_id(token(login("bob").await.unwrap()).await).await;
+    assert_eq!(alice, alice_again);
+    assert_ne!(alice, bob);
+
+    let response = login("not%20valid").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// ログインごとにセッションが記録され、失効させたトークンは使えなくなる（接続中の WebSocket も閉じる）
+#[tokio::test]
+async fn test_sessions_list_and_revoke() {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping sessions test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+    let login = |username: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(format!("/api/auth/dev-login?username={}", username))
+                        .header("user-agent", "session-test/1.0")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let json: Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            json["access_token"].as_str().unwrap().to_string()
+        }
+    };
+    let request = |method: Method, uri: String, token: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+    let list = |uri: String, token: String| {
+        let request = request(Method::GET, uri, &token);
+        async move {
+            let response = request.await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            serde_json::from_slice::<Value>(
+                &response.into_body().collect().await.unwrap().to_bytes(),
+            )
+            .unwrap()
+        }
+    };
+
+    let older_token = login("session_owner").await;
+    let token = login("session_owner").await;
+    let other_token = login("session_other").await;
+
+    // 認証したセッションの last_seen が更新されるため、使っているセッションが先頭に来る
+    let page = list("/api/auth/sessions?limit=1".to_string(), token.clone()).await;
+    assert_eq!(page["sessions"].as_array().unwrap().len(), 1);
+    assert_eq!(page["has_more"], true);
+    let current = &page["sessions"][0];
+    assert_eq!(current["current"], true);
+    assert_eq!(current["user_agent"], "session-test/1.0");
+    let jti = current["id"].as_str().unwrap().to_string();
+    assert_eq!(page["next_cursor"], jti.as_str());
+
+    let page = list(
+        format!("/api/auth/sessions?limit=1&before={}", jti),
+        token.clone(),
+    )
+    .await;
+    assert_eq!(page["sessions"].as_array().unwrap().len(), 1);
+    assert_ne!(page["sessions"][0]["id"], jti.as_str());
+    assert_eq!(page["sessions"][0]["current"], false);
+
+    let response = request(
+        Method::GET,
+        "/api/auth/sessions?before=not-a-cursor".to_string(),
+        &token,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // 失効させるセッションと別のセッションで WebSocket に接続しておく
+    let connect = |token: &str| {
+        let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+        request.headers_mut().insert(
+            "sec-websocket-protocol",
+            format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+        );
+        tokio_tungstenite::connect_async(request)
+    };
+    let (mut socket, _) = connect(&token).await.unwrap();
+    let (mut older_socket, _) = connect(&older_token).await.unwrap();
+    // server_time は接続の登録後に送られる
+    for socket in [&mut socket, &mut older_socket] {
+        tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+            .await
+            .expect("timed out waiting for server_time")
+            .expect("connection closed before server_time")
+            .unwrap();
+    }
+
+    // 他のユーザーのセッションは失効させられない
+    let response = request(
+        Method::DELETE,
+        format!("/api/auth/sessions/{}", jti),
+        &other_token,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = request(
+        Method::DELETE,
+        format!("/api/auth/sessions/{}", jti),
+        &token,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // 失効したセッションの WebSocket にはクローズフレームが届く
+    let close = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Close(frame) = message {
+                return frame;
+            }
+        }
+        None
+    })
+    .await
+    .expect("timed out waiting for close frame")
+    .expect("close frame should have a reason");
+    assert_eq!(close.reason.as_str(), "Session revoked");
+
+    // 別のセッションの接続は閉じない
+    let pending =
+        tokio::time::timeout(std::time::Duration::from_millis(200), older_socket.next()).await;
+    assert!(
+        !matches!(pending, Ok(Some(Ok(Message::Close(_)))) | Ok(None)),
+        "unexpected message: {:?}",
+        pending
+    );
+
+    // 失効したトークンは拒否される
+    let response = request(Method::GET, "/api/auth/me".to_string(), &token)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "AUTHENTICATION_ERROR");
+    assert_eq!(json["error"]["details"]["reason"], "revoked_session");
+
+    let response = request(Method::GET, "/api/auth/me".to_string(), &other_token)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
 /// API 可用性の基本テスト
 #[tokio::test]
 async fn test_api_availability() {
     let ctx = TestContext::new().await;
     let app = ctx.create_app().await;
 
-    // JWT作成
-    let token = common::create_test_jwt("test-user-id");
+    // JWT作成（sub はユーザー ID の UUID でなければ 400 になる）
+    let token = common::create_test_jwt(&uuid::Uuid::new_v4().to_string());
 
     // API エンドポイントの可用性確認（データベースが必要なものは適切なエラーレスポンスを期待）
     let endpoints = vec![
@@ -137,42 +565,4509 @@ async fn test_error_handling() {
     let ctx = TestContext::new().await;
     let app = ctx.create_app().await;
 
-    // 1. 未認証アクセス
+    // 1. 未認証アクセス・不正なトークン（401 の理由は details.reason で区別できる）
+    let expired_token = common::create_expired_test_jwt(&uuid::Uuid::new_v4().to_string());
+    let cases = [
+        (None, "missing_header"),
+        (Some("Basic dXNlcjpwYXNz".to_string()), "invalid_scheme"),
+        (Some("Bearer invalid_token".to_string()), "invalid_token"),
+        (Some(format!("Bearer {}", expired_token)), "token_expired"),
+    ];
+    for (authorization, reason) in cases {
+        let mut request = Request::builder().uri("/api/auth/me");
+        if let Some(authorization) = authorization {
+            request = request.header("authorization", authorization);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "AUTHENTICATION_ERROR");
+        assert_eq!(json["error"]["details"]["reason"], reason);
+    }
+
+    // 3. 存在しないエンドポイント
     let response = app
-        .clone()
         .oneshot(
             Request::builder()
-                .uri("/api/auth/me")
+                .uri("/api/nonexistent")
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
-    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Meilisearch停止時にPostgresフォールバックで検索結果が返ることを確認
+#[tokio::test]
+async fn test_search_fallback_when_meilisearch_unreachable() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping search fallback test: database not available");
+        return;
+    };
+
+    // テスト用JWTと同じシークレットを使用
+    std::env::set_var("JWT_SECRET", "test_secret");
+
+    // 到達不能なMeilisearchを指定
+    let unreachable_meili = MeilisearchClient::new("http://127.0.0.1:1", None::<String>).unwrap();
+    let app = miuchi_chat::create_app(pool, unreachable_meili, common::test_config()).await;
+
+    // テストデータ準備
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let github_id = (uuid::Uuid::new_v4().as_u128() as i64).abs();
+    let user_id = ctx
+        .create_test_user(github_id, &format!("fallback_{}", &suffix[..8]))
+        .await;
+    let room_id = ctx
+        .create_test_room(&format!("fallback_{}", &suffix[..8]), true, user_id)
+        .await;
+    ctx.create_test_message(room_id, user_id, &format!("needle {}", suffix))
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
 
-    // 2. 不正なトークン
     let response = app
-        .clone()
         .oneshot(
             Request::builder()
-                .uri("/api/auth/me")
-                .header("authorization", "Bearer invalid_token")
+                .uri(format!("/api/search/messages?q={}", suffix))
+                .header("authorization", format!("Bearer {}", token))
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
-    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["degraded"], true);
+
+    let results = json["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0]["message"]["content"]
+        .as_str()
+        .unwrap()
+        .contains(&suffix));
+    assert!(results[0]["highlights"].as_array().unwrap().is_empty());
+}
+
+/// 検索が無効（Meilisearch クライアントなし）でもチャットは動き、検索は 503 を返す
+#[tokio::test]
+async fn test_search_disabled_returns_503_but_chat_works() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping disabled search test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, None::<MeilisearchClient>, common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("nosearch_{}", suffix),
+        )
+        .await;
+    let room_name = format!("nosearch_{}", suffix);
+    ctx.create_test_room(&room_name, true, user_id).await;
+    let token = common::create_test_jwt(&user_id.to_string());
 
-    // 3. 存在しないエンドポイント
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
-                .uri("/api/nonexistent")
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/send", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "content": "hello" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/search/messages?q=hello")
+                .header("authorization", format!("Bearer {}", token))
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
-    assert_eq!(response.status(), StatusCode::NOT_FOUND);
-}
\ No newline at end of file
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "SERVICE_UNAVAILABLE");
+}
+/// REST API で送信したメッセージも WebSocket で配信され、検索できる
+/// REST API で送信したメッセージも検索できる
+///
+/// Meilisearch が使える場合はインデックス登録を待ち、使えない場合は DB 検索へのフォールバックで確認する。
+#[tokio::test]
+async fn test_rest_sent_message_is_searchable() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping REST send search test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let github_id = (uuid::Uuid::new_v4().as_u128() as i64).abs();
+    let user_id = ctx
+        .create_test_user(github_id, &format!("restsend_{}", &suffix[..8]))
+        .await;
+    let room_name = format!("restsend_{}", &suffix[..8]);
+    ctx.create_test_room(&room_name, true, user_id).await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    // 別のユーザーが WebSocket でルームに参加しておく
+    let watcher_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("restwatch_{}", &suffix[..8]),
+        )
+        .await;
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!(
+            "miuchi-chat, bearer.{}",
+            common::create_test_jwt(&watcher_id.to_string())
+        )
+        .parse()
+        .unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    socket
+        .send(Message::Text(
+            serde_json::to_string(&WsMessage::JoinRoom {
+                room: room_name.clone(),
+            })
+            .unwrap()
+            .into(),
+        ))
+        .await
+        .unwrap();
+    // 指定の条件に合うメッセージが届くまで待つ
+    async fn receive<S>(socket: &mut S, matches: impl Fn(&WsMessage) -> bool) -> WsMessage
+    where
+        S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+            + Unpin,
+    {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while let Some(Ok(message)) = socket.next().await {
+                if let Message::Text(text) = message {
+                    if let Ok(message) = serde_json::from_str::<WsMessage>(&text) {
+                        if matches(&message) {
+                            return message;
+                        }
+                    }
+                }
+            }
+            panic!("connection closed");
+        })
+        .await
+        .expect("timed out waiting for websocket message")
+    }
+    receive(&mut socket, |m| matches!(m, WsMessage::RoomJoined { .. })).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/{}/send", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "content": format!("needle {}", suffix) }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let message_id = json["message_id"].as_str().unwrap().to_string();
+
+    // WebSocket から送信した場合と同じ形で配信される
+    match receive(&mut socket, |m| matches!(m, WsMessage::Message { .. })).await {
+        WsMessage::Message {
+            id,
+            room,
+            user_id: author_id,
+            username,
+            content,
+            message_type,
+            ..
+        } => {
+            assert_eq!(id, message_id);
+            assert_eq!(room, room_name);
+            assert_eq!(author_id, user_id.to_string());
+            assert_eq!(username, format!("restsend_{}", &suffix[..8]));
+            assert_eq!(content, format!("needle {}", suffix));
+            assert_eq!(message_type, "text");
+        }
+        other => panic!("expected message, got {:?}", other),
+    }
+
+    // Meilisearch のインデックス登録は非同期なので、見つかるまで少し待つ
+    let mut found = false;
+    for _ in 0..50 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/search/messages?q={}", suffix))
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        if json["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|result| result["message"]["id"] == message_id.as_str())
+        {
+            found = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(found, "REST-sent message should be searchable");
+}
+
+/// 招待時のユーザー名は大文字小文字を区別しない
+#[tokio::test]
+async fn test_invite_user_with_mismatched_case() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping invite test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("owner_{}", suffix),
+        )
+        .await;
+    let invitee_name = format!("alice_{}", suffix);
+    let invitee_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &invitee_name)
+        .await;
+    let room_name = format!("invite_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner_id).await;
+    ctx.add_test_member(room_id, owner_id, "owner").await;
+    let token = common::create_test_jwt(&owner_id.to_string());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/invite", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "username": format!("  {} ", invitee_name.to_uppercase()) })
+                        .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["success"], true);
+
+    let is_member: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM room_members WHERE room_id = $1 AND user_id = $2)",
+    )
+    .bind(room_id)
+    .bind(invitee_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(is_member);
+}
+
+/// 招待が監査ログに記録され、オーナーのみ閲覧できることを確認
+#[tokio::test]
+async fn test_room_audit_log_records_invites() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping audit log test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("owner_{}", suffix),
+        )
+        .await;
+    let invitee_name = format!("bob_{}", suffix);
+    let invitee_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &invitee_name)
+        .await;
+    let room_name = format!("audit_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner_id).await;
+    ctx.add_test_member(room_id, owner_id, "owner").await;
+    let owner_token = common::create_test_jwt(&owner_id.to_string());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/invite", room_name))
+                .header("authorization", format!("Bearer {}", owner_token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "username": invitee_name }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/audit", room_name))
+                .header("authorization", format!("Bearer {}", owner_token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 1);
+    assert_eq!(json["entries"][0]["action"], "member.invite");
+    assert_eq!(json["entries"][0]["actor_id"], owner_id.to_string());
+    assert_eq!(json["entries"][0]["target_id"], invitee_id.to_string());
+
+    // 招待されたメンバー（オーナーではない）は閲覧できない
+    let invitee_token = common::create_test_jwt(&invitee_id.to_string());
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/audit", room_name))
+                .header("authorization", format!("Bearer {}", invitee_token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+/// 一括招待はユーザー名ごとの結果を返し、一部の失敗で全体が失敗しない
+#[tokio::test]
+async fn test_bulk_invite_reports_per_username_results() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping bulk invite test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_name = format!("owner_{}", suffix);
+    let owner_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &owner_name)
+        .await;
+    let carol_name = format!("carol_{}", suffix);
+    ctx.create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &carol_name)
+        .await;
+    let room_name = format!("bulk_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner_id).await;
+    ctx.add_test_member(room_id, owner_id, "owner").await;
+    let token = common::create_test_jwt(&owner_id.to_string());
+
+    let missing_name = format!("missing_{}", suffix);
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/invite-bulk", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "usernames": [carol_name, owner_name, missing_name] })
+                        .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let statuses: Vec<&str> = json["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, ["invited", "already_member", "not_found"]);
+    assert!(json["results"][2]["user_id"].is_null());
+}
+
+/// 招待できない場合は 200 ではなくエラーステータスと構造化されたエラーを返す
+#[tokio::test]
+async fn test_invite_user_error_statuses() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping invite error test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_name = format!("owner_{}", suffix);
+    let owner_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &owner_name)
+        .await;
+    let dave_name = format!("dave_{}", suffix);
+    let dave_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &dave_name)
+        .await;
+    let room_name = format!("inverr_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner_id).await;
+    ctx.add_test_member(room_id, owner_id, "owner").await;
+    ctx.add_test_member(room_id, dave_id, "member").await;
+    let public_name = format!("invpub_{}", suffix);
+    ctx.create_test_room(&public_name, true, owner_id).await;
+    let token = common::create_test_jwt(&owner_id.to_string());
+
+    let cases = [
+        (
+            room_name.clone(),
+            owner_name.to_uppercase(),
+            StatusCode::BAD_REQUEST,
+            "BAD_REQUEST",
+        ),
+        (
+            room_name.clone(),
+            dave_name.clone(),
+            StatusCode::CONFLICT,
+            "CONFLICT",
+        ),
+        (
+            room_name.clone(),
+            format!("missing_{}", suffix),
+            StatusCode::NOT_FOUND,
+            "NOT_FOUND",
+        ),
+        (
+            public_name.clone(),
+            dave_name.clone(),
+            StatusCode::BAD_REQUEST,
+            "BAD_REQUEST",
+        ),
+    ];
+    for (room, username, status, code) in cases {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/chat/{}/invite", room))
+                    .header("authorization", format!("Bearer {}", token))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({ "username": username }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            status,
+            "inviting {} into {}",
+            username,
+            room
+        );
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], code);
+    }
+}
+
+/// 同名ルームを同時に作成しても1つだけ成功し、もう一方は 409 ROOM_EXISTS になる
+#[tokio::test]
+async fn test_concurrent_create_room_conflict() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping concurrent create test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("creator_{}", suffix),
+        )
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
+    let room_name = format!("race_{}", suffix);
+
+    let create = |app: axum::Router| {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/chat/rooms")
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({ "name": room_name, "is_public": true }).to_string(),
+            ))
+            .unwrap();
+        app.oneshot(request)
+    };
+
+    let (first, second) = tokio::join!(create(app.clone()), create(app));
+    let mut statuses = [first.unwrap(), second.unwrap()];
+    statuses.sort_by_key(|response| response.status());
+    let [ok, conflict] = statuses;
+    assert_eq!(ok.status(), StatusCode::OK);
+    assert_eq!(conflict.status(), StatusCode::CONFLICT);
+
+    let body = conflict.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "ROOM_EXISTS");
+}
+
+/// ルーム一覧にメンバー数とメッセージ数が含まれる
+#[tokio::test]
+async fn test_room_list_includes_counts() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping room counts test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let alice = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("alice_{}", suffix),
+        )
+        .await;
+    let bob = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("bob_{}", suffix),
+        )
+        .await;
+    let room_name = format!("stats_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, alice).await;
+    ctx.create_test_message(room_id, alice, "one").await;
+    ctx.create_test_message(room_id, alice, "two").await;
+    ctx.create_test_message(room_id, bob, "three").await;
+    let token = common::create_test_jwt(&alice.to_string());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/rooms?q={}", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let room = &json["rooms"][0];
+    assert_eq!(room["name"], room_name);
+    // パブリックルームは発言したユーザー数
+    assert_eq!(room["member_count"], 2);
+    assert_eq!(room["message_count"], 3);
+}
+
+/// /metrics はトークンが必要で、Prometheus 形式で認証失敗数などを返す
+#[tokio::test]
+async fn test_metrics_endpoint_requires_token() {
+    let ctx = TestContext::new().await;
+    let app = ctx.create_app().await;
+
+    // 認証失敗を1件発生させる
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/chat/rooms")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/metrics")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/metrics")
+                .header("authorization", "Bearer test_metrics_token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("miuchi_auth_failures_total"));
+    assert!(text.contains("miuchi_online_users"));
+}
+
+/// /api/ws/stats は /metrics と同じトークンで WebSocket の集計情報を返す
+#[tokio::test]
+async fn test_ws_stats_requires_metrics_token() {
+    let ctx = TestContext::new().await;
+    let app = ctx.create_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/ws/stats")
+                .header("authorization", "Bearer wrong_token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/ws/stats?limit=5")
+                .header("authorization", "Bearer test_metrics_token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total_connections"], 0);
+    assert_eq!(json["active_rooms"], 0);
+    assert!(json["rooms"].as_array().unwrap().is_empty());
+    assert!(json["messages_broadcast"].is_u64());
+}
+
+/// レディネスチェックが依存サービスごとの状態を返すことを確認
+#[tokio::test]
+async fn test_ready_reports_dependency_status() {
+    let ctx = TestContext::new().await;
+    let app = ctx.create_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/ready")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["checks"]["meilisearch"]["status"].is_string());
+    assert!(json["timestamp"].is_string());
+
+    // PostgreSQL が停止している場合のみ 503 になる（Meilisearch の停止は degraded）
+    if json["checks"]["database"]["status"] == "up" {
+        assert_eq!(status, StatusCode::OK);
+        assert!(json["status"] == "ok" || json["status"] == "degraded");
+    } else {
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["status"], "unavailable");
+    }
+}
+
+/// strict=true の場合は範囲外の limit を丸めずに 400 を返す
+#[tokio::test]
+async fn test_messages_strict_limit_validation() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping strict limit test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("strict_{}", suffix),
+        )
+        .await;
+    let room_name = format!("strict_{}", suffix);
+    ctx.create_test_room(&room_name, true, owner).await;
+    let token = common::create_test_jwt(&owner.to_string());
+
+    // 既定では丸められる
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages?limit=500", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for limit in ["0", "101"] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/api/chat/{}/messages?limit={}&strict=true",
+                        room_name, limit
+                    ))
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "limit: {}",
+            limit
+        );
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "VALIDATION_ERROR");
+        assert_eq!(json["error"]["details"]["max"], 100);
+    }
+}
+
+/// メッセージの取得には認証が必要で、プライベートルームはメンバーしか読めない
+#[tokio::test]
+async fn test_messages_require_membership() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping message membership test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("owner_{}", suffix),
+        )
+        .await;
+    let outsider = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("outsider_{}", suffix),
+        )
+        .await;
+    let room_name = format!("members_only_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner).await;
+    ctx.add_test_member(room_id, owner, "owner").await;
+    ctx.create_test_message(room_id, owner, "secret").await;
+
+    let fetch = |token: Option<String>| {
+        let mut request = Request::builder().uri(format!("/api/chat/{}/messages", room_name));
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    let response = fetch(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = fetch(Some(common::create_test_jwt(&outsider.to_string())))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = fetch(Some(common::create_test_jwt(&owner.to_string())))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"][0]["content"], "secret");
+}
+
+/// 設定でメッセージ履歴の limit の上限を変えられる
+#[tokio::test]
+async fn test_messages_limit_cap_from_config() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping message limit config test: database not available");
+        return;
+    };
+
+    let mut config = (*common::test_config()).clone();
+    config.message_page_limits = miuchi_chat::api::pagination::PageLimits { default: 3, max: 5 };
+    let app =
+        miuchi_chat::create_app(pool, ctx.meili_client.clone(), std::sync::Arc::new(config)).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("cap_{}", suffix),
+        )
+        .await;
+    let room_name = format!("cap_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, owner).await;
+    for i in 0..7 {
+        ctx.create_test_message(room_id, owner, &format!("message {}", i))
+            .await;
+    }
+    let token = common::create_test_jwt(&owner.to_string());
+
+    let fetch = |query: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages?{}", room_name, query))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = fetch("").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"].as_array().unwrap().len(), 3);
+
+    let response = fetch("limit=10").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"].as_array().unwrap().len(), 5);
+
+    let response = fetch("limit=10&strict=true").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["error"]["details"]["max"], 5);
+}
+
+/// 複数ルームの最新メッセージを一括取得でき、閲覧できないルームは含まれない
+#[tokio::test]
+async fn test_messages_batch() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping batch messages test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("batch_o_{}", suffix),
+        )
+        .await;
+    let viewer = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("batch_v_{}", suffix),
+        )
+        .await;
+    let public_a = format!("batch_a_{}", suffix);
+    let public_b = format!("batch_b_{}", suffix);
+    let private = format!("batch_p_{}", suffix);
+    let room_a = ctx.create_test_room(&public_a, true, owner).await;
+    ctx.create_test_room(&public_b, true, owner).await;
+    let room_p = ctx.create_test_room(&private, false, owner).await;
+    ctx.create_test_message(room_a, owner, "a1").await;
+    ctx.create_test_message(room_a, owner, "a2").await;
+    ctx.create_test_message(room_a, owner, "a3").await;
+    ctx.create_test_message(room_p, owner, "secret").await;
+    let token = common::create_test_jwt(&viewer.to_string());
+
+    let post = |body: Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri("/api/chat/messages/batch")
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+
+    let response = post(serde_json::json!({
+        "rooms": [public_a, public_b, private, format!("batch_x_{}", suffix)],
+        "limit_per_room": 2
+    }))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let rooms = json["rooms"].as_object().unwrap();
+    assert_eq!(rooms.len(), 2);
+    let contents: Vec<&str> = rooms[&public_a]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents, vec!["a3", "a2"]);
+    assert_eq!(rooms[&public_b], serde_json::json!([]));
+    assert!(!rooms.contains_key(&private));
+
+    let response = post(serde_json::json!({ "rooms": [] })).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let too_many: Vec<String> = (0..51).map(|i| format!("room_{}", i)).collect();
+    let response = post(serde_json::json!({ "rooms": too_many }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// メッセージ一覧は弱い ETag を返し、変化がなければ 304、投稿・編集・削除で ETag が変わる
+#[tokio::test]
+async fn test_messages_conditional_get() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping conditional GET test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("etag_{}", suffix),
+        )
+        .await;
+    let room_name = format!("etag_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, user_id).await;
+    let first_id = ctx.create_test_message(room_id, user_id, "first").await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let get = |if_none_match: Option<String>| {
+        let mut request = Request::builder()
+            .uri(format!("/api/chat/{}/messages", room_name))
+            .header("authorization", format!("Bearer {}", token));
+        if let Some(etag) = if_none_match {
+            request = request.header("if-none-match", etag);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+    let etag_of = |response: &axum::response::Response| {
+        response.headers()["etag"].to_str().unwrap().to_string()
+    };
+
+    let response = get(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = etag_of(&response);
+    assert!(etag.starts_with("W/\""));
+
+    let response = get(Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(etag_of(&response), etag);
+    assert!(response
+        .into_body()
+        .collect()
+        .await
+        .unwrap()
+        .to_bytes()
+        .is_empty());
+
+    // 新しい投稿
+    ctx.create_test_message(room_id, user_id, "second").await;
+    let response = get(Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let after_post = etag_of(&response);
+    assert_ne!(after_post, etag);
+
+    // 編集
+    sqlx::query("UPDATE messages SET content = 'edited', updated_at = NOW() + INTERVAL '1 second' WHERE id = $1")
+        .bind(first_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let response = get(Some(after_post.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let after_edit = etag_of(&response);
+    assert_ne!(after_edit, after_post);
+
+    // 最新ではないメッセージの削除
+    sqlx::query("DELETE FROM messages WHERE id = $1")
+        .bind(first_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let response = get(Some(after_edit.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let after_delete = etag_of(&response);
+    assert_ne!(after_delete, after_edit);
+
+    // 投稿者のユーザー名の変更
+    sqlx::query(
+        "UPDATE users SET username = $1, updated_at = NOW() + INTERVAL '1 second' WHERE id = $2",
+    )
+    .bind(format!("etag_renamed_{}", suffix))
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let response = get(Some(after_delete.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let after_rename = etag_of(&response);
+    assert_ne!(after_rename, after_delete);
+
+    // Markdown 描画の切り替え（rendered_html が変わる）
+    let mut config = (*common::test_config()).clone();
+    config.render_markdown = !config.render_markdown;
+    let rendering_app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        std::sync::Arc::new(config),
+    )
+    .await;
+    let response = rendering_app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("if-none-match", after_rename.clone())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(etag_of(&response), after_rename);
+}
+
+/// スローモードのルームでは間隔内の2通目が 429 になり、ルーム作成者は対象外
+#[tokio::test]
+async fn test_slow_mode_rejects_messages_inside_interval() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping slow mode test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("slow_o_{}", suffix),
+        )
+        .await;
+    let member_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("slow_m_{}", suffix),
+        )
+        .await;
+    let owner_token = common::create_test_jwt(&owner_id.to_string());
+    let member_token = common::create_test_jwt(&member_id.to_string());
+    let room_name = format!("slow_{}", suffix);
+
+    let post = |uri: String, token: &str, body: Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+
+    // 範囲外の間隔は作成できない
+    let response = post(
+        "/api/chat/rooms".to_string(),
+        &owner_token,
+        serde_json::json!({ "name": room_name, "is_public": true, "slow_mode_seconds": 0 }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = post(
+        "/api/chat/rooms".to_string(),
+        &owner_token,
+        serde_json::json!({ "name": room_name, "is_public": true, "slow_mode_seconds": 30 }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let send_uri = format!("/api/chat/{}/send", room_name);
+    let response = post(
+        send_uri.clone(),
+        &member_token,
+        serde_json::json!({ "content": "first" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = post(
+        send_uri.clone(),
+        &member_token,
+        serde_json::json!({ "content": "second" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=30).contains(&retry_after));
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "SLOW_MODE");
+    assert_eq!(json["error"]["details"]["retry_after"], retry_after);
+
+    // ルーム作成者（モデレーター）は続けて送信できる
+    for content in ["mod 1", "mod 2"] {
+        let response = post(
+            send_uri.clone(),
+            &owner_token,
+            serde_json::json!({ "content": content }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+/// ルームごとの文字数上限は既定の上限より長いメッセージを許可する（サーバーの上限まで）
+#[tokio::test]
+async fn test_room_max_message_length_override() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping room message length test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("paste_{}", suffix),
+        )
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
+    let paste_room = format!("paste_{}", suffix);
+    let default_room = format!("nopaste_{}", suffix);
+
+    let post = |uri: String, body: Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+
+    // サーバーの上限（MAX_ROOM_MESSAGE_LENGTH）を超える設定は作成できない
+    let response = post(
+        "/api/chat/rooms".to_string(),
+        serde_json::json!({ "name": paste_room, "is_public": true, "max_message_length": 20000 }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = post(
+        "/api/chat/rooms".to_string(),
+        serde_json::json!({ "name": paste_room, "is_public": true, "max_message_length": 10000 }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["max_message_length"], 10000);
+
+    let response = post(
+        "/api/chat/rooms".to_string(),
+        serde_json::json!({ "name": default_room, "is_public": true }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let content = "a".repeat(9000);
+    let response = post(
+        format!("/api/chat/{}/send", paste_room),
+        serde_json::json!({ "content": content }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // マルチバイト文字も文字数で数える
+    let response = post(
+        format!("/api/chat/{}/send", paste_room),
+        serde_json::json!({ "content": "あ".repeat(9000) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = post(
+        format!("/api/chat/{}/send", default_room),
+        serde_json::json!({ "content": content }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = post(
+        format!("/api/chat/{}/send", paste_room),
+        serde_json::json!({ "content": "a".repeat(10001) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// 同じルームのメッセージは引用でき、一覧に要約が付く。別のルームのメッセージは引用できない
+#[tokio::test]
+async fn test_reply_to_quotes_message_in_same_room_only() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping reply-to test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let author_name = format!("quote_a_{}", suffix);
+    let author_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &author_name)
+        .await;
+    let token = common::create_test_jwt(&author_id.to_string());
+    let room_name = format!("quote_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, author_id).await;
+    let other_room_id = ctx
+        .create_test_room(&format!("quote_other_{}", suffix), true, author_id)
+        .await;
+
+    let long_content = "あ".repeat(150);
+    let quoted_id = ctx
+        .create_test_message(room_id, author_id, &long_content)
+        .await;
+    let other_room_message_id = ctx
+        .create_test_message(other_room_id, author_id, "elsewhere")
+        .await;
+
+    let send = |body: Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/send", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+
+    // 別のルームのメッセージ・存在しない ID は引用できない
+    for reply_to in [
+        other_room_message_id.to_string(),
+        uuid::Uuid::new_v4().to_string(),
+    ] {
+        let response =
+            send(serde_json::json!({ "content": "nope", "reply_to_message_id": reply_to }))
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let response = send(
+        serde_json::json!({ "content": "reply", "reply_to_message_id": quoted_id.to_string() }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let messages = json["messages"].as_array().unwrap();
+
+    let reply = messages.iter().find(|m| m["content"] == "reply").unwrap();
+    assert_eq!(reply["quoted"]["id"], quoted_id.to_string());
+    assert_eq!(reply["quoted"]["username"], author_name);
+    // 本文は100文字に切り詰めて末尾に … を付ける
+    assert_eq!(
+        reply["quoted"]["content_preview"],
+        format!("{}…", "あ".repeat(100))
+    );
+
+    let original = messages
+        .iter()
+        .find(|m| m["id"] == quoted_id.to_string())
+        .unwrap();
+    assert!(original["quoted"].is_null());
+}
+
+/// log_membership_events が有効なルームでは、招待がシステムメッセージとして履歴に残る
+#[tokio::test]
+async fn test_membership_event_is_logged_as_system_message() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping membership event test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_name = format!("log_o_{}", suffix);
+    let guest_name = format!("log_g_{}", suffix);
+    let owner = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &owner_name)
+        .await;
+    ctx.create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &guest_name)
+        .await;
+    let token = common::create_test_jwt(&owner.to_string());
+    let room_name = format!("log_{}", suffix);
+
+    let post = |uri: String, body: Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+
+    let response = post(
+        "/api/chat/rooms".to_string(),
+        serde_json::json!({ "name": room_name, "is_public": false, "log_membership_events": true }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = post(
+        format!("/api/chat/{}/invite", room_name),
+        serde_json::json!({ "username": guest_name }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let messages = json["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["message_type"], "System");
+    assert_eq!(messages[0]["author_name"], "system");
+    assert_eq!(messages[0]["author_is_bot"], true);
+    assert!(messages[0]["content"]
+        .as_str()
+        .unwrap()
+        .contains(guest_name.as_str()));
+}
+
+/// log_membership_events が有効なルームでは、WebSocket での参加・退出もシステムメッセージとして履歴に残る
+#[tokio::test]
+async fn test_join_and_leave_are_logged_as_system_messages() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping join event test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let username = format!("log_j_{}", suffix);
+    let user_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &username)
+        .await;
+    let room_name = format!("log_j_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, user_id).await;
+    sqlx::query("UPDATE rooms SET log_membership_events = true WHERE id = $1")
+        .bind(room_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    for message in [
+        WsMessage::JoinRoom {
+            room: room_name.clone(),
+        },
+        WsMessage::LeaveRoom {
+            room: room_name.clone(),
+        },
+    ] {
+        socket
+            .send(Message::Text(
+                serde_json::to_string(&message).unwrap().into(),
+            ))
+            .await
+            .unwrap();
+    }
+    // 参加時の system メッセージは自分にも配信される
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                if let Ok(WsMessage::Message { message_type, .. }) = serde_json::from_str(&text) {
+                    if message_type == "system" {
+                        return;
+                    }
+                }
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for the join event");
+
+    let contents = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        loop {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/api/chat/{}/messages", room_name))
+                        .header("authorization", format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let json: Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            let messages = json["messages"].as_array().unwrap().clone();
+            if messages.len() == 2 {
+                return messages;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the leave event");
+
+    // 新しい順
+    assert!(contents.iter().all(|m| m["message_type"] == "System"));
+    assert_eq!(
+        contents[1]["content"],
+        format!("{}がルームに参加しました", username)
+    );
+    assert_eq!(
+        contents[0]["content"],
+        format!("{}がルームから退出しました", username)
+    );
+}
+
+/// after カーソルではカーソルより新しいメッセージが古い順に返り、before との併用は 400
+#[tokio::test]
+async fn test_messages_after_cursor() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping after cursor test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("after_{}", suffix),
+        )
+        .await;
+    let room_name = format!("after_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, owner).await;
+    let first = ctx.create_test_message(room_id, owner, "first").await;
+    ctx.create_test_message(room_id, owner, "second").await;
+    ctx.create_test_message(room_id, owner, "third").await;
+    let token = common::create_test_jwt(&owner.to_string());
+
+    let fetch = |query: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages?{}", room_name, query))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = fetch(format!("after={}&limit=1", first)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(json["messages"][0]["content"], "second");
+    assert_eq!(json["has_more"], true);
+    assert!(json["server_time"]
+        .as_str()
+        .is_some_and(|time| time.parse::<chrono::DateTime<chrono::Utc>>().is_ok()));
+
+    // next_cursor で続きを前方にたどれる
+    let next = json["next_cursor"].as_str().unwrap().to_string();
+    let response = fetch(format!("after={}", next)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(json["messages"][0]["content"], "third");
+    assert_eq!(json["has_more"], false);
+    assert_eq!(json["prev_cursor"], json["messages"][0]["id"]);
+
+    // 新しい順の取得では next_cursor が最古（上スクロール用）、prev_cursor が最新（追いつき用）
+    let response = fetch("limit=2".to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"][0]["content"], "third");
+    assert_eq!(json["messages"][1]["content"], "second");
+    assert_eq!(json["next_cursor"], json["messages"][1]["id"]);
+    assert_eq!(json["prev_cursor"], json["messages"][0]["id"]);
+
+    let older = json["next_cursor"].as_str().unwrap().to_string();
+    let response = fetch(format!("before={}", older)).await.unwrap();
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(json["messages"][0]["content"], "first");
+
+    let response = fetch(format!("after={}&before={}", first, first))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = fetch(format!("after={}", uuid::Uuid::new_v4()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// 一覧の `updated_at` / `edited` は DB の更新時刻から作られる（カーソルなし・`before` 指定の両方）
+#[tokio::test]
+async fn test_message_list_includes_updated_at() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping updated_at test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("edited_{}", suffix),
+        )
+        .await;
+    let room_name = format!("edited_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, owner).await;
+    let edited = ctx.create_test_message(room_id, owner, "edited").await;
+    let untouched = ctx.create_test_message(room_id, owner, "untouched").await;
+    // updated_at は更新時のトリガーで now() になるため、created_at を1分前にずらす
+    sqlx::query("UPDATE messages SET created_at = now() - INTERVAL '1 minute' WHERE id = $1")
+        .bind(edited)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let token = common::create_test_jwt(&owner.to_string());
+
+    let fetch = |query: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages?{}", room_name, query))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = fetch(String::new()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let messages = json["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["id"], untouched.to_string());
+    assert_eq!(messages[0]["edited"], false);
+    assert_eq!(messages[0]["updated_at"], messages[0]["created_at"]);
+    assert_eq!(messages[1]["id"], edited.to_string());
+    assert_eq!(messages[1]["edited"], true);
+    let created_at: chrono::DateTime<chrono::Utc> =
+        messages[1]["created_at"].as_str().unwrap().parse().unwrap();
+    let updated_at: chrono::DateTime<chrono::Utc> =
+        messages[1]["updated_at"].as_str().unwrap().parse().unwrap();
+    assert_eq!(updated_at - created_at, chrono::Duration::minutes(1));
+
+    let response = fetch(format!("before={}", untouched)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["messages"][0]["id"], edited.to_string());
+    assert_eq!(json["messages"][0]["edited"], true);
+}
+
+/// 同じ冪等キーでの再送は1件だけ保存され、同じレスポンスを返す
+#[tokio::test]
+async fn test_send_message_idempotency_key() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping idempotency test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("retry_{}", suffix),
+        )
+        .await;
+    let room_name = format!("retry_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, user_id).await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let send = |body: Value, header_key: Option<&'static str>| {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(format!("/api/chat/{}/send", room_name))
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json");
+        if let Some(key) = header_key {
+            request = request.header("x-idempotency-key", key);
+        }
+        app.clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+    };
+
+    // ボディのキーとヘッダーのキーのどちらでも重複が抑止される
+    let first = send(
+        serde_json::json!({ "content": "hello", "idempotency_key": "retry-1" }),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    let first: Value =
+        serde_json::from_slice(&first.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+    let second = send(serde_json::json!({ "content": "hello" }), Some("retry-1"))
+        .await
+        .unwrap();
+    assert_eq!(second.status(), StatusCode::OK);
+    let second: Value =
+        serde_json::from_slice(&second.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+    assert_eq!(first, second);
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM messages WHERE room_id = $1")
+        .bind(room_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+/// ブロックしたユーザーのメッセージは、ブロックした本人の履歴取得からのみ除外される
+#[tokio::test]
+async fn test_blocked_authors_are_filtered_from_messages() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping block filter test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let alice_name = format!("alice_{}", suffix);
+    let alice = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &alice_name)
+        .await;
+    let bob = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("bob_{}", suffix),
+        )
+        .await;
+    let room_name = format!("blocks_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, bob).await;
+    ctx.create_test_message(room_id, alice, "from alice").await;
+    ctx.create_test_message(room_id, bob, "from bob").await;
+
+    // ユーザー名は大文字小文字を区別しない
+    let bob_token = common::create_test_jwt(&bob.to_string());
+    let block = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/blocks/{}", alice_name.to_uppercase()))
+                .header("authorization", format!("Bearer {}", bob_token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(block.status(), StatusCode::OK);
+
+    let fetch = |token: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+    let contents = |json: Value| -> Vec<String> {
+        json["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["content"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // bob からは alice のメッセージが見えない
+    let response = fetch(bob_token.clone()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(contents(json), vec!["from bob"]);
+
+    // 他のユーザーには影響しない
+    let response = fetch(common::create_test_jwt(&alice.to_string()))
+        .await
+        .unwrap();
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(contents(json).len(), 2);
+
+    // 自分自身はブロックできない
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/blocks/{}", alice_name))
+                .header(
+                    "authorization",
+                    format!("Bearer {}", common::create_test_jwt(&alice.to_string())),
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// 通知設定の保存・取得（ルームの設定は全体の設定とは別に保持され、null で削除できる）
+#[tokio::test]
+async fn test_notification_prefs_roundtrip() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping notification prefs test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("prefs_{}", suffix),
+        )
+        .await;
+    let room_name = format!("prefs_{}", suffix);
+    ctx.create_test_room(&room_name, true, user_id).await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let put = |body: Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/api/chat/notification-prefs")
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+
+    let response = put(serde_json::json!({ "mode": "mentions" }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = put(serde_json::json!({ "room": room_name, "mode": "none" }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["global"], "mentions");
+    assert_eq!(json["rooms"][0]["room"], room_name.as_str());
+    assert_eq!(json["rooms"][0]["mode"], "none");
+
+    // ルームの設定を削除すると全体の設定に戻る
+    let response = put(serde_json::json!({ "room": room_name, "mode": null }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/chat/notification-prefs")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["global"], "mentions");
+    assert!(json["rooms"].as_array().unwrap().is_empty());
+
+    let response = put(serde_json::json!({ "room": "no_such_room", "mode": "all" }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// 送信 Webhook の登録・一覧・削除はオーナーのみ、送信先は許可リストのドメインに限る
+#[tokio::test]
+async fn test_room_webhooks_crud() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping webhook test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("hookowner_{}", suffix),
+        )
+        .await;
+    let other = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("hookother_{}", suffix),
+        )
+        .await;
+    let room_name = format!("hooks_{}", suffix);
+    ctx.create_test_room(&room_name, true, owner).await;
+    let owner_token = common::create_test_jwt(&owner.to_string());
+
+    let request = |method: &str, uri: String, token: &str, body: Option<Value>| {
+        let builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json");
+        let body = body
+            .map(|b| Body::from(b.to_string()))
+            .unwrap_or_else(Body::empty);
+        app.clone().oneshot(builder.body(body).unwrap())
+    };
+    let webhooks_uri = format!("/api/chat/{}/webhooks", room_name);
+
+    // 許可リストにないドメイン・http は拒否
+    for url in [
+        "https://evil.example.net/hook",
+        "http://hooks.example.com/hook",
+    ] {
+        let response = request(
+            "POST",
+            webhooks_uri.clone(),
+            &owner_token,
+            Some(serde_json::json!({ "url": url })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "url: {}", url);
+    }
+
+    // オーナー以外は登録できない
+    let response = request(
+        "POST",
+        webhooks_uri.clone(),
+        &common::create_test_jwt(&other.to_string()),
+        Some(serde_json::json!({ "url": "https://hooks.example.com/hook" })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = request(
+        "POST",
+        webhooks_uri.clone(),
+        &owner_token,
+        Some(serde_json::json!({ "url": "https://hooks.example.com/hook" })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert!(created["secret"].as_str().unwrap().starts_with("whsec_"));
+    assert_eq!(created["events"], serde_json::json!(["message.created"]));
+
+    // 一覧にはシークレットを含めない
+    let response = request("GET", webhooks_uri.clone(), &owner_token, None)
+        .await
+        .unwrap();
+    let listed: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(listed["webhooks"][0]["id"], created["id"]);
+    assert!(listed["webhooks"][0].get("secret").is_none());
+
+    // 変更は変更した項目とともに監査ログに残る
+    let webhook_uri = format!("{}/{}", webhooks_uri, created["id"].as_str().unwrap());
+    let response = request(
+        "PUT",
+        webhook_uri.clone(),
+        &owner_token,
+        Some(serde_json::json!({ "url": "https://hooks.example.com/v2" })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = request(
+        "GET",
+        format!("/api/chat/{}/audit", room_name),
+        &owner_token,
+        None,
+    )
+    .await
+    .unwrap();
+    let audit: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(audit["entries"][0]["action"], "webhook.update");
+    assert_eq!(audit["entries"][0]["target_id"], created["id"]);
+    assert_eq!(
+        audit["entries"][0]["metadata"],
+        serde_json::json!({ "url": "https://hooks.example.com/v2" })
+    );
+
+    let response = request("DELETE", webhook_uri.clone(), &owner_token, None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = request("DELETE", webhook_uri, &owner_token, None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// 受信 Webhook のトークンで投稿したメッセージはボットの投稿として履歴に残る
+#[tokio::test]
+async fn test_incoming_webhook_posts_as_bot() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping incoming webhook test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("ciowner_{}", suffix),
+        )
+        .await;
+    let room_name = format!("ci_{}", suffix);
+    ctx.create_test_room(&room_name, true, owner).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/{}/incoming-webhooks", room_name))
+                .header(
+                    "authorization",
+                    format!("Bearer {}", common::create_test_jwt(&owner.to_string())),
+                )
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::json!({ "name": "CI" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let token = created["token"].as_str().unwrap().to_string();
+
+    let post = |token: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/{}/webhook/{}", room_name, token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "content": "build passed" }).to_string(),
+                ))
+                .unwrap(),
+        )
+    };
+
+    // JWT なしでもトークンで投稿できる
+    let response = post(&token).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = post("whin_invalid").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages", room_name))
+                .header(
+                    "authorization",
+                    format!("Bearer {}", common::create_test_jwt(&owner.to_string())),
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let message = &json["messages"][0];
+    assert_eq!(message["content"], "build passed");
+    assert_eq!(message["author_name"], "CI");
+    assert_eq!(message["author_is_bot"], true);
+}
+
+/// seq はルームごとに 1 から連番で振られ、一覧とカーソルは seq の順になる
+#[tokio::test]
+async fn test_messages_ordered_by_room_seq() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping message seq test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("seq_{}", suffix),
+        )
+        .await;
+    let room_name = format!("seq_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, owner).await;
+    let other_room_id = ctx
+        .create_test_room(&format!("seq_other_{}", suffix), true, owner)
+        .await;
+    for i in 0..4 {
+        ctx.create_test_message(room_id, owner, &format!("message {}", i))
+            .await;
+        // 別のルームへの投稿は連番に影響しない
+        ctx.create_test_message(other_room_id, owner, "elsewhere")
+            .await;
+    }
+    let token = common::create_test_jwt(&owner.to_string());
+
+    let fetch = |query: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/messages?{}", room_name, query))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = fetch(String::new()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let seqs: Vec<i64> = json["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["seq"].as_i64().unwrap())
+        .collect();
+    assert_eq!(seqs, vec![4, 3, 2, 1]);
+    assert_eq!(json["messages"][0]["content"], "message 3");
+
+    let cursor = json["messages"][1]["id"].as_str().unwrap().to_string();
+    let response = fetch(format!("before={}", cursor)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let seqs: Vec<i64> = json["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["seq"].as_i64().unwrap())
+        .collect();
+    assert_eq!(seqs, vec![2, 1]);
+
+    let response = fetch(format!("after={}", cursor)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let seqs: Vec<i64> = json["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["seq"].as_i64().unwrap())
+        .collect();
+    assert_eq!(seqs, vec![4]);
+}
+
+/// MAX_ROOMS_PER_USER までは作成でき、超えると 403
+#[tokio::test]
+async fn test_max_rooms_per_user() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping max rooms test: database not available");
+        return;
+    };
+
+    let mut config = (*common::test_config()).clone();
+    config.max_rooms_per_user = 2;
+    let app =
+        miuchi_chat::create_app(pool, ctx.meili_client.clone(), std::sync::Arc::new(config)).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("maxr_{}", suffix),
+        )
+        .await;
+    let token = common::create_test_jwt(&owner.to_string());
+
+    let create = |name: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri("/api/chat/rooms")
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "name": name, "is_public": false }).to_string(),
+                ))
+                .unwrap(),
+        )
+    };
+
+    // 同時に作成しても上限を超えない
+    let responses =
+        futures_util::future::join_all((0..5).map(|i| create(format!("maxr_{}_{}", suffix, i))))
+            .await;
+    let created = responses
+        .iter()
+        .filter(|response| response.as_ref().unwrap().status() == StatusCode::OK)
+        .count();
+    assert_eq!(created, 2);
+    assert!(responses.iter().all(|response| matches!(
+        response.as_ref().unwrap().status(),
+        StatusCode::OK | StatusCode::FORBIDDEN
+    )));
+
+    let response = create(format!("maxr_{}_5", suffix)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["error"]["message"], "作成できるルームは1人2件までです");
+}
+
+/// 長いメッセージの検索結果のハイライトは一致箇所の周辺だけに切り出される
+#[tokio::test]
+async fn test_search_highlight_is_cropped() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping search crop test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("crop_{}", &suffix[..8]),
+        )
+        .await;
+    let room_name = format!("crop_{}", &suffix[..8]);
+    ctx.create_test_room(&room_name, true, user_id).await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let filler = "lorem ".repeat(200);
+    let content = format!("{}needle{} {}", filler, suffix, filler);
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/{}/send", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "content": content }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Meilisearch のインデックス登録は非同期なので、見つかるまで少し待つ
+    for _ in 0..50 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/search/messages?q=needle{}&crop=10", suffix))
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        if json["degraded"] == true {
+            eprintln!("Skipping search crop test: Meilisearch not available");
+            return;
+        }
+        if let Some(result) = json["results"].as_array().unwrap().first() {
+            // 全文は message.content に残る
+            assert_eq!(result["message"]["content"], content.as_str());
+            let highlight = result["highlights"][0].as_str().unwrap();
+            assert!(highlight.contains("<mark>"));
+            assert!(highlight.split_whitespace().count() <= 12, "{}", highlight);
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("Long message should be searchable");
+}
+
+/// sort=recent では新しいメッセージから順に返る
+#[tokio::test]
+async fn test_search_sort_recent() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping search sort test: database not available");
+        return;
+    };
+    if miuchi_chat::api::search::configure_messages_index(&ctx.meili_client)
+        .await
+        .is_err()
+    {
+        eprintln!("Skipping search sort test: Meilisearch not available");
+        return;
+    }
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("sort_{}", &suffix[..8]),
+        )
+        .await;
+    let room_name = format!("sort_{}", &suffix[..8]);
+    ctx.create_test_room(&room_name, true, user_id).await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let mut sent = Vec::new();
+    for i in 0..3 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/api/chat/{}/send", room_name))
+                    .header("authorization", format!("Bearer {}", token))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({ "content": format!("sorted{} {}", suffix, i) })
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        sent.push(json["message_id"].as_str().unwrap().to_string());
+    }
+    sent.reverse();
+
+    // Meilisearch のインデックス登録は非同期なので、すべて見つかるまで少し待つ
+    for _ in 0..50 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/api/search/messages?q=sorted{}&sort=recent",
+                        suffix
+                    ))
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        let results = json["results"].as_array().unwrap();
+        if results.len() == sent.len() {
+            let created_at: Vec<&str> = results
+                .iter()
+                .map(|r| r["message"]["created_at"].as_str().unwrap())
+                .collect();
+            let mut descending = created_at.clone();
+            descending.sort_by(|a, b| b.cmp(a));
+            assert_eq!(created_at, descending);
+
+            let ids: Vec<&str> = results
+                .iter()
+                .map(|r| r["message"]["id"].as_str().unwrap())
+                .collect();
+            assert_eq!(ids, sent);
+            assert_eq!(json["total_hits_estimated"], true);
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("Sent messages should be searchable");
+}
+
+#[tokio::test]
+async fn test_search_rejects_unknown_sort() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping search sort validation test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("sortv_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]),
+        )
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/search/messages?q=hello&sort=oldest")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// after / before で投稿日時の範囲に絞り込める（フォールバック検索で確認）
+#[tokio::test]
+async fn test_search_filters_by_date_range() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping search date range test: database not available");
+        return;
+    };
+
+    // 到達不能なMeilisearchを指定し、Postgresフォールバックで検索する
+    let unreachable_meili = MeilisearchClient::new("http://127.0.0.1:1", None::<String>).unwrap();
+    let app = miuchi_chat::create_app(pool.clone(), unreachable_meili, common::test_config()).await;
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("range_{}", &suffix[..8]),
+        )
+        .await;
+    let room_id = ctx
+        .create_test_room(&format!("range_{}", &suffix[..8]), true, user_id)
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    // 1日ずつずらした日時で投稿しておく
+    let base = 1_700_000_000_i64;
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let id = ctx
+            .create_test_message(room_id, user_id, &format!("ranged{} {}", suffix, i))
+            .await;
+        sqlx::query("UPDATE messages SET created_at = to_timestamp($1), updated_at = to_timestamp($1) WHERE id = $2")
+            .bind((base + i * 86_400) as f64)
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        ids.push(id.to_string());
+    }
+
+    let search = |query: String| {
+        let app = app.clone();
+        let uri = format!("/api/search/messages?q=ranged{}{}", suffix, query);
+        let authorization = format!("Bearer {}", token);
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("authorization", authorization)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let json: Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            (status, json)
+        }
+    };
+    let result_ids = |json: &Value| -> Vec<String> {
+        json["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["message"]["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let (status, json) = search(String::new()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result_ids(&json).len(), 3);
+
+    // 範囲は両端を含む
+    let (status, json) = search(format!(
+        "&after={}&before={}",
+        base + 86_400,
+        base + 2 * 86_400
+    ))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result_ids(&json), vec![ids[2].clone(), ids[1].clone()]);
+
+    let (status, json) = search(format!("&before={}", base + 3_600)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result_ids(&json), vec![ids[0].clone()]);
+
+    // after が before より後なら 400
+    let (status, json) = search(format!("&after={}&before={}", base + 86_400, base)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["error"]["code"], "VALIDATION_ERROR");
+}
+
+/// メンバー一覧は参加順にページングでき、total は総数を返す
+#[tokio::test]
+async fn test_room_members_pagination() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping members pagination test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("mem_{}", suffix),
+        )
+        .await;
+    let room_name = format!("mem_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner).await;
+    ctx.add_test_member(room_id, owner, "owner").await;
+    for i in 0..6 {
+        let member = ctx
+            .create_test_user(
+                (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+                &format!("mem_{}_{}", suffix, i),
+            )
+            .await;
+        ctx.add_test_member(room_id, member, "member").await;
+    }
+    let token = common::create_test_jwt(&owner.to_string());
+
+    let fetch = |query: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri(format!("/api/chat/{}/members?{}", room_name, query))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let mut seen = Vec::new();
+    let mut offset = 0;
+    loop {
+        let response = fetch(format!("limit=3&offset={}", offset)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        assert_eq!(json["total"], 7);
+        let members = json["members"].as_array().unwrap();
+        assert!(members.len() <= 3);
+        seen.extend(
+            members
+                .iter()
+                .map(|m| m["user_id"].as_str().unwrap().to_string()),
+        );
+        offset += 3;
+        if json["has_more"] != true {
+            break;
+        }
+    }
+
+    // すべてのメンバーが重複なく一度ずつ返る
+    assert_eq!(seen.len(), 7);
+    let unique: std::collections::HashSet<_> = seen.iter().collect();
+    assert_eq!(unique.len(), 7);
+    assert_eq!(seen[0], owner.to_string());
+
+    let response = fetch("limit=1000&strict=true".to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_avatar_identicon_fallback_with_etag() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping avatar test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    // avatar_url を持たないユーザーにはアイデンティコンを返す
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("avatar_{}", suffix),
+        )
+        .await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/avatar/{}", user_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+    assert!(response.headers()["cache-control"]
+        .to_str()
+        .unwrap()
+        .contains("max-age="));
+    let etag = response.headers()["etag"].clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(body.starts_with(b"<svg "));
+
+    // 同じ ETag を送ると 304
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/avatar/{}", user_id))
+                .header("if-none-match", etag.clone())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()["etag"], etag);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/avatar/{}", uuid::Uuid::new_v4()))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/avatar/not-a-uuid")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// 上限を超える WebSocket フレームはプロトコル層で拒否され、クローズコード 1009 で切断される
+#[tokio::test]
+async fn test_oversized_websocket_frame_closes_connection() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::{
+        client::IntoClientRequest, protocol::frame::coding::CloseCode, Message,
+    };
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping oversized frame test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("ws_big_{}", suffix),
+        )
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    // 128KB（上限は 64KB）
+    socket
+        .send(Message::Text("x".repeat(128 * 1024).into()))
+        .await
+        .unwrap();
+
+    let close = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(message) = socket.next().await {
+            match message {
+                Ok(Message::Close(frame)) => return frame,
+                Ok(_) => continue,
+                Err(_) => return None,
+            }
+        }
+        None
+    })
+    .await
+    .expect("timed out waiting for close frame");
+    assert_eq!(close.map(|frame| frame.code), Some(CloseCode::Size));
+}
+
+/// 接続直後に server_time が送られる
+#[tokio::test]
+async fn test_websocket_sends_server_time_on_connect() {
+    use futures_util::StreamExt;
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping server time test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("ws_time_{}", suffix),
+        )
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    let before = chrono::Utc::now() - chrono::Duration::seconds(5);
+    let first = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+        .await
+        .expect("timed out waiting for server_time")
+        .expect("connection closed before server_time")
+        .unwrap();
+    let Message::Text(text) = first else {
+        panic!("expected a text frame, got {:?}", first);
+    };
+    match serde_json::from_str::<WsMessage>(&text).unwrap() {
+        WsMessage::ServerTime { server_time } => {
+            assert!(server_time >= before && server_time <= chrono::Utc::now());
+        }
+        other => panic!("expected server_time, got {:?}", other),
+    }
+}
+
+/// メッセージを ID で取得でき、閲覧できない非公開ルームのメッセージは存在しない場合と同じ 404
+#[tokio::test]
+async fn test_get_message_by_id() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping get message test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("msgid_o_{}", suffix),
+        )
+        .await;
+    let outsider = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("msgid_x_{}", suffix),
+        )
+        .await;
+    let public_room = ctx
+        .create_test_room(&format!("msgid_pub_{}", suffix), true, owner)
+        .await;
+    let private_room = ctx
+        .create_test_room(&format!("msgid_prv_{}", suffix), false, owner)
+        .await;
+    ctx.add_test_member(private_room, owner, "owner").await;
+    let public_message = ctx.create_test_message(public_room, owner, "hello").await;
+    let private_message = ctx.create_test_message(private_room, owner, "secret").await;
+
+    let get = |id: String, user_id: uuid::Uuid| {
+        let app = app.clone();
+        let authorization = format!("Bearer {}", common::create_test_jwt(&user_id.to_string()));
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/api/chat/messages/{}", id))
+                        .header("authorization", authorization)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let json: Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            (status, json)
+        }
+    };
+
+    let (status, json) = get(public_message.to_string(), outsider).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["content"], "hello");
+    assert_eq!(json["author_name"], format!("msgid_o_{}", suffix));
+
+    let (status, json) = get(private_message.to_string(), owner).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["content"], "secret");
+
+    // 非公開ルームのメッセージは存在しない ID と同じ応答になる
+    let (status, hidden) = get(private_message.to_string(), outsider).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, missing) = get(uuid::Uuid::new_v4().to_string(), outsider).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    // timestamp 以外は同じ
+    assert_eq!(hidden["error"]["code"], missing["error"]["code"]);
+    assert_eq!(hidden["error"]["message"], missing["error"]["message"]);
+
+    let (status, _) = get("not-a-uuid".to_string(), outsider).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// 既読位置がメッセージ以降のメンバーを返し、既読位置は後退しない
+#[tokio::test]
+async fn test_message_seen_by_members() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping message seen test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let alice = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("seen_a_{}", suffix),
+        )
+        .await;
+    let bob = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("seen_b_{}", suffix),
+        )
+        .await;
+    let outsider = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("seen_x_{}", suffix),
+        )
+        .await;
+    let room_name = format!("seen_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, alice).await;
+    ctx.add_test_member(room_id, alice, "owner").await;
+    ctx.add_test_member(room_id, bob, "member").await;
+    let first = ctx.create_test_message(room_id, alice, "first").await;
+    let second = ctx.create_test_message(room_id, alice, "second").await;
+    let third = ctx.create_test_message(room_id, bob, "third").await;
+
+    let room = miuchi_chat::models::Room::find_by_id(&pool, room_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(room.mark_read(&pool, alice, third).await.unwrap());
+    assert!(room.mark_read(&pool, bob, second).await.unwrap());
+    // 既読位置より前のメッセージでは後退しない
+    assert!(!room.mark_read(&pool, bob, first).await.unwrap());
+    // メンバーでなければ既読位置を持たない
+    assert!(!room.mark_read(&pool, outsider, third).await.unwrap());
+
+    let get = |message_id: uuid::Uuid, user_id: uuid::Uuid| {
+        let app = app.clone();
+        let uri = format!("/api/chat/{}/messages/{}/seen", room_name, message_id);
+        let authorization = format!("Bearer {}", common::create_test_jwt(&user_id.to_string()));
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("authorization", authorization)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let json: Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            (status, json)
+        }
+    };
+    let usernames = |json: &Value| -> Vec<String> {
+        json["users"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|u| u["username"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let (status, json) = get(first, bob).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 2);
+    assert_eq!(
+        usernames(&json),
+        vec![format!("seen_a_{}", suffix), format!("seen_b_{}", suffix)]
+    );
+
+    let (status, json) = get(third, bob).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 1);
+    assert_eq!(usernames(&json), vec![format!("seen_a_{}", suffix)]);
+
+    // 非公開ルームはメンバー以外には見せない
+    let (status, _) = get(first, outsider).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = get(uuid::Uuid::new_v4(), alice).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// 設定と異なる iss のトークンは署名が正しくても拒否される
+#[tokio::test]
+async fn test_token_with_wrong_issuer_is_rejected() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping JWT issuer test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("iss_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]),
+        )
+        .await;
+
+    for (issuer, expected) in [
+        (miuchi_chat::config::DEFAULT_JWT_ISSUER, StatusCode::OK),
+        ("https://staging.example.com", StatusCode::UNAUTHORIZED),
+    ] {
+        let token = common::create_test_jwt_with_issuer(&user_id.to_string(), issuer);
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/auth/me")
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), expected, "issuer {}", issuer);
+    }
+}
+
+/// メッセージを通報でき、モデレーターが未解決の通報を確認して解決できる
+#[tokio::test]
+async fn test_message_report_workflow() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping message report test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("rep_o_{}", suffix),
+        )
+        .await;
+    let reporter = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("rep_r_{}", suffix),
+        )
+        .await;
+    let room_name = format!("rep_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, owner).await;
+    let message_id = ctx
+        .create_test_message(room_id, owner, "buy cheap stuff")
+        .await;
+
+    let request = |method: &str, uri: String, user_id: uuid::Uuid, body: Option<Value>| {
+        let app = app.clone();
+        let builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(
+                "authorization",
+                format!("Bearer {}", common::create_test_jwt(&user_id.to_string())),
+            )
+            .header("content-type", "application/json");
+        let request = builder
+            .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+            .unwrap();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = response.into_body().collect().await.unwrap().to_bytes();
+            let json: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+            (status, json)
+        }
+    };
+    let report_uri = format!("/api/chat/{}/messages/{}/report", room_name, message_id);
+
+    let (status, _) = request(
+        "POST",
+        report_uri.clone(),
+        reporter,
+        Some(serde_json::json!({ "reason": "   " })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, json) = request(
+        "POST",
+        report_uri.clone(),
+        reporter,
+        Some(serde_json::json!({ "reason": "spam" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let report_id = json["id"].as_str().unwrap().to_string();
+
+    // 同じユーザーによる同じメッセージの通報は重複
+    let (status, _) = request(
+        "POST",
+        report_uri,
+        reporter,
+        Some(serde_json::json!({ "reason": "spam again" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // 一覧と解決はモデレーターのみ
+    let reports_uri = format!("/api/chat/{}/reports", room_name);
+    let (status, _) = request("GET", reports_uri.clone(), reporter, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, json) = request("GET", reports_uri.clone(), owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let reports = json["reports"].as_array().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0]["id"], report_id.as_str());
+    assert_eq!(reports[0]["reason"], "spam");
+    assert_eq!(reports[0]["reporter_name"], format!("rep_r_{}", suffix));
+    assert_eq!(reports[0]["message_content"], "buy cheap stuff");
+    assert_eq!(json["has_more"], false);
+
+    // 2件目の通報を作り、1件ずつページングする
+    let other_message_id = ctx.create_test_message(room_id, owner, "more spam").await;
+    let (status, json) = request(
+        "POST",
+        format!(
+            "/api/chat/{}/messages/{}/report",
+            room_name, other_message_id
+        ),
+        reporter,
+        Some(serde_json::json!({ "reason": "spam" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let other_report_id = json["id"].as_str().unwrap().to_string();
+
+    let (status, json) = request("GET", format!("{}?limit=1", reports_uri), owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["reports"].as_array().unwrap().len(), 1);
+    assert_eq!(json["reports"][0]["id"], report_id.as_str());
+    assert_eq!(json["has_more"], true);
+    assert_eq!(json["next_cursor"], report_id.as_str());
+
+    let (status, _) = request("GET", format!("{}?after=bogus", reports_uri), owner, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let resolve_uri = format!("/api/chat/{}/reports/{}/resolve", room_name, report_id);
+    let (status, _) = request("POST", resolve_uri.clone(), reporter, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = request("POST", resolve_uri.clone(), owner, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request("POST", resolve_uri, owner, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // 解決済みになった通報もカーソルとして使える
+    let (_, json) = request(
+        "GET",
+        format!("{}?limit=1&after={}", reports_uri, report_id),
+        owner,
+        None,
+    )
+    .await;
+    let reports = json["reports"].as_array().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0]["id"], other_report_id.as_str());
+
+    let (_, json) = request("GET", reports_uri, owner, None).await;
+    let reports = json["reports"].as_array().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0]["id"], other_report_id.as_str());
+}
+
+#[tokio::test]
+async fn test_update_room_rename_and_visibility() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping room update test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("upd_o_{}", suffix),
+        )
+        .await;
+    let other = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("upd_x_{}", suffix),
+        )
+        .await;
+    let room_name = format!("upd_{}", suffix);
+    let taken_name = format!("upd_taken_{}", suffix);
+    ctx.create_test_room(&room_name, true, owner).await;
+    ctx.create_test_room(&taken_name, true, other).await;
+
+    let request = |method: &str, uri: String, user_id: uuid::Uuid, body: Option<Value>| {
+        let app = app.clone();
+        let builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(
+                "authorization",
+                format!("Bearer {}", common::create_test_jwt(&user_id.to_string())),
+            )
+            .header("content-type", "application/json");
+        let request = builder
+            .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+            .unwrap();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = response.into_body().collect().await.unwrap().to_bytes();
+            let json: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+            (status, json)
+        }
+    };
+    let room_uri = format!("/api/chat/{}", room_name);
+
+    // オーナー以外は変更できない
+    let (status, _) = request(
+        "PATCH",
+        room_uri.clone(),
+        other,
+        Some(serde_json::json!({ "description": "hijacked" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 既存のルーム名への変更は衝突
+    let (status, json) = request(
+        "PATCH",
+        room_uri.clone(),
+        owner,
+        Some(serde_json::json!({ "name": taken_name })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(json["error"]["code"], "ROOM_EXISTS");
+
+    let (status, _) = request(
+        "PATCH",
+        room_uri.clone(),
+        owner,
+        Some(serde_json::json!({})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // 名前は作成時と同じく正規化され、非公開にしたオーナーはメンバーとして残る
+    let new_name = format!("upd_new_{}", suffix);
+    let (status, json) = request(
+        "PATCH",
+        room_uri.clone(),
+        owner,
+        Some(serde_json::json!({
+            "name": format!("  {}  ", new_name.to_uppercase()),
+            "description": "renamed",
+            "is_public": false,
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["name"], new_name.as_str());
+    assert_eq!(json["description"], "renamed");
+    assert_eq!(json["is_public"], false);
+
+    let (status, _) = request(
+        "PATCH",
+        room_uri,
+        owner,
+        Some(serde_json::json!({ "description": "" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let members_uri = format!("/api/chat/{}/members", new_name);
+    let (status, _) = request("GET", members_uri.clone(), owner, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = request("GET", members_uri, other, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 空文字列で説明を消す
+    let (status, json) = request(
+        "PATCH",
+        format!("/api/chat/{}", new_name),
+        owner,
+        Some(serde_json::json!({ "description": "" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["description"], Value::Null);
+}
+
+/// HIDE_PRIVATE_ROOMS=true ではプライベートルームのメンバー以外に存在しないルームと同じ 404 を返す
+#[tokio::test]
+async fn test_private_room_access_denied_modes() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping private room visibility test: database not available");
+        return;
+    };
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("hide_o_{}", suffix),
+        )
+        .await;
+    let outsider = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("hide_x_{}", suffix),
+        )
+        .await;
+    let room_name = format!("hide_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner).await;
+    ctx.add_test_member(room_id, owner, "owner").await;
+    let missing_room = format!("hide_missing_{}", suffix);
+
+    for hide in [false, true] {
+        let mut config = (*common::test_config()).clone();
+        config.hide_private_rooms = hide;
+        let app = miuchi_chat::create_app(
+            pool.clone(),
+            ctx.meili_client.clone(),
+            std::sync::Arc::new(config),
+        )
+        .await;
+
+        let request = |method: &str, uri: String, user_id: uuid::Uuid, body: Option<Value>| {
+            let app = app.clone();
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(
+                    "authorization",
+                    format!("Bearer {}", common::create_test_jwt(&user_id.to_string())),
+                )
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = response.into_body().collect().await.unwrap().to_bytes();
+                let json: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+                (status, json)
+            }
+        };
+
+        let expected = if hide {
+            StatusCode::NOT_FOUND
+        } else {
+            StatusCode::FORBIDDEN
+        };
+        for (method, path, body) in [
+            ("GET", "messages", None),
+            ("GET", "members", None),
+            ("GET", "pins", None),
+            ("GET", "audit", None),
+            ("GET", "reports", None),
+            ("GET", "webhooks", None),
+            ("POST", "send", Some(serde_json::json!({ "content": "hi" }))),
+            (
+                "POST",
+                "invite",
+                Some(serde_json::json!({ "username": "someone" })),
+            ),
+        ] {
+            let (status, _) = request(
+                method,
+                format!("/api/chat/{}/{}", room_name, path),
+                outsider,
+                body,
+            )
+            .await;
+            assert_eq!(status, expected, "{} {} (hide={})", method, path, hide);
+        }
+
+        // メンバーはどちらのモードでも閲覧できる
+        let (status, _) = request(
+            "GET",
+            format!("/api/chat/{}/messages", room_name),
+            owner,
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        if hide {
+            // 存在しないルームとレスポンスで区別できない
+            let (_, hidden) = request(
+                "GET",
+                format!("/api/chat/{}/members", room_name),
+                outsider,
+                None,
+            )
+            .await;
+            let (status, missing) = request(
+                "GET",
+                format!("/api/chat/{}/members", missing_room),
+                outsider,
+                None,
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(hidden["error"]["code"], missing["error"]["code"]);
+            assert_eq!(hidden["error"]["message"], missing["error"]["message"]);
+        }
+
+        // WebSocket の join_room も同じ規則
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_app = app.clone();
+        tokio::spawn(async move { axum::serve(listener, server_app).await.unwrap() });
+
+        let mut ws_request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+        ws_request.headers_mut().insert(
+            "sec-websocket-protocol",
+            format!(
+                "miuchi-chat, bearer.{}",
+                common::create_test_jwt(&outsider.to_string())
+            )
+            .parse()
+            .unwrap(),
+        );
+        let (mut socket, _) = tokio_tungstenite::connect_async(ws_request).await.unwrap();
+        let join = WsMessage::JoinRoom {
+            room: room_name.clone(),
+        };
+        socket
+            .send(Message::Text(serde_json::to_string(&join).unwrap().into()))
+            .await
+            .unwrap();
+
+        let error = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while let Some(Ok(message)) = socket.next().await {
+                if let Message::Text(text) = message {
+                    if let Ok(WsMessage::Error { message, .. }) = serde_json::from_str(&text) {
+                        return Some(message);
+                    }
+                }
+            }
+            None
+        })
+        .await
+        .expect("timed out waiting for join error");
+        let expected_error = if hide {
+            "Room not found"
+        } else {
+            "You are not a member of this private room"
+        };
+        assert_eq!(error.as_deref(), Some(expected_error));
+    }
+}
+
+/// join_room の応答に自分の既読位置が含まれる
+#[tokio::test]
+async fn test_room_joined_includes_last_read_message_id() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping join read state test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("ws_read_{}", suffix),
+        )
+        .await;
+    let room_name = format!("ws_read_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, user_id).await;
+    ctx.add_test_member(room_id, user_id, "owner").await;
+    let message_id = ctx.create_test_message(room_id, user_id, "read me").await;
+    sqlx::query(
+        "UPDATE room_members SET last_read_message_id = $1 WHERE room_id = $2 AND user_id = $3",
+    )
+    .bind(message_id)
+    .bind(room_id)
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let token = common::create_test_jwt(&user_id.to_string());
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    let join = WsMessage::JoinRoom {
+        room: room_name.clone(),
+    };
+    socket
+        .send(Message::Text(serde_json::to_string(&join).unwrap().into()))
+        .await
+        .unwrap();
+
+    let joined = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                if let Ok(joined @ WsMessage::RoomJoined { .. }) = serde_json::from_str(&text) {
+                    return Some(joined);
+                }
+            }
+        }
+        None
+    })
+    .await
+    .expect("timed out waiting for room_joined");
+    match joined {
+        Some(WsMessage::RoomJoined {
+            room,
+            last_read_message_id,
+            ..
+        }) => {
+            assert_eq!(room, room_name);
+            assert_eq!(last_read_message_id, Some(message_id.to_string()));
+        }
+        other => panic!("expected room_joined, got {:?}", other),
+    }
+}
+
+/// パブリックルームの非メンバーが ID 指定で mark_read すると、メンバーになりルーム名で seen_update が届く
+#[tokio::test]
+async fn test_mark_read_by_room_id_broadcasts_by_name() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping mark read test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("mr_owner_{}", suffix),
+        )
+        .await;
+    let reader_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("mr_reader_{}", suffix),
+        )
+        .await;
+    let room_name = format!("mark_read_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, owner_id).await;
+    let message_id = ctx.create_test_message(room_id, owner_id, "read me").await;
+    let is_member = || {
+        sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM room_members WHERE room_id = $1 AND user_id = $2)",
+        )
+        .bind(room_id)
+        .bind(reader_id)
+        .fetch_one(&pool)
+    };
+
+    let token = common::create_test_jwt(&reader_id.to_string());
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    // パブリックルームを読んでいるだけのユーザーの既読は記録せず、メンバーにもしない
+    for message in [
+        WsMessage::JoinRoom {
+            room: room_name.clone(),
+        },
+        WsMessage::MarkRead {
+            room: room_id.to_string(),
+            message_id: message_id.to_string(),
+        },
+        WsMessage::Ping { timestamp: Some(1) },
+    ] {
+        socket
+            .send(Message::Text(
+                serde_json::to_string(&message).unwrap().into(),
+            ))
+            .await
+            .unwrap();
+    }
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                match serde_json::from_str(&text) {
+                    Ok(WsMessage::Pong { .. }) => return,
+                    Ok(WsMessage::SeenUpdate { .. }) => {
+                        panic!("non-member mark_read was broadcast")
+                    }
+                    _ => {}
+                }
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for pong");
+    assert!(!is_member().await.unwrap());
+
+    ctx.add_test_member(room_id, reader_id, "member").await;
+    socket
+        .send(Message::Text(
+            serde_json::to_string(&WsMessage::MarkRead {
+                room: room_id.to_string(),
+                message_id: message_id.to_string(),
+            })
+            .unwrap()
+            .into(),
+        ))
+        .await
+        .unwrap();
+
+    let seen = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                if let Ok(seen @ WsMessage::SeenUpdate { .. }) = serde_json::from_str(&text) {
+                    return Some(seen);
+                }
+            }
+        }
+        None
+    })
+    .await
+    .expect("timed out waiting for seen_update");
+    match seen {
+        Some(WsMessage::SeenUpdate {
+            room,
+            message_id: seen_id,
+            user_id,
+        }) => {
+            assert_eq!(room, room_name);
+            assert_eq!(seen_id, message_id.to_string());
+            assert_eq!(user_id, reader_id.to_string());
+        }
+        other => panic!("expected seen_update, got {:?}", other),
+    }
+
+    let last_read: Option<uuid::Uuid> = sqlx::query_scalar(
+        "SELECT last_read_message_id FROM room_members WHERE room_id = $1 AND user_id = $2",
+    )
+    .bind(room_id)
+    .bind(reader_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(last_read, Some(message_id));
+}
+
+#[tokio::test]
+async fn test_duplicate_join_room_broadcasts_user_joined_once() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping duplicate join test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        common::test_config(),
+    )
+    .await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let alice_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("dup_a_{}", suffix),
+        )
+        .await;
+    let bob_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("dup_b_{}", suffix),
+        )
+        .await;
+    let room_name = format!("dup_join_{}", suffix);
+    ctx.create_test_room(&room_name, true, alice_id).await;
+
+    let connect = |user_id: uuid::Uuid| {
+        let token = common::create_test_jwt(&user_id.to_string());
+        let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+        request.headers_mut().insert(
+            "sec-websocket-protocol",
+            format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+        );
+        tokio_tungstenite::connect_async(request)
+    };
+    let (mut alice, _) = connect(alice_id).await.unwrap();
+    let (mut bob, _) = connect(bob_id).await.unwrap();
+
+    let text = |message: WsMessage| Message::Text(serde_json::to_string(&message).unwrap().into());
+    let join = WsMessage::JoinRoom {
+        room: room_name.clone(),
+    };
+
+    // 指定の種類のメッセージが届くまで待ち、それまでに届いたメッセージを返す
+    async fn receive_until<S>(socket: &mut S, done: impl Fn(&WsMessage) -> bool) -> Vec<WsMessage>
+    where
+        S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+            + Unpin,
+    {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            let mut received = Vec::new();
+            while let Some(Ok(message)) = socket.next().await {
+                if let Message::Text(text) = message {
+                    if let Ok(message) = serde_json::from_str::<WsMessage>(&text) {
+                        let finished = done(&message);
+                        received.push(message);
+                        if finished {
+                            break;
+                        }
+                    }
+                }
+            }
+            received
+        })
+        .await
+        .expect("timed out waiting for websocket message")
+    }
+
+    alice.send(text(join.clone())).await.unwrap();
+    receive_until(&mut alice, |m| matches!(m, WsMessage::RoomJoined { .. })).await;
+
+    // bob が同じルームに2回 join しても、どちらにも room_joined が返る
+    for _ in 0..2 {
+        bob.send(text(join.clone())).await.unwrap();
+        receive_until(&mut bob, |m| matches!(m, WsMessage::RoomJoined { .. })).await;
+    }
+
+    // 退出通知より前に届いた参加通知を数える（同じ接続のメッセージは順に処理される）
+    bob.send(text(WsMessage::LeaveRoom {
+        room: room_name.clone(),
+    }))
+    .await
+    .unwrap();
+    let received = receive_until(&mut alice, |m| matches!(m, WsMessage::UserLeft { .. })).await;
+    let bob_joined = received
+        .iter()
+        .filter(|m| matches!(m, WsMessage::UserJoined { user_id, .. } if *user_id == bob_id.to_string()))
+        .count();
+    assert_eq!(bob_joined, 1, "unexpected messages: {:?}", received);
+}
+
+/// 受信 Webhook のボットは同名の実在ユーザーを招待・メンションの解決で隠さない
+#[tokio::test]
+async fn test_username_lookup_ignores_bots() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping username lookup test: database not available");
+        return;
+    };
+
+    let username = format!("shadow_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    let bot_id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO users (github_id, username, is_bot) VALUES ($1, $2, true) RETURNING id",
+    )
+    .bind(-(uuid::Uuid::new_v4().as_u128() as i64).abs())
+    .bind(&username)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let found = miuchi_chat::models::User::find_by_username_ci(&pool, &username)
+        .await
+        .unwrap();
+    assert!(found.is_none());
+
+    let user_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &username)
+        .await;
+    let found = miuchi_chat::models::User::find_by_username_ci(&pool, &username.to_uppercase())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, user_id);
+    assert_ne!(found.id, bot_id);
+}
+
+/// JSON の ping もレート制限に数えられ、送りすぎると rate_limited が返る
+#[tokio::test]
+async fn test_websocket_ping_counts_toward_rate_limit() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping ping rate limit test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("ws_ping_{}", suffix),
+        )
+        .await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    for _ in 0..miuchi_chat::config::RATE_LIMIT_MESSAGES * 3 {
+        socket
+            .send(Message::Text(r#"{"type":"ping"}"#.into()))
+            .await
+            .unwrap();
+    }
+
+    let rate_limited = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(frame)) = socket.next().await {
+            let Message::Text(text) = frame else {
+                continue;
+            };
+            if let Ok(WsMessage::RateLimited { .. }) = serde_json::from_str(&text) {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+    assert!(rate_limited, "expected rate_limited after a burst of pings");
+}
+
+/// 禁止語を含む投稿は REST では理由付きの 400、WebSocket ではエラーになり保存されない
+#[tokio::test]
+async fn test_banned_words_are_rejected_over_rest_and_ws() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::config::ModerationMode;
+    use miuchi_chat::moderation::BannedWordsModerator;
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping moderation test: database not available");
+        return;
+    };
+
+    let mut config = (*common::test_config()).clone();
+    config.moderator = std::sync::Arc::new(BannedWordsModerator::new(
+        vec!["forbidden".to_string()],
+        ModerationMode::Reject,
+    ));
+    let app = miuchi_chat::create_app(
+        pool.clone(),
+        ctx.meili_client.clone(),
+        std::sync::Arc::new(config),
+    )
+    .await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let user_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("banned_{}", suffix),
+        )
+        .await;
+    let room_name = format!("banned_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, user_id).await;
+    let token = common::create_test_jwt(&user_id.to_string());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/send", room_name))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "content": "a Forbidden word" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let json: Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["error"]["code"], "VALIDATION_ERROR");
+    assert!(json["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("禁止されている語句"));
+
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    let send = WsMessage::SendMessage {
+        room: room_name.clone(),
+        content: "forbidden!".to_string(),
+        message_type: None,
+        client_msg_id: None,
+        reply_to_message_id: None,
+    };
+    socket
+        .send(Message::Text(serde_json::to_string(&send).unwrap().into()))
+        .await
+        .unwrap();
+
+    let error = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(frame)) = socket.next().await {
+            let Message::Text(text) = frame else {
+                continue;
+            };
+            if let Ok(WsMessage::Error { message, .. }) = serde_json::from_str(&text) {
+                return Some(message);
+            }
+        }
+        None
+    })
+    .await
+    .expect("timed out waiting for the error");
+    assert!(error.unwrap().contains("禁止されている語句"));
+
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE room_id = $1")
+        .bind(room_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0);
+}
+
+/// どのルームにも参加していない接続にもユーザー宛ての通知（invited_to_room）が届く
+#[tokio::test]
+async fn test_invite_reaches_connection_without_rooms() {
+    use futures_util::StreamExt;
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping roomless invite test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("nr_o_{}", suffix),
+        )
+        .await;
+    let invitee_name = format!("nr_b_{}", suffix);
+    let invitee_id = ctx
+        .create_test_user((uuid::Uuid::new_v4().as_u128() as i64).abs(), &invitee_name)
+        .await;
+    let room_name = format!("noroom_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, false, owner_id).await;
+    ctx.add_test_member(room_id, owner_id, "owner").await;
+
+    let token = common::create_test_jwt(&invitee_id.to_string());
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("miuchi-chat, bearer.{}", token).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    async fn next<S>(socket: &mut S) -> WsMessage
+    where
+        S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+            + Unpin,
+    {
+        // ping などテキスト以外のフレームは読み飛ばす
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let message = socket.next().await.expect("connection closed").unwrap();
+                if let Message::Text(text) = message {
+                    return serde_json::from_str::<WsMessage>(&text).unwrap();
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for websocket message")
+    }
+
+    // server_time は接続の登録後に送られる
+    assert!(matches!(
+        next(&mut socket).await,
+        WsMessage::ServerTime { .. }
+    ));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/invite", room_name))
+                .header(
+                    "authorization",
+                    format!("Bearer {}", common::create_test_jwt(&owner_id.to_string())),
+                )
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "username": invitee_name }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    match next(&mut socket).await {
+        WsMessage::InvitedToRoom { room, .. } => assert_eq!(room, room_name),
+        other => panic!("expected invited_to_room, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_join_by_room_id_receives_rest_sent_message() {
+    use futures_util::{SinkExt, StreamExt};
+    use miuchi_chat::ws::WsMessage;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping join by room ID test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let sender_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("byid_sender_{}", suffix),
+        )
+        .await;
+    let watcher_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("byid_watcher_{}", suffix),
+        )
+        .await;
+    let room_name = format!("byid_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, sender_id).await;
+
+    // フロントエンドと同じくルーム ID で参加する
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!(
+            "miuchi-chat, bearer.{}",
+            common::create_test_jwt(&watcher_id.to_string())
+        )
+        .parse()
+        .unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    socket
+        .send(Message::Text(
+            serde_json::to_string(&WsMessage::JoinRoom {
+                room: room_id.to_string(),
+            })
+            .unwrap()
+            .into(),
+        ))
+        .await
+        .unwrap();
+    async fn receive<S>(socket: &mut S, matches: impl Fn(&WsMessage) -> bool) -> WsMessage
+    where
+        S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+            + Unpin,
+    {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while let Some(Ok(message)) = socket.next().await {
+                if let Message::Text(text) = message {
+                    if let Ok(message) = serde_json::from_str::<WsMessage>(&text) {
+                        if matches(&message) {
+                            return message;
+                        }
+                    }
+                }
+            }
+            panic!("connection closed");
+        })
+        .await
+        .expect("timed out waiting for websocket message")
+    }
+    match receive(&mut socket, |m| matches!(m, WsMessage::RoomJoined { .. })).await {
+        WsMessage::RoomJoined { room, .. } => assert_eq!(room, room_name),
+        other => panic!("expected room_joined, got {:?}", other),
+    }
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/{}/send", room_name))
+                .header(
+                    "authorization",
+                    format!("Bearer {}", common::create_test_jwt(&sender_id.to_string())),
+                )
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "content": "sent over REST" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    match receive(&mut socket, |m| matches!(m, WsMessage::Message { .. })).await {
+        WsMessage::Message { room, content, .. } => {
+            assert_eq!(room, room_name);
+            assert_eq!(content, "sent over REST");
+        }
+        other => panic!("expected message, got {:?}", other),
+    }
+}
+
+/// 検索結果には閲覧できないプライベートルームのメッセージを含めない
+#[tokio::test]
+async fn test_search_excludes_inaccessible_private_rooms() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping private room search test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let owner_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("psearch_owner_{}", &suffix[..8]),
+        )
+        .await;
+    let outsider_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("psearch_out_{}", &suffix[..8]),
+        )
+        .await;
+    let room_name = format!("psearch_{}", &suffix[..8]);
+    let room_id = ctx.create_test_room(&room_name, false, owner_id).await;
+    ctx.add_test_member(room_id, owner_id, "owner").await;
+    let owner_token = common::create_test_jwt(&owner_id.to_string());
+    let outsider_token = common::create_test_jwt(&outsider_id.to_string());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/chat/{}/send", room_name))
+                .header("authorization", format!("Bearer {}", owner_token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "content": format!("secret{}", suffix) }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let search = |uri: String, token: String| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("authorization", format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let json: Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            (status, json)
+        }
+    };
+
+    // ルームを指定した場合はメンバー以外には 403
+    let (status, _) = search(
+        format!("/api/search/messages?q=secret{}&room={}", suffix, room_name),
+        outsider_token.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // メンバーの検索で見つかるまで待ってから、メンバー以外には見つからないことを確かめる
+    for _ in 0..50 {
+        let (status, json) = search(
+            format!("/api/search/messages?q=secret{}", suffix),
+            owner_token.clone(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        if !json["results"].as_array().unwrap().is_empty() {
+            let (status, json) = search(
+                format!("/api/search/messages?q=secret{}", suffix),
+                outsider_token.clone(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(json["results"].as_array().unwrap().is_empty(), "{}", json);
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("Message should be searchable by a room member");
+}
+
+/// 同時にピン留めしてもルームの上限（50件）を超えない
+#[tokio::test]
+async fn test_concurrent_pins_respect_limit() {
+    let ctx = TestContext::new().await;
+    let Some(pool) = ctx.pool.clone() else {
+        eprintln!("Skipping pin limit test: database not available");
+        return;
+    };
+
+    let app = miuchi_chat::create_app(pool, ctx.meili_client.clone(), common::test_config()).await;
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let owner_id = ctx
+        .create_test_user(
+            (uuid::Uuid::new_v4().as_u128() as i64).abs(),
+            &format!("pins_{}", suffix),
+        )
+        .await;
+    let room_name = format!("pins_{}", suffix);
+    let room_id = ctx.create_test_room(&room_name, true, owner_id).await;
+    let token = common::create_test_jwt(&owner_id.to_string());
+
+    let mut message_ids = Vec::new();
+    for i in 0..55 {
+        message_ids.push(
+            ctx.create_test_message(room_id, owner_id, &format!("pin me {}", i))
+                .await,
+        );
+    }
+
+    let responses = futures_util::future::join_all(message_ids.iter().map(|message_id| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/chat/{}/pins/{}", room_name, message_id))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    }))
+    .await;
+    let statuses: Vec<StatusCode> = responses
+        .into_iter()
+        .map(|response| response.unwrap().status())
+        .collect();
+    assert_eq!(
+        statuses.iter().filter(|&&s| s == StatusCode::OK).count(),
+        50
+    );
+    assert!(statuses
+        .iter()
+        .all(|&s| s == StatusCode::OK || s == StatusCode::CONFLICT));
+}
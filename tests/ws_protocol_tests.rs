@@ -0,0 +1,69 @@
+/**
+ * WebSocketプロトコルの互換性テスト
+ * tests/fixtures/ws_messages.json はフロントエンドのテストとも共有している
+ */
+use miuchi_chat::ws::{WsErrorCode, WsMessage};
+use serde_json::Value;
+use std::collections::HashSet;
+
+fn load_fixtures() -> Vec<Value> {
+    let raw = include_str!("fixtures/ws_messages.json");
+    serde_json::from_str(raw).expect("ws_messages.json must be a JSON array")
+}
+
+/// 全フィクスチャがWsMessageとして往復変換できることを確認
+#[test]
+fn test_ws_message_fixtures_round_trip() {
+    for fixture in load_fixtures() {
+        let message: WsMessage = serde_json::from_value(fixture.clone())
+            .unwrap_or_else(|e| panic!("Failed to deserialize {}: {}", fixture, e));
+        let serialized = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            serialized, fixture,
+            "Round trip mismatch for {}",
+            fixture["type"]
+        );
+    }
+}
+
+/// フィクスチャの type が重複していないことを確認
+#[test]
+fn test_ws_message_fixtures_are_unique() {
+    let fixtures = load_fixtures();
+    let types: HashSet<&str> = fixtures
+        .iter()
+        .map(|f| f["type"].as_str().expect("fixture must have a type"))
+        .collect();
+    assert_eq!(types.len(), fixtures.len());
+}
+
+/// エラーコードが固定の数値で往復変換でき、未知の値は拒否されることを確認
+#[test]
+fn test_ws_error_codes_are_stable() {
+    let codes = [
+        (WsErrorCode::InvalidJson, 4000),
+        (WsErrorCode::UnsupportedFrame, 4001),
+        (WsErrorCode::MessageTooLarge, 4002),
+        (WsErrorCode::InvalidMessage, 4003),
+        (WsErrorCode::RequestFailed, 4004),
+        (WsErrorCode::Timeout, 4005),
+        (WsErrorCode::MessagesDropped, 4006),
+        (WsErrorCode::Internal, 4010),
+    ];
+    for (code, value) in codes {
+        assert_eq!(serde_json::to_value(code).unwrap(), Value::from(value));
+        assert_eq!(
+            serde_json::from_value::<WsErrorCode>(Value::from(value)).unwrap(),
+            code
+        );
+    }
+
+    let error = serde_json::json!({ "type": "error", "message": "oops", "code": 1003 });
+    assert!(serde_json::from_value::<WsMessage>(error).is_err());
+
+    let error = serde_json::json!({ "type": "error", "message": "oops", "code": null });
+    assert!(matches!(
+        serde_json::from_value::<WsMessage>(error).unwrap(),
+        WsMessage::Error { code: None, .. }
+    ));
+}